@@ -0,0 +1,293 @@
+//! Authenticated encryption with associated data (AEAD) for encrypting values at rest.
+//!
+//! Values are encrypted with XChaCha20-Poly1305 using a random 192-bit nonce. The ciphertext is
+//! encoded as `v1.<key id>.<base64(nonce || ciphertext)>`, the key id makes it possible to rotate
+//! data-encryption keys while still being able to decrypt values encrypted with a retired key.
+//!
+//! # Example
+//!
+//! ```
+//! use crypto::aead::KeyRing;
+//!
+//! let keys = KeyRing::new("1", b"01234567890123456789012345678901").unwrap();
+//!
+//! let ciphertext = keys.encrypt(b"secret", b"user:1").unwrap();
+//! let plaintext = keys.decrypt(&ciphertext, b"user:1").unwrap();
+//!
+//! assert_eq!(plaintext, b"secret");
+//! ```
+
+use std::collections::HashMap;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+
+/// Version prefix of the ciphertext encoding.
+const VERSION: &str = "v1";
+
+/// Length of a data-encryption key in bytes.
+pub const KEY_LENGTH: usize = 32;
+
+/// Length of the nonce in bytes.
+const NONCE_LENGTH: usize = 24;
+
+/// An error type for AEAD-related errors.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The key is not 32 bytes long or the key id is malformed.
+    #[error("Invalid key")]
+    InvalidKey,
+
+    /// The keyring does not contain the key the value was encrypted with.
+    #[error("Unknown key id: {0}")]
+    UnknownKey(String),
+
+    /// The keyring does not contain a primary key to encrypt with.
+    #[error("No primary key")]
+    NoPrimaryKey,
+
+    /// The ciphertext is not encoded correctly.
+    #[error("Invalid ciphertext")]
+    InvalidCiphertext,
+
+    /// The value could not be encrypted.
+    #[error("Encryption failed")]
+    Encryption,
+
+    /// The value could not be decrypted, either the key or the associated data is wrong or the
+    /// ciphertext has been tampered with.
+    #[error("Decryption failed")]
+    Decryption,
+}
+
+/// A set of data-encryption keys identified by a key id.
+///
+/// New values are always encrypted with the primary key, the other keys are only used for
+/// decrypting values that were encrypted before a key rotation.
+#[derive(Clone, Default)]
+pub struct KeyRing {
+    primary_key_id: Option<String>,
+    keys: HashMap<String, [u8; KEY_LENGTH]>,
+}
+
+impl KeyRing {
+    /// Creates a new keyring with the given key as primary key.
+    pub fn new<C>(key_id: C, key: &[u8]) -> Result<Self, Error>
+    where
+        C: Into<String>,
+    {
+        let key_id = key_id.into();
+
+        let mut keyring = Self::default();
+        keyring.add_key(key_id.clone(), key)?;
+        keyring.primary_key_id = Some(key_id);
+
+        Ok(keyring)
+    }
+
+    /// Parses a keyring from a comma separated list of `<key id>:<base64 key>` pairs.
+    ///
+    /// The first key in the list becomes the primary key.
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        let mut keyring = Self::default();
+
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key_id, key) = pair.split_once(':').ok_or(Error::InvalidKey)?;
+            let key = base64::engine::general_purpose::STANDARD
+                .decode(key)
+                .map_err(|_| Error::InvalidKey)?;
+
+            keyring.add_key(key_id, &key)?;
+            if keyring.primary_key_id.is_none() {
+                keyring.primary_key_id = Some(key_id.to_owned());
+            }
+        }
+
+        if keyring.primary_key_id.is_none() {
+            return Err(Error::NoPrimaryKey);
+        }
+
+        Ok(keyring)
+    }
+
+    /// Adds a key which can be used to decrypt values.
+    pub fn add_key<C>(&mut self, key_id: C, key: &[u8]) -> Result<&mut Self, Error>
+    where
+        C: Into<String>,
+    {
+        let key_id = key_id.into();
+        if key_id.is_empty() || key_id.contains(['.', ':', ',']) {
+            return Err(Error::InvalidKey);
+        }
+
+        let key: [u8; KEY_LENGTH] = key.try_into().map_err(|_| Error::InvalidKey)?;
+        self.keys.insert(key_id, key);
+
+        Ok(self)
+    }
+
+    /// Returns the id of the key that new values are encrypted with.
+    pub fn primary_key_id(&self) -> Option<&str> {
+        self.primary_key_id.as_deref()
+    }
+
+    /// Encrypts the plaintext with the primary key.
+    ///
+    /// The associated data is authenticated but not encrypted, it must be the same when decrypting
+    /// (e.g. the ID of the row the value belongs to).
+    pub fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> Result<String, Error> {
+        let key_id = self.primary_key_id.as_ref().ok_or(Error::NoPrimaryKey)?;
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| Error::UnknownKey(key_id.clone()))?;
+
+        let cipher = XChaCha20Poly1305::new(key.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: associated_data,
+                },
+            )
+            .map_err(|_| Error::Encryption)?;
+
+        let mut data = Vec::with_capacity(NONCE_LENGTH + ciphertext.len());
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+
+        Ok(format!(
+            "{}.{}.{}",
+            VERSION,
+            key_id,
+            URL_SAFE_NO_PAD.encode(data)
+        ))
+    }
+
+    /// Decrypts a value created by [`KeyRing::encrypt`] with the key it was encrypted with.
+    pub fn decrypt(&self, ciphertext: &str, associated_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let (key_id, data) = split_ciphertext(ciphertext)?;
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| Error::UnknownKey(key_id.to_owned()))?;
+
+        let data = URL_SAFE_NO_PAD
+            .decode(data)
+            .map_err(|_| Error::InvalidCiphertext)?;
+        if data.len() < NONCE_LENGTH {
+            return Err(Error::InvalidCiphertext);
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+        let cipher = XChaCha20Poly1305::new(key.into());
+
+        cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: associated_data,
+                },
+            )
+            .map_err(|_| Error::Decryption)
+    }
+
+    /// Returns true if the value was not encrypted with the primary key and should be re-encrypted.
+    pub fn needs_reencryption(&self, ciphertext: &str) -> bool {
+        match split_ciphertext(ciphertext) {
+            Ok((key_id, _)) => self.primary_key_id() != Some(key_id),
+            Err(_) => true,
+        }
+    }
+}
+
+impl std::fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the keys themselves
+        f.debug_struct("KeyRing")
+            .field("primary_key_id", &self.primary_key_id)
+            .field("key_ids", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// Returns true if the value looks like a ciphertext created by [`KeyRing::encrypt`].
+pub fn is_encrypted(value: &str) -> bool {
+    split_ciphertext(value).is_ok()
+}
+
+fn split_ciphertext(ciphertext: &str) -> Result<(&str, &str), Error> {
+    let mut parts = ciphertext.splitn(3, '.');
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(VERSION), Some(key_id), Some(data)) if !key_id.is_empty() => Ok((key_id, data)),
+        _ => Err(Error::InvalidCiphertext),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_1: &[u8] = b"01234567890123456789012345678901";
+    const KEY_2: &[u8] = b"abcdefghijklmnopqrstuvwxyz012345";
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let keys = KeyRing::new("1", KEY_1).unwrap();
+
+        let ciphertext = keys.encrypt(b"JBSWY3DPEHPK3PXP", b"user:1").unwrap();
+        assert!(ciphertext.starts_with("v1.1."));
+        assert!(is_encrypted(&ciphertext));
+        assert!(!is_encrypted("JBSWY3DPEHPK3PXP"));
+
+        let plaintext = keys.decrypt(&ciphertext, b"user:1").unwrap();
+        assert_eq!(plaintext, b"JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn test_decrypt_wrong_associated_data() {
+        let keys = KeyRing::new("1", KEY_1).unwrap();
+
+        let ciphertext = keys.encrypt(b"JBSWY3DPEHPK3PXP", b"user:1").unwrap();
+        assert!(keys.decrypt(&ciphertext, b"user:2").is_err());
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let old_keys = KeyRing::new("1", KEY_1).unwrap();
+        let ciphertext = old_keys.encrypt(b"JBSWY3DPEHPK3PXP", b"").unwrap();
+
+        let mut keys = KeyRing::new("2", KEY_2).unwrap();
+        assert!(matches!(
+            keys.decrypt(&ciphertext, b""),
+            Err(Error::UnknownKey(_))
+        ));
+
+        keys.add_key("1", KEY_1).unwrap();
+        assert!(keys.needs_reencryption(&ciphertext));
+        assert_eq!(keys.decrypt(&ciphertext, b"").unwrap(), b"JBSWY3DPEHPK3PXP");
+
+        let ciphertext = keys.encrypt(b"JBSWY3DPEHPK3PXP", b"").unwrap();
+        assert!(!keys.needs_reencryption(&ciphertext));
+    }
+
+    #[test]
+    fn test_parse_keyring() {
+        let keys = KeyRing::parse(
+            "2:YWJjZGVmZ2hpamtsbW5vcHFyc3R1dnd4eXowMTIzNDU=,1:MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2Nzg5MDE=",
+        )
+        .unwrap();
+        assert_eq!(keys.primary_key_id(), Some("2"));
+
+        assert!(KeyRing::parse("").is_err());
+        assert!(KeyRing::parse("1:c2hvcnQ=").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+pub mod breached;
 pub mod email;
 pub mod ip;
 pub mod password;
+pub mod phone;
@@ -0,0 +1,404 @@
+//! # Breached passwords
+//! Checks passwords against a local copy of a breached password dataset in the format of
+//! [Pwned Passwords](https://haveibeenpwned.com/Passwords). Passwords are looked up by their SHA-1
+//! hash, nothing leaves the host.
+//!
+//! Two layouts of the dataset are supported:
+//! - A directory of range files as written by the Pwned Passwords downloader, one file per 5
+//!   character hash prefix (`00000.txt` to `FFFFF.txt`) with `<hash suffix>:<count>` lines. A
+//!   missing range file has no breached hashes.
+//! - A compact binary index built with [`write_index`], less than half the size of the text files.
+//!   It starts with [`INDEX_MAGIC`] and the end of every bucket of hashes with the same first 2
+//!   bytes, followed by the remaining 18 bytes of every hash in ascending order.
+//!
+//! Neither is loaded into memory, only the range file or bucket of a password is read when it is
+//! checked.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use data_encoding::{HEXUPPER, HEXUPPER_PERMISSIVE};
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+/// The first bytes of a binary index.
+pub const INDEX_MAGIC: &[u8; 8] = b"PWNDIDX1";
+
+/// Length of a SHA-1 hash in bytes.
+const HASH_LENGTH: usize = 20;
+
+/// Length of a hash in the index, the first 2 bytes are given by the bucket.
+const ENTRY_LENGTH: usize = HASH_LENGTH - 2;
+
+const BUCKETS: usize = 1 << 16;
+
+/// Length of the index header, the magic and the end of every bucket.
+const HEADER_LENGTH: u64 = (INDEX_MAGIC.len() + BUCKETS * 8) as u64;
+
+/// Number of hash prefixes in a directory of range files.
+const RANGES: u32 = 1 << 20;
+
+/// A SHA-1 hash of a password.
+pub type Hash = [u8; HASH_LENGTH];
+
+#[derive(Debug, Error)]
+pub enum BreachedError {
+    #[error("failed to read breached passwords: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("invalid breached passwords dataset: {0}")]
+    InvalidDataset(String),
+
+    /// The hashes passed to [`write_index`] are not sorted.
+    #[error("hashes are not in ascending order")]
+    Unsorted,
+}
+
+/// A breached password dataset on disk.
+pub struct BreachedPasswords {
+    path: PathBuf,
+    source: Source,
+}
+
+enum Source {
+    Ranges,
+    Index {
+        file: Mutex<File>,
+
+        /// The number of hashes up to and including every bucket.
+        bucket_ends: Vec<u64>,
+    },
+}
+
+impl BreachedPasswords {
+    /// Open a directory of range files or a binary index.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BreachedError> {
+        let path = path.as_ref().to_owned();
+        if path.is_dir() {
+            return Ok(Self {
+                path,
+                source: Source::Ranges,
+            });
+        }
+
+        let mut file = File::open(&path)?;
+        let mut header = vec![0u8; HEADER_LENGTH as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| invalid("header is truncated"))?;
+
+        if &header[..INDEX_MAGIC.len()] != INDEX_MAGIC {
+            return Err(invalid("not a breached password index"));
+        }
+
+        let bucket_ends: Vec<u64> = header[INDEX_MAGIC.len()..]
+            .chunks_exact(8)
+            .map(|end| u64::from_le_bytes(end.try_into().unwrap()))
+            .collect();
+        if bucket_ends.windows(2).any(|ends| ends[0] > ends[1]) {
+            return Err(invalid("buckets are not in ascending order"));
+        }
+
+        let length = bucket_ends[BUCKETS - 1]
+            .checked_mul(ENTRY_LENGTH as u64)
+            .and_then(|length| length.checked_add(HEADER_LENGTH));
+        if length != Some(file.metadata()?.len()) {
+            return Err(invalid("length does not match the number of hashes"));
+        }
+
+        Ok(Self {
+            path,
+            source: Source::Index {
+                file: Mutex::new(file),
+                bucket_ends,
+            },
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check whether a password is in the dataset.
+    pub fn contains(&self, password: &str) -> Result<bool, BreachedError> {
+        self.contains_hash(&Sha1::digest(password.as_bytes()).into())
+    }
+
+    /// Check whether the SHA-1 hash of a password is in the dataset.
+    pub fn contains_hash(&self, hash: &Hash) -> Result<bool, BreachedError> {
+        match &self.source {
+            Source::Ranges => self.range_contains(hash),
+            Source::Index { file, bucket_ends } => index_contains(file, bucket_ends, hash),
+        }
+    }
+
+    fn range_contains(&self, hash: &Hash) -> Result<bool, BreachedError> {
+        let hash = HEXUPPER.encode(hash);
+        let (prefix, suffix) = hash.split_at(5);
+
+        let file = match File::open(self.path.join(format!("{}.txt", prefix))) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+
+        for line in BufReader::new(file).lines() {
+            if let Some((line_suffix, count)) = line?.trim().split_once(':') {
+                // Padding entries have a count of 0
+                if line_suffix.eq_ignore_ascii_case(suffix) && count != "0" {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+impl fmt::Debug for BreachedPasswords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self.source {
+            Source::Ranges => "ranges",
+            Source::Index { .. } => "index",
+        };
+
+        f.debug_struct("BreachedPasswords")
+            .field("path", &self.path)
+            .field("format", &format)
+            .finish()
+    }
+}
+
+fn invalid(reason: &str) -> BreachedError {
+    BreachedError::InvalidDataset(reason.to_owned())
+}
+
+fn bucket(hash: &Hash) -> usize {
+    usize::from(u16::from_be_bytes([hash[0], hash[1]]))
+}
+
+fn index_contains(
+    file: &Mutex<File>,
+    bucket_ends: &[u64],
+    hash: &Hash,
+) -> Result<bool, BreachedError> {
+    let bucket = bucket(hash);
+    let start = if bucket == 0 {
+        0
+    } else {
+        bucket_ends[bucket - 1]
+    };
+    let end = bucket_ends[bucket];
+
+    let mut entries = vec![0u8; (end - start) as usize * ENTRY_LENGTH];
+    {
+        // Every read seeks first, a panic of another reader leaves nothing to clean up
+        let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+        file.seek(SeekFrom::Start(HEADER_LENGTH + start * ENTRY_LENGTH as u64))?;
+        file.read_exact(&mut entries)?;
+    }
+
+    let suffix = &hash[2..];
+    let (mut low, mut high) = (0, entries.len() / ENTRY_LENGTH);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match entries[middle * ENTRY_LENGTH..(middle + 1) * ENTRY_LENGTH].cmp(suffix) {
+            std::cmp::Ordering::Less => low = middle + 1,
+            std::cmp::Ordering::Greater => high = middle,
+            std::cmp::Ordering::Equal => return Ok(true),
+        }
+    }
+
+    Ok(false)
+}
+
+/// Read the hashes of a text dataset in the order they are stored, either a single file of
+/// `<hash>:<count>` lines or a directory of range files. Entries with a count of 0 are skipped.
+pub fn read_hashes(
+    path: impl AsRef<Path>,
+) -> Result<Box<dyn Iterator<Item = Result<Hash, BreachedError>>>, BreachedError> {
+    let path = path.as_ref().to_owned();
+    if !path.is_dir() {
+        return Ok(Box::new(parse_lines(File::open(path)?, String::new())));
+    }
+
+    let hashes = (0..RANGES).flat_map(move |prefix| {
+        let prefix = format!("{:05X}", prefix);
+        let hashes: Box<dyn Iterator<Item = Result<Hash, BreachedError>>> =
+            match File::open(path.join(format!("{}.txt", prefix))) {
+                Ok(file) => Box::new(parse_lines(file, prefix)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Box::new(std::iter::empty()),
+                Err(e) => Box::new(std::iter::once(Err(e.into()))),
+            };
+        hashes
+    });
+
+    Ok(Box::new(hashes))
+}
+
+/// Parse the lines of a text file, `prefix` is prepended to the hash of every line.
+fn parse_lines(file: File, prefix: String) -> impl Iterator<Item = Result<Hash, BreachedError>> {
+    BufReader::new(file)
+        .lines()
+        .filter_map(move |line| match line {
+            Ok(line) => parse_line(&format!("{}{}", prefix, line.trim())).transpose(),
+            Err(e) => Some(Err(e.into())),
+        })
+}
+
+/// Parse a `<hash>:<count>` line, returns `None` for empty lines and padding entries.
+fn parse_line(line: &str) -> Result<Option<Hash>, BreachedError> {
+    if line.is_empty() {
+        return Ok(None);
+    }
+
+    let (hash, count) = line.split_once(':').unwrap_or((line, ""));
+    if count == "0" {
+        return Ok(None);
+    }
+
+    HEXUPPER_PERMISSIVE
+        .decode(hash.as_bytes())
+        .ok()
+        .and_then(|hash| Hash::try_from(hash).ok())
+        .map(Some)
+        .ok_or_else(|| BreachedError::InvalidDataset(format!("invalid line: {}", line)))
+}
+
+/// Write a binary index of hashes in ascending order, as returned by [`read_hashes`]. Duplicate
+/// hashes are only written once. Returns the number of written hashes.
+pub fn write_index<W: Write + Seek>(
+    hashes: impl IntoIterator<Item = Result<Hash, BreachedError>>,
+    mut writer: W,
+) -> Result<u64, BreachedError> {
+    // The bucket ends are filled in once every hash is written
+    writer.write_all(INDEX_MAGIC)?;
+    writer.write_all(&vec![0u8; BUCKETS * 8])?;
+
+    let mut bucket_ends = vec![0u64; BUCKETS];
+    let mut count = 0;
+    let mut previous: Option<Hash> = None;
+    for hash in hashes {
+        let hash = hash?;
+        match previous {
+            Some(previous) if hash < previous => return Err(BreachedError::Unsorted),
+            Some(previous) if hash == previous => continue,
+            _ => {}
+        }
+
+        writer.write_all(&hash[2..])?;
+        count += 1;
+        bucket_ends[bucket(&hash)] = count;
+        previous = Some(hash);
+    }
+
+    // Empty buckets end where the previous bucket ends
+    let mut end = 0;
+    for bucket_end in bucket_ends.iter_mut() {
+        end = end.max(*bucket_end);
+        *bucket_end = end;
+    }
+
+    writer.seek(SeekFrom::Start(INDEX_MAGIC.len() as u64))?;
+    for end in bucket_ends {
+        writer.write_all(&end.to_le_bytes())?;
+    }
+    writer.flush()?;
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-1 of "password".
+    const PASSWORD_HASH: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("breached-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    /// Write a directory of range files with the hash of "password", a padding entry of
+    /// "password1" and an unrelated hash.
+    fn write_ranges(path: &Path) {
+        std::fs::create_dir(path).unwrap();
+        std::fs::write(
+            path.join("5BAA6.txt"),
+            format!(
+                "003D68EB55068C33ACE09247EE4C639306B:3\r\n{}:9545824\r\n",
+                &PASSWORD_HASH[5..]
+            ),
+        )
+        .unwrap();
+        // SHA-1 of "password1" is E38AD214943DAAD1D64C102FAEC29DE4AFE9DA3D
+        std::fs::write(
+            path.join("E38AD.txt"),
+            "214943DAAD1D64C102FAEC29DE4AFE9DA3D:0\r\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_ranges() {
+        let path = temp_path("ranges");
+        write_ranges(&path);
+
+        let breached = BreachedPasswords::open(&path).unwrap();
+        assert!(breached.contains("password").unwrap());
+        assert!(!breached.contains("password1").unwrap());
+        assert!(!breached.contains("correct horse battery staple").unwrap());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_index() {
+        let ranges = temp_path("index-ranges");
+        write_ranges(&ranges);
+        let path = temp_path("index");
+
+        let count =
+            write_index(read_hashes(&ranges).unwrap(), File::create(&path).unwrap()).unwrap();
+        assert_eq!(count, 2);
+
+        let breached = BreachedPasswords::open(&path).unwrap();
+        assert!(breached.contains("password").unwrap());
+        assert!(!breached.contains("password1").unwrap());
+        assert!(!breached.contains("correct horse battery staple").unwrap());
+
+        std::fs::remove_dir_all(ranges).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_unsorted() {
+        let hashes = [PASSWORD_HASH, "003D68EB55068C33ACE09247EE4C639306B00000"]
+            .map(|hash| Ok(parse_line(hash).unwrap().unwrap()));
+
+        assert!(matches!(
+            write_index(hashes, io::Cursor::new(Vec::new())),
+            Err(BreachedError::Unsorted)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_index() {
+        let path = temp_path("invalid");
+        std::fs::write(&path, b"not an index").unwrap();
+
+        assert!(matches!(
+            BreachedPasswords::open(&path),
+            Err(BreachedError::InvalidDataset(_))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::warn;
+
+use super::breached::BreachedPasswords;
 
 /// Provide password requirements configuration to organizations (e.g. min length, max length, etc.)
 ///
@@ -24,6 +27,9 @@ pub struct PasswordRequirements {
 
     /// Minimum zxcvbn score required for password
     pub min_zxcvbn_score: u8,
+
+    /// Reject passwords found in the breached password dataset, if one is given
+    pub check_breached: bool,
 }
 
 impl Default for PasswordRequirements {
@@ -38,6 +44,7 @@ impl Default for PasswordRequirements {
             min_numbers: 0,
             min_symbols: 0,
             min_zxcvbn_score: 3,
+            check_breached: true,
         }
     }
 }
@@ -58,6 +65,8 @@ pub enum PasswordValidationError {
     SymbolCount(usize, usize),
     #[error("password not strong enough")]
     NotStrongEnough,
+    #[error("password has appeared in a data breach")]
+    Breached,
 }
 
 /// Validates a password according to the provided requirements.
@@ -71,11 +80,14 @@ pub enum PasswordValidationError {
 /// * `requirements` - A `PasswordRequirements` object that specifies the rules the password must follow.
 /// * `password` - A string slice that contains the password to validate.
 /// * `user_inputs` - A vector of strings that the password should not contain. (ex. email)
+/// * `breached_passwords` - The breached password dataset, checked if `requirements.check_breached` is set. A
+///   dataset that can not be read is logged and skipped.
 pub fn validate_password(
     password: &str,
     user_inputs: &[&str],
     check_strength: bool,
     requirements: PasswordRequirements,
+    breached_passwords: Option<&BreachedPasswords>,
 ) -> Result<(), Vec<PasswordValidationError>> {
     // validate password, return all errors
     let mut valid_password = true;
@@ -166,6 +178,18 @@ pub fn validate_password(
         }
     }
 
+    // Check against the breached password dataset
+    if let Some(breached_passwords) = breached_passwords.filter(|_| requirements.check_breached) {
+        match breached_passwords.contains(password) {
+            Ok(true) => {
+                valid_password = false;
+                errors.push(PasswordValidationError::Breached);
+            }
+            Ok(false) => {}
+            Err(e) => warn!("failed to check breached passwords: {}", e),
+        }
+    }
+
     // If password is not valid, return all errors
     if !valid_password {
         return Err(errors);
@@ -0,0 +1,87 @@
+/// Maximum number of digits in an E.164 phone number, including the country code.
+const MAX_DIGITS: usize = 15;
+
+/// Minimum number of digits accepted, shorter numbers are not routable internationally.
+const MIN_DIGITS: usize = 8;
+
+/// Normalizes a phone number to the E.164 format (`+46701234567`).
+///
+/// Spaces, dashes, dots and parentheses used to group digits are removed. The number must start
+/// with `+` followed by the country code, returns `None` if it is not a valid number.
+///
+/// # Example
+///
+/// ```
+/// use crypto::input::phone::normalize_phone_number;
+///
+/// assert_eq!(
+///     normalize_phone_number("+46 (70) 123-45 67"),
+///     Some("+46701234567".to_owned())
+/// );
+/// assert_eq!(normalize_phone_number("070-123 45 67"), None);
+/// ```
+#[must_use]
+pub fn normalize_phone_number(val: &str) -> Option<String> {
+    let digits = val.trim().strip_prefix('+')?;
+
+    let mut normalized = String::with_capacity(MAX_DIGITS + 1);
+    normalized.push('+');
+    for c in digits.chars() {
+        match c {
+            '0'..='9' => normalized.push(c),
+            ' ' | '-' | '.' | '(' | ')' => {}
+            _ => return None,
+        }
+    }
+
+    // Country codes never start with a zero
+    let len = normalized.len() - 1;
+    if !(MIN_DIGITS..=MAX_DIGITS).contains(&len) || normalized[1..].starts_with('0') {
+        return None;
+    }
+
+    Some(normalized)
+}
+
+/// Validates whether the given string is a phone number in the E.164 format, see
+/// [`normalize_phone_number`].
+#[must_use]
+pub fn validate_phone_number(val: &str) -> bool {
+    normalize_phone_number(val).map_or(false, |normalized| normalized == val)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_phone_number() {
+        let tests = vec![
+            ("+46701234567", Some("+46701234567")),
+            (" +1 (555) 010-0199 ", Some("+15550100199")),
+            ("+44.20.7946.0018", Some("+442079460018")),
+            ("0701234567", None),
+            ("+0701234567", None),
+            ("+4670123456789012", None),
+            ("+4670", None),
+            ("+46 70 abc 45 67", None),
+            ("", None),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(
+                normalize_phone_number(input).as_deref(),
+                expected,
+                "Phone number `{}` was not normalized correctly",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_phone_number() {
+        assert!(validate_phone_number("+46701234567"));
+        assert!(!validate_phone_number("+46 70 123 45 67"));
+        assert!(!validate_phone_number("46701234567"));
+    }
+}
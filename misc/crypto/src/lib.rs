@@ -1,5 +1,6 @@
 //! crypto is a Rust library that provides cryptographic utilities, such as password hashing and verification
-//! using the Argon2 algorithm, as well as Time-based One-Time Password (TOTP) generation and verification.
+//! using the Argon2 algorithm, Time-based One-Time Password (TOTP) generation and verification, as well as
+//! authenticated encryption of data at rest.
 //!
 //! # Usage
 //!
@@ -34,19 +35,20 @@
 //!
 //! ```
 //! use data_encoding::BASE32_NOPAD;
-//! use crypto::totp::{generate_totp, verify_totp};
+//! use crypto::totp::{generate_totp, verify_totp, Algorithm};
 //!
 //! let secret = "JBSWY3DPEHPK3PXP";
 //! let secret = BASE32_NOPAD.encode(secret.as_bytes());
 //! let interval = 30;
 //! let tolerance = Some(1);
 //!
-//! let totp = generate_totp(&secret.as_bytes(), interval).unwrap();
-//! let is_valid = verify_totp(&totp, &secret.as_bytes(), interval, tolerance).unwrap();
+//! let totp = generate_totp(&secret.as_bytes(), interval, Algorithm::Sha1, 6).unwrap();
+//! let is_valid = verify_totp(&totp, &secret.as_bytes(), interval, Algorithm::Sha1, 6, tolerance).unwrap();
 //!
 //! assert!(is_valid);
 //! ````
 
+pub mod aead;
 pub mod extended_select;
 pub mod input;
 pub mod password;
@@ -1,16 +1,69 @@
 //! A module for handling password hashing and verification using the Argon2 password hashing
 //! algorithm with salt generation.
+//!
+//! Hashes created by other systems, e.g. of imported users, are identified with [`identify_hash`].
+//! Besides argon2 with any parameters, bcrypt, scrypt and PBKDF2-SHA256 hashes are verified. Use
+//! [`needs_rehash`] after a successful verification to replace such hashes with an argon2 hash of
+//! the current parameters.
+//!
+//! The argon2 costs and an optional pepper of new hashes are set with a [`HashConfig`], the free
+//! functions use the default costs without a pepper.
+//!
+//! # Pepper
+//! A pepper is a secret key held outside of the database. The password is first hashed with
+//! HMAC-SHA256 keyed with the pepper, the argon2 hash then records the id of the pepper in its
+//! `keyid` parameter. New hashes use the primary pepper, the other peppers are only used to verify
+//! hashes created before a rotation.
 
-use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use argon2::{
+    password_hash::{self, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use pbkdf2::Pbkdf2;
 use rand::rngs::OsRng;
+use scrypt::Scrypt;
+use sha2::Sha256;
 use thiserror::Error;
 
+/// Minimum length of a pepper in bytes.
+pub const MIN_PEPPER_LENGTH: usize = 32;
+
+/// Highest memory cost tried by [`benchmark`] in KiB, 1 GiB.
+const MAX_BENCHMARK_M_COST: u32 = 1024 * 1024;
+
+/// Highest iteration cost tried by [`benchmark`].
+const MAX_BENCHMARK_T_COST: u32 = 10;
+
 /// An error type for hashing and verification of passwords.
 #[derive(Error, Debug)]
 pub enum CryptoError {
     #[error("Invalid password hash: {0}")]
     InvalidPasswordHash(String, argon2::password_hash::Error),
 
+    #[error("Invalid bcrypt hash: {0}")]
+    InvalidBcryptHash(bcrypt::BcryptError),
+
+    #[error("Unsupported password hash")]
+    UnsupportedPasswordHash,
+
+    #[error("Invalid argon2 parameters: {0}")]
+    InvalidParams(argon2::Error),
+
+    /// The pepper is shorter than [`MIN_PEPPER_LENGTH`] or the pepper id is malformed.
+    #[error("Invalid pepper")]
+    InvalidPepper,
+
+    /// The hash was created with a pepper that is not configured.
+    #[error("Unknown pepper id: {0}")]
+    UnknownPepper(String),
+
     #[error("Hash Error")]
     HashError(argon2::password_hash::Error),
 
@@ -18,7 +71,241 @@ pub enum CryptoError {
     NotMatching,
 }
 
-/// Hashes and salts a password using Argon2, and returns the resulting hash as a PHC-formatted string.
+/// The argon2 costs and peppers used to hash passwords.
+#[derive(Clone)]
+pub struct HashConfig {
+    params: argon2::Params,
+
+    primary_pepper_id: Option<String>,
+    peppers: HashMap<String, Vec<u8>>,
+}
+
+impl Default for HashConfig {
+    fn default() -> Self {
+        Self::new(argon2::Params::default())
+    }
+}
+
+impl HashConfig {
+    /// Creates a config without peppers.
+    pub fn new(params: argon2::Params) -> Self {
+        Self {
+            params,
+            primary_pepper_id: None,
+            peppers: HashMap::new(),
+        }
+    }
+
+    /// Creates a config with the given memory cost in KiB, number of iterations and degree of
+    /// parallelism.
+    pub fn with_costs(m_cost: u32, t_cost: u32, p_cost: u32) -> Result<Self, CryptoError> {
+        let params = argon2::Params::new(m_cost, t_cost, p_cost, None)
+            .map_err(CryptoError::InvalidParams)?;
+
+        Ok(Self::new(params))
+    }
+
+    /// Adds a pepper, the first pepper becomes the primary pepper. Pepper ids are at most 8 bytes
+    /// long.
+    pub fn add_pepper<C>(&mut self, pepper_id: C, pepper: &[u8]) -> Result<&mut Self, CryptoError>
+    where
+        C: Into<String>,
+    {
+        let pepper_id = pepper_id.into();
+        if pepper_id.is_empty()
+            || pepper_id.len() > argon2::Params::MAX_KEYID_LEN
+            || pepper_id.contains([':', ','])
+            || pepper.len() < MIN_PEPPER_LENGTH
+        {
+            return Err(CryptoError::InvalidPepper);
+        }
+
+        if self.primary_pepper_id.is_none() {
+            self.primary_pepper_id = Some(pepper_id.clone());
+        }
+        self.peppers.insert(pepper_id, pepper.to_vec());
+
+        Ok(self)
+    }
+
+    /// Adds the peppers of a comma separated list of `<pepper id>:<base64 pepper>` pairs, the
+    /// first pepper in the list becomes the primary pepper.
+    pub fn parse_peppers(&mut self, value: &str) -> Result<&mut Self, CryptoError> {
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (pepper_id, pepper) = pair.split_once(':').ok_or(CryptoError::InvalidPepper)?;
+            let pepper = STANDARD
+                .decode(pepper)
+                .map_err(|_| CryptoError::InvalidPepper)?;
+
+            self.add_pepper(pepper_id, &pepper)?;
+        }
+
+        Ok(self)
+    }
+
+    pub fn params(&self) -> &argon2::Params {
+        &self.params
+    }
+
+    /// Returns the id of the pepper that new hashes are created with.
+    pub fn primary_pepper_id(&self) -> Option<&str> {
+        self.primary_pepper_id.as_deref()
+    }
+
+    /// Hashes and salts a password using Argon2 with the configured costs and the primary pepper,
+    /// and returns the resulting hash as a PHC-formatted string.
+    ///
+    /// # Arguments
+    ///
+    /// * `password` - The password to be hashed and salted.
+    pub fn hash_password(&self, password: &str) -> Result<String, CryptoError> {
+        // Generate salt
+        let salt = SaltString::generate(&mut OsRng);
+
+        let mut params = argon2::ParamsBuilder::new();
+        params
+            .m_cost(self.params.m_cost())
+            .t_cost(self.params.t_cost())
+            .p_cost(self.params.p_cost());
+
+        let password = match &self.primary_pepper_id {
+            Some(pepper_id) => {
+                let key_id =
+                    argon2::KeyId::new(pepper_id.as_bytes()).map_err(CryptoError::InvalidParams)?;
+                params.keyid(key_id);
+
+                self.pepper(pepper_id, password)?
+            }
+            None => password.as_bytes().to_vec(),
+        };
+
+        let params = params.build().map_err(CryptoError::InvalidParams)?;
+        let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+        // Hash password to PHC string ($argon2id$v=19$...)
+        let password_hash = argon2
+            .hash_password(&password, &salt)
+            .map_err(CryptoError::HashError)?
+            .to_string();
+
+        Ok(password_hash)
+    }
+
+    /// Verifies a password against a given password hash. Returns () if the password matches the
+    /// hash.
+    ///
+    /// Argon2 hashes are verified with the parameters and pepper of the hash, the other
+    /// algorithms of [`identify_hash`] are verified as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `password` - The password to be verified.
+    /// * `password_hash` - The PHC-formatted or bcrypt password hash string to verify against.
+    pub fn verify_password(&self, password: &str, password_hash: &str) -> Result<(), CryptoError> {
+        let algorithm = identify_hash(password_hash).ok_or(CryptoError::UnsupportedPasswordHash)?;
+
+        if algorithm == HashAlgorithm::Bcrypt {
+            return match bcrypt::verify(password, password_hash) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(CryptoError::NotMatching),
+                Err(e) => Err(CryptoError::InvalidBcryptHash(e)),
+            };
+        }
+
+        let hash = PasswordHash::new(password_hash)
+            .map_err(|e| CryptoError::InvalidPasswordHash(password_hash.to_string(), e))?;
+
+        let result = match algorithm {
+            HashAlgorithm::Argon2 => match pepper_id(&hash) {
+                Some(pepper_id) => {
+                    let password = self.pepper(&pepper_id, password)?;
+                    Argon2::default().verify_password(&password, &hash)
+                }
+                None => Argon2::default().verify_password(password.as_bytes(), &hash),
+            },
+            _ => hash.verify_password(&[&Scrypt, &Pbkdf2], password),
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(password_hash::Error::Password) => Err(CryptoError::NotMatching),
+            Err(e) => Err(CryptoError::InvalidPasswordHash(
+                password_hash.to_string(),
+                e,
+            )),
+        }
+    }
+
+    /// Whether a password hash is weaker than the hashes of [`HashConfig::hash_password`], i.e. it
+    /// is not an argon2id hash with at least the configured memory, iteration and parallelism
+    /// costs or it was not created with the primary pepper.
+    ///
+    /// Call after the password was verified, the password is then hashed again and the hash
+    /// replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `password_hash` - The password hash string to check.
+    pub fn needs_rehash(&self, password_hash: &str) -> bool {
+        let hash = match PasswordHash::new(password_hash) {
+            Ok(hash) if hash.algorithm == argon2::Algorithm::Argon2id.ident() => hash,
+            _ => return true,
+        };
+
+        let params = match argon2::Params::try_from(&hash) {
+            Ok(params) => params,
+            Err(_) => return true,
+        };
+
+        hash.version != Some(argon2::Version::V0x13.into())
+            || params.m_cost() < self.params.m_cost()
+            || params.t_cost() < self.params.t_cost()
+            || params.p_cost() < self.params.p_cost()
+            || params
+                .output_len()
+                .unwrap_or(argon2::Params::DEFAULT_OUTPUT_LEN)
+                < argon2::Params::DEFAULT_OUTPUT_LEN
+            || pepper_id(&hash).as_deref() != self.primary_pepper_id()
+    }
+
+    /// HMAC-SHA256 of the password keyed with a pepper.
+    fn pepper(&self, pepper_id: &str, password: &str) -> Result<Vec<u8>, CryptoError> {
+        let pepper = self
+            .peppers
+            .get(pepper_id)
+            .ok_or_else(|| CryptoError::UnknownPepper(pepper_id.to_owned()))?;
+
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(pepper).expect("HMAC can take a key of any size");
+        mac.update(password.as_bytes());
+
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+}
+
+impl std::fmt::Debug for HashConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the peppers themselves
+        f.debug_struct("HashConfig")
+            .field("params", &self.params)
+            .field("primary_pepper_id", &self.primary_pepper_id)
+            .field("pepper_ids", &self.peppers.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// The id of the pepper an argon2 hash was created with, if any.
+fn pepper_id(hash: &PasswordHash) -> Option<String> {
+    let params = argon2::Params::try_from(hash).ok()?;
+
+    match params.keyid() {
+        [] => None,
+        key_id => Some(String::from_utf8_lossy(key_id).into_owned()),
+    }
+}
+
+/// Hashes and salts a password using Argon2 with the default costs, and returns the resulting hash
+/// as a PHC-formatted string.
 ///
 /// # Arguments
 ///
@@ -33,39 +320,332 @@ pub enum CryptoError {
 /// let password_hash = hash_and_salt_password(password).unwrap();
 /// ```
 pub fn hash_and_salt_password(password: &str) -> Result<String, CryptoError> {
-    // Generate salt
-    let salt = SaltString::generate(&mut OsRng);
+    HashConfig::default().hash_password(password)
+}
+
+/// Verifies a password against a given password hash without a pepper, see
+/// [`HashConfig::verify_password`].
+///
+/// # Arguments
+///
+/// * `password` - The password to be verified.
+/// * `password_hash` - The PHC-formatted or bcrypt password hash string to verify against.
+pub fn verify_password(password: &str, password_hash: &str) -> Result<(), CryptoError> {
+    HashConfig::default().verify_password(password, password_hash)
+}
 
-    // Default argon configuration
-    let argon2 = Argon2::default();
+/// Whether a password hash is weaker than the hashes of [`hash_and_salt_password`], see
+/// [`HashConfig::needs_rehash`].
+///
+/// # Arguments
+///
+/// * `password_hash` - The password hash string to check.
+pub fn needs_rehash(password_hash: &str) -> bool {
+    HashConfig::default().needs_rehash(password_hash)
+}
 
-    // Hash password to PHC string ($argon2id$v=19$...)
-    let password_hash = argon2
-        .hash_password(password.as_bytes(), &salt)
-        .map_err(CryptoError::HashError)?
-        .to_string();
+/// Measures argon2id hashing on this host and recommends the highest costs whose hash takes at
+/// most `target`, together with the measured duration.
+///
+/// The memory cost is doubled from the default up to 1 GiB first, the number of iterations is then
+/// raised while the target is met. The default costs are recommended if they already take longer.
+///
+/// # Arguments
+///
+/// * `target` - The longest a single hash may take.
+/// * `p_cost` - The degree of parallelism, e.g. the number of cores available for hashing.
+pub fn benchmark(target: Duration, p_cost: u32) -> Result<(argon2::Params, Duration), CryptoError> {
+    let params = |m_cost, t_cost| {
+        argon2::Params::new(m_cost, t_cost, p_cost, None).map_err(CryptoError::InvalidParams)
+    };
+
+    let default = params(
+        argon2::Params::DEFAULT_M_COST,
+        argon2::Params::DEFAULT_T_COST,
+    )?;
+    let mut best = (default.clone(), measure(&default)?);
+
+    let mut m_cost = argon2::Params::DEFAULT_M_COST;
+    while m_cost * 2 <= MAX_BENCHMARK_M_COST {
+        let candidate = params(m_cost * 2, argon2::Params::DEFAULT_T_COST)?;
+        let duration = measure(&candidate)?;
+        if duration > target {
+            break;
+        }
+
+        m_cost *= 2;
+        best = (candidate, duration);
+    }
+
+    let mut t_cost = argon2::Params::DEFAULT_T_COST;
+    while t_cost < MAX_BENCHMARK_T_COST {
+        let candidate = params(m_cost, t_cost + 1)?;
+        let duration = measure(&candidate)?;
+        if duration > target {
+            break;
+        }
 
-    Ok(password_hash)
+        t_cost += 1;
+        best = (candidate, duration);
+    }
+
+    Ok(best)
+}
+
+/// The median duration of three hashes with the given parameters.
+fn measure(params: &argon2::Params) -> Result<Duration, CryptoError> {
+    let config = HashConfig::new(params.clone());
+
+    let mut durations = [Duration::ZERO; 3];
+    for duration in durations.iter_mut() {
+        let start = Instant::now();
+        config.hash_password("benchmark")?;
+        *duration = start.elapsed();
+    }
+
+    durations.sort();
+    Ok(durations[1])
+}
+
+/// The algorithm of a password hash, see [`identify_hash`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Argon2,
+    Bcrypt,
+    Scrypt,
+    Pbkdf2Sha256,
 }
 
-/// Verifies a password against a given password hash. Returns () if the password matches the hash.
+/// Identifies the algorithm of a password hash, e.g. one created by another system.
+///
+/// Argon2, scrypt and PBKDF2-SHA256 hashes are PHC-formatted strings (`$scrypt$ln=15,r=8,p=1$...`),
+/// bcrypt hashes use the modular crypt format (`$2b$12$...`). Returns `None` for malformed hashes
+/// and other algorithms.
 ///
 /// # Arguments
 ///
-/// * `password` - The password to be verified.
-/// * `password_hash` - The PHC-formatted password hash string to verify against.
-pub fn verify_password(password: &str, password_hash: &str) -> Result<(), CryptoError> {
-    // Default argon configuration
-    let argon2 = Argon2::default();
+/// * `password_hash` - The password hash to identify.
+pub fn identify_hash(password_hash: &str) -> Option<HashAlgorithm> {
+    if password_hash.starts_with("$2") {
+        return is_bcrypt_hash(password_hash).then_some(HashAlgorithm::Bcrypt);
+    }
 
-    let hash = PasswordHash::new(password_hash)
-        .map_err(|e| CryptoError::InvalidPasswordHash(password_hash.to_string(), e))?;
+    let hash = PasswordHash::new(password_hash).ok()?;
 
-    // Verify password
-    let is_valid = argon2.verify_password(password.as_bytes(), &hash).is_ok();
-    if !is_valid {
-        return Err(CryptoError::NotMatching);
+    // A hash without salt or output can not be verified
+    if hash.salt.is_none() || hash.hash.is_none() {
+        return None;
     }
 
-    Ok(())
+    match hash.algorithm.as_str() {
+        "argon2id" | "argon2i" | "argon2d" => Some(HashAlgorithm::Argon2),
+        "scrypt" => Some(HashAlgorithm::Scrypt),
+        "pbkdf2-sha256" => Some(HashAlgorithm::Pbkdf2Sha256),
+        _ => None,
+    }
+}
+
+/// Checks the `$2<variant>$<cost>$<salt and hash>` format of bcrypt, the salt and hash are 53
+/// characters of the bcrypt base64 alphabet.
+fn is_bcrypt_hash(password_hash: &str) -> bool {
+    let parts: Vec<&str> = password_hash.split('$').collect();
+    let (variant, cost, salt_and_hash) = match parts.as_slice() {
+        ["", variant, cost, salt_and_hash] => (*variant, *cost, *salt_and_hash),
+        _ => return false,
+    };
+
+    matches!(variant, "2a" | "2b" | "2x" | "2y")
+        && cost.len() == 2
+        && matches!(cost.parse::<u32>(), Ok(4..=31))
+        && salt_and_hash.len() == 53
+        && salt_and_hash
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_and_verify() {
+        let password_hash = hash_and_salt_password("password").unwrap();
+
+        assert!(verify_password("password", &password_hash).is_ok());
+        assert!(matches!(
+            verify_password("wrong password", &password_hash),
+            Err(CryptoError::NotMatching)
+        ));
+    }
+
+    #[test]
+    fn test_identify_hash() {
+        let argon2 = hash_and_salt_password("password").unwrap();
+        assert_eq!(identify_hash(&argon2), Some(HashAlgorithm::Argon2));
+
+        assert_eq!(
+            identify_hash("$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW"),
+            Some(HashAlgorithm::Bcrypt)
+        );
+        assert_eq!(
+            identify_hash("$scrypt$ln=16,r=8,p=1$aM15713r3Xsvxbi31lqr1Q$nFNh2CVHVjNldFVKDHDlm4CbdRSCdEBsjjJxD+iCs5E"),
+            Some(HashAlgorithm::Scrypt)
+        );
+        assert_eq!(
+            identify_hash("$pbkdf2-sha256$i=29000$N2ZMiZEyRijH2bsXsvaeUw$LxSXOmWJnPjhyxZCiiNMcqsTh/KG3ntiiOhTvu4r5MQ"),
+            Some(HashAlgorithm::Pbkdf2Sha256)
+        );
+
+        // Wrong cost, truncated hash, missing output and unsupported algorithms
+        assert_eq!(
+            identify_hash("$2b$99$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW"),
+            None
+        );
+        assert_eq!(identify_hash("$2b$12$R9h/cIPz0gi.URNNX3kh2O"), None);
+        assert_eq!(
+            identify_hash("$scrypt$ln=16,r=8,p=1$aM15713r3Xsvxbi31lqr1Q"),
+            None
+        );
+        assert_eq!(identify_hash("$1$saltsalt$qjXMvbEw8oaL.CzflDugX/"), None);
+        assert_eq!(identify_hash("5f4dcc3b5aa765d61d8327deb882cf99"), None);
+    }
+
+    #[test]
+    fn test_verify_foreign_hashes() {
+        let salt = SaltString::generate(&mut OsRng);
+
+        let bcrypt = bcrypt::hash("password", 4).unwrap();
+        let scrypt = Scrypt
+            .hash_password_customized(
+                b"password",
+                None,
+                None,
+                scrypt::Params::new(4, 8, 1, 32).unwrap(),
+                &salt,
+            )
+            .unwrap()
+            .to_string();
+        let pbkdf2 = Pbkdf2
+            .hash_password_customized(
+                b"password",
+                Some(pbkdf2::Algorithm::Pbkdf2Sha256.ident()),
+                None,
+                pbkdf2::Params {
+                    rounds: 1000,
+                    output_length: 32,
+                },
+                &salt,
+            )
+            .unwrap()
+            .to_string();
+        let argon2 = Argon2::new(
+            argon2::Algorithm::Argon2i,
+            argon2::Version::V0x10,
+            argon2::Params::new(1024, 1, 1, None).unwrap(),
+        )
+        .hash_password(b"password", &salt)
+        .unwrap()
+        .to_string();
+
+        for password_hash in [&bcrypt, &scrypt, &pbkdf2, &argon2] {
+            assert!(verify_password("password", password_hash).is_ok());
+            assert!(matches!(
+                verify_password("wrong password", password_hash),
+                Err(CryptoError::NotMatching)
+            ));
+            assert!(needs_rehash(password_hash));
+        }
+
+        assert!(matches!(
+            verify_password("password", "5f4dcc3b5aa765d61d8327deb882cf99"),
+            Err(CryptoError::UnsupportedPasswordHash)
+        ));
+    }
+
+    #[test]
+    fn test_needs_rehash() {
+        let password_hash = hash_and_salt_password("password").unwrap();
+        assert!(!needs_rehash(&password_hash));
+
+        // Lower memory cost than the default
+        let salt = SaltString::generate(&mut OsRng);
+        let weak = Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            argon2::Params::new(4096, 2, 1, None).unwrap(),
+        )
+        .hash_password(b"password", &salt)
+        .unwrap()
+        .to_string();
+        assert!(needs_rehash(&weak));
+    }
+
+    #[test]
+    fn test_pepper() {
+        let mut config = HashConfig::with_costs(1024, 1, 1).unwrap();
+        config.add_pepper("1", &[1; 32]).unwrap();
+
+        let password_hash = config.hash_password("password").unwrap();
+        assert!(password_hash.contains("keyid="));
+        assert!(config.verify_password("password", &password_hash).is_ok());
+        assert!(matches!(
+            config.verify_password("wrong password", &password_hash),
+            Err(CryptoError::NotMatching)
+        ));
+        assert!(!config.needs_rehash(&password_hash));
+
+        // The hash can not be verified without the pepper
+        assert!(matches!(
+            verify_password("password", &password_hash),
+            Err(CryptoError::UnknownPepper(_))
+        ));
+
+        // After a rotation the hash is still verified but has to be replaced
+        let mut rotated = HashConfig::with_costs(1024, 1, 1).unwrap();
+        rotated.add_pepper("2", &[2; 32]).unwrap();
+        rotated.add_pepper("1", &[1; 32]).unwrap();
+        assert!(rotated.verify_password("password", &password_hash).is_ok());
+        assert!(rotated.needs_rehash(&password_hash));
+
+        // Hashes without a pepper are replaced once a pepper is configured
+        let unpeppered = HashConfig::with_costs(1024, 1, 1)
+            .unwrap()
+            .hash_password("password")
+            .unwrap();
+        assert!(config.needs_rehash(&unpeppered));
+
+        assert!(HashConfig::default().add_pepper("1", &[1; 16]).is_err());
+        assert!(HashConfig::default()
+            .add_pepper("too long id", &[1; 32])
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse_peppers() {
+        let mut config = HashConfig::default();
+        config
+            .parse_peppers(
+                "new:AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=, old:AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgI=",
+            )
+            .unwrap();
+
+        assert_eq!(config.primary_pepper_id(), Some("new"));
+        assert!(HashConfig::default().parse_peppers("invalid").is_err());
+    }
+
+    #[test]
+    fn test_configured_costs() {
+        let config = HashConfig::with_costs(2048, 3, 1).unwrap();
+        let password_hash = config.hash_password("password").unwrap();
+
+        assert!(password_hash.contains("m=2048,t=3,p=1"));
+        assert!(verify_password("password", &password_hash).is_ok());
+
+        // Weaker than the default memory cost, but not than the configured costs
+        assert!(needs_rehash(&password_hash));
+        assert!(!config.needs_rehash(&password_hash));
+        assert!(HashConfig::with_costs(4096, 3, 1)
+            .unwrap()
+            .needs_rehash(&password_hash));
+    }
 }
@@ -3,10 +3,15 @@
 
 use chrono::{DateTime, Utc};
 pub use data_encoding::BASE32_NOPAD;
-use hmac::{Hmac, Mac};
+use hmac::{digest::KeyInit, Hmac, Mac};
 
 use rand::{distributions::Alphanumeric, Rng};
 use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+mod uri;
+
+pub use uri::*;
 
 /// An error type for TOTP-related errors.
 #[derive(Debug, thiserror::Error)]
@@ -14,6 +19,62 @@ pub enum Error {
     /// An error indicating that the provided secret is invalid.
     #[error("Invalid secret")]
     InvalidSecret,
+
+    /// An error indicating that the algorithm is not supported.
+    #[error("Invalid algorithm")]
+    InvalidAlgorithm,
+
+    /// An error indicating that the number of digits is not supported.
+    #[error("Invalid number of digits")]
+    InvalidDigits,
+
+    /// An error indicating that a QR code could not be rendered.
+    #[error("Failed to render QR code")]
+    QrCode,
+}
+
+/// Minimum number of digits of a generated code.
+pub const MIN_DIGITS: u32 = 6;
+
+/// Maximum number of digits of a generated code.
+pub const MAX_DIGITS: u32 = 8;
+
+/// Minimum interval in seconds during which a code is valid.
+pub const MIN_INTERVAL: u32 = 15;
+
+/// Maximum interval in seconds during which a code is valid.
+pub const MAX_INTERVAL: u32 = 300;
+
+/// The HMAC algorithm used to generate codes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl std::fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Algorithm::Sha1 => write!(f, "SHA1"),
+            Algorithm::Sha256 => write!(f, "SHA256"),
+            Algorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "SHA1" => Ok(Algorithm::Sha1),
+            "SHA256" => Ok(Algorithm::Sha256),
+            "SHA512" => Ok(Algorithm::Sha512),
+            _ => Err(Error::InvalidAlgorithm),
+        }
+    }
 }
 
 impl From<data_encoding::DecodeError> for Error {
@@ -36,34 +97,41 @@ impl From<sha1::digest::InvalidLength> for Error {
 ///
 /// # Arguments
 ///
-/// * `secret` - A byte slice representing the secret key, usually shared between the server and the client.
-/// * `interval` - A `u64` representing the time interval (in seconds) during which the generated TOTP is valid.
+/// * `secret` - A byte slice representing the secret key (encoded with base32), usually shared between the server and the client.
+/// * `interval` - A `u32` representing the time interval (in seconds) during which the generated TOTP is valid.
+/// * `algorithm` - The HMAC algorithm used to generate the TOTP.
+/// * `digits` - The number of digits of the TOTP, between 6 and 8.
 ///
 /// # Examples
 ///
 /// ```
 /// use data_encoding::BASE32_NOPAD;
-/// use crypto::totp::generate_totp;
+/// use crypto::totp::{generate_totp, Algorithm};
 ///
 /// let secret = "JBSWY3DPEHPK3PXP";
 /// let secret = BASE32_NOPAD.encode(secret.as_bytes());
 /// let interval = 30;
 ///
-/// let totp = generate_totp(&secret.as_bytes(), interval).unwrap();
+/// let totp = generate_totp(&secret.as_bytes(), interval, Algorithm::Sha1, 6).unwrap();
 /// println!("Generated TOTP: {}", totp);
 /// ```
 ///
 /// # Returns
 ///
 /// A `Result<String, totp::Error>` containing the generated TOTP or an error.
-pub fn generate_totp(secret: &[u8], interval: u32) -> Result<String, Error> {
+pub fn generate_totp(
+    secret: &[u8],
+    interval: u32,
+    algorithm: Algorithm,
+    digits: u32,
+) -> Result<String, Error> {
     let secret = BASE32_NOPAD.decode(secret)?; // Decode the secret to bytes
 
     let utc: DateTime<Utc> = Utc::now();
     let current_time = utc.timestamp() as u64;
     let counter = current_time / interval as u64;
 
-    generate_hotp(&secret, counter)
+    generate_hotp(&secret, counter, algorithm, digits)
 }
 
 /// Generates an HMAC-based One-Time Password (HOTP) using the provided secret and counter.
@@ -76,27 +144,41 @@ pub fn generate_totp(secret: &[u8], interval: u32) -> Result<String, Error> {
 ///
 /// * `secret` - A byte slice representing the secret key, usually shared between the server and the client.
 /// * `counter` - A `u64` representing the counter value, which increases each time a new HOTP is generated.
+/// * `algorithm` - The HMAC algorithm used to generate the HOTP.
+/// * `digits` - The number of digits of the HOTP, between 6 and 8.
 ///
 /// # Examples
 ///
 /// ```
-/// use crypto::totp::generate_hotp;
+/// use crypto::totp::{generate_hotp, Algorithm};
 ///
 /// let secret = b"mysecretkey";
 /// let counter = 42;
-/// let hotp = generate_hotp(secret, counter).unwrap();
+/// let hotp = generate_hotp(secret, counter, Algorithm::Sha1, 6).unwrap();
 /// println!("Generated HOTP: {}", hotp);
 /// ```
 ///
 /// # Returns
 ///
 /// A `Result<String, Error>` containing the generated HOTP or an error.
-pub fn generate_hotp(secret: &[u8], counter: u64) -> Result<String, Error> {
-    let mut mac = Hmac::<Sha1>::new_from_slice(secret)?;
-    mac.update(&counter.to_be_bytes());
-    let result = mac.finalize().into_bytes();
+pub fn generate_hotp(
+    secret: &[u8],
+    counter: u64,
+    algorithm: Algorithm,
+    digits: u32,
+) -> Result<String, Error> {
+    if !(MIN_DIGITS..=MAX_DIGITS).contains(&digits) {
+        return Err(Error::InvalidDigits);
+    }
+
+    let result = match algorithm {
+        Algorithm::Sha1 => hmac_counter::<Hmac<Sha1>>(secret, counter)?,
+        Algorithm::Sha256 => hmac_counter::<Hmac<Sha256>>(secret, counter)?,
+        Algorithm::Sha512 => hmac_counter::<Hmac<Sha512>>(secret, counter)?,
+    };
 
-    let offset = (result[19] & 0xF) as usize;
+    // Dynamic truncation (RFC 4226 section 5.3)
+    let offset = (result[result.len() - 1] & 0xF) as usize;
     let truncated_hash = u32::from_be_bytes([
         result[offset] & 0x7F,
         result[offset + 1],
@@ -104,10 +186,25 @@ pub fn generate_hotp(secret: &[u8], counter: u64) -> Result<String, Error> {
         result[offset + 3],
     ]);
 
-    let otp = format!("{:06}", truncated_hash % 1_000_000);
+    let otp = format!(
+        "{:0width$}",
+        truncated_hash % 10_u32.pow(digits),
+        width = digits as usize
+    );
     Ok(otp)
 }
 
+/// Computes the HMAC of the counter with the given secret.
+fn hmac_counter<M>(secret: &[u8], counter: u64) -> Result<Vec<u8>, Error>
+where
+    M: Mac + KeyInit,
+{
+    let mut mac = <M as KeyInit>::new_from_slice(secret)?;
+    mac.update(&counter.to_be_bytes());
+
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
 /// Verifies a Time-based One-Time Password (TOTP) using the provided input, secret, interval, and tolerance.
 ///
 /// This function checks if the provided TOTP is valid for the given secret and time interval, considering
@@ -118,7 +215,9 @@ pub fn generate_hotp(secret: &[u8], counter: u64) -> Result<String, Error> {
 ///
 /// * `input_totp` - A `String` representing the TOTP to be verified.
 /// * `secret` - A byte slice representing the secret key (encoded with base32), usually shared between the server and the client.
-/// * `interval` - A `u64` representing the time interval (in seconds) during which the generated TOTP is valid.
+/// * `interval` - A `u32` representing the time interval (in seconds) during which the generated TOTP is valid.
+/// * `algorithm` - The HMAC algorithm used to generate the TOTP.
+/// * `digits` - The number of digits of the TOTP, between 6 and 8.
 /// * `tolerance` - An `Option<u64>` representing the number of intervals to allow as a tolerance for time synchronization.
 ///                 If not provided, the tolerance is assumed to be zero.
 ///
@@ -126,7 +225,7 @@ pub fn generate_hotp(secret: &[u8], counter: u64) -> Result<String, Error> {
 ///
 /// ```
 /// use data_encoding::BASE32_NOPAD;
-/// use crypto::totp::verify_totp;
+/// use crypto::totp::{verify_totp, Algorithm};
 ///
 /// let secret = "JBSWY3DPEHPK3PXP";
 /// let secret = BASE32_NOPAD.encode(secret.as_bytes());
@@ -134,7 +233,7 @@ pub fn generate_hotp(secret: &[u8], counter: u64) -> Result<String, Error> {
 /// let tolerance = Some(1);
 /// let input_totp = "123456";
 ///
-/// let is_valid = verify_totp(input_totp, &secret.as_bytes(), interval, tolerance).unwrap();
+/// let is_valid = verify_totp(input_totp, &secret.as_bytes(), interval, Algorithm::Sha1, 6, tolerance).unwrap();
 /// println!("Is the TOTP valid? {}", is_valid);
 /// ```
 ///
@@ -145,6 +244,8 @@ pub fn verify_totp(
     input_totp: &str,
     secret: &[u8],
     interval: u32,
+    algorithm: Algorithm,
+    digits: u32,
     tolerance: Option<u64>,
 ) -> Result<bool, Error> {
     let secret = BASE32_NOPAD.decode(secret)?; // Decode the secret to bytes
@@ -156,7 +257,7 @@ pub fn verify_totp(
     let tolerance = tolerance.unwrap_or(0);
 
     for i in (counter.saturating_sub(tolerance))..=(counter + tolerance) {
-        let expected_totp = generate_hotp(&secret, i)?;
+        let expected_totp = generate_hotp(&secret, i, algorithm, digits)?;
         if input_totp == expected_totp {
             return Ok(true);
         }
@@ -165,6 +266,92 @@ pub fn verify_totp(
     Ok(false)
 }
 
+/// Verifies an HMAC-based One-Time Password (HOTP) against the counter of the device.
+///
+/// Hardware tokens advance their counter every time the button is pressed, also when the code is never
+/// used. The code is therefore compared against the counters `counter..=counter + look_ahead`.
+///
+/// # Arguments
+///
+/// * `input_hotp` - The HOTP to be verified.
+/// * `secret` - A byte slice representing the secret key (encoded with base32).
+/// * `counter` - The next counter expected from the device.
+/// * `algorithm` - The HMAC algorithm used to generate the HOTP.
+/// * `digits` - The number of digits of the HOTP, between 6 and 8.
+/// * `look_ahead` - The number of counters after `counter` that are accepted.
+///
+/// # Returns
+///
+/// The counter the HOTP was generated with, the next expected counter is one higher. `None` if the
+/// HOTP does not match any counter in the window.
+pub fn verify_hotp(
+    input_hotp: &str,
+    secret: &[u8],
+    counter: u64,
+    algorithm: Algorithm,
+    digits: u32,
+    look_ahead: u64,
+) -> Result<Option<u64>, Error> {
+    let secret = BASE32_NOPAD.decode(secret)?; // Decode the secret to bytes
+
+    for i in counter..=counter.saturating_add(look_ahead) {
+        let expected_hotp = generate_hotp(&secret, i, algorithm, digits)?;
+        if input_hotp == expected_hotp {
+            return Ok(Some(i));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Resynchronises the counter of an HOTP device from two consecutive codes.
+///
+/// A device whose counter drifted further than the look-ahead window of [`verify_hotp`] can only be
+/// recovered by searching a larger window. A single code is too likely to match by chance in a large
+/// window, so two codes generated one after another are required.
+///
+/// # Returns
+///
+/// The counter the second HOTP was generated with, the next expected counter is one higher. `None`
+/// if no two consecutive counters in `counter..=counter + window` match the codes.
+pub fn resync_hotp(
+    first_hotp: &str,
+    second_hotp: &str,
+    secret: &[u8],
+    counter: u64,
+    algorithm: Algorithm,
+    digits: u32,
+    window: u64,
+) -> Result<Option<u64>, Error> {
+    let secret = BASE32_NOPAD.decode(secret)?; // Decode the secret to bytes
+
+    let mut previous = generate_hotp(&secret, counter, algorithm, digits)?;
+    for i in counter.saturating_add(1)..=counter.saturating_add(window).saturating_add(1) {
+        let current = generate_hotp(&secret, i, algorithm, digits)?;
+        if first_hotp == previous && second_hotp == current {
+            return Ok(Some(i));
+        }
+
+        previous = current;
+    }
+
+    Ok(None)
+}
+
+/// Converts a hex encoded secret, as shipped with most hardware tokens, to the base32 encoding used
+/// by the other functions of this module.
+///
+/// Whitespace is ignored and both upper and lower case are accepted.
+pub fn secret_from_hex(secret: &str) -> Result<String, Error> {
+    let secret = secret
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let secret = data_encoding::HEXLOWER_PERMISSIVE.decode(secret.as_bytes())?;
+
+    Ok(BASE32_NOPAD.encode(&secret))
+}
+
 pub fn generate_totp_secret() -> String {
     let random_secret: String = rand::thread_rng()
         .sample_iter(&Alphanumeric)
@@ -188,3 +375,211 @@ pub fn generate_backup_code() -> String {
     // Split the 8 characters into two groups of 4 with a dash in between
     format!("{}-{}", &code[0..4], &code[4..8])
 }
+
+/// Generates a random numeric code with the given number of digits, e.g. for codes sent by SMS.
+pub fn generate_numeric_code(digits: u32) -> String {
+    let mut rng = rand::thread_rng();
+
+    (0..digits)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+        .collect()
+}
+
+/// Hashes a backup code using Argon2 so that it can be stored at rest, returns a PHC-formatted string.
+///
+/// The dash separating the two groups of the code is ignored, `abcd-efgh` and `abcdefgh` produce
+/// hashes that verify against the same input.
+///
+/// # Example
+///
+/// ```
+/// use crypto::totp::{generate_backup_code, hash_backup_code, verify_backup_code};
+///
+/// let code = generate_backup_code();
+/// let code_hash = hash_backup_code(&code).unwrap();
+///
+/// assert!(verify_backup_code(&code, &code_hash));
+/// ```
+pub fn hash_backup_code(code: &str) -> Result<String, crate::password::CryptoError> {
+    crate::password::hash_and_salt_password(&normalize_backup_code(code))
+}
+
+/// Verifies a backup code against a hash created by [`hash_backup_code`].
+///
+/// The comparison of the derived hash is done in constant time by Argon2.
+pub fn verify_backup_code(code: &str, code_hash: &str) -> bool {
+    crate::password::verify_password(&normalize_backup_code(code), code_hash).is_ok()
+}
+
+fn normalize_backup_code(code: &str) -> String {
+    code.trim().replace('-', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test vectors from RFC 6238 appendix B.
+    #[test]
+    fn test_rfc6238_vectors() {
+        let sha1_secret = b"12345678901234567890";
+        let sha256_secret = b"12345678901234567890123456789012";
+        let sha512_secret = b"1234567890123456789012345678901234567890123456789012345678901234";
+
+        let vectors: [(u64, &str, &str, &str); 6] = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+
+        for (time, sha1, sha256, sha512) in vectors {
+            let counter = time / 30;
+
+            assert_eq!(
+                generate_hotp(sha1_secret, counter, Algorithm::Sha1, 8).unwrap(),
+                sha1
+            );
+            assert_eq!(
+                generate_hotp(sha256_secret, counter, Algorithm::Sha256, 8).unwrap(),
+                sha256
+            );
+            assert_eq!(
+                generate_hotp(sha512_secret, counter, Algorithm::Sha512, 8).unwrap(),
+                sha512
+            );
+        }
+    }
+
+    #[test]
+    fn test_generate_hotp_digits() {
+        // RFC 4226 appendix D
+        let secret = b"12345678901234567890";
+        assert_eq!(
+            generate_hotp(secret, 0, Algorithm::Sha1, 6).unwrap(),
+            "755224"
+        );
+        assert_eq!(
+            generate_hotp(secret, 1, Algorithm::Sha1, 6).unwrap(),
+            "287082"
+        );
+
+        assert!(matches!(
+            generate_hotp(secret, 0, Algorithm::Sha1, 5),
+            Err(Error::InvalidDigits)
+        ));
+        assert!(matches!(
+            generate_hotp(secret, 0, Algorithm::Sha1, 9),
+            Err(Error::InvalidDigits)
+        ));
+    }
+
+    #[test]
+    fn test_verify_totp() {
+        let secret = generate_totp_secret();
+
+        for algorithm in [Algorithm::Sha1, Algorithm::Sha256, Algorithm::Sha512] {
+            let code = generate_totp(secret.as_bytes(), 30, algorithm, 8).unwrap();
+            assert_eq!(code.len(), 8);
+            assert!(verify_totp(&code, secret.as_bytes(), 30, algorithm, 8, Some(1)).unwrap());
+        }
+    }
+
+    /// HOTP values from RFC 4226 appendix D for the counters 0 to 9.
+    const RFC4226_VECTORS: [&str; 10] = [
+        "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583", "399871",
+        "520489",
+    ];
+
+    #[test]
+    fn test_verify_hotp() {
+        let secret = BASE32_NOPAD.encode(b"12345678901234567890");
+        let secret = secret.as_bytes();
+
+        assert_eq!(
+            verify_hotp(RFC4226_VECTORS[0], secret, 0, Algorithm::Sha1, 6, 0).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            verify_hotp(RFC4226_VECTORS[5], secret, 2, Algorithm::Sha1, 6, 3).unwrap(),
+            Some(5)
+        );
+
+        // Outside of the look-ahead window and already used codes
+        assert_eq!(
+            verify_hotp(RFC4226_VECTORS[6], secret, 2, Algorithm::Sha1, 6, 3).unwrap(),
+            None
+        );
+        assert_eq!(
+            verify_hotp(RFC4226_VECTORS[1], secret, 2, Algorithm::Sha1, 6, 3).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resync_hotp() {
+        let secret = BASE32_NOPAD.encode(b"12345678901234567890");
+        let secret = secret.as_bytes();
+
+        assert_eq!(
+            resync_hotp(
+                RFC4226_VECTORS[7],
+                RFC4226_VECTORS[8],
+                secret,
+                1,
+                Algorithm::Sha1,
+                6,
+                10
+            )
+            .unwrap(),
+            Some(8)
+        );
+
+        // The codes have to be consecutive
+        assert_eq!(
+            resync_hotp(
+                RFC4226_VECTORS[7],
+                RFC4226_VECTORS[9],
+                secret,
+                1,
+                Algorithm::Sha1,
+                6,
+                10
+            )
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_secret_from_hex() {
+        let secret = secret_from_hex("3132333435 3637383930 3132333435 3637383930").unwrap();
+        assert_eq!(secret, BASE32_NOPAD.encode(b"12345678901234567890"));
+
+        assert!(matches!(
+            secret_from_hex("not hex"),
+            Err(Error::InvalidSecret)
+        ));
+    }
+
+    #[test]
+    fn test_verify_backup_code() {
+        let code = generate_backup_code();
+        let code_hash = hash_backup_code(&code).unwrap();
+
+        assert_ne!(code, code_hash);
+        assert!(verify_backup_code(&code, &code_hash));
+        assert!(verify_backup_code(&code.replace('-', ""), &code_hash));
+        assert!(!verify_backup_code("abcd-efgh", &code_hash));
+    }
+
+    #[test]
+    fn test_generate_numeric_code() {
+        let code = generate_numeric_code(6);
+
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+    }
+}
@@ -0,0 +1,184 @@
+//! Key URI Format (`otpauth://`) provisioning URIs and QR codes for authenticator apps.
+//!
+//! See <https://github.com/google/google-authenticator/wiki/Key-Uri-Format>.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{DynamicImage, ImageOutputFormat, Luma};
+use qrcode::{render::svg, QrCode};
+
+use super::{Algorithm, Error};
+
+/// Minimum width and height of rendered QR codes in pixels.
+const QR_CODE_MIN_DIMENSIONS: u32 = 200;
+
+/// A provisioning URI for an authenticator app.
+///
+/// # Example
+///
+/// ```
+/// use crypto::totp::KeyUri;
+///
+/// let uri = KeyUri::new("JBSWY3DPEHPK3PXP", "alice@example.com")
+///     .issuer("Example Co")
+///     .to_string();
+///
+/// assert_eq!(
+///     uri,
+///     "otpauth://totp/Example%20Co:alice%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example%20Co&algorithm=SHA1&digits=6&period=30"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyUri {
+    secret: String,
+    account_name: String,
+    issuer: Option<String>,
+
+    algorithm: Algorithm,
+    digits: u32,
+    period: u32,
+}
+
+impl KeyUri {
+    /// Creates a new URI for the base32 encoded secret, using SHA1, 6 digits and a period of 30 seconds.
+    pub fn new<S, A>(secret: S, account_name: A) -> Self
+    where
+        S: Into<String>,
+        A: Into<String>,
+    {
+        Self {
+            secret: secret.into(),
+            account_name: account_name.into(),
+            issuer: None,
+
+            algorithm: Algorithm::default(),
+            digits: 6,
+            period: 30,
+        }
+    }
+
+    /// The provider or service the account belongs to, shown above the code in authenticator apps.
+    pub fn issuer<I>(mut self, issuer: I) -> Self
+    where
+        I: Into<String>,
+    {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.digits = digits;
+        self
+    }
+
+    pub fn period(mut self, period: u32) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Renders the URI as an SVG QR code.
+    pub fn to_qr_svg(&self) -> Result<String, Error> {
+        let code = self.to_qr_code()?;
+
+        Ok(code
+            .render::<svg::Color>()
+            .min_dimensions(QR_CODE_MIN_DIMENSIONS, QR_CODE_MIN_DIMENSIONS)
+            .build())
+    }
+
+    /// Renders the URI as a PNG QR code, encoded as a `data:image/png;base64,` URI.
+    pub fn to_qr_png_data_uri(&self) -> Result<String, Error> {
+        let code = self.to_qr_code()?;
+        let image = code
+            .render::<Luma<u8>>()
+            .min_dimensions(QR_CODE_MIN_DIMENSIONS, QR_CODE_MIN_DIMENSIONS)
+            .build();
+
+        let mut png = Vec::new();
+        DynamicImage::ImageLuma8(image)
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .map_err(|_| Error::QrCode)?;
+
+        Ok(format!("data:image/png;base64,{}", STANDARD.encode(png)))
+    }
+
+    fn to_qr_code(&self) -> Result<QrCode, Error> {
+        QrCode::new(self.to_string().as_bytes()).map_err(|_| Error::QrCode)
+    }
+}
+
+impl std::fmt::Display for KeyUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "otpauth://totp/")?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "{}:", percent_encode(issuer))?;
+        }
+        write!(f, "{}", percent_encode(&self.account_name))?;
+
+        write!(f, "?secret={}", percent_encode(&self.secret))?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "&issuer={}", percent_encode(issuer))?;
+        }
+
+        write!(
+            f,
+            "&algorithm={}&digits={}&period={}",
+            self.algorithm, self.digits, self.period
+        )
+    }
+}
+
+/// Percent-encodes everything except unreserved characters (RFC 3986).
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_uri() {
+        let uri = KeyUri::new("JBSWY3DPEHPK3PXP", "alice@example.com")
+            .issuer("ACME:Co")
+            .algorithm(Algorithm::Sha256)
+            .digits(8)
+            .period(60);
+
+        assert_eq!(
+            uri.to_string(),
+            "otpauth://totp/ACME%3ACo:alice%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%3ACo&algorithm=SHA256&digits=8&period=60"
+        );
+
+        let uri = KeyUri::new("JBSWY3DPEHPK3PXP", "alice");
+        assert_eq!(
+            uri.to_string(),
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_qr_code() {
+        let uri = KeyUri::new("JBSWY3DPEHPK3PXP", "alice@example.com").issuer("ACME");
+
+        assert!(uri.to_qr_svg().unwrap().contains("<svg"));
+        assert!(uri
+            .to_qr_png_data_uri()
+            .unwrap()
+            .starts_with("data:image/png;base64,"));
+    }
+}
@@ -19,6 +19,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "authcore.proto",
                 "auth/basic.proto",
                 "session.proto",
+                "rbac.proto",
+                "metadata.proto",
+                "user_admin.proto",
                 "error.proto",
             ],
             &["../../protos/authcore"],
@@ -27,7 +30,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .build_client(true)
         .build_server(false)
-        .compile(&["email.proto"], &["../../protos/messaging"])?;
+        .compile(&["email.proto", "sms.proto"], &["../../protos/messaging"])?;
 
     Ok(())
 }
@@ -1,4 +1,18 @@
 pub mod basic;
+pub mod email_otp;
+pub mod hashing;
+pub mod hotp;
+pub mod invitation;
+pub mod metadata;
+pub mod mfa;
+pub mod mfa_recovery;
+pub mod otp;
+pub mod rbac;
+pub mod reauthenticate;
+pub mod sms;
 pub mod token;
 pub mod totp;
+pub mod trusted_device;
+pub mod user_admin;
+pub mod user_import;
 pub mod verification;
@@ -1,2 +1,3 @@
 pub mod login;
+pub mod password;
 pub mod register;
@@ -1,17 +1,23 @@
-use chrono::Duration;
+use chrono::{Duration, Utc};
 use crypto::snowflake::Snowflake;
 use thiserror::Error;
+use tracing::warn;
 
 use crate::{
-    core::token::{self, RefreshTokenError},
+    core::{
+        hashing::HashingError,
+        metadata, rbac,
+        token::{self, Authentication, RefreshTokenError},
+        trusted_device,
+    },
     models::{
         application::ReplicatedApplication,
         error::ModelError::{self},
         prisma,
-        user::{User, UserToken, UserWith},
+        user::{basic_auth::BasicAuth, User, UserToken, UserWith},
         PrismaClient,
     },
-    state::AppState,
+    state::{AppState, CONFIG},
 };
 
 #[derive(Debug, Error)]
@@ -36,12 +42,28 @@ pub enum BasicLoginError {
     #[error("account does not exist")]
     NotFound,
 
+    /// The account has been disabled by the application.
+    #[error("account is disabled")]
+    Disabled,
+
+    /// Too many passwords are being hashed, the client should retry later.
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
     #[error("database error")]
     QueryError(#[from] prisma_client_rust::QueryError),
 
     #[error("user needs further verification through 2FA")]
     NeedFurtherVerificationThrough2FA(Box<User>),
 
+    /// The application requires a second factor and the grace period of the user is over.
+    #[error("user needs to enrol a second factor")]
+    NeedMfaEnrolment(Box<User>),
+
+    /// The password has expired or has been marked to be changed.
+    #[error("user needs to change their password")]
+    NeedPasswordChange(Box<User>),
+
     #[error("unknown error")]
     Unknown,
 }
@@ -52,15 +74,19 @@ pub enum BasicLoginError {
 ///
 /// * `state` - The app state.
 /// * `data` - The login data.
+/// * `trusted_device_token` - The trusted device token of the device, if any, which lets a user
+///   skip the second factor.
 pub async fn with_basic_auth(
+    state: &AppState,
     prisma_client: &PrismaClient,
     email: String,
     password: String,
     application_id: Snowflake,
     ip_address: String,
+    trusted_device_token: Option<String>,
 ) -> Result<User, BasicLoginError> {
     // Get application from database.
-    let application = match ReplicatedApplication::get(prisma_client, application_id).await {
+    let mut application = match ReplicatedApplication::get(prisma_client, application_id).await {
         Ok(app) => app,
         Err(ModelError::NotFound) => {
             return Err(BasicLoginError::ApplicationDoesNotExist);
@@ -76,7 +102,7 @@ pub async fn with_basic_auth(
             prisma_client,
             email,
             application.application_id(),
-            vec![UserWith::BasicAuth, UserWith::TOTP],
+            vec![UserWith::BasicAuth, UserWith::TOTP, UserWith::PhoneNumber],
         )
         .await
         {
@@ -91,23 +117,68 @@ pub async fn with_basic_auth(
     };
 
     // Check if the user has a password.
-    let auth = user.basic_auth(None).await;
-
-    if auth.is_none() {
-        return Err(BasicLoginError::WrongCredentials);
-    }
+    let auth = match user.basic_auth(None).await {
+        Some(auth) => auth.clone(),
+        None => return Err(BasicLoginError::WrongCredentials),
+    };
 
     // Check if the password is correct.
-    if crypto::password::verify_password(&password, auth.as_ref().unwrap().password_hash()).is_err()
+    match state
+        .hashing_pool()
+        .verify_password(password.clone(), auth.password_hash().to_owned())
+        .await
     {
-        return Err(BasicLoginError::WrongCredentials);
+        Ok(()) => {}
+        Err(HashingError::Overloaded) => return Err(BasicLoginError::Overloaded),
+        Err(_) => return Err(BasicLoginError::WrongCredentials),
+    }
+
+    // Imported hashes, hashes with weaker costs and hashes with a rotated pepper are replaced
+    // after a successful login. The hash is replaced outside of the transaction of the login,
+    // which is not committed if the user still needs a second factor.
+    if CONFIG.password_hashing().needs_rehash(auth.password_hash()) {
+        rehash_password(state, auth, password).await;
     }
 
-    // If user does not have 2FA enabled, return the user.
-    if user.totp().is_some() {
-        return Err(BasicLoginError::NeedFurtherVerificationThrough2FA(
-            Box::new(user),
-        ));
+    // Disabled users are only told after giving the correct password.
+    if user.disabled() {
+        return Err(BasicLoginError::Disabled);
+    }
+
+    // If user does not have 2FA enabled or signs in from a trusted device, return the user.
+    if user.mfa_enabled() {
+        let trusted = match trusted_device_token {
+            Some(token) => trusted_device::is_trusted(
+                state,
+                prisma_client,
+                &token,
+                user.id(),
+                application.application_id(),
+            )
+            .await
+            .map_err(|_| BasicLoginError::Unknown)?,
+            None => false,
+        };
+
+        if !trusted {
+            return Err(BasicLoginError::NeedFurtherVerificationThrough2FA(
+                Box::new(user),
+            ));
+        }
+    } else {
+        // Users without a second factor have to enrol one once their grace period is over
+        let mfa_config = application.mfa_config(prisma_client).await?;
+
+        if let Some(deadline) = mfa_config.enrolment_deadline(user.created_at()) {
+            if deadline <= Utc::now() {
+                return Err(BasicLoginError::NeedMfaEnrolment(Box::new(user)));
+            }
+        }
+    }
+
+    // Users with an expired password only get a session after changing it
+    if needs_password_change(prisma_client, &mut application, &mut user).await {
+        return Err(BasicLoginError::NeedPasswordChange(Box::new(user)));
     }
 
     prisma_client
@@ -125,10 +196,58 @@ pub async fn with_basic_auth(
     Ok(user)
 }
 
+/// Whether the user has to change their password before a session is created, because it is older
+/// than the maximum password age of the application or has been marked to be changed.
+pub async fn needs_password_change(
+    prisma_client: &PrismaClient,
+    application: &mut ReplicatedApplication,
+    user: &mut User,
+) -> bool {
+    let max_age = application
+        .basic_auth_config(prisma_client)
+        .await
+        .password_max_age();
+
+    user.basic_auth(Some(prisma_client))
+        .await
+        .map_or(false, |auth| auth.needs_password_change(max_age))
+}
+
+/// Hash a verified password with the current costs and pepper and replace the previous hash. A
+/// failure is only logged, the hash is replaced with a later login instead.
+async fn rehash_password(state: &AppState, mut auth: BasicAuth, password: String) {
+    let password_hash = match state.hashing_pool().hash_password(password).await {
+        Ok(password_hash) => password_hash,
+        // Rehashing is skipped while the hashing pool is busy
+        Err(HashingError::Overloaded) => return,
+        Err(e) => {
+            warn!(
+                "failed to rehash password of user {}: {}",
+                auth.user_id(),
+                e
+            );
+            return;
+        }
+    };
+
+    if let Err(e) = auth
+        .update_password_hash(state.prisma(), password_hash)
+        .await
+    {
+        warn!(
+            "failed to replace password hash of user {}: {}",
+            auth.user_id(),
+            e
+        );
+    }
+}
+
+/// Create a refresh token and an access token for a user that has just authenticated.
 pub async fn create_refresh_and_access_token(
     state: &AppState,
     prisma_client: &PrismaClient,
     user: &User,
+    authentication: Authentication,
     ip_address: Option<String>,
     user_agent: Option<String>,
 ) -> Result<(UserToken, String), RefreshTokenError> {
@@ -137,18 +256,24 @@ pub async fn create_refresh_and_access_token(
         state,
         prisma_client,
         user.id(),
+        authentication.clone(),
         chrono::Utc::now() + Duration::days(30),
         ip_address,
         user_agent,
     )
     .await?;
 
-    // Generate access token
+    // Generate access token with the roles, permissions and projected metadata of the user
+    let authorization = rbac::get_authorization(prisma_client, user.id()).await?;
+    let metadata = metadata::get_token_claims(prisma_client, user.id()).await?;
     let access_token = token::new_access_token(
         state,
         user.id(),
         chrono::Utc::now() + Duration::hours(1),
         refresh_token.id(),
+        authentication,
+        authorization,
+        metadata,
     )?;
 
     Ok((refresh_token, access_token))
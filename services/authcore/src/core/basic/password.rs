@@ -0,0 +1,196 @@
+//! # Password changes
+//! Replaces the password of an existing user, either changed by the user with their current
+//! password or set by the backend of the application.
+//!
+//! The new password has to meet the password requirements of the application and can not be one
+//! of the last [`password_history_size`](crate::models::application::BasicAuthConfig::password_history_size)
+//! passwords of the user, including the current one. Replaced hashes are kept in the
+//! [`PasswordHistory`] of the user, which is pruned to the history size with every change and
+//! deleted together with the user.
+
+use crypto::{input::password, snowflake::Snowflake};
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    core::hashing::HashingError,
+    models::{
+        application::ReplicatedApplication,
+        error::ModelError,
+        user::{PasswordHistory, User, UserWith},
+        PrismaClient,
+    },
+    state::{AppState, CONFIG},
+};
+
+/// Largest password history size of an application. Every password in the history is verified
+/// on the hashing pool when a password is changed.
+pub const MAX_HISTORY_SIZE: u8 = 24;
+
+#[derive(Debug, Error)]
+pub enum PasswordChangeError {
+    #[error("user not found")]
+    NotFound,
+
+    /// The current password is wrong or the user has no password.
+    #[error("wrong credentials")]
+    WrongCredentials,
+
+    #[error("invalid password")]
+    PasswordFormat(Vec<password::PasswordValidationError>),
+
+    /// The password is one of the recent passwords of the user.
+    #[error("password has been used recently")]
+    Reused,
+
+    /// Too many passwords are being hashed, the client should retry later.
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("failed to hash password")]
+    HashError,
+
+    #[error("failed to change password")]
+    Model(#[from] ModelError),
+}
+
+/// Change the password of a user after verifying their current password.
+pub async fn change_password(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    current_password: String,
+    new_password: String,
+) -> Result<(), PasswordChangeError> {
+    let mut user = get_user(prisma_client, user_id).await?;
+
+    let password_hash = match user.basic_auth(None).await {
+        Some(auth) => auth.password_hash().to_owned(),
+        None => return Err(PasswordChangeError::WrongCredentials),
+    };
+
+    match state
+        .hashing_pool()
+        .verify_password(current_password, password_hash)
+        .await
+    {
+        Ok(()) => {}
+        Err(HashingError::Overloaded) => return Err(PasswordChangeError::Overloaded),
+        Err(_) => return Err(PasswordChangeError::WrongCredentials),
+    }
+
+    replace_password(state, prisma_client, &mut user, new_password).await
+}
+
+/// Set the password of a user without their current password, e.g. by the backend of the
+/// application. Users without a password are given one.
+pub async fn set_password(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    password: String,
+) -> Result<(), PasswordChangeError> {
+    let mut user = get_user(prisma_client, user_id).await?;
+
+    replace_password(state, prisma_client, &mut user, password).await
+}
+
+async fn get_user(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<User, PasswordChangeError> {
+    match User::get(
+        prisma_client,
+        user_id,
+        vec![UserWith::BasicAuth, UserWith::EmailAddress],
+    )
+    .await
+    {
+        Ok(user) => Ok(user),
+        Err(ModelError::NotFound) => Err(PasswordChangeError::NotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn replace_password(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user: &mut User,
+    password: String,
+) -> Result<(), PasswordChangeError> {
+    let mut application = ReplicatedApplication::get(prisma_client, user.application_id()).await?;
+    let basic_auth_config = application.basic_auth_config(prisma_client).await;
+
+    let email = user
+        .email_address()
+        .map(|email_address| email_address.email_address().to_owned());
+    let user_inputs: Vec<&str> = email.iter().map(String::as_str).collect();
+
+    if let Err(e) = password::validate_password(
+        &password,
+        &user_inputs,
+        true,
+        basic_auth_config.as_password_requirements_config(),
+        CONFIG.breached_passwords(),
+    ) {
+        return Err(PasswordChangeError::PasswordFormat(e));
+    }
+
+    // The current password counts towards the history size
+    let history_size = i64::from(basic_auth_config.password_history_size());
+    let current_hash = user
+        .basic_auth(None)
+        .await
+        .map(|auth| auth.password_hash().to_owned());
+
+    if history_size > 0 {
+        let previous_hashes = PasswordHistory::recent(prisma_client, user.id(), history_size)
+            .await?
+            .into_iter()
+            .map(|entry| entry.password_hash().to_owned());
+        let recent_hashes = current_hash
+            .iter()
+            .cloned()
+            .chain(previous_hashes)
+            .take(history_size as usize);
+
+        for password_hash in recent_hashes {
+            match state
+                .hashing_pool()
+                .verify_password(password.clone(), password_hash)
+                .await
+            {
+                Ok(()) => return Err(PasswordChangeError::Reused),
+                Err(HashingError::Overloaded) => return Err(PasswordChangeError::Overloaded),
+                Err(_) => {}
+            }
+        }
+    }
+
+    let password_hash = match state.hashing_pool().hash_password(password).await {
+        Ok(password_hash) => password_hash,
+        Err(HashingError::Overloaded) => return Err(PasswordChangeError::Overloaded),
+        Err(_) => return Err(PasswordChangeError::HashError),
+    };
+    user.set_password(prisma_client, password_hash).await?;
+
+    // Keep the replaced hash, the entries beyond the history size are no longer needed
+    let keep = (history_size - 1).max(0);
+    match current_hash {
+        Some(current_hash) if keep > 0 => {
+            PasswordHistory::push(
+                prisma_client,
+                state.id_generator().next_snowflake().unwrap(),
+                user.id(),
+                current_hash,
+                keep,
+            )
+            .await?
+        }
+        _ => PasswordHistory::prune(prisma_client, user.id(), keep).await?,
+    }
+
+    info!("password of user {} changed", user.id());
+
+    Ok(())
+}
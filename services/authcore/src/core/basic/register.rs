@@ -3,8 +3,9 @@ use thiserror::Error;
 use tracing::info;
 
 use crate::{
+    core::hashing::HashingError,
     models::{application::ReplicatedApplication, error::ModelError, user::User, PrismaClient},
-    state::AppState,
+    state::{AppState, CONFIG},
 };
 
 #[derive(Debug, Error)]
@@ -21,6 +22,10 @@ pub enum BasicRegistrationError {
     #[error("application does not exist")]
     ApplicationDoesNotExist,
 
+    /// Too many passwords are being hashed, the client should retry later.
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
     #[error("internal server error")]
     InternalServerError,
 }
@@ -76,9 +81,13 @@ pub async fn with_basic_auth(
         .as_password_requirements_config();
 
     // Validate the password
-    if let Err(e) =
-        password::validate_password(&data.password, &[&data.email], true, password_requirements)
-    {
+    if let Err(e) = password::validate_password(
+        &data.password,
+        &[&data.email],
+        true,
+        password_requirements,
+        CONFIG.breached_passwords(),
+    ) {
         return Err(BasicRegistrationError::PasswordFormat(e));
     }
 
@@ -91,13 +100,14 @@ pub async fn with_basic_auth(
     );
 
     // add password to builder
-    let password_hash = crypto::password::hash_and_salt_password(&data.password);
-    if password_hash.is_err() {
-        return Err(BasicRegistrationError::InternalServerError);
-    }
+    let password_hash = match state.hashing_pool().hash_password(data.password).await {
+        Ok(password_hash) => password_hash,
+        Err(HashingError::Overloaded) => return Err(BasicRegistrationError::Overloaded),
+        Err(_) => return Err(BasicRegistrationError::InternalServerError),
+    };
 
     // Set the password hash
-    user.basic_auth(password_hash.unwrap());
+    user.basic_auth(password_hash);
 
     // create user
     let user = user.build().await;
@@ -0,0 +1,192 @@
+//! # Email one-time codes
+//! Codes sent by email are a second factor for users who can not use an authenticator app. The
+//! application has to allow email codes (see
+//! [`MFAConfig::email_otp_enabled`](crate::models::application::MFAConfig::email_otp_enabled)) and the user enables
+//! them for their verified email address.
+//!
+//! During the login a code is requested with the TOTP flow token and verified like a code from an
+//! authenticator. Codes and throttling are shared with SMS codes, see [`crate::core::otp`].
+//!
+//! Disabling email codes for an application stops codes from being sent and accepted, users that
+//! only have email codes enabled have to sign in with a backup code.
+
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    core::{
+        mfa,
+        otp::{self, SendCodeError, VerifyCodeError},
+        totp,
+    },
+    grpc::client::email::{EmailApplication, EmailData, SendCodeEmailRequest},
+    models::{
+        application::ReplicatedApplication,
+        error::ModelError,
+        prisma::{OneTimeCodeChannel, OneTimeCodePurpose},
+        user::{totp::TOTPBackupCode, EmailAddress, User, UserWith},
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// Sender of emails containing one-time codes.
+const CODE_EMAIL_FROM: &str = "verify@antonhagser.se";
+
+/// Whether an application allows email codes as a second factor.
+pub async fn is_allowed(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+) -> Result<bool, ModelError> {
+    let mut application = ReplicatedApplication::get(prisma_client, application_id).await?;
+    let mfa_config = application.mfa_config(prisma_client).await?;
+
+    Ok(mfa_config.email_otp_enabled())
+}
+
+/// Generate a sign-in code, store its hash and send it to the email address. Returns the time the
+/// code expires.
+///
+/// The caller must check that the application allows email codes.
+pub async fn send_code(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    email_address: &EmailAddress,
+    application_id: Snowflake,
+) -> Result<DateTime<Utc>, SendCodeError> {
+    send_code_for(
+        state,
+        prisma_client,
+        email_address,
+        application_id,
+        OneTimeCodePurpose::SignIn,
+    )
+    .await
+}
+
+/// Generate a code for the given purpose, store its hash and send it to the email address. Returns
+/// the time the code expires.
+pub async fn send_code_for(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    email_address: &EmailAddress,
+    application_id: Snowflake,
+    purpose: OneTimeCodePurpose,
+) -> Result<DateTime<Utc>, SendCodeError> {
+    let application = ReplicatedApplication::get(prisma_client, application_id).await?;
+
+    let (code, expires_at) = otp::issue_code(
+        state,
+        prisma_client,
+        email_address.user_id(),
+        OneTimeCodeChannel::Email,
+        purpose,
+    )
+    .await?;
+
+    let request = tonic::Request::new(SendCodeEmailRequest {
+        code,
+        email_data: Some(EmailData {
+            from: CODE_EMAIL_FROM.into(),
+            to: vec![email_address.email_address().to_owned()],
+            cc: vec![],
+            bcc: vec![],
+            reply_to: "".into(),
+        }),
+        email_application: Some(EmailApplication {
+            name: application.name().to_owned(),
+        }),
+    });
+
+    let mut email_grpc_client = state.email_grpc_client().lock().await;
+    email_grpc_client.send_code_email(request).await?;
+    drop(email_grpc_client);
+
+    info!(
+        "sent {:?} code email to email address {}",
+        purpose,
+        email_address.id().to_id_signed()
+    );
+
+    Ok(expires_at)
+}
+
+/// Verify a sign-in code sent by email, a valid code can only be used once.
+pub async fn verify_code(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    code: &str,
+) -> Result<bool, VerifyCodeError> {
+    otp::verify_code(
+        state,
+        prisma_client,
+        user_id,
+        OneTimeCodeChannel::Email,
+        OneTimeCodePurpose::SignIn,
+        code,
+    )
+    .await
+}
+
+#[derive(Debug, Error)]
+pub enum EnableMfaError {
+    #[error("no email address")]
+    NotFound,
+
+    #[error("email address is not verified")]
+    NotVerified,
+
+    #[error("email codes or 2fa enrolment are not allowed by the application")]
+    NotAllowed,
+
+    #[error("failed to generate backup codes")]
+    BackupCodes(#[from] totp::BackupCodesError),
+
+    #[error("failed to enable email 2fa")]
+    Model(#[from] ModelError),
+}
+
+/// Accept codes sent by email as a second factor for a user with a verified email address.
+///
+/// When the user has no backup codes left a new set is returned in plaintext, the codes can not be
+/// retrieved again.
+pub async fn enable_mfa(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Option<Vec<String>>, EnableMfaError> {
+    let user = match User::get(prisma_client, user_id, vec![UserWith::EmailAddress]).await {
+        Ok(user) => user,
+        Err(ModelError::NotFound) => return Err(EnableMfaError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut email_address = match user.email_address() {
+        Some(email_address) => email_address.clone(),
+        None => return Err(EnableMfaError::NotFound),
+    };
+
+    if !email_address.verified() {
+        return Err(EnableMfaError::NotVerified);
+    }
+
+    if !is_allowed(prisma_client, user.application_id()).await?
+        || !mfa::enrolment_allowed(prisma_client, user.application_id()).await?
+    {
+        return Err(EnableMfaError::NotAllowed);
+    }
+
+    email_address.set_mfa_enabled(prisma_client, true).await?;
+
+    // Backup codes are shared by all devices, they are only generated with the first device
+    if TOTPBackupCode::count_remaining(prisma_client, user_id).await? > 0 {
+        return Ok(None);
+    }
+
+    let backup_codes = totp::new_backup_codes(state, prisma_client, user_id).await?;
+
+    Ok(Some(backup_codes))
+}
@@ -0,0 +1,228 @@
+//! # Password hashing pool
+//! Hashing and verifying a password takes tens of milliseconds of CPU time with the argon2 costs
+//! of [`Config::password_hashing`](crate::state::Config::password_hashing). Run on the tokio worker
+//! threads, a burst of logins would stall every other request.
+//!
+//! Passwords, backup codes and one-time codes sent by SMS or email are therefore hashed on the
+//! blocking thread pool, at most `PASSWORD_HASHING_WORKERS` at a time. Up to
+//! `PASSWORD_HASHING_QUEUE_SIZE` further requests wait for a worker, beyond that requests are
+//! rejected right away with [`HashingError::Overloaded`], which is returned to the client as
+//! `503 Service Unavailable`.
+//!
+//! The time requests wait for a worker is recorded in [`HashingMetrics`], which are logged by the
+//! metrics task.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crypto::password::CryptoError;
+use thiserror::Error;
+use tokio::sync::Semaphore;
+
+use crate::state::CONFIG;
+
+#[derive(Debug, Error)]
+pub enum HashingError {
+    /// All workers are busy and the queue is full.
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("password hashing failed: {0}")]
+    Crypto(#[from] CryptoError),
+
+    #[error("password hashing task failed")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// Hashes and verifies passwords with a bounded number of blocking threads.
+pub struct HashingPool {
+    workers: Arc<Semaphore>,
+
+    /// The number of requests that are waiting for or running on a worker.
+    pending: Arc<AtomicUsize>,
+    max_pending: usize,
+
+    metrics: HashingMetrics,
+}
+
+impl HashingPool {
+    /// Creates a pool of `workers` threads with room for `queue_size` waiting requests.
+    pub fn new(workers: usize, queue_size: usize) -> Self {
+        Self {
+            workers: Arc::new(Semaphore::new(workers)),
+            pending: Arc::new(AtomicUsize::new(0)),
+            max_pending: workers + queue_size,
+            metrics: HashingMetrics::default(),
+        }
+    }
+
+    /// Hash a password with the configured costs and pepper.
+    pub async fn hash_password(&self, password: String) -> Result<String, HashingError> {
+        self.run(move || CONFIG.password_hashing().hash_password(&password))
+            .await
+    }
+
+    /// Verify a password against a hash, a wrong password is a [`CryptoError::NotMatching`].
+    pub async fn verify_password(
+        &self,
+        password: String,
+        password_hash: String,
+    ) -> Result<(), HashingError> {
+        self.run(move || {
+            CONFIG
+                .password_hashing()
+                .verify_password(&password, &password_hash)
+        })
+        .await
+    }
+
+    /// Hash backup codes so that they can be stored at rest, in the order of the codes.
+    pub async fn hash_backup_codes(&self, codes: Vec<String>) -> Result<Vec<String>, HashingError> {
+        self.run(move || {
+            codes
+                .iter()
+                .map(|code| crypto::totp::hash_backup_code(code))
+                .collect()
+        })
+        .await
+    }
+
+    /// Find the hash a backup code matches, returns its index in `code_hashes`.
+    ///
+    /// All hashes are verified on one worker, so that a code takes a single place in the queue.
+    pub async fn find_backup_code(
+        &self,
+        code: String,
+        code_hashes: Vec<String>,
+    ) -> Result<Option<usize>, HashingError> {
+        self.run(move || {
+            Ok(code_hashes
+                .iter()
+                .position(|code_hash| crypto::totp::verify_backup_code(&code, code_hash)))
+        })
+        .await
+    }
+
+    /// Hash a one-time code sent by SMS or email.
+    pub async fn hash_one_time_code(&self, code: String) -> Result<String, HashingError> {
+        self.run(move || crypto::password::hash_and_salt_password(&code))
+            .await
+    }
+
+    /// Verify a one-time code against its hash, a wrong code is a [`CryptoError::NotMatching`].
+    pub async fn verify_one_time_code(
+        &self,
+        code: String,
+        code_hash: String,
+    ) -> Result<(), HashingError> {
+        self.run(move || crypto::password::verify_password(code.trim(), &code_hash))
+            .await
+    }
+
+    pub fn metrics(&self) -> &HashingMetrics {
+        &self.metrics
+    }
+
+    /// The number of requests that are waiting for or running on a worker.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    async fn run<F, T>(&self, f: F) -> Result<T, HashingError>
+    where
+        F: FnOnce() -> Result<T, CryptoError> + Send + 'static,
+        T: Send + 'static,
+    {
+        // Reserve a place in the queue, or shed the load if there is none left
+        if self.pending.fetch_add(1, Ordering::AcqRel) >= self.max_pending {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+            self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(HashingError::Overloaded);
+        }
+        let pending = PendingGuard(self.pending.clone());
+
+        let queued_at = Instant::now();
+        let permit = self
+            .workers
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore is never closed");
+        self.metrics.record_queue_time(queued_at.elapsed());
+
+        // The permit and the place in the queue are held until the hash is done, even if the
+        // caller stops waiting for it
+        let result = tokio::task::spawn_blocking(move || {
+            let result = f();
+            drop(permit);
+            drop(pending);
+            result
+        })
+        .await?;
+
+        Ok(result?)
+    }
+}
+
+/// Releases the place in the queue when the hash is done, or when the request is cancelled before
+/// it got a worker.
+struct PendingGuard(Arc<AtomicUsize>);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Counters of the hashing pool since they were last taken.
+#[derive(Debug, Default)]
+pub struct HashingMetrics {
+    completed: AtomicU64,
+    rejected: AtomicU64,
+
+    /// Sum and maximum of the time requests waited for a worker, in microseconds.
+    total_queue_time: AtomicU64,
+    max_queue_time: AtomicU64,
+}
+
+/// A snapshot of [`HashingMetrics`].
+#[derive(Debug, Clone, Copy)]
+pub struct HashingMetricsSnapshot {
+    /// Requests that got a worker.
+    pub completed: u64,
+    /// Requests rejected because the queue was full.
+    pub rejected: u64,
+
+    pub average_queue_time: Duration,
+    pub max_queue_time: Duration,
+}
+
+impl HashingMetrics {
+    fn record_queue_time(&self, queue_time: Duration) {
+        let micros = queue_time.as_micros().try_into().unwrap_or(u64::MAX);
+
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        self.total_queue_time.fetch_add(micros, Ordering::Relaxed);
+        self.max_queue_time.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    /// Take a snapshot of the counters and reset them.
+    pub fn take(&self) -> HashingMetricsSnapshot {
+        let completed = self.completed.swap(0, Ordering::Relaxed);
+        let total_queue_time = self.total_queue_time.swap(0, Ordering::Relaxed);
+
+        HashingMetricsSnapshot {
+            completed,
+            rejected: self.rejected.swap(0, Ordering::Relaxed),
+            average_queue_time: Duration::from_micros(
+                total_queue_time.checked_div(completed).unwrap_or(0),
+            ),
+            max_queue_time: Duration::from_micros(self.max_queue_time.swap(0, Ordering::Relaxed)),
+        }
+    }
+}
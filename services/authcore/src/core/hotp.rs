@@ -0,0 +1,255 @@
+//! # HOTP hardware tokens
+//! Hardware OATH tokens generate counter-based codes (HOTP, RFC 4226) instead of time-based codes.
+//! They are stored as devices next to the TOTP authenticators and are verified on the same endpoint.
+//!
+//! ## Import
+//! The secret of a hardware token is shipped by the vendor instead of being generated by the server.
+//! The user imports the secret together with the current code of the token, the device is activated
+//! right away when the code matches.
+//!
+//! ## Counter
+//! The server stores the next counter expected from the token. Pressing the button without using the
+//! code advances the counter of the token, codes within a look-ahead window are therefore accepted
+//! (see [`HOTP_LOOK_AHEAD`]). A token that drifted further is resynchronised with two consecutive
+//! codes (see [`HOTP_RESYNC_WINDOW`]).
+
+use crypto::{snowflake::Snowflake, totp::Algorithm};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    core::{mfa, totp},
+    models::{
+        error::ModelError,
+        prisma,
+        user::{
+            totp::{OtpKind, TOTPBackupCode, HOTP_LOOK_AHEAD, HOTP_RESYNC_WINDOW, TOTP},
+            User, UserWith,
+        },
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// Minimum length of an imported secret in bytes, RFC 4226 requires at least 128 bits.
+const MIN_SECRET_LENGTH: usize = 16;
+
+/// Largest counter accepted for an imported token, the counter is stored as a signed 64-bit integer
+/// and has to leave room for the look-ahead and resync windows.
+const MAX_IMPORT_COUNTER: u64 = i64::MAX as u64 - HOTP_RESYNC_WINDOW;
+
+/// Encoding of an imported secret.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretEncoding {
+    #[default]
+    Base32,
+    Hex,
+}
+
+/// A hardware token to import.
+#[derive(Debug, Clone)]
+pub struct HotpImport {
+    pub name: Option<String>,
+    pub secret: String,
+    pub secret_encoding: SecretEncoding,
+    pub algorithm: Algorithm,
+    pub digits: u32,
+    /// Counter of the token as shipped by the vendor, usually zero.
+    pub counter: u64,
+    /// Current code of the token, proves that the secret belongs to the token.
+    pub code: String,
+}
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("user not found")]
+    NotFound,
+
+    #[error("the application does not allow a second factor")]
+    NotAllowed,
+
+    #[error("invalid secret")]
+    InvalidSecret,
+
+    #[error("invalid number of digits")]
+    InvalidDigits,
+
+    #[error("invalid counter")]
+    InvalidCounter,
+
+    #[error("wrong code")]
+    WrongCode,
+
+    #[error("failed to generate backup codes")]
+    BackupCodes(#[from] totp::BackupCodesError),
+
+    #[error("failed to import hotp token")]
+    Model(#[from] ModelError),
+}
+
+/// Import a hardware token of a user after verifying its current code.
+///
+/// Activates the device and TOTP for the user. When this is the first device of the user a set of
+/// backup codes is returned in plaintext, the codes can not be retrieved again.
+pub async fn import(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    import: HotpImport,
+) -> Result<(TOTP, Option<Vec<String>>), ImportError> {
+    let user = match User::get(prisma_client, user_id, vec![]).await {
+        Ok(user) => user,
+        Err(ModelError::NotFound) => return Err(ImportError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    if !mfa::enrolment_allowed(prisma_client, user.application_id()).await? {
+        return Err(ImportError::NotAllowed);
+    }
+
+    if !(crypto::totp::MIN_DIGITS..=crypto::totp::MAX_DIGITS).contains(&import.digits) {
+        return Err(ImportError::InvalidDigits);
+    }
+
+    if import.counter > MAX_IMPORT_COUNTER {
+        return Err(ImportError::InvalidCounter);
+    }
+
+    let secret = normalize_secret(&import.secret, import.secret_encoding)?;
+
+    // Only codes of the token itself are accepted
+    let counter = match crypto::totp::verify_hotp(
+        &import.code,
+        secret.as_bytes(),
+        import.counter,
+        import.algorithm,
+        import.digits,
+        HOTP_LOOK_AHEAD,
+    ) {
+        Ok(Some(counter)) => counter,
+        Ok(None) => return Err(ImportError::WrongCode),
+        Err(_) => return Err(ImportError::InvalidSecret),
+    };
+
+    // The interval is not used by HOTP devices
+    let totp = TOTP::builder(
+        state.id_generator().next_snowflake().unwrap(),
+        user_id,
+        secret,
+        30,
+    )
+    .name(import.name)
+    .algorithm(import.algorithm)
+    .digits(import.digits)
+    .hotp(counter + 1)
+    .create(prisma_client)
+    .await?;
+
+    prisma_client
+        .user()
+        .update(
+            prisma::user::id::equals(user_id.to_id_signed()),
+            vec![prisma::user::totp_enabled::set(true)],
+        )
+        .exec()
+        .await
+        .map_err(ModelError::from)?;
+
+    info!(
+        "imported hotp token {} for user {}",
+        totp.id(),
+        user_id.to_id_signed()
+    );
+
+    // Backup codes are shared by all devices, they are only generated with the first device
+    if TOTPBackupCode::count_remaining(prisma_client, user_id).await? > 0 {
+        return Ok((totp, None));
+    }
+
+    let backup_codes = totp::new_backup_codes(state, prisma_client, user_id).await?;
+
+    Ok((totp, Some(backup_codes)))
+}
+
+/// Decode an imported secret and encode it with base32 like generated secrets.
+fn normalize_secret(secret: &str, encoding: SecretEncoding) -> Result<String, ImportError> {
+    let secret = match encoding {
+        SecretEncoding::Base32 => secret
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '=')
+            .collect::<String>()
+            .to_uppercase(),
+        SecretEncoding::Hex => {
+            crypto::totp::secret_from_hex(secret).map_err(|_| ImportError::InvalidSecret)?
+        }
+    };
+
+    match crypto::totp::BASE32_NOPAD.decode(secret.as_bytes()) {
+        Ok(bytes) if bytes.len() >= MIN_SECRET_LENGTH => Ok(secret),
+        _ => Err(ImportError::InvalidSecret),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ResyncError {
+    #[error("hotp device not found")]
+    NotFound,
+
+    #[error("wrong codes")]
+    WrongCodes,
+
+    #[error("failed to resynchronise hotp token")]
+    Model(#[from] ModelError),
+}
+
+/// Resynchronise the counter of a hardware token with two consecutive codes.
+///
+/// Without a device ID every active HOTP device of the user is tried, a user that can not sign in
+/// does not know the ID of their device. Returns the ID of the resynchronised device.
+pub async fn resync(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    device_id: Option<Snowflake>,
+    first_code: &str,
+    second_code: &str,
+) -> Result<Snowflake, ResyncError> {
+    let user = match User::get(prisma_client, user_id, vec![UserWith::TOTP]).await {
+        Ok(user) => user,
+        Err(ModelError::NotFound) => return Err(ResyncError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    let devices = user
+        .active_totps()
+        .filter(|totp| totp.kind() == OtpKind::Hotp)
+        .filter(|totp| device_id.map_or(true, |device_id| totp.id() == device_id))
+        .collect::<Vec<_>>();
+    if devices.is_empty() {
+        return Err(ResyncError::NotFound);
+    }
+
+    for device in devices {
+        if device
+            .resync(prisma_client, first_code, second_code)
+            .await?
+        {
+            info!(
+                "resynchronised hotp token {} of user {}",
+                device.id(),
+                user_id.to_id_signed()
+            );
+
+            return Ok(device.id());
+        }
+    }
+
+    info!(
+        "failed to resynchronise hotp token of user {} within {} counters",
+        user_id.to_id_signed(),
+        HOTP_RESYNC_WINDOW
+    );
+
+    Err(ResyncError::WrongCodes)
+}
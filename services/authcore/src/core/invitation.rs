@@ -0,0 +1,386 @@
+//! # Invitations
+//! The backend of an application can invite users, who then choose their own password.
+//!
+//! ## Flow
+//! 1. The backend invites an email address through the UserAdmin gRPC service, which creates a
+//!    pending user without a password and emails them an invitation link
+//! 2. The link leads to the invitation page of the application (see
+//!    [`VerificationConfig::invitation_url`](crate::models::application::VerificationConfig::invitation_url)),
+//!    with the invitation token as the `token` query parameter
+//! 3. The page accepts the invitation with the token and a password at `/invitation/accept`, which
+//!    sets the password and verifies the email address
+//!
+//! Until it is accepted an invitation can be resent, which replaces the token, or revoked, which
+//! deletes the pending user.
+
+use chrono::{DateTime, Duration, Utc};
+use crypto::{input::password, snowflake::Snowflake, tokens::paseto};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    core::{
+        hashing::HashingError,
+        token::{generate_generic_token, verify_generic_token},
+    },
+    grpc::client::email::{EmailApplication, EmailData, SendInvitationEmailRequest},
+    models::{
+        application::ReplicatedApplication,
+        error::ModelError,
+        prisma::UserTokenType,
+        user::{EmailAddress, User, UserToken, UserWith},
+        PrismaClient,
+    },
+    state::{AppState, CONFIG},
+};
+
+/// Sender of invitation emails.
+const INVITATION_EMAIL_FROM: &str = "invite@antonhagser.se";
+
+#[derive(Debug, Error)]
+pub enum InvitationError {
+    #[error("application does not exist")]
+    ApplicationDoesNotExist,
+
+    #[error("application has no invitation url")]
+    NoInvitationUrl,
+
+    #[error("invalid email address")]
+    EmailFormat,
+
+    #[error("email address already exists")]
+    AlreadyExists,
+
+    #[error("invitation not found")]
+    NotFound,
+
+    #[error("invalid invitation token")]
+    InvalidToken,
+
+    #[error("invitation expired")]
+    Expired,
+
+    #[error("invalid password")]
+    PasswordFormat(Vec<password::PasswordValidationError>),
+
+    #[error("failed to hash password")]
+    HashError,
+
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("failed to create invitation token")]
+    Token(#[from] paseto::Error),
+
+    #[error("failed to send invitation email")]
+    Email(#[from] tonic::Status),
+
+    #[error("invitation model error")]
+    Model(#[from] ModelError),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct InvitationTokenData {
+    application_id: Snowflake,
+}
+
+/// A pending invitation of a user.
+#[derive(Debug, Clone)]
+pub struct Invitation {
+    user: User,
+    token: UserToken,
+}
+
+impl Invitation {
+    /// The pending user, with their email address.
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    pub fn email_address(&self) -> &str {
+        self.user
+            .email_address()
+            .map_or("", |email_address| email_address.email_address())
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.token.created_at()
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.token.expires_at()
+    }
+
+    pub fn expired(&self) -> bool {
+        self.token.expires_at() < Utc::now()
+    }
+}
+
+/// Invite a user to an application, the user is created without a password and receives an
+/// invitation email.
+pub async fn invite(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    email: String,
+) -> Result<Invitation, InvitationError> {
+    if !crypto::input::email::validate_email(&email) {
+        return Err(InvitationError::EmailFormat);
+    }
+
+    match EmailAddress::find_by_address(prisma_client, &email, application_id).await {
+        Ok(_) => return Err(InvitationError::AlreadyExists),
+        Err(ModelError::NotFound) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut application = get_application(prisma_client, application_id).await?;
+
+    let user = User::builder(state.id_generator(), prisma_client, application_id, email)
+        .build()
+        .await?;
+
+    let invitation = send_invitation(state, prisma_client, &mut application, user).await?;
+
+    info!(
+        "invited user {} to application {}",
+        invitation.user.id(),
+        application_id
+    );
+
+    Ok(invitation)
+}
+
+/// List the pending invitations of an application, including expired invitations, newest first.
+pub async fn list(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+) -> Result<Vec<Invitation>, InvitationError> {
+    let tokens =
+        UserToken::list_for_application(prisma_client, application_id, UserTokenType::Invitation)
+            .await?;
+
+    let users = User::get_many(
+        prisma_client,
+        tokens.iter().map(UserToken::user_id).collect(),
+        vec![UserWith::EmailAddress],
+    )
+    .await?;
+
+    // Every user has a single invitation token, see `send_invitation`
+    Ok(tokens
+        .into_iter()
+        .filter_map(|token| {
+            let user = users.iter().find(|user| user.id() == token.user_id())?;
+
+            Some(Invitation {
+                user: user.clone(),
+                token,
+            })
+        })
+        .collect())
+}
+
+/// Resend the invitation of a pending user with a new token, the previous link stops working.
+pub async fn resend(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<Invitation, InvitationError> {
+    let user = get_pending_user(prisma_client, application_id, user_id).await?;
+    let mut application = get_application(prisma_client, application_id).await?;
+
+    send_invitation(state, prisma_client, &mut application, user).await
+}
+
+/// Revoke the invitation of a pending user, which deletes the user.
+pub async fn revoke(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<(), InvitationError> {
+    get_pending_user(prisma_client, application_id, user_id).await?;
+    User::delete(prisma_client, user_id).await?;
+
+    info!("revoked invitation of user {}", user_id);
+
+    Ok(())
+}
+
+/// Accept an invitation with the password chosen by the user, which also verifies their email
+/// address.
+pub async fn accept(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    token: &str,
+    password: String,
+) -> Result<User, InvitationError> {
+    let mut claims = verify_generic_token::<InvitationTokenData>(state, token)
+        .map_err(|_| InvitationError::InvalidToken)?;
+
+    let user_id: Snowflake = claims
+        .subject()
+        .and_then(|subject| subject.parse().ok())
+        .ok_or(InvitationError::InvalidToken)?;
+    let (token_id, application_id) = match claims.other_mut().as_mut() {
+        Some(other) => match other.take_generic() {
+            Some(data) => (other.token_id(), data.application_id),
+            None => return Err(InvitationError::InvalidToken),
+        },
+        None => return Err(InvitationError::InvalidToken),
+    };
+
+    // Resent and accepted invitations are removed from the database
+    let token =
+        match UserToken::get(prisma_client, user_id, token_id, UserTokenType::Invitation).await {
+            Ok(token) => token,
+            Err(ModelError::NotFound) => return Err(InvitationError::InvalidToken),
+            Err(e) => return Err(e.into()),
+        };
+    if token.expires_at() < Utc::now() {
+        return Err(InvitationError::Expired);
+    }
+
+    let mut user = get_pending_user(prisma_client, application_id, user_id).await?;
+    let email_address = user
+        .email_address()
+        .cloned()
+        .ok_or(InvitationError::NotFound)?;
+
+    // The password requirements of the application apply to invited users
+    let mut application = get_application(prisma_client, application_id).await?;
+    let password_requirements = application
+        .basic_auth_config(prisma_client)
+        .await
+        .as_password_requirements_config();
+
+    if let Err(e) = password::validate_password(
+        &password,
+        &[email_address.email_address()],
+        true,
+        password_requirements,
+        CONFIG.breached_passwords(),
+    ) {
+        return Err(InvitationError::PasswordFormat(e));
+    }
+
+    let password_hash = match state.hashing_pool().hash_password(password).await {
+        Ok(password_hash) => password_hash,
+        Err(HashingError::Overloaded) => return Err(InvitationError::Overloaded),
+        Err(_) => return Err(InvitationError::HashError),
+    };
+    user.set_password(prisma_client, password_hash).await?;
+
+    // The invitation was sent to the email address
+    email_address
+        .set_verified(prisma_client, application_id)
+        .await?;
+
+    UserToken::delete_all(prisma_client, user_id, UserTokenType::Invitation).await?;
+
+    info!("user {} accepted their invitation", user_id);
+
+    Ok(user)
+}
+
+async fn get_application(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+) -> Result<ReplicatedApplication, InvitationError> {
+    match ReplicatedApplication::get(prisma_client, application_id).await {
+        Ok(application) => Ok(application),
+        Err(ModelError::NotFound) => Err(InvitationError::ApplicationDoesNotExist),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Get a user of an application that has been invited and has not set a password yet.
+async fn get_pending_user(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<User, InvitationError> {
+    let user = match User::get(prisma_client, user_id, vec![UserWith::EmailAddress]).await {
+        Ok(user) if user.application_id() == application_id => user,
+        Ok(_) | Err(ModelError::NotFound) => return Err(InvitationError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    let invited = UserToken::count(prisma_client, user_id, UserTokenType::Invitation).await? > 0;
+    if !invited || user.password_enabled() {
+        return Err(InvitationError::NotFound);
+    }
+
+    Ok(user)
+}
+
+/// Replace the invitation token of a user and email them the invitation link.
+async fn send_invitation(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    application: &mut ReplicatedApplication,
+    user: User,
+) -> Result<Invitation, InvitationError> {
+    let verification_config = application.verification_config(prisma_client).await;
+    let invitation_url = verification_config
+        .invitation_url()
+        .cloned()
+        .ok_or(InvitationError::NoInvitationUrl)?;
+    let email = user
+        .email_address()
+        .map(|email_address| email_address.email_address().to_owned())
+        .ok_or(InvitationError::NotFound)?;
+
+    let token_id = state.id_generator().next_snowflake().unwrap();
+    let expires_at =
+        Utc::now() + Duration::seconds(verification_config.invitation_expires_after().into());
+    let token = generate_generic_token(
+        state,
+        token_id,
+        user.id(),
+        expires_at,
+        InvitationTokenData {
+            application_id: application.application_id(),
+        },
+    )?;
+
+    UserToken::delete_all(prisma_client, user.id(), UserTokenType::Invitation).await?;
+    let token = UserToken::builder(
+        token_id,
+        user.id(),
+        UserTokenType::Invitation,
+        token,
+        expires_at,
+    )
+    .build(prisma_client)
+    .await?;
+
+    let separator = if invitation_url.contains('?') {
+        '&'
+    } else {
+        '?'
+    };
+    let request = tonic::Request::new(SendInvitationEmailRequest {
+        invitation_url: format!("{}{}token={}", invitation_url, separator, token.token()),
+        expires_at: expires_at.timestamp(),
+        email_data: Some(EmailData {
+            from: INVITATION_EMAIL_FROM.into(),
+            to: vec![email],
+            cc: vec![],
+            bcc: vec![],
+            reply_to: "".into(),
+        }),
+        email_application: Some(EmailApplication {
+            name: application.name().to_owned(),
+        }),
+    });
+
+    // The invitation is rolled back if the email can not be sent
+    let mut email_grpc_client = state.email_grpc_client().lock().await;
+    email_grpc_client.send_invitation_email(request).await?;
+    drop(email_grpc_client);
+
+    Ok(Invitation { user, token })
+}
@@ -0,0 +1,265 @@
+//! # User metadata
+//! Applications store key/value entries with JSON values on their users. Every entry has a scope:
+//! - `PUBLIC`: readable by the user, written by the backend of the application
+//! - `PRIVATE`: readable and writable by the user
+//! - `APP_ONLY`: only accessible by the backend of the application
+//!
+//! Users access their entries over HTTP (`/user/metadata`), the backend of the application over
+//! the UserMetadata gRPC service, which only accesses the users of the given application (see
+//! [`check_user`]).
+//!
+//! The backend can project entries into the `metadata` claim of the user's access tokens, the
+//! claim is returned by the `Validate` RPC of the Session service. Projected entries apply to
+//! access tokens issued afterwards.
+
+use crypto::snowflake::Snowflake;
+use thiserror::Error;
+
+use crate::{
+    models::{
+        error::ModelError,
+        user::{metadata::MetadataScope, User, UserMetadata},
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// Maximum length of a key.
+pub const MAX_KEY_LENGTH: usize = 64;
+
+/// Maximum size of a JSON serialized value in bytes.
+pub const MAX_VALUE_SIZE: usize = 4096;
+
+/// Maximum number of entries of a user in all scopes.
+pub const MAX_ENTRIES: i64 = 50;
+
+/// Maximum size of the JSON serialized values projected into an access token in bytes.
+pub const MAX_TOKEN_SIZE: usize = 1024;
+
+/// Scopes of the entries a user can read.
+pub const USER_READABLE_SCOPES: [MetadataScope; 2] =
+    [MetadataScope::Public, MetadataScope::Private];
+
+#[derive(Debug, Error)]
+pub enum MetadataError {
+    #[error("user not found")]
+    UserNotFound,
+
+    #[error("metadata entry not found")]
+    NotFound,
+
+    #[error("invalid key")]
+    InvalidKey,
+
+    #[error("value is too large")]
+    ValueTooLarge,
+
+    #[error("user has too many metadata entries")]
+    TooManyEntries,
+
+    #[error("metadata projected into the access token is too large")]
+    TokenTooLarge,
+
+    #[error("metadata entry is not writable by the user")]
+    ReadOnly,
+
+    #[error("metadata model error")]
+    Model(#[from] ModelError),
+}
+
+/// Keys consist of letters, digits and `_`, `-` or `.`.
+fn validate_key(key: &str) -> Result<(), MetadataError> {
+    let valid = !key.is_empty()
+        && key.len() <= MAX_KEY_LENGTH
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+    if !valid {
+        return Err(MetadataError::InvalidKey);
+    }
+
+    Ok(())
+}
+
+fn value_size(value: &serde_json::Value) -> usize {
+    // Serializing a JSON value can not fail
+    serde_json::to_vec(value).map_or(0, |value| value.len())
+}
+
+/// Validate the key and the size of the value of an entry.
+pub fn validate_entry(key: &str, value: &serde_json::Value) -> Result<(), MetadataError> {
+    validate_key(key)?;
+
+    if value_size(value) > MAX_VALUE_SIZE {
+        return Err(MetadataError::ValueTooLarge);
+    }
+
+    Ok(())
+}
+
+/// Check that a user belongs to the application, users of other applications are not found.
+///
+/// The backend of an application can only access the entries of its own users.
+pub async fn check_user(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<(), MetadataError> {
+    match User::get(prisma_client, user_id, vec![]).await {
+        Ok(user) if user.application_id() == application_id => Ok(()),
+        Ok(_) | Err(ModelError::NotFound) => Err(MetadataError::UserNotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// List the entries of a user in the given scopes.
+pub async fn list(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    scopes: Vec<MetadataScope>,
+) -> Result<Vec<UserMetadata>, MetadataError> {
+    // Distinguish a user without entries from a missing user
+    match User::get(prisma_client, user_id, vec![]).await {
+        Ok(_) => {}
+        Err(ModelError::NotFound) => return Err(MetadataError::UserNotFound),
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(UserMetadata::list(prisma_client, user_id, scopes).await?)
+}
+
+/// Get an entry of a user, entries outside of the given scopes are not found.
+pub async fn get(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    key: String,
+    scopes: &[MetadataScope],
+) -> Result<UserMetadata, MetadataError> {
+    match UserMetadata::get(prisma_client, user_id, key).await {
+        Ok(metadata) if scopes.contains(&metadata.scope()) => Ok(metadata),
+        Ok(_) | Err(ModelError::NotFound) => Err(MetadataError::NotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Create or replace an entry of a user.
+pub async fn set(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    key: String,
+    scope: MetadataScope,
+    value: serde_json::Value,
+    in_token: bool,
+) -> Result<UserMetadata, MetadataError> {
+    validate_entry(&key, &value)?;
+    let size = value_size(&value);
+
+    let existing = match UserMetadata::get(prisma_client, user_id, key.clone()).await {
+        Ok(metadata) => Some(metadata),
+        Err(ModelError::NotFound) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    if in_token {
+        let token_size: usize = UserMetadata::list_in_token(prisma_client, user_id)
+            .await?
+            .iter()
+            .filter(|metadata| metadata.key() != key)
+            .map(|metadata| value_size(metadata.value()))
+            .sum();
+
+        if token_size + size > MAX_TOKEN_SIZE {
+            return Err(MetadataError::TokenTooLarge);
+        }
+    }
+
+    match existing {
+        Some(mut metadata) => {
+            metadata
+                .update(prisma_client, scope, value, in_token)
+                .await?;
+            Ok(metadata)
+        }
+        None => {
+            match User::get(prisma_client, user_id, vec![]).await {
+                Ok(_) => {}
+                Err(ModelError::NotFound) => return Err(MetadataError::UserNotFound),
+                Err(e) => return Err(e.into()),
+            }
+
+            if UserMetadata::count(prisma_client, user_id).await? >= MAX_ENTRIES {
+                return Err(MetadataError::TooManyEntries);
+            }
+
+            Ok(UserMetadata::create(
+                prisma_client,
+                state.id_generator().next_snowflake().unwrap(),
+                user_id,
+                scope,
+                key,
+                value,
+                in_token,
+            )
+            .await?)
+        }
+    }
+}
+
+/// Create or replace a private entry on behalf of the user, entries in other scopes are read-only
+/// for the user. The token projection of an existing entry is kept.
+pub async fn set_by_user(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    key: String,
+    value: serde_json::Value,
+) -> Result<UserMetadata, MetadataError> {
+    let in_token = match UserMetadata::get(prisma_client, user_id, key.clone()).await {
+        Ok(metadata) if metadata.scope() == MetadataScope::Private => metadata.in_token(),
+        Ok(_) => return Err(MetadataError::ReadOnly),
+        Err(ModelError::NotFound) => false,
+        Err(e) => return Err(e.into()),
+    };
+
+    set(
+        state,
+        prisma_client,
+        user_id,
+        key,
+        MetadataScope::Private,
+        value,
+        in_token,
+    )
+    .await
+}
+
+/// Delete an entry of a user, entries outside of the given scopes are not found.
+pub async fn delete(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    key: String,
+    scopes: &[MetadataScope],
+) -> Result<(), MetadataError> {
+    get(prisma_client, user_id, key.clone(), scopes).await?;
+
+    if !UserMetadata::delete(prisma_client, user_id, key).await? {
+        return Err(MetadataError::NotFound);
+    }
+
+    Ok(())
+}
+
+/// The entries of a user projected into the `metadata` claim of their access tokens.
+pub async fn get_token_claims(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<serde_json::Map<String, serde_json::Value>, ModelError> {
+    let metadata = UserMetadata::list_in_token(prisma_client, user_id).await?;
+
+    Ok(metadata
+        .into_iter()
+        .map(|metadata| (metadata.key().to_owned(), metadata.value().clone()))
+        .collect())
+}
@@ -0,0 +1,318 @@
+//! # MFA devices
+//! A user can enrol multiple second factors (MFA devices), TOTP authenticators, HOTP hardware
+//! tokens, their verified phone number for codes sent by SMS and their verified email address for codes sent by email. A
+//! code of any active device is accepted when signing in.
+//!
+//! Backup codes are not a device, they are listed as an available factor while the user has unused
+//! codes left.
+//!
+//! ## Policy
+//! The MFA policy of the application decides whether users can enrol a device at all (`OFF`), and
+//! whether they have to (`REQUIRED`). Under a required policy users without a device receive an
+//! enrolment token instead of a session once the grace period is over, and can not remove their
+//! last device.
+
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    core::email_otp,
+    models::{
+        application::{MFAConfig, MfaPolicy, ReplicatedApplication},
+        error::ModelError,
+        prisma,
+        user::{
+            totp::{OtpKind, TOTPBackupCode, TOTP},
+            EmailAddress, PhoneNumber, User, UserWith,
+        },
+        PrismaClient,
+    },
+};
+
+/// A type of second factor a user can sign in with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FactorType {
+    Totp,
+    Hotp,
+    Sms,
+    Email,
+    BackupCode,
+}
+
+/// A second factor device enrolled by a user.
+#[derive(Debug, Clone, Serialize)]
+pub struct MFADevice {
+    id: Snowflake,
+    factor_type: FactorType,
+    name: String,
+
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<&TOTP> for MFADevice {
+    fn from(value: &TOTP) -> Self {
+        Self {
+            id: value.id(),
+            factor_type: value.kind().into(),
+            name: value.name().to_owned(),
+
+            created_at: value.created_at(),
+            last_used_at: value.last_used_at(),
+        }
+    }
+}
+
+impl From<OtpKind> for FactorType {
+    fn from(value: OtpKind) -> Self {
+        match value {
+            OtpKind::Totp => FactorType::Totp,
+            OtpKind::Hotp => FactorType::Hotp,
+        }
+    }
+}
+
+impl From<&PhoneNumber> for MFADevice {
+    fn from(value: &PhoneNumber) -> Self {
+        Self {
+            id: value.id(),
+            factor_type: FactorType::Sms,
+            name: value.masked(),
+
+            created_at: value.created_at(),
+            last_used_at: None,
+        }
+    }
+}
+
+impl From<&EmailAddress> for MFADevice {
+    fn from(value: &EmailAddress) -> Self {
+        Self {
+            id: value.id(),
+            factor_type: FactorType::Email,
+            name: value.masked(),
+
+            created_at: value.created_at(),
+            last_used_at: None,
+        }
+    }
+}
+
+/// List the active devices of a user.
+pub async fn list_devices(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Vec<MFADevice>, ModelError> {
+    let totps = TOTP::list(prisma_client, user_id).await?;
+    let mut devices: Vec<MFADevice> = totps.iter().map(MFADevice::from).collect();
+
+    if let Some(phone_number) = get_sms_device(prisma_client, user_id).await? {
+        devices.push(MFADevice::from(&phone_number));
+    }
+
+    if let Some(email_address) = get_email_device(prisma_client, user_id).await? {
+        devices.push(MFADevice::from(&email_address));
+    }
+
+    Ok(devices)
+}
+
+/// Get the phone number of a user if it is enabled for SMS 2FA.
+async fn get_sms_device(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Option<PhoneNumber>, ModelError> {
+    match PhoneNumber::get_by_user(prisma_client, user_id).await {
+        Ok(phone_number) if phone_number.mfa_enabled() => Ok(Some(phone_number)),
+        Ok(_) | Err(ModelError::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get the email address of a user if it is enabled for email 2FA and the application allows email
+/// codes.
+async fn get_email_device(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Option<EmailAddress>, ModelError> {
+    let user = match User::get(prisma_client, user_id, vec![UserWith::EmailAddress]).await {
+        Ok(user) => user,
+        Err(ModelError::NotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    match user.email_address() {
+        Some(email_address)
+            if email_address.mfa_enabled()
+                && email_otp::is_allowed(prisma_client, user.application_id()).await? =>
+        {
+            Ok(Some(email_address.clone()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// The factor types a user can currently sign in with, the user must be fetched with their TOTP
+/// devices, phone number and email address.
+pub async fn available_factors(
+    prisma_client: &PrismaClient,
+    user: &User,
+) -> Result<Vec<FactorType>, ModelError> {
+    let mut factors = Vec::new();
+
+    for kind in [OtpKind::Totp, OtpKind::Hotp] {
+        if user.active_totps().any(|totp| totp.kind() == kind) {
+            factors.push(kind.into());
+        }
+    }
+
+    if user.phone_number().map_or(false, |p| p.mfa_enabled()) {
+        factors.push(FactorType::Sms);
+    }
+
+    if user.email_address().map_or(false, |e| e.mfa_enabled())
+        && email_otp::is_allowed(prisma_client, user.application_id()).await?
+    {
+        factors.push(FactorType::Email);
+    }
+
+    if TOTPBackupCode::count_remaining(prisma_client, user.id()).await? > 0 {
+        factors.push(FactorType::BackupCode);
+    }
+
+    Ok(factors)
+}
+
+async fn get_mfa_config(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+) -> Result<MFAConfig, ModelError> {
+    let mut application = ReplicatedApplication::get(prisma_client, application_id).await?;
+    let mfa_config = application.mfa_config(prisma_client).await?;
+
+    Ok(mfa_config)
+}
+
+/// Whether the MFA policy of the application allows users to enrol a device.
+pub async fn enrolment_allowed(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+) -> Result<bool, ModelError> {
+    let mfa_config = get_mfa_config(prisma_client, application_id).await?;
+
+    Ok(mfa_config.enrolment_allowed())
+}
+
+#[derive(Debug, Error)]
+pub enum RemoveDeviceError {
+    #[error("device not found")]
+    NotFound,
+
+    #[error("the application requires a second factor")]
+    Required,
+
+    #[error("failed to remove device")]
+    Model(#[from] ModelError),
+}
+
+/// Remove a device of a user.
+///
+/// Removing the phone number or email address device disables SMS or email 2FA, the phone number
+/// and email address themselves are kept. When the last active device is removed 2FA is disabled
+/// for the user and the remaining backup codes are expired.
+///
+/// The last active device can not be removed when the application requires a second factor, the
+/// caller has to roll back the transaction on error.
+pub async fn remove_device(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    device_id: Snowflake,
+) -> Result<(), RemoveDeviceError> {
+    match TOTP::get(prisma_client, user_id, device_id).await {
+        Ok(totp) => TOTP::delete(prisma_client, totp.id()).await?,
+        Err(ModelError::NotFound) => match get_sms_device(prisma_client, user_id).await? {
+            Some(mut phone_number) if phone_number.id() == device_id => {
+                phone_number.set_mfa_enabled(prisma_client, false).await?
+            }
+            _ => match get_email_device(prisma_client, user_id).await? {
+                Some(mut email_address) if email_address.id() == device_id => {
+                    email_address.set_mfa_enabled(prisma_client, false).await?
+                }
+                _ => return Err(RemoveDeviceError::NotFound),
+            },
+        },
+        Err(e) => return Err(e.into()),
+    };
+
+    let has_totps = !TOTP::list(prisma_client, user_id).await?.is_empty();
+    if !has_totps {
+        prisma_client
+            .user()
+            .update(
+                prisma::user::id::equals(user_id.to_id_signed()),
+                vec![prisma::user::totp_enabled::set(false)],
+            )
+            .exec()
+            .await
+            .map_err(ModelError::from)?;
+    }
+
+    if !has_totps
+        && get_sms_device(prisma_client, user_id).await?.is_none()
+        && get_email_device(prisma_client, user_id).await?.is_none()
+    {
+        let user = User::get(prisma_client, user_id, vec![]).await?;
+        let mfa_config = get_mfa_config(prisma_client, user.application_id()).await?;
+
+        if mfa_config.mfa_policy() == MfaPolicy::Required {
+            return Err(RemoveDeviceError::Required);
+        }
+
+        TOTPBackupCode::expire_all(prisma_client, user_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Remove all devices of a user and expire the remaining backup codes, which disables 2FA for the
+/// user, e.g. once the recovery of a lost second factor completes.
+///
+/// Unlike [`remove_device`] this ignores the MFA policy of the application, a user that has to
+/// use a second factor enrols a new device with the next login.
+pub async fn remove_all_devices(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<(), ModelError> {
+    for totp in TOTP::list(prisma_client, user_id).await? {
+        TOTP::delete(prisma_client, totp.id()).await?;
+    }
+
+    prisma_client
+        .user()
+        .update(
+            prisma::user::id::equals(user_id.to_id_signed()),
+            vec![prisma::user::totp_enabled::set(false)],
+        )
+        .exec()
+        .await?;
+
+    if let Some(mut phone_number) = get_sms_device(prisma_client, user_id).await? {
+        phone_number.set_mfa_enabled(prisma_client, false).await?;
+    }
+
+    // Email codes can be enabled while the application does not allow them
+    let user = User::get(prisma_client, user_id, vec![UserWith::EmailAddress]).await?;
+    if let Some(email_address) = user.email_address().filter(|e| e.mfa_enabled()) {
+        email_address
+            .clone()
+            .set_mfa_enabled(prisma_client, false)
+            .await?;
+    }
+
+    TOTPBackupCode::expire_all(prisma_client, user_id).await?;
+
+    Ok(())
+}
@@ -0,0 +1,399 @@
+//! # Recovery of a lost second factor
+//! A user that lost their second factors and backup codes can remove them with their verified email
+//! address instead of contacting support.
+//!
+//! ## Flow
+//! 1. User logs in with email and password and receives a TOTP flow token
+//! 2. User requests a recovery code with the flow token, the code is sent to the verified email address
+//! 3. User starts the recovery with the code, the email address is notified when the second factors will
+//!    be removed
+//! 4. After the waiting period of the application (see
+//!    [`MFAConfig::mfa_recovery_waiting_days`](crate::models::application::MFAConfig::mfa_recovery_waiting_days))
+//!    the second factors, backup codes and trusted devices are removed and the email address is notified
+//!
+//! While the recovery is pending it can be cancelled by the user, signed in with a second factor, or
+//! by an admin through the Platform gRPC service. Every step is logged.
+
+use chrono::{DateTime, Duration, Utc};
+use crypto::snowflake::Snowflake;
+use thiserror::Error;
+use tracing::{error, info};
+
+use crate::{
+    core::{
+        email_otp, mfa,
+        otp::{self, SendCodeError, VerifyCodeError},
+        trusted_device,
+    },
+    grpc::client::email::{
+        EmailApplication, EmailData, MfaRecoveryStatus as EmailMfaRecoveryStatus,
+        SendMfaRecoveryEmailRequest,
+    },
+    models::{
+        application::ReplicatedApplication,
+        error::ModelError,
+        prisma::{OneTimeCodeChannel, OneTimeCodePurpose},
+        user::{EmailAddress, MFARecovery, User, UserWith},
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// Interval of the background task completing recoveries whose waiting period is over.
+pub const COMPLETE_TASK_INTERVAL_MINUTES: u64 = 15;
+
+/// Sender of emails about the recovery of a second factor.
+const RECOVERY_EMAIL_FROM: &str = "security@antonhagser.se";
+
+#[derive(Debug, Error)]
+pub enum MfaRecoveryError {
+    #[error("user not found")]
+    NotFound,
+
+    #[error("user has no verified email address")]
+    NoVerifiedEmailAddress,
+
+    #[error("user has no second factor")]
+    MfaNotEnabled,
+
+    #[error("a recovery is already pending")]
+    AlreadyPending,
+
+    #[error("no recovery is pending")]
+    NotPending,
+
+    #[error("wrong recovery code")]
+    WrongCode,
+
+    #[error("failed to send recovery code")]
+    SendCode(#[from] SendCodeError),
+
+    #[error("failed to verify recovery code")]
+    VerifyCode(#[from] VerifyCodeError),
+
+    #[error("failed to recover second factor")]
+    Model(#[from] ModelError),
+}
+
+/// Get a user that can recover their second factor, together with their verified email address.
+async fn get_recoverable_user(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<(User, EmailAddress), MfaRecoveryError> {
+    let user = match User::get(
+        prisma_client,
+        user_id,
+        vec![
+            UserWith::TOTP,
+            UserWith::PhoneNumber,
+            UserWith::EmailAddress,
+        ],
+    )
+    .await
+    {
+        Ok(user) => user,
+        Err(ModelError::NotFound) => return Err(MfaRecoveryError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    if !user.mfa_enabled() {
+        return Err(MfaRecoveryError::MfaNotEnabled);
+    }
+
+    let email_address = match user.email_address().filter(|e| e.verified()) {
+        Some(email_address) => email_address.clone(),
+        None => return Err(MfaRecoveryError::NoVerifiedEmailAddress),
+    };
+
+    Ok((user, email_address))
+}
+
+/// Send a recovery code to the verified email address of a user, the code proves that the user
+/// owns the email address. Returns the email address and the time the code expires.
+pub async fn send_code(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<(EmailAddress, DateTime<Utc>), MfaRecoveryError> {
+    let (user, email_address) = get_recoverable_user(prisma_client, user_id).await?;
+
+    if MFARecovery::get_pending(prisma_client, user_id)
+        .await?
+        .is_some()
+    {
+        return Err(MfaRecoveryError::AlreadyPending);
+    }
+
+    let expires_at = email_otp::send_code_for(
+        state,
+        prisma_client,
+        &email_address,
+        user.application_id(),
+        OneTimeCodePurpose::MfaRecovery,
+    )
+    .await?;
+
+    info!("sent mfa recovery code to user {}", user_id);
+
+    Ok((email_address, expires_at))
+}
+
+/// Start the recovery of a user's second factors with the code sent to their email address. The
+/// second factors are removed once the waiting period of the application is over.
+pub async fn start(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    code: &str,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+) -> Result<MFARecovery, MfaRecoveryError> {
+    let (user, email_address) = get_recoverable_user(prisma_client, user_id).await?;
+
+    if MFARecovery::get_pending(prisma_client, user_id)
+        .await?
+        .is_some()
+    {
+        return Err(MfaRecoveryError::AlreadyPending);
+    }
+
+    let valid = otp::verify_code(
+        state,
+        prisma_client,
+        user_id,
+        OneTimeCodeChannel::Email,
+        OneTimeCodePurpose::MfaRecovery,
+        code,
+    )
+    .await?;
+    if !valid {
+        info!("wrong mfa recovery code for user {}", user_id);
+        return Err(MfaRecoveryError::WrongCode);
+    }
+
+    let mut application = ReplicatedApplication::get(prisma_client, user.application_id()).await?;
+    let mfa_config = application
+        .mfa_config(prisma_client)
+        .await
+        .map_err(ModelError::from)?;
+
+    let completes_at = Utc::now() + Duration::days(mfa_config.mfa_recovery_waiting_days().into());
+
+    let recovery = MFARecovery::create(
+        prisma_client,
+        state.id_generator().next_snowflake().unwrap(),
+        user_id,
+        completes_at,
+        ip_address,
+        user_agent,
+    )
+    .await?;
+
+    info!(
+        "started mfa recovery {} for user {}, completes at {}",
+        recovery.id(),
+        user_id,
+        completes_at
+    );
+
+    notify(
+        state,
+        &email_address,
+        application.name(),
+        EmailMfaRecoveryStatus::Started,
+        Some(completes_at),
+    )
+    .await;
+
+    Ok(recovery)
+}
+
+/// Cancel the pending recovery of a user.
+///
+/// `cancelled_by` records who cancelled the recovery, e.g. `user` or `admin`.
+pub async fn cancel(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    cancelled_by: &str,
+) -> Result<MFARecovery, MfaRecoveryError> {
+    let mut recovery = match MFARecovery::get_pending(prisma_client, user_id).await? {
+        Some(recovery) => recovery,
+        None => return Err(MfaRecoveryError::NotPending),
+    };
+
+    if !recovery.cancel(prisma_client, cancelled_by).await? {
+        return Err(MfaRecoveryError::NotPending);
+    }
+
+    info!(
+        "mfa recovery {} of user {} cancelled by {}",
+        recovery.id(),
+        user_id,
+        cancelled_by
+    );
+
+    notify_user(
+        state,
+        prisma_client,
+        user_id,
+        EmailMfaRecoveryStatus::Cancelled,
+    )
+    .await;
+
+    Ok(recovery)
+}
+
+/// Complete a recovery whose waiting period is over, removes the second factors, backup codes
+/// and trusted devices of the user.
+async fn complete(state: &AppState, mut recovery: MFARecovery) -> Result<(), ModelError> {
+    let user_id = recovery.user_id();
+
+    let (transaction_controller, prisma_client) = state.prisma()._transaction().begin().await?;
+
+    let result = async {
+        // The recovery might have been cancelled in the meantime
+        if !recovery.complete(&prisma_client).await? {
+            return Ok(false);
+        }
+
+        mfa::remove_all_devices(&prisma_client, user_id).await?;
+        trusted_device::revoke_all(&prisma_client, user_id).await?;
+
+        Ok::<_, ModelError>(true)
+    }
+    .await;
+
+    match result {
+        Ok(true) => {
+            transaction_controller.commit(prisma_client).await?;
+        }
+        Ok(false) => {
+            transaction_controller.rollback(prisma_client).await?;
+            return Ok(());
+        }
+        Err(e) => {
+            let _ = transaction_controller.rollback(prisma_client).await;
+            return Err(e);
+        }
+    }
+
+    info!(
+        "completed mfa recovery {}, removed the second factors of user {}",
+        recovery.id(),
+        user_id
+    );
+
+    notify_user(
+        state,
+        state.prisma(),
+        user_id,
+        EmailMfaRecoveryStatus::Completed,
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Notify the verified email address of a user about their recovery.
+async fn notify_user(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    status: EmailMfaRecoveryStatus,
+) {
+    let user = match User::get(prisma_client, user_id, vec![UserWith::EmailAddress]).await {
+        Ok(user) => user,
+        Err(e) => {
+            error!("failed to get user {} to notify: {}", user_id, e);
+            return;
+        }
+    };
+
+    let email_address = match user.email_address().filter(|e| e.verified()) {
+        Some(email_address) => email_address,
+        None => return,
+    };
+
+    let application = match ReplicatedApplication::get(prisma_client, user.application_id()).await {
+        Ok(application) => application,
+        Err(e) => {
+            error!(
+                "failed to get application to notify user {}: {}",
+                user_id, e
+            );
+            return;
+        }
+    };
+
+    notify(state, email_address, application.name(), status, None).await;
+}
+
+/// Send a notification about a recovery, failures are logged since the recovery itself succeeded.
+async fn notify(
+    state: &AppState,
+    email_address: &EmailAddress,
+    application_name: &str,
+    status: EmailMfaRecoveryStatus,
+    completes_at: Option<DateTime<Utc>>,
+) {
+    let request = tonic::Request::new(SendMfaRecoveryEmailRequest {
+        status: status as i32,
+        completes_at: completes_at.map_or(0, |completes_at| completes_at.timestamp()),
+        email_data: Some(EmailData {
+            from: RECOVERY_EMAIL_FROM.into(),
+            to: vec![email_address.email_address().to_owned()],
+            cc: vec![],
+            bcc: vec![],
+            reply_to: "".into(),
+        }),
+        email_application: Some(EmailApplication {
+            name: application_name.to_owned(),
+        }),
+    });
+
+    let mut email_grpc_client = state.email_grpc_client().lock().await;
+    let result = email_grpc_client.send_mfa_recovery_email(request).await;
+    drop(email_grpc_client);
+
+    match result {
+        Ok(_) => info!(
+            "sent mfa recovery {:?} email to email address {}",
+            status,
+            email_address.id().to_id_signed()
+        ),
+        Err(e) => error!(
+            "failed to send mfa recovery {:?} email to email address {}: {}",
+            status,
+            email_address.id().to_id_signed(),
+            e
+        ),
+    }
+}
+
+/// Periodically complete the recoveries whose waiting period is over.
+pub async fn complete_due_recoveries_task(state: AppState) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+        COMPLETE_TASK_INTERVAL_MINUTES * 60,
+    ));
+
+    loop {
+        interval.tick().await;
+
+        let recoveries = match MFARecovery::list_due(state.prisma()).await {
+            Ok(recoveries) => recoveries,
+            Err(e) => {
+                error!("failed to list due mfa recoveries: {}", e);
+                continue;
+            }
+        };
+
+        for recovery in recoveries {
+            let recovery_id = recovery.id();
+
+            if let Err(e) = complete(&state, recovery).await {
+                error!("failed to complete mfa recovery {}: {}", recovery_id, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! # One-time codes
+//! Codes sent to a user by SMS (see [`crate::core::sms`]) or email (see [`crate::core::email_otp`])
+//! to verify a phone number or as a second factor when signing in.
+//!
+//! ## Codes
+//! Codes are 6 digits, valid for 10 minutes and stored as argon2 hashes, which are computed on the
+//! [`HashingPool`](crate::core::hashing::HashingPool). Only the latest code sent through a channel
+//! for a purpose can be used and at most 5 codes can be tried against it.
+//!
+//! ## Throttling
+//! A new code can be sent through a channel once a minute and at most 5 times an hour.
+
+use chrono::{DateTime, Duration, Utc};
+use crypto::snowflake::Snowflake;
+use thiserror::Error;
+
+use crate::{
+    core::{hashing::HashingError, sms::SmsTransportError},
+    models::{
+        error::ModelError,
+        prisma::{OneTimeCodeChannel, OneTimeCodePurpose},
+        user::OneTimeCode,
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// Number of digits of a code.
+pub const CODE_DIGITS: u32 = 6;
+
+/// Time a code can be used after it has been sent.
+pub const CODE_LIFETIME_MINUTES: i64 = 10;
+
+/// Number of codes that can be tried against a sent code before it is invalidated.
+pub const MAX_CODE_ATTEMPTS: i32 = 5;
+
+/// Minimum time between two codes sent through the same channel.
+pub const RESEND_INTERVAL_SECONDS: i64 = 60;
+
+/// Maximum number of codes sent through the same channel within an hour.
+pub const MAX_CODES_PER_HOUR: i64 = 5;
+
+#[derive(Debug, Error)]
+pub enum SendCodeError {
+    #[error("a code was sent recently, retry in {retry_after} seconds")]
+    TooSoon { retry_after: i64 },
+
+    #[error("too many codes sent")]
+    TooManyRequests,
+
+    #[error("failed to send code by sms")]
+    Sms(#[from] SmsTransportError),
+
+    #[error("failed to send code by email")]
+    Email(#[from] tonic::Status),
+
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("failed to hash code")]
+    HashError,
+
+    #[error("failed to send code")]
+    Model(#[from] ModelError),
+}
+
+/// Generate a code for a user and store its hash, the caller delivers the returned code. Returns
+/// the code and the time it expires.
+pub async fn issue_code(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    channel: OneTimeCodeChannel,
+    purpose: OneTimeCodePurpose,
+) -> Result<(String, DateTime<Utc>), SendCodeError> {
+    let now = Utc::now();
+    let hour_ago = now - Duration::hours(1);
+
+    // Codes older than an hour are no longer needed for throttling
+    OneTimeCode::delete_sent_before(prisma_client, user_id, hour_ago).await?;
+
+    if let Some(last_sent) = OneTimeCode::last_sent(prisma_client, user_id, channel).await? {
+        let resend_at = last_sent.created_at() + Duration::seconds(RESEND_INTERVAL_SECONDS);
+        if resend_at > now {
+            return Err(SendCodeError::TooSoon {
+                retry_after: (resend_at - now).num_seconds().max(1),
+            });
+        }
+    }
+
+    if OneTimeCode::count_sent_since(prisma_client, user_id, channel, hour_ago).await?
+        >= MAX_CODES_PER_HOUR
+    {
+        return Err(SendCodeError::TooManyRequests);
+    }
+
+    let code = crypto::totp::generate_numeric_code(CODE_DIGITS);
+    let expires_at = now + Duration::minutes(CODE_LIFETIME_MINUTES);
+    let code_hash = match state.hashing_pool().hash_one_time_code(code.clone()).await {
+        Ok(code_hash) => code_hash,
+        Err(HashingError::Overloaded) => return Err(SendCodeError::Overloaded),
+        Err(_) => return Err(SendCodeError::HashError),
+    };
+
+    OneTimeCode::create(
+        prisma_client,
+        state.id_generator().next_snowflake().unwrap(),
+        user_id,
+        channel,
+        purpose,
+        code_hash,
+        expires_at,
+    )
+    .await?;
+
+    Ok((code, expires_at))
+}
+
+#[derive(Debug, Error)]
+pub enum VerifyCodeError {
+    #[error("no code has been sent or the code has expired")]
+    NotFound,
+
+    #[error("too many attempts with code")]
+    TooManyAttempts,
+
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("failed to verify code")]
+    Model(#[from] ModelError),
+}
+
+/// Verify a code sent to a user, a valid code can only be used once.
+pub async fn verify_code(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    channel: OneTimeCodeChannel,
+    purpose: OneTimeCodePurpose,
+    code: &str,
+) -> Result<bool, VerifyCodeError> {
+    let one_time_code = OneTimeCode::latest(prisma_client, user_id, channel, purpose)
+        .await?
+        .ok_or(VerifyCodeError::NotFound)?;
+
+    if !one_time_code
+        .try_attempt(prisma_client, MAX_CODE_ATTEMPTS)
+        .await?
+    {
+        return Err(VerifyCodeError::TooManyAttempts);
+    }
+
+    match state
+        .hashing_pool()
+        .verify_one_time_code(code.to_owned(), one_time_code.code_hash().to_owned())
+        .await
+    {
+        Ok(()) => {}
+        Err(HashingError::Overloaded) => return Err(VerifyCodeError::Overloaded),
+        Err(_) => return Ok(false),
+    }
+
+    Ok(one_time_code.mark_used(prisma_client).await?)
+}
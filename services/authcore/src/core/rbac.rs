@@ -0,0 +1,285 @@
+//! # Role-based access control
+//! Every application defines its own permissions (e.g. `invoices:read`) and roles, a role is a
+//! named set of permissions. Users are assigned roles of their application.
+//!
+//! The roles of a user and the permissions they grant are embedded in every access token (see
+//! [`Authorization`]) and returned by the `Validate` RPC of the Session service, so that backends
+//! can authorize requests without a lookup. Routes of AuthCore itself are guarded with
+//! [`require_permission`](crate::http::middleware::require_permission).
+//!
+//! Roles and permissions are managed by the backend of the application through the Rbac gRPC
+//! service.
+
+use crypto::snowflake::Snowflake;
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    core::token::Authorization,
+    models::{
+        error::ModelError,
+        role::{Permission, Role},
+        user::User,
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// Maximum length of the name of a role or permission.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum RbacError {
+    #[error("invalid name")]
+    InvalidName,
+
+    #[error("role or permission already exists")]
+    AlreadyExists,
+
+    #[error("role not found")]
+    RoleNotFound,
+
+    #[error("permission not found: {0}")]
+    PermissionNotFound(String),
+
+    #[error("user not found")]
+    UserNotFound,
+
+    #[error("rbac model error")]
+    Model(#[from] ModelError),
+}
+
+/// Names of roles and permissions consist of lowercase letters, digits and `_`, `-`, `.` or `:`.
+fn validate_name(name: &str) -> Result<(), RbacError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name.chars().all(|c| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.' | ':')
+        });
+
+    if !valid {
+        return Err(RbacError::InvalidName);
+    }
+
+    Ok(())
+}
+
+/// Map a unique constraint violation to [`RbacError::AlreadyExists`].
+fn map_create_error(e: ModelError) -> RbacError {
+    match e {
+        ModelError::DatabaseError(ref query_error)
+            if query_error.is_prisma_error::<
+                prisma_client_rust::prisma_errors::query_engine::UniqueKeyViolation,
+            >() =>
+        {
+            RbacError::AlreadyExists
+        }
+        e => e.into(),
+    }
+}
+
+/// Get the roles of a user and the permissions they grant, embedded in the access tokens of the
+/// user.
+pub async fn get_authorization(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Authorization, ModelError> {
+    let roles = Role::list_by_user(prisma_client, user_id).await?;
+
+    let permissions = roles
+        .iter()
+        .flat_map(|role| role.permissions().iter().cloned())
+        .collect();
+    let roles = roles.iter().map(|role| role.name().to_owned()).collect();
+
+    Ok(Authorization::new(roles, permissions))
+}
+
+pub async fn create_permission(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    name: String,
+    description: String,
+) -> Result<Permission, RbacError> {
+    validate_name(&name)?;
+
+    let permission = Permission::create(
+        prisma_client,
+        state.id_generator().next_snowflake().unwrap(),
+        application_id,
+        name,
+        description,
+    )
+    .await
+    .map_err(map_create_error)?;
+
+    info!(
+        "created permission {} of application {}",
+        permission.name(),
+        application_id
+    );
+
+    Ok(permission)
+}
+
+/// Delete a permission, it is removed from every role.
+pub async fn delete_permission(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    name: String,
+) -> Result<(), RbacError> {
+    if !Permission::delete(prisma_client, application_id, name.clone()).await? {
+        return Err(RbacError::PermissionNotFound(name));
+    }
+
+    info!(
+        "deleted permission {} of application {}",
+        name, application_id
+    );
+
+    Ok(())
+}
+
+/// Get the permissions with the given names, every permission must exist.
+async fn get_permissions(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    names: Vec<String>,
+) -> Result<Vec<Permission>, RbacError> {
+    let permissions = Permission::get_many(prisma_client, application_id, names.clone()).await?;
+
+    if let Some(missing) = names
+        .into_iter()
+        .find(|name| !permissions.iter().any(|p| p.name() == name))
+    {
+        return Err(RbacError::PermissionNotFound(missing));
+    }
+
+    Ok(permissions)
+}
+
+/// Create a role granting the given permissions, the permissions must exist.
+pub async fn create_role(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    name: String,
+    description: String,
+    permissions: Vec<String>,
+) -> Result<Role, RbacError> {
+    validate_name(&name)?;
+
+    let permissions = get_permissions(prisma_client, application_id, permissions).await?;
+
+    let role = Role::create(
+        prisma_client,
+        state.id_generator().next_snowflake().unwrap(),
+        application_id,
+        name,
+        description,
+        &permissions,
+    )
+    .await
+    .map_err(map_create_error)?;
+
+    info!(
+        "created role {} of application {}",
+        role.name(),
+        application_id
+    );
+
+    Ok(role)
+}
+
+/// Replace the permissions granted by a role, the permissions must exist.
+pub async fn set_role_permissions(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    name: String,
+    permissions: Vec<String>,
+) -> Result<Role, RbacError> {
+    let mut role = get_role(prisma_client, application_id, name).await?;
+    let permissions = get_permissions(prisma_client, application_id, permissions).await?;
+
+    role.set_permissions(prisma_client, &permissions).await?;
+
+    info!(
+        "set permissions of role {} of application {}",
+        role.name(),
+        application_id
+    );
+
+    Ok(role)
+}
+
+/// Delete a role, it is removed from every user.
+pub async fn delete_role(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    name: String,
+) -> Result<(), RbacError> {
+    if !Role::delete(prisma_client, application_id, name.clone()).await? {
+        return Err(RbacError::RoleNotFound);
+    }
+
+    info!("deleted role {} of application {}", name, application_id);
+
+    Ok(())
+}
+
+async fn get_role(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    name: String,
+) -> Result<Role, RbacError> {
+    match Role::get_by_name(prisma_client, application_id, name).await {
+        Ok(role) => Ok(role),
+        Err(ModelError::NotFound) => Err(RbacError::RoleNotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Get the application of a user, roles are only assigned within the application of the user.
+async fn get_user_application_id(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Snowflake, RbacError> {
+    match User::get(prisma_client, user_id, vec![]).await {
+        Ok(user) => Ok(user.application_id()),
+        Err(ModelError::NotFound) => Err(RbacError::UserNotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Assign a role of the user's application to the user, assigning a role twice has no effect.
+pub async fn assign_role(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    role: String,
+) -> Result<(), RbacError> {
+    let application_id = get_user_application_id(prisma_client, user_id).await?;
+    let role = get_role(prisma_client, application_id, role).await?;
+
+    if role.assign(prisma_client, user_id).await? {
+        info!("assigned role {} to user {}", role.name(), user_id);
+    }
+
+    Ok(())
+}
+
+/// Remove a role from a user, removing a role the user does not have has no effect.
+pub async fn unassign_role(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    role: String,
+) -> Result<(), RbacError> {
+    let application_id = get_user_application_id(prisma_client, user_id).await?;
+    let role = get_role(prisma_client, application_id, role).await?;
+
+    if role.unassign(prisma_client, user_id).await? {
+        info!("removed role {} from user {}", role.name(), user_id);
+    }
+
+    Ok(())
+}
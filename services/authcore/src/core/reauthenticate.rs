@@ -0,0 +1,179 @@
+//! # Step-up re-authentication
+//! Sensitive operations, e.g. removing a second factor, require that the user authenticated
+//! recently (see [`crate::http::middleware::require_recent_authentication`]). Access tokens carry
+//! the time (`auth_time`) and the methods (`amr`, `acr`) of the login, which are kept when the
+//! access token is refreshed.
+//!
+//! A signed in user re-authenticates with their password, a second factor code or both, and
+//! receives a short-lived elevated access token with a fresh `auth_time`. A second factor code
+//! counts as a multi-factor authentication since the session was created with the password.
+//!
+//! A user can try [`MAX_ATTEMPTS`] re-authentications within [`ATTEMPT_WINDOW_MINUTES`] after
+//! the first attempt, a successful re-authentication resets the attempts.
+
+use chrono::{DateTime, Duration, Utc};
+use crypto::{snowflake::Snowflake, tokens::paseto};
+use thiserror::Error;
+
+use crate::{
+    core::{
+        hashing::HashingError,
+        metadata, rbac,
+        token::{self, Authentication, AuthenticationMethod},
+        totp::{self, VerifyFactorError},
+    },
+    models::{
+        error::ModelError,
+        prisma::UserTokenType,
+        user::{User, UserToken, UserWith},
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// Lifetime of an elevated access token.
+pub const ELEVATED_TOKEN_LIFETIME_MINUTES: i64 = 5;
+
+/// Number of re-authentications a user can try within [`ATTEMPT_WINDOW_MINUTES`].
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// Time after the first attempt during which the attempts of a user are counted.
+pub const ATTEMPT_WINDOW_MINUTES: i64 = 15;
+
+#[derive(Debug, Error)]
+pub enum ReauthenticateError {
+    #[error("no password or code given")]
+    MissingCredentials,
+
+    #[error("wrong credentials")]
+    WrongCredentials,
+
+    /// The user tried too many re-authentications, the client should retry later.
+    #[error("too many attempts")]
+    TooManyAttempts,
+
+    #[error("user not found")]
+    NotFound,
+
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("failed to generate elevated token")]
+    Token(#[from] paseto::Error),
+
+    #[error("failed to re-authenticate")]
+    Model(#[from] ModelError),
+}
+
+/// Re-authenticate a signed in user with their password and/or a second factor code, returns an
+/// elevated access token and the time it expires.
+///
+/// The elevated token belongs to the same session (refresh token) as the access token used to
+/// call this.
+pub async fn reauthenticate(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    refresh_token_id: Snowflake,
+    password: Option<String>,
+    code: Option<String>,
+) -> Result<(String, DateTime<Utc>), ReauthenticateError> {
+    if password.is_none() && code.is_none() {
+        return Err(ReauthenticateError::MissingCredentials);
+    }
+
+    let mut user = match User::get(
+        prisma_client,
+        user_id,
+        vec![
+            UserWith::BasicAuth,
+            UserWith::TOTP,
+            UserWith::PhoneNumber,
+            UserWith::EmailAddress,
+        ],
+    )
+    .await
+    {
+        Ok(user) => user,
+        Err(ModelError::NotFound) => return Err(ReauthenticateError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    let attempts = get_attempts(state, user_id).await?;
+    if !attempts.try_attempt(state.prisma(), MAX_ATTEMPTS).await? {
+        return Err(ReauthenticateError::TooManyAttempts);
+    }
+
+    let mut amr = Vec::new();
+
+    if let Some(password) = password {
+        let password_hash = match user.basic_auth(None).await {
+            Some(auth) => auth.password_hash().to_owned(),
+            None => return Err(ReauthenticateError::WrongCredentials),
+        };
+
+        match state
+            .hashing_pool()
+            .verify_password(password, password_hash)
+            .await
+        {
+            Ok(()) => {}
+            Err(HashingError::Overloaded) => return Err(ReauthenticateError::Overloaded),
+            Err(_) => return Err(ReauthenticateError::WrongCredentials),
+        }
+
+        amr.push(AuthenticationMethod::Pwd);
+    }
+
+    if let Some(code) = code {
+        match totp::verify_code(state, prisma_client, &user, code).await {
+            Ok(Some(factor_type)) => amr.push(factor_type.into()),
+            Ok(None) => return Err(ReauthenticateError::WrongCredentials),
+            Err(VerifyFactorError::Overloaded) => return Err(ReauthenticateError::Overloaded),
+            Err(VerifyFactorError::Model(e)) => return Err(e.into()),
+        }
+    }
+
+    let authorization = rbac::get_authorization(prisma_client, user_id).await?;
+    let metadata = metadata::get_token_claims(prisma_client, user_id).await?;
+
+    let expires_at = Utc::now() + Duration::minutes(ELEVATED_TOKEN_LIFETIME_MINUTES);
+    let elevated_token = token::new_access_token(
+        state,
+        user_id,
+        expires_at,
+        refresh_token_id,
+        Authentication::new(amr),
+        authorization,
+        metadata,
+    )?;
+
+    UserToken::delete(state.prisma(), attempts.id()).await?;
+
+    Ok((elevated_token, expires_at))
+}
+
+/// Get the token counting the re-authentication attempts of a user, a new token is created when
+/// the previous one has expired.
+///
+/// Attempts are counted outside of any transaction so that failed attempts are always stored.
+async fn get_attempts(state: &AppState, user_id: Snowflake) -> Result<UserToken, ModelError> {
+    let tokens = UserToken::list(state.prisma(), user_id, UserTokenType::Reauthentication).await?;
+    if let Some(token) = tokens.into_iter().next() {
+        return Ok(token);
+    }
+
+    UserToken::delete_all(state.prisma(), user_id, UserTokenType::Reauthentication).await?;
+
+    // The token is never given out, it only has to be unique
+    let token_id = state.id_generator().next_snowflake().unwrap();
+    UserToken::builder(
+        token_id,
+        user_id,
+        UserTokenType::Reauthentication,
+        format!("reauthentication:{}", token_id),
+        Utc::now() + Duration::minutes(ATTEMPT_WINDOW_MINUTES),
+    )
+    .build(state.prisma())
+    .await
+}
@@ -0,0 +1,253 @@
+//! # SMS one-time codes
+//! Codes sent by SMS are used to verify a user's phone number and, once the number is verified and
+//! the user has enabled it, as a second factor when signing in. Codes and throttling are shared with
+//! email codes, see [`crate::core::otp`].
+//!
+//! ## 2FA
+//! Once the phone number is verified the user can enable it as a second factor. During the login a
+//! code is sent with the TOTP flow token and verified like a code from an authenticator.
+//!
+//! ## Transport
+//! Messages are delivered through a [`SmsTransport`], by default the `SmsService` of the messaging
+//! service ([`GrpcSmsTransport`]). Setting `SMS_TRANSPORT=stub` uses the in-process
+//! [`StubSmsTransport`], which only records the messages.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::{
+    core::{
+        mfa,
+        otp::{self, SendCodeError, VerifyCodeError},
+        totp,
+    },
+    grpc::client::{
+        sms::{self, SendCodeSmsRequest, SmsApplication},
+        SmsClient,
+    },
+    models::{
+        application::ReplicatedApplication,
+        error::ModelError,
+        prisma::{OneTimeCodeChannel, OneTimeCodePurpose},
+        user::{totp::TOTPBackupCode, PhoneNumber, User},
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// A message containing a one-time code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmsMessage {
+    /// E.164 formatted phone number.
+    pub to: String,
+    pub code: String,
+    pub purpose: OneTimeCodePurpose,
+
+    /// Name of the application the code was sent for.
+    pub application_name: String,
+}
+
+#[derive(Debug, Error)]
+pub enum SmsTransportError {
+    #[error("failed to send sms: {0}")]
+    Send(#[from] tonic::Status),
+}
+
+/// Delivers SMS messages.
+#[tonic::async_trait]
+pub trait SmsTransport: Send + Sync {
+    async fn send(&self, message: SmsMessage) -> Result<(), SmsTransportError>;
+}
+
+/// Sends messages through the `SmsService` of the messaging service.
+pub struct GrpcSmsTransport {
+    client: Mutex<SmsClient>,
+}
+
+impl GrpcSmsTransport {
+    pub fn new(client: SmsClient) -> Self {
+        Self {
+            client: Mutex::new(client),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl SmsTransport for GrpcSmsTransport {
+    async fn send(&self, message: SmsMessage) -> Result<(), SmsTransportError> {
+        let purpose = match message.purpose {
+            OneTimeCodePurpose::Verification => sms::SmsCodePurpose::Verification,
+            OneTimeCodePurpose::SignIn => sms::SmsCodePurpose::SignIn,
+        };
+
+        let request = tonic::Request::new(SendCodeSmsRequest {
+            to: message.to,
+            code: message.code,
+            purpose: purpose.into(),
+            sms_application: Some(SmsApplication {
+                name: message.application_name,
+            }),
+        });
+
+        let mut client = self.client.lock().await;
+        client.send_code_sms(request).await?;
+
+        Ok(())
+    }
+}
+
+/// Records messages in memory instead of sending them, for tests and local development.
+///
+/// Clones share the recorded messages, so a test can keep a clone to read the codes sent.
+#[derive(Debug, Clone, Default)]
+pub struct StubSmsTransport {
+    sent: Arc<std::sync::Mutex<Vec<SmsMessage>>>,
+}
+
+impl StubSmsTransport {
+    /// All messages sent so far, oldest first.
+    pub fn sent_messages(&self) -> Vec<SmsMessage> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// The code of the latest message sent to a phone number.
+    pub fn last_code_sent_to(&self, to: &str) -> Option<String> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|message| message.to == to)
+            .map(|message| message.code.clone())
+    }
+}
+
+#[tonic::async_trait]
+impl SmsTransport for StubSmsTransport {
+    async fn send(&self, message: SmsMessage) -> Result<(), SmsTransportError> {
+        info!(
+            "stub sms transport, {:?} code for {}: {}",
+            message.purpose, message.to, message.code
+        );
+
+        self.sent.lock().unwrap().push(message);
+        Ok(())
+    }
+}
+
+/// Generate a code, store its hash and send it to the phone number. Returns the time the code
+/// expires.
+pub async fn send_code(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    phone_number: &PhoneNumber,
+    purpose: OneTimeCodePurpose,
+) -> Result<DateTime<Utc>, SendCodeError> {
+    let application =
+        ReplicatedApplication::get(prisma_client, phone_number.application_id()).await?;
+
+    let (code, expires_at) = otp::issue_code(
+        state,
+        prisma_client,
+        phone_number.user_id(),
+        OneTimeCodeChannel::Sms,
+        purpose,
+    )
+    .await?;
+
+    state
+        .sms_transport()
+        .send(SmsMessage {
+            to: phone_number.phone_number().to_owned(),
+            code,
+            purpose,
+            application_name: application.name().to_owned(),
+        })
+        .await?;
+
+    info!(
+        "sent {:?} sms code to phone number {}",
+        purpose,
+        phone_number.id().to_id_signed()
+    );
+
+    Ok(expires_at)
+}
+
+/// Verify a code sent to a phone number, a valid code can only be used once.
+pub async fn verify_code(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    phone_number: &PhoneNumber,
+    purpose: OneTimeCodePurpose,
+    code: &str,
+) -> Result<bool, VerifyCodeError> {
+    otp::verify_code(
+        state,
+        prisma_client,
+        phone_number.user_id(),
+        OneTimeCodeChannel::Sms,
+        purpose,
+        code,
+    )
+    .await
+}
+
+#[derive(Debug, Error)]
+pub enum EnableMfaError {
+    #[error("no phone number")]
+    NotFound,
+
+    #[error("phone number is not verified")]
+    NotVerified,
+
+    #[error("2fa enrolment is not allowed by the application")]
+    NotAllowed,
+
+    #[error("failed to generate backup codes")]
+    BackupCodes(#[from] totp::BackupCodesError),
+
+    #[error("failed to enable sms 2fa")]
+    Model(#[from] ModelError),
+}
+
+/// Accept codes sent by SMS as a second factor for a user with a verified phone number.
+///
+/// When the user has no backup codes left a new set is returned in plaintext, the codes can not be
+/// retrieved again.
+pub async fn enable_mfa(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Option<Vec<String>>, EnableMfaError> {
+    let mut phone_number = match PhoneNumber::get_by_user(prisma_client, user_id).await {
+        Ok(phone_number) => phone_number,
+        Err(ModelError::NotFound) => return Err(EnableMfaError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    if !phone_number.verified() {
+        return Err(EnableMfaError::NotVerified);
+    }
+
+    let user = User::get(prisma_client, user_id, vec![]).await?;
+    if !mfa::enrolment_allowed(prisma_client, user.application_id()).await? {
+        return Err(EnableMfaError::NotAllowed);
+    }
+
+    phone_number.set_mfa_enabled(prisma_client, true).await?;
+
+    // Backup codes are shared by all devices, they are only generated with the first device
+    if TOTPBackupCode::count_remaining(prisma_client, user_id).await? > 0 {
+        return Ok(None);
+    }
+
+    let backup_codes = totp::new_backup_codes(state, prisma_client, user_id).await?;
+
+    Ok(Some(backup_codes))
+}
@@ -1,7 +1,13 @@
 mod access;
+mod authentication;
+mod authorization;
 mod generic;
 mod refresh;
+mod restricted;
 
 pub use access::*;
+pub use authentication::*;
+pub use authorization::*;
 pub use generic::*;
 pub use refresh::*;
+pub use restricted::*;
@@ -6,17 +6,51 @@ use crypto::{
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use super::{Authentication, Authorization};
 use crate::state::AppState;
 
 #[derive(Debug, Error)]
 pub enum AccessTokenError {
     #[error("internal paseto error")]
     PasetoError(#[from] paseto::Error),
+
+    #[error("token is not an access token")]
+    InvalidToken,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AccessTokenClaims {
     refresh_token_id: Snowflake,
+
+    /// The `auth_time`, `amr` and `acr` claims.
+    #[serde(flatten)]
+    authentication: Authentication,
+
+    /// The `roles` and `permissions` claims.
+    #[serde(flatten)]
+    authorization: Authorization,
+
+    /// User metadata entries projected into the token, see [`crate::core::metadata`].
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    metadata: serde_json::Map<String, serde_json::Value>,
+}
+
+impl AccessTokenClaims {
+    pub fn refresh_token_id(&self) -> Snowflake {
+        self.refresh_token_id
+    }
+
+    pub fn authentication(&self) -> &Authentication {
+        &self.authentication
+    }
+
+    pub fn authorization(&self) -> &Authorization {
+        &self.authorization
+    }
+
+    pub fn metadata(&self) -> &serde_json::Map<String, serde_json::Value> {
+        &self.metadata
+    }
 }
 
 pub fn new_access_token(
@@ -24,6 +58,9 @@ pub fn new_access_token(
     user_id: Snowflake,
     expiration: DateTime<Utc>,
     refresh_token_id: Snowflake,
+    authentication: Authentication,
+    authorization: Authorization,
+    metadata: serde_json::Map<String, serde_json::Value>,
 ) -> Result<String, paseto::Error> {
     // TODO: A client application should be able to define a custom paseto token layout, also be able to switch to using JWTs
     let default_claims = DefaultClaims::builder(
@@ -34,7 +71,12 @@ pub fn new_access_token(
     .subject(user_id)
     .audience("AuthCore")
     .not_before(Utc::now())
-    .other(AccessTokenClaims { refresh_token_id })
+    .other(AccessTokenClaims {
+        refresh_token_id,
+        authentication,
+        authorization,
+        metadata,
+    })
     .build();
 
     paseto::encrypt_token(default_claims, state.paseto_key())
@@ -43,8 +85,13 @@ pub fn new_access_token(
 pub fn verify_access_token(
     state: &AppState,
     token: &str,
-) -> Result<OwnedClaims<()>, AccessTokenError> {
-    let claims = paseto::validate_token(token, state.paseto_key())?;
+) -> Result<OwnedClaims<AccessTokenClaims>, AccessTokenError> {
+    let claims: OwnedClaims<AccessTokenClaims> = paseto::validate_token(token, state.paseto_key())?;
+
+    // Restricted tokens are signed with the same key, but have a different audience
+    if claims.audience().map(String::as_str) != Some("AuthCore") {
+        return Err(AccessTokenError::InvalidToken);
+    }
 
     Ok(claims)
 }
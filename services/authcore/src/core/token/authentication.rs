@@ -0,0 +1,121 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::core::mfa::FactorType;
+
+/// Authentication context class of a single factor authentication.
+pub const ACR_SINGLE_FACTOR: &str = "1";
+
+/// Authentication context class of a multi-factor authentication.
+pub const ACR_MULTI_FACTOR: &str = "2";
+
+/// A method a user authenticated with, serialized as an `amr` value (RFC 8176).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthenticationMethod {
+    /// Password.
+    Pwd,
+    /// One-time code from an authenticator, sent by email or a backup code.
+    Otp,
+    /// One-time code sent by SMS.
+    Sms,
+    /// More than one factor was used.
+    Mfa,
+}
+
+impl AuthenticationMethod {
+    /// Whether the method is a second factor.
+    pub fn is_second_factor(&self) -> bool {
+        matches!(self, AuthenticationMethod::Otp | AuthenticationMethod::Sms)
+    }
+}
+
+impl From<FactorType> for AuthenticationMethod {
+    fn from(value: FactorType) -> Self {
+        match value {
+            FactorType::Sms => AuthenticationMethod::Sms,
+            FactorType::Totp | FactorType::Hotp | FactorType::Email | FactorType::BackupCode => {
+                AuthenticationMethod::Otp
+            }
+        }
+    }
+}
+
+/// When and how a user authenticated, carried from the refresh token to every access token as the
+/// `auth_time`, `amr` and `acr` claims.
+///
+/// Tokens issued before these claims existed are read as [`Authentication::legacy`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default = "Authentication::legacy")]
+pub struct Authentication {
+    /// Unix timestamp of the authentication.
+    auth_time: i64,
+    amr: Vec<AuthenticationMethod>,
+    acr: String,
+}
+
+impl Authentication {
+    /// An authentication that happened now with the given methods.
+    pub fn new(mut amr: Vec<AuthenticationMethod>) -> Self {
+        amr.dedup();
+
+        let second_factor = amr.iter().any(|m| m.is_second_factor());
+        if second_factor && amr.len() > 1 {
+            amr.push(AuthenticationMethod::Mfa);
+        }
+
+        Self {
+            auth_time: Utc::now().timestamp(),
+            amr,
+            acr: if second_factor {
+                ACR_MULTI_FACTOR
+            } else {
+                ACR_SINGLE_FACTOR
+            }
+            .to_owned(),
+        }
+    }
+
+    /// The authentication of a session created before these claims existed, a single-factor
+    /// password authentication at the Unix epoch. The session stays valid, but never counts as a
+    /// recent authentication.
+    pub fn legacy() -> Self {
+        Self {
+            auth_time: 0,
+            amr: vec![AuthenticationMethod::Pwd],
+            acr: ACR_SINGLE_FACTOR.to_owned(),
+        }
+    }
+
+    /// An authentication with a password only.
+    pub fn password() -> Self {
+        Self::new(vec![AuthenticationMethod::Pwd])
+    }
+
+    /// An authentication with a password and a second factor.
+    pub fn password_and_second_factor(factor_type: FactorType) -> Self {
+        Self::new(vec![AuthenticationMethod::Pwd, factor_type.into()])
+    }
+
+    pub fn auth_time(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.auth_time, 0).unwrap()
+    }
+
+    pub fn amr(&self) -> &[AuthenticationMethod] {
+        self.amr.as_ref()
+    }
+
+    pub fn acr(&self) -> &str {
+        self.acr.as_ref()
+    }
+
+    /// Whether a second factor was used.
+    pub fn is_multi_factor(&self) -> bool {
+        self.acr == ACR_MULTI_FACTOR
+    }
+
+    /// Whether the authentication happened within the given time.
+    pub fn is_within(&self, max_age: Duration) -> bool {
+        self.auth_time() + max_age >= Utc::now()
+    }
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// The roles and permissions of a user, carried in every access token as the `roles` and
+/// `permissions` claims. See [`crate::core::rbac`].
+///
+/// The claims are taken when the access token is issued, changes to the roles of a user apply to
+/// access tokens issued afterwards.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Authorization {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    roles: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    permissions: Vec<String>,
+}
+
+impl Authorization {
+    /// Roles and permissions are sorted and deduplicated.
+    pub fn new(mut roles: Vec<String>, mut permissions: Vec<String>) -> Self {
+        roles.sort();
+        roles.dedup();
+        permissions.sort();
+        permissions.dedup();
+
+        Self { roles, permissions }
+    }
+
+    pub fn roles(&self) -> &[String] {
+        self.roles.as_ref()
+    }
+
+    pub fn permissions(&self) -> &[String] {
+        self.permissions.as_ref()
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles
+            .binary_search_by(|r| r.as_str().cmp(role))
+            .is_ok()
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions
+            .binary_search_by(|p| p.as_str().cmp(permission))
+            .is_ok()
+    }
+}
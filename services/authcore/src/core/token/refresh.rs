@@ -7,8 +7,10 @@ use crypto::{
     snowflake::Snowflake,
     tokens::paseto::{self, DefaultClaims},
 };
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use super::Authentication;
 use crate::{
     models::{error::ModelError, prisma::UserTokenType, user::UserToken, PrismaClient},
     state::AppState,
@@ -35,10 +37,19 @@ pub enum RefreshTokenError {
     QueryError(#[from] prisma_client_rust::QueryError),
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshTokenClaims {
+    /// How the user authenticated when the refresh token was created, copied to access tokens.
+    /// Missing in refresh tokens created before it was added, see [`Authentication`].
+    #[serde(flatten)]
+    authentication: Authentication,
+}
+
 /// Create a new refresh token.
 /// # Arguments
 /// * `state` - The app state.
 /// * `user_id` - The user ID.
+/// * `authentication` - How the user authenticated.
 /// * `expires_at` - The expiration date.
 /// * `ip_address` - The IP address.
 /// * `user_agent` - The user agent.
@@ -50,6 +61,7 @@ pub async fn new_refresh_token(
     state: &AppState,
     prisma_client: &PrismaClient,
     user_id: Snowflake,
+    authentication: Authentication,
     expires_at: DateTime<Utc>,
     ip_address: Option<String>,
     user_agent: Option<String>,
@@ -58,6 +70,7 @@ pub async fn new_refresh_token(
 
     let default_claims = DefaultClaims::builder("AuthCore", expires_at, token_id)
         .subject(user_id)
+        .other(RefreshTokenClaims { authentication })
         .build();
 
     let token = paseto::encrypt_token(default_claims, state.paseto_key())?;
@@ -78,15 +91,19 @@ pub async fn new_refresh_token(
 /// * `application_id` - The application ID.
 ///
 /// # Returns
-/// * `Ok(UserToken)` - The user token.
+/// * `Ok((UserToken, Authentication))` - The user token and how the user authenticated.
 /// * `Err(RefreshTokenError)` - The error.
 pub async fn verify_refresh_token(
     state: &AppState,
     prisma_client: &PrismaClient,
     token: &str,
-) -> Result<UserToken, RefreshTokenError> {
+) -> Result<(UserToken, Authentication), RefreshTokenError> {
     // Parse the token
-    let claims = paseto::validate_token::<()>(token, state.paseto_key())?;
+    let mut claims = paseto::validate_token::<RefreshTokenClaims>(token, state.paseto_key())?;
+    let authentication = match claims.other_mut().take() {
+        Some(other) => other.authentication,
+        None => Authentication::legacy(),
+    };
 
     // Validate it against the database
     let token = UserToken::get(
@@ -101,7 +118,7 @@ pub async fn verify_refresh_token(
         return Err(RefreshTokenError::TokenExpired);
     }
 
-    Ok(token)
+    Ok((token, authentication))
 }
 
 pub fn create_refresh_cookie<'a>(
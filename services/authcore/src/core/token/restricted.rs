@@ -0,0 +1,72 @@
+use chrono::{DateTime, Duration, Utc};
+use crypto::{
+    snowflake::Snowflake,
+    tokens::paseto::{self, DefaultClaims, OwnedClaims},
+};
+
+use super::AccessTokenError;
+use crate::state::AppState;
+
+/// Lifetime of a restricted token.
+pub const RESTRICTED_TOKEN_LIFETIME_MINUTES: i64 = 15;
+
+/// What a restricted token can be used for. Restricted tokens are issued instead of a session when
+/// the user has to do something before signing in, they are not accepted as access tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestrictedScope {
+    /// Enrol a second factor, required by the MFA policy of the application.
+    MfaEnrolment,
+    /// Change an expired password or a password marked to be changed.
+    PasswordChange,
+}
+
+impl RestrictedScope {
+    /// Audience of restricted tokens with the scope.
+    fn audience(&self) -> &'static str {
+        match self {
+            RestrictedScope::MfaEnrolment => "AuthCore:mfa_enrolment",
+            RestrictedScope::PasswordChange => "AuthCore:password_change",
+        }
+    }
+}
+
+/// Generate a restricted token for the user, returns the token and the time it expires.
+pub fn new_restricted_token(
+    state: &AppState,
+    user_id: Snowflake,
+    scope: RestrictedScope,
+) -> Result<(String, DateTime<Utc>), paseto::Error> {
+    let expires_at = Utc::now() + Duration::minutes(RESTRICTED_TOKEN_LIFETIME_MINUTES);
+
+    let default_claims = DefaultClaims::builder(
+        "AuthCore",
+        expires_at,
+        state.id_generator().next_snowflake().unwrap(),
+    )
+    .subject(user_id)
+    .audience(scope.audience())
+    .not_before(Utc::now())
+    .build();
+
+    let token = paseto::encrypt_token(default_claims, state.paseto_key())?;
+
+    Ok((token, expires_at))
+}
+
+/// Verify a restricted token with the given scope, returns the ID of the user.
+pub fn verify_restricted_token(
+    state: &AppState,
+    token: &str,
+    scope: RestrictedScope,
+) -> Result<Snowflake, AccessTokenError> {
+    let claims: OwnedClaims<serde_json::Value> = paseto::validate_token(token, state.paseto_key())?;
+
+    if claims.audience().map(String::as_str) != Some(scope.audience()) {
+        return Err(AccessTokenError::InvalidToken);
+    }
+
+    claims
+        .subject()
+        .and_then(|subject| subject.parse().ok())
+        .ok_or(AccessTokenError::InvalidToken)
+}
@@ -7,6 +7,8 @@
 //! 3. If the user has TOTP enabled, the server returns an error with the code `NeedFurtherVerificationThrough2FA` which contains the user's ID and a TOTP flow token
 //! 4. If the user does not have TOTP enabled, the server generates a refresh token and an access token and returns them to the user
 //!
+//! The second factor is skipped on a device the user has trusted, see [`crate::core::trusted_device`].
+//!
 //! ## Verify
 //! 1. User sends a request to the server with the TOTP flow token and the TOTP code
 //! 2. Server checks if the TOTP flow token is valid
@@ -21,16 +23,75 @@
 //! 2. Server checks if the token is signed with the server's private key
 //! 3. Server checks if the token is expired (created more than 5 minutes ago)
 //! 4. Server checks if the token contains the user's ID
+//! 5. Server checks if the token is used from the device (`X-Device-ID` header) and session (`totp_flow_session` cookie) it was issued to
+//! 6. Server counts the attempt, after 5 attempts the token is deleted
+//!
+//! A flow token is single-use, it is deleted as soon as a code has been verified successfully.
+//!
+//! ## Enrolment
+//! 1. User calls setup, the server creates a pending TOTP and returns the secret and provisioning URI
+//! 2. User adds the secret to their authenticator and confirms the enrolment with a code
+//! 3. Server activates TOTP for the user and returns the backup codes
+//!
+//! Pending enrolments that are not confirmed within 15 minutes expire and are deleted, as do pending
+//! enrolments after 5 wrong codes.
+//!
+//! ## Devices
+//! A user can enrol multiple authenticator devices, a code of any active device is accepted.
+//! HOTP hardware tokens are devices as well, see [`crate::core::hotp`].
+//! Codes sent by SMS are accepted as well when the user has enabled SMS 2FA, see [`crate::core::sms`].
+//!
+//! ## Backup codes
+//! Backup codes belong to the user rather than a device, they are stored as argon2 hashes and can only be
+//! used once. A user can regenerate the set of backup codes after re-authenticating with their password,
+//! which expires all remaining codes.
 
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
 use crypto::{snowflake::Snowflake, tokens::jsonwebtoken::Claims};
+use hyper::HeaderMap;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::{error, info};
 
 use crate::{
-    models::{prisma::UserTokenType, user::UserToken, PrismaClient},
+    core::{email_otp, hashing::HashingError, mfa::FactorType, otp::VerifyCodeError, sms},
+    models::{
+        error::ModelError,
+        prisma,
+        prisma::{OneTimeCodePurpose, UserTokenType},
+        user::{
+            totp::{TOTPBackupCode, TOTP},
+            User, UserToken, UserWith,
+        },
+        PrismaClient,
+    },
     state::AppState,
 };
 
+/// Number of backup codes generated for a user at a time.
+pub const BACKUP_CODE_COUNT: usize = 10;
+
+/// Number of codes that can be tried with a single TOTP flow token before it is invalidated.
+pub const MAX_FLOW_TOKEN_ATTEMPTS: i32 = 5;
+
+/// Time a user has to confirm a new TOTP enrolment with a code.
+pub const ENROLMENT_LIFETIME_MINUTES: i64 = 15;
+
+/// Number of codes that can be tried to confirm a TOTP enrolment before it is deleted.
+pub const MAX_ENROLMENT_ATTEMPTS: i32 = 5;
+
+/// Lifetime of a TOTP flow token.
+const FLOW_TOKEN_LIFETIME_MINUTES: i64 = 5;
+
+/// Name of the cookie holding the session ID a TOTP flow token is bound to.
+const FLOW_SESSION_COOKIE_NAME: &str = "totp_flow_session";
+
+/// Header containing the client supplied device ID a TOTP flow token is bound to.
+const DEVICE_ID_HEADER: &str = "x-device-id";
+
 /// Represents the default payload of a JWT, containing the subject (sub), issuer (iss), and expiration (exp).
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FlowTokenClaims {
@@ -104,7 +165,7 @@ pub async fn new_totp_flow_token(
     user_agent: Option<String>,
 ) -> Result<String, GenerateFlowTokenError> {
     let token_id = state.id_generator().next_snowflake().unwrap();
-    let exp = chrono::Utc::now() + chrono::Duration::minutes(5);
+    let exp = chrono::Utc::now() + chrono::Duration::minutes(FLOW_TOKEN_LIFETIME_MINUTES);
     let claims = FlowTokenClaims {
         sub: user_id.to_string(),
         iss: "authcore".to_string(),
@@ -139,22 +200,47 @@ pub enum VerifyFlowTokenError {
     Expired,
     #[error("flow token is invalid")]
     Invalid,
+    #[error("too many attempts with flow token")]
+    TooManyAttempts,
     #[error("failed to fetch flow token from database")]
     FetchToken(#[from] crate::models::error::ModelError),
 }
 
+/// A verified TOTP flow token which has been charged one attempt.
+#[derive(Debug)]
+pub struct FlowToken {
+    claims: FlowTokenClaims,
+    remaining_attempts: i32,
+}
+
+impl FlowToken {
+    pub fn claims(&self) -> &FlowTokenClaims {
+        &self.claims
+    }
+
+    /// Number of attempts left after the current one.
+    pub fn remaining_attempts(&self) -> i32 {
+        self.remaining_attempts
+    }
+}
+
+/// Verify a TOTP flow token and count an attempt of using it.
+///
+/// The token must be used from the same device and session it was issued to. Once the token has
+/// been used [`MAX_FLOW_TOKEN_ATTEMPTS`] times it is deleted, a successful verification should
+/// call [`consume_totp_flow_token`] so that the token can not be reused.
 pub async fn verify_totp_flow_token(
     state: &AppState,
     token: String,
     device_id: Option<String>,
     session_id: Option<String>,
     user_agent: Option<String>,
-) -> Result<FlowTokenClaims, VerifyFlowTokenError> {
+) -> Result<FlowToken, VerifyFlowTokenError> {
     // Verify the token
     let claims: FlowTokenClaims =
         crypto::tokens::jsonwebtoken::JWT::verify_token(&token, state.jwt_pub_key())?;
 
-    if let Err(e) = internal_verify_totp_flow_token(
+    let database_token = internal_verify_totp_flow_token(
         state.prisma(),
         &token,
         &claims,
@@ -162,18 +248,62 @@ pub async fn verify_totp_flow_token(
         session_id,
         user_agent,
     )
-    .await
+    .await?;
+
+    // Count the attempt, this is done outside of the request transaction so that failed attempts
+    // are always stored
+    if !database_token
+        .try_attempt(state.prisma(), MAX_FLOW_TOKEN_ATTEMPTS)
+        .await?
     {
-        // TODO: Delete the token from the database
-        // Though remember that multiple tries are allowed, so we should only delete the token if the user has tried too many times
-        // Could be done by storing the number of tries in the token itself?
+        UserToken::delete(state.prisma(), database_token.id()).await?;
+        return Err(VerifyFlowTokenError::TooManyAttempts);
+    }
 
-        Err(e)
-    } else {
-        // TODO: Delete the token from the database
+    Ok(FlowToken {
+        claims,
+        remaining_attempts: MAX_FLOW_TOKEN_ATTEMPTS - database_token.attempts() - 1,
+    })
+}
 
-        Ok(claims)
+/// Check that a TOTP flow token is valid without counting an attempt, e.g. before sending a code
+/// by SMS for the login.
+///
+/// The same device and session binding applies as for [`verify_totp_flow_token`].
+pub async fn check_totp_flow_token(
+    state: &AppState,
+    token: String,
+    device_id: Option<String>,
+    session_id: Option<String>,
+    user_agent: Option<String>,
+) -> Result<FlowTokenClaims, VerifyFlowTokenError> {
+    let claims: FlowTokenClaims =
+        crypto::tokens::jsonwebtoken::JWT::verify_token(&token, state.jwt_pub_key())?;
+
+    let database_token = internal_verify_totp_flow_token(
+        state.prisma(),
+        &token,
+        &claims,
+        device_id,
+        session_id,
+        user_agent,
+    )
+    .await?;
+
+    // A token without attempts left is deleted on its next use
+    if database_token.attempts() >= MAX_FLOW_TOKEN_ATTEMPTS {
+        return Err(VerifyFlowTokenError::TooManyAttempts);
     }
+
+    Ok(claims)
+}
+
+/// Delete a TOTP flow token after a successful verification, or after the last attempt failed.
+pub async fn consume_totp_flow_token(
+    prisma_client: &PrismaClient,
+    flow_token: &FlowToken,
+) -> Result<(), ModelError> {
+    UserToken::delete(prisma_client, flow_token.claims.jti).await
 }
 
 async fn internal_verify_totp_flow_token(
@@ -183,7 +313,7 @@ async fn internal_verify_totp_flow_token(
     device_id: Option<String>,
     session_id: Option<String>,
     user_agent: Option<String>,
-) -> Result<(), VerifyFlowTokenError> {
+) -> Result<UserToken, VerifyFlowTokenError> {
     // Check if the token is expired
     if chrono::Utc::now().timestamp() as usize > claims.exp() {
         return Err(VerifyFlowTokenError::Expired);
@@ -200,35 +330,357 @@ async fn internal_verify_totp_flow_token(
         .parse::<Snowflake>()
         .map_err(|_| VerifyFlowTokenError::Invalid)?;
 
-    // Check if the token contains the device ID
-    if let Some(device_id) = device_id {
-        if claims.device_id != Some(device_id) {
-            return Err(VerifyFlowTokenError::Invalid);
-        }
+    // Check if the token is used from the device it was issued to
+    if claims.device_id.is_some() && claims.device_id != device_id {
+        return Err(VerifyFlowTokenError::Invalid);
     }
 
-    // Check if the token contains the session ID
-    if let Some(session_id) = session_id {
-        if claims.session_id != Some(session_id) {
-            return Err(VerifyFlowTokenError::Invalid);
-        }
+    // Check if the token is used from the session it was issued to
+    if claims.session_id.is_some() && claims.session_id != session_id {
+        return Err(VerifyFlowTokenError::Invalid);
     }
 
-    // Check if the token contains the user agent
-    if let Some(user_agent) = user_agent {
-        if claims.user_agent != Some(user_agent) {
-            return Err(VerifyFlowTokenError::Invalid);
-        }
+    // Check if the token is used with the user agent it was issued to, a missing header does not
+    // skip the check
+    if claims.user_agent != user_agent {
+        return Err(VerifyFlowTokenError::Invalid);
     }
 
     // Fetch token from database and check if it exists
     let database_token =
-        UserToken::get(prisma_client, user_id, claims.jti, UserTokenType::TotpFlow).await?;
+        match UserToken::get(prisma_client, user_id, claims.jti, UserTokenType::TotpFlow).await {
+            Ok(token) => token,
+            Err(ModelError::NotFound) => return Err(VerifyFlowTokenError::Invalid),
+            Err(e) => return Err(e.into()),
+        };
 
     // Check if the token matches the one in the database
     if database_token.token() != token {
         return Err(VerifyFlowTokenError::Invalid);
     }
 
-    Ok(())
+    Ok(database_token)
+}
+
+/// Generate a new session ID for a TOTP flow, stored in a cookie on the client.
+pub fn new_flow_session_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Create the cookie binding a TOTP flow token to the session that started the login.
+pub fn create_flow_session_cookie<'a>(session_id: String) -> Cookie<'a> {
+    Cookie::build(FLOW_SESSION_COOKIE_NAME, session_id)
+        .domain("localhost")
+        .secure(false) // TODO: set to true
+        .http_only(true)
+        .max_age(time::Duration::minutes(FLOW_TOKEN_LIFETIME_MINUTES))
+        .path("/")
+        .same_site(SameSite::Strict)
+        .finish()
+}
+
+/// Get the session ID of the current TOTP flow from the cookie jar.
+pub fn get_flow_session_id(jar: &CookieJar) -> Option<String> {
+    jar.get(FLOW_SESSION_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+}
+
+/// Remove the TOTP flow session cookie once the flow is finished.
+pub fn remove_flow_session_cookie(jar: CookieJar) -> CookieJar {
+    jar.remove(
+        Cookie::build(FLOW_SESSION_COOKIE_NAME, "")
+            .domain("localhost")
+            .path("/")
+            .finish(),
+    )
+}
+
+/// Get the client supplied device ID from the `X-Device-ID` header.
+pub fn get_device_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(DEVICE_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_owned())
+}
+
+#[derive(Debug, Error)]
+pub enum BackupCodesError {
+    #[error("wrong credentials")]
+    WrongCredentials,
+
+    #[error("totp is not enabled")]
+    NotEnabled,
+
+    #[error("user not found")]
+    NotFound,
+
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("failed to hash backup codes")]
+    HashError,
+
+    #[error("failed to store backup codes")]
+    Model(#[from] ModelError),
+}
+
+/// Generate a set of backup codes and store them (hashed) for the given user, returns the plaintext codes.
+pub async fn new_backup_codes(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Vec<String>, BackupCodesError> {
+    let backup_codes = (0..BACKUP_CODE_COUNT)
+        .map(|_| crypto::totp::generate_backup_code())
+        .collect::<Vec<_>>();
+
+    let code_hashes = match state
+        .hashing_pool()
+        .hash_backup_codes(backup_codes.clone())
+        .await
+    {
+        Ok(code_hashes) => code_hashes,
+        Err(HashingError::Overloaded) => return Err(BackupCodesError::Overloaded),
+        Err(_) => return Err(BackupCodesError::HashError),
+    };
+
+    TOTPBackupCode::builder(
+        user_id,
+        code_hashes
+            .into_iter()
+            .map(|code_hash| (state.id_generator().next_snowflake().unwrap(), code_hash))
+            .collect(),
+    )
+    .create(prisma_client)
+    .await?;
+
+    Ok(backup_codes)
+}
+
+/// Regenerate the backup codes of a user after re-authenticating with their password.
+///
+/// All remaining backup codes are expired and a new set is returned in plaintext, the codes can not be
+/// retrieved again.
+pub async fn regenerate_backup_codes(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    password: String,
+) -> Result<Vec<String>, BackupCodesError> {
+    let mut user = match User::get(prisma_client, user_id, vec![UserWith::BasicAuth]).await {
+        Ok(user) => user,
+        Err(ModelError::NotFound) => return Err(BackupCodesError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    // Re-authenticate the user
+    let password_hash = match user.basic_auth(None).await {
+        Some(auth) => auth.password_hash().to_owned(),
+        None => return Err(BackupCodesError::WrongCredentials),
+    };
+
+    match state
+        .hashing_pool()
+        .verify_password(password, password_hash)
+        .await
+    {
+        Ok(()) => {}
+        Err(HashingError::Overloaded) => return Err(BackupCodesError::Overloaded),
+        Err(_) => return Err(BackupCodesError::WrongCredentials),
+    }
+
+    if !user.totp_enabled() {
+        return Err(BackupCodesError::NotEnabled);
+    }
+
+    // Invalidate the old set before creating the new one
+    TOTPBackupCode::expire_all(prisma_client, user_id).await?;
+    let backup_codes = new_backup_codes(state, prisma_client, user_id).await?;
+
+    Ok(backup_codes)
+}
+
+#[derive(Debug, Error)]
+pub enum ConfirmEnrolmentError {
+    #[error("no pending enrolment")]
+    NotFound,
+
+    #[error("enrolment is expired")]
+    Expired,
+
+    #[error("wrong code")]
+    WrongCode,
+
+    /// Too many wrong codes were given, the pending enrolment has been deleted.
+    #[error("too many attempts")]
+    TooManyAttempts,
+
+    #[error("failed to generate backup codes")]
+    BackupCodes(#[from] BackupCodesError),
+
+    #[error("failed to confirm enrolment")]
+    Model(#[from] ModelError),
+}
+
+/// Confirm a pending TOTP enrolment with a code from the authenticator.
+///
+/// Activates the device and TOTP for the user. When this is the first device of the user a set of
+/// backup codes is returned in plaintext, the codes can not be retrieved again.
+pub async fn confirm_enrolment(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    code: String,
+) -> Result<Option<Vec<String>>, ConfirmEnrolmentError> {
+    let user = match User::get(prisma_client, user_id, vec![UserWith::TOTP]).await {
+        Ok(user) => user,
+        Err(ModelError::NotFound) => return Err(ConfirmEnrolmentError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut totp = match user.totps().iter().find(|totp| totp.is_pending()) {
+        Some(totp) => totp.clone(),
+        None => return Err(ConfirmEnrolmentError::NotFound),
+    };
+
+    if totp.is_expired_enrolment() {
+        TOTP::delete(prisma_client, totp.id()).await?;
+        return Err(ConfirmEnrolmentError::Expired);
+    }
+
+    // Attempts are counted outside of the transaction, which is not committed for a wrong code
+    if !totp
+        .try_confirm_attempt(state.prisma(), MAX_ENROLMENT_ATTEMPTS)
+        .await?
+    {
+        TOTP::delete(state.prisma(), totp.id()).await?;
+        return Err(ConfirmEnrolmentError::TooManyAttempts);
+    }
+
+    // Only codes from the new authenticator are accepted
+    if !totp.verify(prisma_client, code).await? {
+        return Err(ConfirmEnrolmentError::WrongCode);
+    }
+
+    totp.confirm(prisma_client).await?;
+    prisma_client
+        .user()
+        .update(
+            prisma::user::id::equals(user_id.to_id_signed()),
+            vec![prisma::user::totp_enabled::set(true)],
+        )
+        .exec()
+        .await
+        .map_err(ModelError::from)?;
+
+    // Backup codes are shared by all devices, they are only generated with the first device
+    if TOTPBackupCode::count_remaining(prisma_client, user_id).await? > 0 {
+        return Ok(None);
+    }
+
+    let backup_codes = new_backup_codes(state, prisma_client, user_id).await?;
+
+    Ok(Some(backup_codes))
+}
+
+#[derive(Debug, Error)]
+pub enum VerifyFactorError {
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("failed to verify code")]
+    Model(#[from] ModelError),
+}
+
+/// Verify a second factor code of a user against all of their active devices and the latest codes
+/// sent by SMS or email, or against their backup codes if the code contains a dash. Returns the
+/// type of factor the code belongs to if it is valid.
+///
+/// The user must be fetched with their TOTP devices, phone number and email address.
+///
+/// Codes sent by SMS or email are verified outside of the transaction of `prisma_client`, so that
+/// their attempts are stored even if the transaction is not committed after a wrong code.
+pub async fn verify_code(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user: &User,
+    code: String,
+) -> Result<Option<FactorType>, VerifyFactorError> {
+    if code.contains('-') {
+        let backup_codes = TOTPBackupCode::list_remaining(prisma_client, user.id()).await?;
+        let code_hashes = backup_codes
+            .iter()
+            .map(|backup_code| backup_code.code_hash().to_owned())
+            .collect();
+
+        let index = match state
+            .hashing_pool()
+            .find_backup_code(code, code_hashes)
+            .await
+        {
+            Ok(index) => index,
+            Err(HashingError::Overloaded) => return Err(VerifyFactorError::Overloaded),
+            Err(_) => None,
+        };
+
+        let valid = match index {
+            Some(index) => backup_codes[index].redeem(prisma_client).await?,
+            None => false,
+        };
+        return Ok(valid.then_some(FactorType::BackupCode));
+    }
+
+    for totp in user.active_totps() {
+        if totp.verify(prisma_client, code.clone()).await? {
+            return Ok(Some(totp.kind().into()));
+        }
+    }
+
+    if let Some(phone_number) = user.phone_number().filter(|p| p.mfa_enabled()) {
+        match sms::verify_code(
+            state,
+            state.prisma(),
+            phone_number,
+            OneTimeCodePurpose::SignIn,
+            &code,
+        )
+        .await
+        {
+            Ok(true) => return Ok(Some(FactorType::Sms)),
+            Err(VerifyCodeError::Overloaded) => return Err(VerifyFactorError::Overloaded),
+            Err(VerifyCodeError::Model(e)) => return Err(e.into()),
+            Ok(false) | Err(_) => {}
+        }
+    }
+
+    if user.email_address().map_or(false, |e| e.mfa_enabled())
+        && email_otp::is_allowed(prisma_client, user.application_id()).await?
+    {
+        match email_otp::verify_code(state, state.prisma(), user.id(), &code).await {
+            Ok(true) => return Ok(Some(FactorType::Email)),
+            Err(VerifyCodeError::Overloaded) => return Err(VerifyFactorError::Overloaded),
+            Err(VerifyCodeError::Model(e)) => return Err(e.into()),
+            Ok(false) | Err(_) => {}
+        }
+    }
+
+    Ok(None)
+}
+
+/// Periodically delete pending enrolments that have been abandoned.
+pub async fn delete_expired_enrolments_task(state: AppState) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+        ENROLMENT_LIFETIME_MINUTES as u64 * 60,
+    ));
+
+    loop {
+        interval.tick().await;
+
+        match TOTP::delete_expired_enrolments(state.prisma()).await {
+            Ok(0) => {}
+            Ok(count) => info!("deleted {} expired totp enrolments", count),
+            Err(e) => error!("failed to delete expired totp enrolments: {}", e),
+        }
+    }
 }
@@ -0,0 +1,252 @@
+//! # Trusted devices
+//! After signing in with a second factor a user can choose to trust the device, the login then
+//! skips the second factor on that device for the number of days configured by the application
+//! (see [`MFAConfig`]).
+//!
+//! The device receives a trusted device token in a cookie. The token is an encrypted PASETO bound
+//! to the user (subject) and the application (audience), it is also stored as a [`UserToken`] so
+//! that the user can list and revoke their trusted devices.
+//!
+//! Disabling trusted devices for an application stops new devices from being trusted and existing
+//! tokens from being accepted.
+
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use chrono::{DateTime, Duration, Utc};
+use crypto::{
+    snowflake::Snowflake,
+    tokens::paseto::{self, DefaultClaims},
+};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    models::{
+        application::{MFAConfig, ReplicatedApplication},
+        error::ModelError,
+        prisma::UserTokenType,
+        user::UserToken,
+        PrismaClient,
+    },
+    state::AppState,
+};
+
+/// A device a user has trusted to skip the second factor.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrustedDevice {
+    id: Snowflake,
+
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+impl From<&UserToken> for TrustedDevice {
+    fn from(value: &UserToken) -> Self {
+        Self {
+            id: value.id(),
+
+            ip_address: value.ip_address().cloned(),
+            user_agent: value.user_agent().cloned(),
+
+            created_at: value.created_at(),
+            expires_at: value.expires_at(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TrustDeviceError {
+    #[error("trusted devices are not enabled for the application")]
+    NotEnabled,
+
+    #[error("internal paseto error")]
+    PasetoError(#[from] paseto::Error),
+
+    #[error("failed to store trusted device")]
+    Model(#[from] ModelError),
+}
+
+/// Get the MFA config of an application.
+async fn get_mfa_config(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+) -> Result<MFAConfig, ModelError> {
+    let mut application = ReplicatedApplication::get(prisma_client, application_id).await?;
+    Ok(application.mfa_config(prisma_client).await?)
+}
+
+/// Trust the device of a user that has just signed in with a second factor, returns the stored
+/// token which should be sent to the device with [`create_trusted_device_cookie`].
+pub async fn trust_device(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    application_id: Snowflake,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+) -> Result<UserToken, TrustDeviceError> {
+    let mfa_config = get_mfa_config(prisma_client, application_id).await?;
+    if !mfa_config.trusted_devices_enabled() {
+        return Err(TrustDeviceError::NotEnabled);
+    }
+
+    let token_id = state.id_generator().next_snowflake().unwrap();
+    let expires_at = Utc::now() + Duration::days(mfa_config.trusted_device_days() as i64);
+
+    let default_claims = DefaultClaims::builder("AuthCore", expires_at, token_id)
+        .subject(user_id)
+        .audience(application_id)
+        .build();
+
+    let token = paseto::encrypt_token(default_claims, state.paseto_key())?;
+
+    let token = UserToken::builder(
+        token_id,
+        user_id,
+        UserTokenType::TrustedDevice,
+        token,
+        expires_at,
+    )
+    .ip_address(ip_address)
+    .user_agent(user_agent)
+    .build(prisma_client)
+    .await?;
+
+    Ok(token)
+}
+
+/// Whether a trusted device token lets a user of an application skip the second factor.
+///
+/// Invalid, expired and revoked tokens are not trusted, neither is any token when the application
+/// has disabled trusted devices.
+pub async fn is_trusted(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    token: &str,
+    user_id: Snowflake,
+    application_id: Snowflake,
+) -> Result<bool, ModelError> {
+    let claims = match paseto::validate_token::<serde_json::Value>(token, state.paseto_key()) {
+        Ok(claims) => claims,
+        Err(_) => return Ok(false),
+    };
+
+    // The token must be bound to the user and the application
+    if claims.subject() != Some(&user_id.to_string())
+        || claims.audience() != Some(&application_id.to_string())
+    {
+        return Ok(false);
+    }
+
+    let token_id: Snowflake = match claims.token_id().to_owned().try_into() {
+        Ok(token_id) => token_id,
+        Err(_) => return Ok(false),
+    };
+
+    // Revoked tokens are deleted
+    let database_token = match UserToken::get(
+        prisma_client,
+        user_id,
+        token_id,
+        UserTokenType::TrustedDevice,
+    )
+    .await
+    {
+        Ok(token) => token,
+        Err(ModelError::NotFound) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    if database_token.token() != token || database_token.expires_at() < Utc::now() {
+        return Ok(false);
+    }
+
+    let mfa_config = get_mfa_config(prisma_client, application_id).await?;
+    Ok(mfa_config.trusted_devices_enabled())
+}
+
+/// List the trusted devices of a user.
+pub async fn list(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<Vec<TrustedDevice>, ModelError> {
+    let tokens = UserToken::list(prisma_client, user_id, UserTokenType::TrustedDevice).await?;
+
+    Ok(tokens.iter().map(TrustedDevice::from).collect())
+}
+
+#[derive(Debug, Error)]
+pub enum RevokeError {
+    #[error("trusted device not found")]
+    NotFound,
+
+    #[error("failed to revoke trusted device")]
+    Model(#[from] ModelError),
+}
+
+/// Revoke a trusted device of a user, the device has to sign in with a second factor again.
+pub async fn revoke(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+    device_id: Snowflake,
+) -> Result<(), RevokeError> {
+    let token = match UserToken::get(
+        prisma_client,
+        user_id,
+        device_id,
+        UserTokenType::TrustedDevice,
+    )
+    .await
+    {
+        Ok(token) => token,
+        Err(ModelError::NotFound) => return Err(RevokeError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+
+    UserToken::delete(prisma_client, token.id()).await?;
+
+    Ok(())
+}
+
+/// Revoke all trusted devices of a user, returns the number of revoked devices.
+pub async fn revoke_all(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<i64, ModelError> {
+    UserToken::delete_all(prisma_client, user_id, UserTokenType::TrustedDevice).await
+}
+
+pub fn create_trusted_device_cookie<'a>(
+    token: String,
+    expire: DateTime<Utc>,
+    application_id: Snowflake,
+) -> Cookie<'a> {
+    let expiration_time = time::OffsetDateTime::from_unix_timestamp(expire.timestamp()).unwrap();
+
+    Cookie::build(build_trusted_device_cookie_name(application_id), token)
+        .domain("localhost")
+        .secure(false) // TODO: set to true
+        .http_only(true)
+        .expires(expiration_time)
+        .path("/")
+        .same_site(SameSite::Strict)
+        .finish()
+}
+
+pub fn get_trusted_device_cookie(jar: &CookieJar, application_id: Snowflake) -> Option<String> {
+    jar.get(&build_trusted_device_cookie_name(application_id))
+        .map(|cookie| cookie.value().to_owned())
+}
+
+fn build_trusted_device_cookie_name(application_id: Snowflake) -> String {
+    let application_id = crypto::totp::BASE32_NOPAD
+        .encode(application_id.to_string().as_bytes())
+        .to_lowercase();
+
+    format!("trusted_device_{}", application_id)
+}
@@ -0,0 +1,357 @@
+//! # User administration
+//! Lets the backend of an application manage its users over the UserAdmin gRPC service. Every
+//! operation is scoped to an application, users of other applications are not found.
+//!
+//! Disabling a user, resetting their second factors and forcing a logout revoke the refresh
+//! tokens of the user. Access tokens that were already issued stay valid until they expire.
+
+use crypto::{input::password, snowflake::Snowflake};
+use thiserror::Error;
+
+use crate::{
+    core::{
+        basic::{self, password::PasswordChangeError},
+        hashing::HashingError,
+        mfa, trusted_device,
+    },
+    models::{
+        application::ReplicatedApplication,
+        error::ModelError,
+        prisma::UserTokenType,
+        user::{EmailAddress, User, UserToken, UserWith},
+        PrismaClient,
+    },
+    state::{AppState, CONFIG},
+};
+
+/// Number of users listed if no limit is given.
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// Maximum number of users listed at once.
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Error)]
+pub enum UserAdminError {
+    #[error("application does not exist")]
+    ApplicationDoesNotExist,
+
+    #[error("user not found")]
+    NotFound,
+
+    #[error("invalid email address")]
+    EmailFormat,
+
+    #[error("invalid password")]
+    PasswordFormat(Vec<password::PasswordValidationError>),
+
+    #[error("email address already exists")]
+    AlreadyExists,
+
+    #[error("password has been used recently")]
+    PasswordReused,
+
+    /// The user signs in without a password, e.g. an invited user who has not accepted yet.
+    #[error("user has no password")]
+    NoPassword,
+
+    #[error("failed to hash password")]
+    HashError,
+
+    #[error("password hashing is overloaded")]
+    Overloaded,
+
+    #[error("user admin model error")]
+    Model(#[from] ModelError),
+}
+
+impl From<PasswordChangeError> for UserAdminError {
+    fn from(value: PasswordChangeError) -> Self {
+        match value {
+            PasswordChangeError::NotFound | PasswordChangeError::WrongCredentials => {
+                UserAdminError::NotFound
+            }
+            PasswordChangeError::PasswordFormat(errors) => UserAdminError::PasswordFormat(errors),
+            PasswordChangeError::Reused => UserAdminError::PasswordReused,
+            PasswordChangeError::Overloaded => UserAdminError::Overloaded,
+            PasswordChangeError::HashError => UserAdminError::HashError,
+            PasswordChangeError::Model(e) => UserAdminError::Model(e),
+        }
+    }
+}
+
+/// The joins used for users returned to the backend.
+fn user_with() -> Vec<UserWith> {
+    vec![
+        UserWith::EmailAddress,
+        UserWith::PhoneNumber,
+        UserWith::TOTP,
+    ]
+}
+
+/// Get a user of an application.
+pub async fn get_user(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<User, UserAdminError> {
+    match User::get(prisma_client, user_id, user_with()).await {
+        Ok(user) if user.application_id() == application_id => Ok(user),
+        Ok(_) | Err(ModelError::NotFound) => Err(UserAdminError::NotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Get a user of an application by their email address.
+pub async fn find_user_by_email(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    email: String,
+) -> Result<User, UserAdminError> {
+    match User::find_by_email(prisma_client, email, application_id, user_with()).await {
+        Ok(user) => Ok(user),
+        Err(ModelError::NotFound) => Err(UserAdminError::NotFound),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// List the users of an application, optionally only those whose email address contains
+/// `email_query`.
+///
+/// Users are ordered by ID, a page starts after the `after` cursor. Returns the cursor of the next
+/// page, which is `None` on the last page.
+pub async fn list_users(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    email_query: Option<String>,
+    after: Option<Snowflake>,
+    limit: Option<i64>,
+) -> Result<(Vec<User>, Option<Snowflake>), UserAdminError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    // Fetch one more user to know whether there is a next page
+    let mut users = User::list(
+        prisma_client,
+        application_id,
+        email_query,
+        after,
+        limit + 1,
+        user_with(),
+    )
+    .await?;
+
+    let next_cursor = if users.len() as i64 > limit {
+        users.truncate(limit as usize);
+        users.last().map(User::id)
+    } else {
+        None
+    };
+
+    Ok((users, next_cursor))
+}
+
+/// Create a user of an application. Without a password the user can only sign in with other
+/// methods or after setting a password.
+pub async fn create_user(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    email: String,
+    password: Option<String>,
+    email_verified: bool,
+) -> Result<User, UserAdminError> {
+    if !crypto::input::email::validate_email(&email) {
+        return Err(UserAdminError::EmailFormat);
+    }
+
+    match EmailAddress::find_by_address(prisma_client, &email, application_id).await {
+        Ok(_) => return Err(UserAdminError::AlreadyExists),
+        Err(ModelError::NotFound) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    let mut application = match ReplicatedApplication::get(prisma_client, application_id).await {
+        Ok(application) => application,
+        Err(ModelError::NotFound) => return Err(UserAdminError::ApplicationDoesNotExist),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut user = User::builder(
+        state.id_generator(),
+        prisma_client,
+        application_id,
+        email.clone(),
+    );
+    user.email_verified(email_verified);
+
+    if let Some(password) = password {
+        // The password requirements of the application also apply to users created by it
+        let password_requirements = application
+            .basic_auth_config(prisma_client)
+            .await
+            .as_password_requirements_config();
+
+        if let Err(e) = password::validate_password(
+            &password,
+            &[&email],
+            true,
+            password_requirements,
+            CONFIG.breached_passwords(),
+        ) {
+            return Err(UserAdminError::PasswordFormat(e));
+        }
+
+        let password_hash = match state.hashing_pool().hash_password(password).await {
+            Ok(password_hash) => password_hash,
+            Err(HashingError::Overloaded) => return Err(UserAdminError::Overloaded),
+            Err(_) => return Err(UserAdminError::HashError),
+        };
+        user.basic_auth(password_hash);
+    }
+
+    let user = user.build().await?;
+
+    get_user(prisma_client, application_id, user.id()).await
+}
+
+/// Change the email address of a user and whether it is verified. A changed address is
+/// unverified unless `verified` is set.
+pub async fn update_user(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+    email: Option<String>,
+    verified: Option<bool>,
+) -> Result<User, UserAdminError> {
+    let user = get_user(prisma_client, application_id, user_id).await?;
+    let mut email_address = match user.email_address() {
+        Some(email_address) => email_address.clone(),
+        None => return Err(UserAdminError::NotFound),
+    };
+
+    let email = match email {
+        Some(email) if email != email_address.email_address() => {
+            if !crypto::input::email::validate_email(&email) {
+                return Err(UserAdminError::EmailFormat);
+            }
+
+            match EmailAddress::find_by_address(prisma_client, &email, application_id).await {
+                Ok(_) => return Err(UserAdminError::AlreadyExists),
+                Err(ModelError::NotFound) => (),
+                Err(e) => return Err(e.into()),
+            }
+
+            email
+        }
+        _ => email_address.email_address().to_owned(),
+    };
+
+    let changed = email != email_address.email_address();
+    let verified = verified.unwrap_or(!changed && email_address.verified());
+
+    email_address.update(prisma_client, email, verified).await?;
+
+    get_user(prisma_client, application_id, user_id).await
+}
+
+/// Disable or enable a user, disabling signs the user out everywhere.
+pub async fn set_disabled(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+    disabled: bool,
+) -> Result<User, UserAdminError> {
+    let mut user = get_user(prisma_client, application_id, user_id).await?;
+    user.set_disabled(prisma_client, disabled).await?;
+
+    if disabled {
+        revoke_sessions(prisma_client, user_id).await?;
+    }
+
+    Ok(user)
+}
+
+/// Set the password of a user, users without a password are given one. With `require_change`
+/// the password is temporary and the user has to change it on their next login.
+pub async fn set_password(
+    state: &AppState,
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+    password: String,
+    require_change: bool,
+) -> Result<User, UserAdminError> {
+    get_user(prisma_client, application_id, user_id).await?;
+    basic::password::set_password(state, prisma_client, user_id, password).await?;
+
+    if require_change {
+        return require_password_change(prisma_client, application_id, user_id).await;
+    }
+
+    get_user(prisma_client, application_id, user_id).await
+}
+
+/// Require a user to change their password on their next login. Existing sessions are kept, see
+/// [`force_logout`] to also sign the user out.
+pub async fn require_password_change(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<User, UserAdminError> {
+    let mut user = get_user(prisma_client, application_id, user_id).await?;
+
+    let mut auth = match user.basic_auth(Some(prisma_client)).await {
+        Some(auth) => auth.clone(),
+        None => return Err(UserAdminError::NoPassword),
+    };
+    auth.set_must_change_password(prisma_client, true).await?;
+
+    Ok(user)
+}
+
+/// Delete a user together with all of their data.
+pub async fn delete_user(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<(), UserAdminError> {
+    get_user(prisma_client, application_id, user_id).await?;
+    User::delete(prisma_client, user_id).await?;
+
+    Ok(())
+}
+
+/// Remove all second factors and trusted devices of a user, e.g. after they lost their device.
+/// The user is signed out everywhere.
+pub async fn reset_mfa(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<(), UserAdminError> {
+    get_user(prisma_client, application_id, user_id).await?;
+
+    mfa::remove_all_devices(prisma_client, user_id).await?;
+    trusted_device::revoke_all(prisma_client, user_id).await?;
+    revoke_sessions(prisma_client, user_id).await?;
+
+    Ok(())
+}
+
+/// Sign a user out everywhere, returns the number of revoked sessions.
+pub async fn force_logout(
+    prisma_client: &PrismaClient,
+    application_id: Snowflake,
+    user_id: Snowflake,
+) -> Result<i64, UserAdminError> {
+    get_user(prisma_client, application_id, user_id).await?;
+
+    Ok(revoke_sessions(prisma_client, user_id).await?)
+}
+
+/// Revoke the refresh tokens of a user and any 2FA flow in progress.
+async fn revoke_sessions(
+    prisma_client: &PrismaClient,
+    user_id: Snowflake,
+) -> Result<i64, ModelError> {
+    UserToken::delete_all(prisma_client, user_id, UserTokenType::TotpFlow).await?;
+    UserToken::delete_all(prisma_client, user_id, UserTokenType::Refresh).await
+}
@@ -0,0 +1,366 @@
+//! # User import
+//! Migrates the users of another identity system into an application, either streamed over the
+//! `ImportUsers` RPC of the UserAdmin gRPC service or read from a file by the `import-users`
+//! command.
+//!
+//! Every user is imported with their email address, whether it is verified, the hash of their
+//! password and metadata entries. Password hashes are stored as-is, see
+//! [`identify_hash`](crypto::password::identify_hash) for the accepted algorithms, and replaced
+//! with an argon2 hash the next time the user signs in. Users without a password hash can only sign
+//! in after setting a password.
+//!
+//! Invalid rows are skipped and reported together with their row number, the remaining rows are
+//! still imported. A dry run validates every row without creating any users.
+//!
+//! ## File formats
+//! JSON lines, one user per line:
+//! ```json
+//! {"email": "alice@example.com", "password_hash": "$2b$12$...", "email_verified": true, "public_metadata": {"plan": "pro"}}
+//! ```
+//!
+//! CSV with a header row and the columns `email`, `password_hash`, `email_verified`,
+//! `public_metadata`, `private_metadata` and `app_metadata`, where the metadata columns contain
+//! JSON objects. Only `email` is required.
+
+use std::{collections::HashSet, io::BufRead};
+
+use crypto::snowflake::{Snowflake, SnowflakeGenerator};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::info;
+
+use crate::{
+    core::metadata::{self, MetadataError},
+    models::{
+        application::ReplicatedApplication,
+        error::ModelError,
+        user::{metadata::MetadataScope, EmailAddress, User},
+        PrismaClient,
+    },
+};
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("application does not exist")]
+    ApplicationDoesNotExist,
+
+    #[error("invalid row: {0}")]
+    Parse(String),
+
+    #[error("invalid email address")]
+    EmailFormat,
+
+    #[error("email address already exists")]
+    AlreadyExists,
+
+    #[error("email address is imported by an earlier row")]
+    Duplicate,
+
+    #[error("unsupported password hash")]
+    UnsupportedPasswordHash,
+
+    #[error("invalid metadata: {0}")]
+    Metadata(#[from] MetadataError),
+
+    /// A key is used in more than one metadata scope, a key is unique per user.
+    #[error("metadata key {0} is used in more than one scope")]
+    DuplicateMetadataKey(String),
+
+    #[error("import model error")]
+    Model(#[from] ModelError),
+}
+
+pub type Metadata = serde_json::Map<String, serde_json::Value>;
+
+/// A user to import.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImportUser {
+    pub email: String,
+
+    /// The hash of the password of the user, `None` or empty for users without a password.
+    #[serde(default)]
+    pub password_hash: Option<String>,
+
+    #[serde(default)]
+    pub email_verified: bool,
+
+    #[serde(default)]
+    pub public_metadata: Metadata,
+    #[serde(default)]
+    pub private_metadata: Metadata,
+    #[serde(default)]
+    pub app_metadata: Metadata,
+}
+
+impl ImportUser {
+    fn password_hash(&self) -> Option<&str> {
+        self.password_hash
+            .as_deref()
+            .filter(|hash| !hash.is_empty())
+    }
+
+    /// The metadata entries of the user by scope.
+    fn metadata(&self) -> [(MetadataScope, &Metadata); 3] {
+        [
+            (MetadataScope::Public, &self.public_metadata),
+            (MetadataScope::Private, &self.private_metadata),
+            (MetadataScope::AppOnly, &self.app_metadata),
+        ]
+    }
+
+    /// Validate the user without accessing the database.
+    fn validate(&self) -> Result<(), ImportError> {
+        if !crypto::input::email::validate_email(&self.email) {
+            return Err(ImportError::EmailFormat);
+        }
+
+        if let Some(password_hash) = self.password_hash() {
+            if crypto::password::identify_hash(password_hash).is_none() {
+                return Err(ImportError::UnsupportedPasswordHash);
+            }
+        }
+
+        let entries: usize = self.metadata().iter().map(|(_, m)| m.len()).sum();
+        if entries as i64 > metadata::MAX_ENTRIES {
+            return Err(MetadataError::TooManyEntries.into());
+        }
+
+        let mut keys = HashSet::new();
+        for (_, entries) in self.metadata() {
+            for (key, value) in entries {
+                metadata::validate_entry(key, value)?;
+
+                if !keys.insert(key) {
+                    return Err(ImportError::DuplicateMetadataKey(key.to_owned()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A row of a CSV file, the metadata columns contain JSON objects.
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    email: String,
+    password_hash: Option<String>,
+    email_verified: Option<bool>,
+    public_metadata: Option<String>,
+    private_metadata: Option<String>,
+    app_metadata: Option<String>,
+}
+
+impl TryFrom<CsvRow> for ImportUser {
+    type Error = ImportError;
+
+    fn try_from(value: CsvRow) -> Result<Self, Self::Error> {
+        fn parse_metadata(column: &str, cell: Option<String>) -> Result<Metadata, ImportError> {
+            match cell.filter(|cell| !cell.is_empty()) {
+                Some(cell) => serde_json::from_str(&cell)
+                    .map_err(|e| ImportError::Parse(format!("{}: {}", column, e))),
+                None => Ok(Metadata::new()),
+            }
+        }
+
+        Ok(ImportUser {
+            email: value.email,
+            password_hash: value.password_hash,
+            email_verified: value.email_verified.unwrap_or(false),
+            public_metadata: parse_metadata("public_metadata", value.public_metadata)?,
+            private_metadata: parse_metadata("private_metadata", value.private_metadata)?,
+            app_metadata: parse_metadata("app_metadata", value.app_metadata)?,
+        })
+    }
+}
+
+/// Read users from JSON lines, empty lines are skipped.
+pub fn read_jsonl<R: BufRead>(reader: R) -> impl Iterator<Item = Result<ImportUser, ImportError>> {
+    reader
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| {
+            let line = line.map_err(|e| ImportError::Parse(e.to_string()))?;
+            serde_json::from_str(&line).map_err(|e| ImportError::Parse(e.to_string()))
+        })
+}
+
+/// Read users from a CSV file with a header row.
+pub fn read_csv<R: BufRead>(reader: R) -> impl Iterator<Item = Result<ImportUser, ImportError>> {
+    csv::Reader::from_reader(reader)
+        .into_deserialize::<CsvRow>()
+        .map(|row| {
+            row.map_err(|e| ImportError::Parse(e.to_string()))
+                .and_then(ImportUser::try_from)
+        })
+}
+
+/// An invalid row of an import.
+#[derive(Debug)]
+pub struct RowError {
+    row: u64,
+    email: String,
+    error: ImportError,
+}
+
+impl RowError {
+    /// The number of the row, starting at 1.
+    pub fn row(&self) -> u64 {
+        self.row
+    }
+
+    /// The email address of the row, empty if the row could not be parsed.
+    pub fn email(&self) -> &str {
+        self.email.as_ref()
+    }
+
+    pub fn error(&self) -> &ImportError {
+        &self.error
+    }
+}
+
+/// The outcome of an import.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    rows: u64,
+    imported: u64,
+    errors: Vec<RowError>,
+}
+
+impl ImportReport {
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// The number of created users, or of valid rows for a dry run.
+    pub fn imported(&self) -> u64 {
+        self.imported
+    }
+
+    pub fn errors(&self) -> &[RowError] {
+        self.errors.as_ref()
+    }
+}
+
+/// Imports the rows of an import one by one.
+pub struct Importer {
+    application_id: Snowflake,
+    dry_run: bool,
+
+    /// The email addresses of the valid rows so far.
+    emails: HashSet<String>,
+    report: ImportReport,
+}
+
+impl Importer {
+    pub async fn new(
+        prisma_client: &PrismaClient,
+        application_id: Snowflake,
+        dry_run: bool,
+    ) -> Result<Self, ImportError> {
+        match ReplicatedApplication::get(prisma_client, application_id).await {
+            Ok(_) => {}
+            Err(ModelError::NotFound) => return Err(ImportError::ApplicationDoesNotExist),
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(Self {
+            application_id,
+            dry_run,
+            emails: HashSet::new(),
+            report: ImportReport::default(),
+        })
+    }
+
+    /// Import the next row. Invalid rows are added to the report, database errors abort the
+    /// import.
+    ///
+    /// Every user is created in its own transaction, users of earlier rows are kept if the import
+    /// is aborted.
+    pub async fn import(
+        &mut self,
+        id_generator: &SnowflakeGenerator,
+        prisma_client: &PrismaClient,
+        user: Result<ImportUser, ImportError>,
+    ) -> Result<(), ImportError> {
+        self.report.rows += 1;
+
+        let (email, result) = match user {
+            Ok(user) => (
+                user.email.clone(),
+                self.import_user(id_generator, prisma_client, user).await,
+            ),
+            Err(e) => (String::new(), Err(e)),
+        };
+
+        match result {
+            Ok(()) => self.report.imported += 1,
+            Err(ImportError::Model(e)) => return Err(ImportError::Model(e)),
+            Err(error) => self.report.errors.push(RowError {
+                row: self.report.rows,
+                email,
+                error,
+            }),
+        }
+
+        Ok(())
+    }
+
+    async fn import_user(
+        &mut self,
+        id_generator: &SnowflakeGenerator,
+        prisma_client: &PrismaClient,
+        user: ImportUser,
+    ) -> Result<(), ImportError> {
+        user.validate()?;
+
+        if self.emails.contains(&user.email) {
+            return Err(ImportError::Duplicate);
+        }
+
+        match EmailAddress::find_by_address(prisma_client, &user.email, self.application_id).await {
+            Ok(_) => return Err(ImportError::AlreadyExists),
+            Err(ModelError::NotFound) => (),
+            Err(e) => return Err(e.into()),
+        }
+
+        self.emails.insert(user.email.clone());
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mut builder = User::builder(
+            id_generator,
+            prisma_client,
+            self.application_id,
+            user.email.clone(),
+        );
+        builder.email_verified(user.email_verified);
+
+        if let Some(password_hash) = user.password_hash() {
+            builder.basic_auth(password_hash.to_owned());
+        }
+
+        for (scope, entries) in user.metadata() {
+            for (key, value) in entries {
+                builder.user_metadata(key.clone(), scope, value.clone());
+            }
+        }
+
+        builder.build().await?;
+
+        Ok(())
+    }
+
+    pub fn finish(self) -> ImportReport {
+        info!(
+            "imported {} of {} users into application {}{}",
+            self.report.imported,
+            self.report.rows,
+            self.application_id,
+            if self.dry_run { " (dry run)" } else { "" }
+        );
+
+        self.report
+    }
+}
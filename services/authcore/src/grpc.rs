@@ -7,8 +7,11 @@ use crate::AppState;
 
 mod auth;
 mod error;
+mod metadata;
 mod platform;
+mod rbac;
 mod session;
+mod user_admin;
 
 pub mod client;
 
@@ -47,11 +50,23 @@ pub async fn run(addr: SocketAddr, state: AppState) -> Result<(), GrpcServerErro
     let inner = session::SessionServer::new(state.clone());
     let svc_session = session::session_server::SessionServer::new(inner);
 
+    let inner = rbac::RbacServer::new(state.clone());
+    let svc_rbac = rbac::rbac_server::RbacServer::new(inner);
+
+    let inner = metadata::UserMetadataServer::new(state.clone());
+    let svc_metadata = metadata::user_metadata_server::UserMetadataServer::new(inner);
+
+    let inner = user_admin::UserAdminServer::new(state.clone());
+    let svc_user_admin = user_admin::user_admin_server::UserAdminServer::new(inner);
+
     tracing::info!("grpc listening on {}", addr);
     tonic::transport::Server::builder()
         .add_service(svc_platform)
         .add_service(svc_basic)
         .add_service(svc_session)
+        .add_service(svc_rbac)
+        .add_service(svc_metadata)
+        .add_service(svc_user_admin)
         .serve(addr)
         .await
         .map_err(GrpcServerError::GRPCServerError)?;
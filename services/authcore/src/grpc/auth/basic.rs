@@ -76,6 +76,11 @@ impl BasicAuth for BasicServer {
                     "application does not exist",
                     Code::NotFound,
                 ),
+                BasicRegistrationError::Overloaded => (
+                    ErrorCode::Overloaded,
+                    "too many registrations are being processed, try again later",
+                    Code::Unavailable,
+                ),
                 BasicRegistrationError::InternalServerError => (
                     ErrorCode::InternalServerError,
                     "internal server error",
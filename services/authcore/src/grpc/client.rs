@@ -1,13 +1,21 @@
 use tonic::transport::Channel;
 
-use self::email::email_service_client::EmailServiceClient;
+use self::{
+    email::email_service_client::EmailServiceClient, sms::sms_service_client::SmsServiceClient,
+};
 
 /// Tonic-generated gRPC bindings
 pub mod email {
     tonic::include_proto!("messaging.email");
 }
 
+/// Tonic-generated gRPC bindings
+pub mod sms {
+    tonic::include_proto!("messaging.sms");
+}
+
 pub type EmailClient = EmailServiceClient<Channel>;
+pub type SmsClient = SmsServiceClient<Channel>;
 
 pub async fn connect_to_email_grpc_server() -> Result<EmailClient, Box<dyn std::error::Error>> {
     let client =
@@ -15,3 +23,9 @@ pub async fn connect_to_email_grpc_server() -> Result<EmailClient, Box<dyn std::
 
     Ok(client)
 }
+
+pub async fn connect_to_sms_grpc_server() -> Result<SmsClient, Box<dyn std::error::Error>> {
+    let client = sms::sms_service_client::SmsServiceClient::connect("http://0.0.0.0:50051").await?;
+
+    Ok(client)
+}
@@ -0,0 +1,176 @@
+/// Tonic-generated gRPC bindings
+mod proto_metadata {
+    tonic::include_proto!("authcore.metadata");
+}
+
+use crypto::snowflake::Snowflake;
+pub use proto_metadata::*;
+use tracing::error;
+
+use crate::{
+    core::metadata::{self, MetadataError},
+    models::user::{metadata::MetadataScope as Scope, UserMetadata as Metadata},
+    state::AppState,
+};
+
+use self::user_metadata_server::UserMetadata;
+
+/// The backend of the application can access entries in every scope.
+const ALL_SCOPES: [Scope; 3] = [Scope::AppOnly, Scope::Public, Scope::Private];
+
+pub struct UserMetadataServer {
+    state: AppState,
+}
+
+impl UserMetadataServer {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+}
+
+impl From<Scope> for MetadataScope {
+    fn from(value: Scope) -> Self {
+        match value {
+            Scope::AppOnly => MetadataScope::AppOnly,
+            Scope::Public => MetadataScope::Public,
+            Scope::Private => MetadataScope::Private,
+        }
+    }
+}
+
+impl From<Metadata> for MetadataEntry {
+    fn from(value: Metadata) -> Self {
+        Self {
+            key: value.key().to_owned(),
+            scope: MetadataScope::from(value.scope()).into(),
+            value_json: value.value().to_string(),
+            in_token: value.in_token(),
+            updated_at: value.updated_at().to_rfc3339(),
+        }
+    }
+}
+
+fn scope_from_i32(value: i32) -> Result<Scope, tonic::Status> {
+    match MetadataScope::from_i32(value) {
+        Some(MetadataScope::AppOnly) => Ok(Scope::AppOnly),
+        Some(MetadataScope::Public) => Ok(Scope::Public),
+        Some(MetadataScope::Private) => Ok(Scope::Private),
+        None => Err(tonic::Status::invalid_argument("scope is invalid")),
+    }
+}
+
+fn parse_id(id: String, name: &str) -> Result<Snowflake, tonic::Status> {
+    id.try_into()
+        .map_err(|_| tonic::Status::invalid_argument(format!("{} is invalid", name)))
+}
+
+impl From<MetadataError> for tonic::Status {
+    fn from(value: MetadataError) -> Self {
+        match value {
+            MetadataError::UserNotFound => tonic::Status::not_found("user not found"),
+            MetadataError::NotFound => tonic::Status::not_found("metadata entry not found"),
+            MetadataError::InvalidKey => tonic::Status::invalid_argument(format!(
+                "key must be 1 to {} letters, digits, '_', '-' or '.'",
+                metadata::MAX_KEY_LENGTH
+            )),
+            MetadataError::ValueTooLarge => tonic::Status::invalid_argument(format!(
+                "value must be at most {} bytes",
+                metadata::MAX_VALUE_SIZE
+            )),
+            MetadataError::TooManyEntries => tonic::Status::resource_exhausted(format!(
+                "a user can have at most {} metadata entries",
+                metadata::MAX_ENTRIES
+            )),
+            MetadataError::TokenTooLarge => tonic::Status::resource_exhausted(format!(
+                "metadata projected into the access token must be at most {} bytes",
+                metadata::MAX_TOKEN_SIZE
+            )),
+            MetadataError::ReadOnly => {
+                tonic::Status::permission_denied("metadata entry is read-only")
+            }
+            MetadataError::Model(e) => {
+                error!("metadata model error: {}", e);
+                tonic::Status::internal("internal server error")
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl UserMetadata for UserMetadataServer {
+    async fn list_metadata(
+        &self,
+        request: tonic::Request<ListMetadataRequest>,
+    ) -> Result<tonic::Response<ListMetadataResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+        metadata::check_user(self.state.prisma(), application_id, user_id).await?;
+
+        let entries = metadata::list(self.state.prisma(), user_id, ALL_SCOPES.to_vec()).await?;
+
+        Ok(tonic::Response::new(ListMetadataResponse {
+            metadata: entries.into_iter().map(MetadataEntry::from).collect(),
+        }))
+    }
+
+    async fn get_metadata(
+        &self,
+        request: tonic::Request<GetMetadataRequest>,
+    ) -> Result<tonic::Response<GetMetadataResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+        metadata::check_user(self.state.prisma(), application_id, user_id).await?;
+
+        let entry = metadata::get(self.state.prisma(), user_id, data.key, &ALL_SCOPES).await?;
+
+        Ok(tonic::Response::new(GetMetadataResponse {
+            entry: Some(entry.into()),
+        }))
+    }
+
+    async fn set_metadata(
+        &self,
+        request: tonic::Request<SetMetadataRequest>,
+    ) -> Result<tonic::Response<SetMetadataResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+        let scope = scope_from_i32(data.scope)?;
+
+        let value: serde_json::Value = serde_json::from_str(&data.value_json)
+            .map_err(|_| tonic::Status::invalid_argument("value is not valid JSON"))?;
+
+        metadata::check_user(self.state.prisma(), application_id, user_id).await?;
+
+        let entry = metadata::set(
+            &self.state,
+            self.state.prisma(),
+            user_id,
+            data.key,
+            scope,
+            value,
+            data.in_token,
+        )
+        .await?;
+
+        Ok(tonic::Response::new(SetMetadataResponse {
+            entry: Some(entry.into()),
+        }))
+    }
+
+    async fn delete_metadata(
+        &self,
+        request: tonic::Request<DeleteMetadataRequest>,
+    ) -> Result<tonic::Response<DeleteMetadataResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+        metadata::check_user(self.state.prisma(), application_id, user_id).await?;
+
+        metadata::delete(self.state.prisma(), user_id, data.key, &ALL_SCOPES).await?;
+
+        Ok(tonic::Response::new(DeleteMetadataResponse {}))
+    }
+}
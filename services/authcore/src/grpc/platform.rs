@@ -3,13 +3,26 @@ use std::result::Result;
 use tracing::error;
 
 use crate::{
-    models::application::{BasicAuthConfig, ReplicatedApplication, VerificationConfig},
+    core::{
+        basic::password,
+        mfa_recovery::{self, MfaRecoveryError},
+    },
+    models::{
+        application::{
+            BasicAuthConfig, BasicAuthConfigBuilder, MFAConfig, MFAConfigBuilder, MfaPolicy,
+            ReplicatedApplication, VerificationConfig, DEFAULT_MFA_RECOVERY_WAITING_DAYS,
+            DEFAULT_TOTP_DIGITS, DEFAULT_TOTP_INTERVAL, DEFAULT_TRUSTED_DEVICE_DAYS,
+        },
+        error::ModelError,
+    },
     state::AppState,
 };
 
 use super::authcore::{
-    AddApplicationRequest, AddApplicationResponse, DeleteApplicationRequest,
-    DeleteApplicationResponse, GetVersionRequest, GetVersionResponse,
+    AddApplicationRequest, AddApplicationResponse, CancelMfaRecoveryRequest,
+    CancelMfaRecoveryResponse, DeleteApplicationRequest, DeleteApplicationResponse,
+    GetVersionRequest, GetVersionResponse, SetBasicAuthConfigRequest, SetBasicAuthConfigResponse,
+    SetMfaConfigRequest, SetMfaConfigResponse, SetMfaPolicyRequest, SetMfaPolicyResponse,
 };
 
 pub struct PlatformServer {
@@ -22,6 +35,111 @@ impl PlatformServer {
     }
 }
 
+/// Largest number of days accepted for the periods of the MFA and basic auth configs, about 10
+/// years.
+const MAX_DAYS: u32 = 3650;
+
+fn check_days(value: u32, name: &str) -> Result<u32, tonic::Status> {
+    if value > MAX_DAYS {
+        return Err(tonic::Status::invalid_argument(format!(
+            "{} is invalid",
+            name
+        )));
+    }
+
+    Ok(value)
+}
+
+fn mfa_policy_from_i32(value: i32) -> Result<MfaPolicy, tonic::Status> {
+    match super::authcore::MfaPolicy::from_i32(value) {
+        Some(super::authcore::MfaPolicy::Optional) => Ok(MfaPolicy::Optional),
+        Some(super::authcore::MfaPolicy::Off) => Ok(MfaPolicy::Off),
+        Some(super::authcore::MfaPolicy::Required) => Ok(MfaPolicy::Required),
+        None => Err(tonic::Status::invalid_argument("mfa policy is invalid")),
+    }
+}
+
+/// Build the MFA config of a request, values that are zero are set to their defaults so that an
+/// update replaces the whole config.
+fn mfa_config_from_request(
+    config: &super::authcore::MfaConfig,
+) -> Result<MFAConfigBuilder, tonic::Status> {
+    let mut mfa_config_builder = MFAConfig::builder();
+
+    let totp_algorithm = match super::authcore::TotpAlgorithm::from_i32(config.totp_algorithm) {
+        Some(super::authcore::TotpAlgorithm::Sha1) => crypto::totp::Algorithm::Sha1,
+        Some(super::authcore::TotpAlgorithm::Sha256) => crypto::totp::Algorithm::Sha256,
+        Some(super::authcore::TotpAlgorithm::Sha512) => crypto::totp::Algorithm::Sha512,
+        None => return Err(tonic::Status::invalid_argument("totp algorithm is invalid")),
+    };
+    mfa_config_builder.totp_algorithm(totp_algorithm);
+
+    // Zero means the default value
+    let totp_digits = match config.totp_digits {
+        0 => DEFAULT_TOTP_DIGITS,
+        digits => digits,
+    };
+    if !(crypto::totp::MIN_DIGITS..=crypto::totp::MAX_DIGITS).contains(&totp_digits) {
+        return Err(tonic::Status::invalid_argument("totp digits are invalid"));
+    }
+    mfa_config_builder.totp_digits(totp_digits);
+
+    let totp_interval = match config.totp_interval {
+        0 => DEFAULT_TOTP_INTERVAL,
+        interval => interval,
+    };
+    if !(crypto::totp::MIN_INTERVAL..=crypto::totp::MAX_INTERVAL).contains(&totp_interval) {
+        return Err(tonic::Status::invalid_argument("totp interval is invalid"));
+    }
+    mfa_config_builder.totp_interval(totp_interval);
+
+    mfa_config_builder.email_otp_enabled(config.email_otp_enabled);
+
+    mfa_config_builder.trusted_devices_enabled(config.trusted_devices_enabled);
+    mfa_config_builder.trusted_device_days(match config.trusted_device_days {
+        0 => DEFAULT_TRUSTED_DEVICE_DAYS,
+        days => check_days(days, "trusted device days")?,
+    });
+
+    mfa_config_builder.mfa_policy(mfa_policy_from_i32(config.mfa_policy)?);
+    mfa_config_builder.mfa_grace_period_days(check_days(
+        config.mfa_grace_period_days,
+        "mfa grace period days",
+    )?);
+
+    mfa_config_builder.mfa_recovery_waiting_days(match config.mfa_recovery_waiting_days {
+        0 => DEFAULT_MFA_RECOVERY_WAITING_DAYS,
+        days => check_days(days, "mfa recovery waiting days")?,
+    });
+
+    Ok(mfa_config_builder)
+}
+
+fn basic_auth_config_from_request(
+    config: super::authcore::BasicAuthConfig,
+) -> Result<BasicAuthConfigBuilder, tonic::Status> {
+    let mut basic_auth_config_builder = BasicAuthConfig::builder();
+    basic_auth_config_builder.check_breached_passwords(config.check_breached_passwords);
+
+    match u8::try_from(config.password_history_size) {
+        Ok(size) if size <= password::MAX_HISTORY_SIZE => {
+            basic_auth_config_builder.password_history_size(size);
+        }
+        _ => {
+            return Err(tonic::Status::invalid_argument(
+                "password history size is invalid",
+            ))
+        }
+    }
+
+    basic_auth_config_builder.password_max_age_days(check_days(
+        config.password_max_age_days,
+        "password max age days",
+    )?);
+
+    Ok(basic_auth_config_builder)
+}
+
 #[tonic::async_trait]
 impl super::authcore::platform_server::Platform for PlatformServer {
     async fn get_version(
@@ -42,14 +160,25 @@ impl super::authcore::platform_server::Platform for PlatformServer {
         let (_, _, request) = request.into_parts();
 
         // Configure based on request
-        let basic_auth_config_builder = BasicAuthConfig::builder();
+        let basic_auth_config_builder = match request.basic_auth_config {
+            Some(config) => basic_auth_config_from_request(config)?,
+            None => BasicAuthConfig::builder(),
+        };
         let domain_name = request.domain_name;
+        let name = request.name;
 
         let mut verification_config_builder = VerificationConfig::builder();
         if let Some(config) = request.verification_config {
             verification_config_builder.email_redirect_url(config.email_redirect_url);
             verification_config_builder.expires_after(config.email_verification_ttl);
 
+            if !config.invitation_url.is_empty() {
+                verification_config_builder.invitation_url(config.invitation_url);
+            }
+            if config.invitation_ttl != 0 {
+                verification_config_builder.invitation_expires_after(config.invitation_ttl);
+            }
+
             let email_verification_type =
                 super::authcore::EmailVerificationType::from_i32(config.email_verification_type)
                     .unwrap(); // TODO: Fix unwrap
@@ -71,6 +200,11 @@ impl super::authcore::platform_server::Platform for PlatformServer {
             ));
         };
 
+        let mfa_config_builder = match &request.mfa_config {
+            Some(config) => mfa_config_from_request(config)?,
+            None => MFAConfig::builder(),
+        };
+
         // Verify data
         let application_id = if let Ok(id) = request.application_id.try_into() {
             if ReplicatedApplication::get(self.state.prisma(), id)
@@ -89,8 +223,10 @@ impl super::authcore::platform_server::Platform for PlatformServer {
             self.state.prisma(),
             application_id,
             domain_name,
+            name,
             basic_auth_config_builder,
             verification_config_builder,
+            mfa_config_builder,
         )
         .await
         {
@@ -108,6 +244,157 @@ impl super::authcore::platform_server::Platform for PlatformServer {
         Ok(tonic::Response::new(response))
     }
 
+    async fn set_mfa_policy(
+        &self,
+        request: tonic::Request<SetMfaPolicyRequest>,
+    ) -> Result<tonic::Response<SetMfaPolicyResponse>, tonic::Status> {
+        let (_, _, data) = request.into_parts();
+
+        // Verify data
+        let application_id = if let Ok(id) = data.application_id.try_into() {
+            id
+        } else {
+            return Err(tonic::Status::invalid_argument("application id is invalid"));
+        };
+
+        let mfa_policy = mfa_policy_from_i32(data.mfa_policy)?;
+        let mfa_grace_period_days =
+            check_days(data.mfa_grace_period_days, "mfa grace period days")?;
+
+        let mut application =
+            match ReplicatedApplication::get(self.state.prisma(), application_id).await {
+                Ok(application) => application,
+                Err(ModelError::NotFound) => {
+                    return Err(tonic::Status::not_found("application not found"))
+                }
+                Err(e) => {
+                    error!("failed to get application: {}", e);
+                    return Err(tonic::Status::internal("internal server error"));
+                }
+            };
+
+        if let Err(e) = application
+            .set_mfa_policy(self.state.prisma(), mfa_policy, mfa_grace_period_days)
+            .await
+        {
+            error!("failed to set mfa policy: {}", e);
+            return Err(tonic::Status::internal("internal server error"));
+        }
+
+        Ok(tonic::Response::new(SetMfaPolicyResponse {}))
+    }
+
+    async fn set_mfa_config(
+        &self,
+        request: tonic::Request<SetMfaConfigRequest>,
+    ) -> Result<tonic::Response<SetMfaConfigResponse>, tonic::Status> {
+        let (_, _, data) = request.into_parts();
+
+        // Verify data
+        let application_id = if let Ok(id) = data.application_id.try_into() {
+            id
+        } else {
+            return Err(tonic::Status::invalid_argument("application id is invalid"));
+        };
+
+        let mfa_config_builder = match data.mfa_config {
+            Some(config) => mfa_config_from_request(&config)?,
+            None => return Err(tonic::Status::invalid_argument("mfa config is required")),
+        };
+
+        let mut application =
+            match ReplicatedApplication::get(self.state.prisma(), application_id).await {
+                Ok(application) => application,
+                Err(ModelError::NotFound) => {
+                    return Err(tonic::Status::not_found("application not found"))
+                }
+                Err(e) => {
+                    error!("failed to get application: {}", e);
+                    return Err(tonic::Status::internal("internal server error"));
+                }
+            };
+
+        if let Err(e) = application
+            .set_mfa_config(self.state.prisma(), mfa_config_builder)
+            .await
+        {
+            error!("failed to set mfa config: {}", e);
+            return Err(tonic::Status::internal("internal server error"));
+        }
+
+        Ok(tonic::Response::new(SetMfaConfigResponse {}))
+    }
+
+    async fn set_basic_auth_config(
+        &self,
+        request: tonic::Request<SetBasicAuthConfigRequest>,
+    ) -> Result<tonic::Response<SetBasicAuthConfigResponse>, tonic::Status> {
+        let (_, _, data) = request.into_parts();
+
+        // Verify data
+        let application_id = if let Ok(id) = data.application_id.try_into() {
+            id
+        } else {
+            return Err(tonic::Status::invalid_argument("application id is invalid"));
+        };
+
+        let basic_auth_config_builder = match data.basic_auth_config {
+            Some(config) => basic_auth_config_from_request(config)?,
+            None => {
+                return Err(tonic::Status::invalid_argument(
+                    "basic auth config is required",
+                ))
+            }
+        };
+
+        let mut application =
+            match ReplicatedApplication::get(self.state.prisma(), application_id).await {
+                Ok(application) => application,
+                Err(ModelError::NotFound) => {
+                    return Err(tonic::Status::not_found("application not found"))
+                }
+                Err(e) => {
+                    error!("failed to get application: {}", e);
+                    return Err(tonic::Status::internal("internal server error"));
+                }
+            };
+
+        if let Err(e) = application
+            .set_basic_auth_config(self.state.prisma(), basic_auth_config_builder)
+            .await
+        {
+            error!("failed to set basic auth config: {}", e);
+            return Err(tonic::Status::internal("internal server error"));
+        }
+
+        Ok(tonic::Response::new(SetBasicAuthConfigResponse {}))
+    }
+
+    async fn cancel_mfa_recovery(
+        &self,
+        request: tonic::Request<CancelMfaRecoveryRequest>,
+    ) -> Result<tonic::Response<CancelMfaRecoveryResponse>, tonic::Status> {
+        let (_, _, data) = request.into_parts();
+
+        // Verify data
+        let user_id = if let Ok(id) = data.user_id.try_into() {
+            id
+        } else {
+            return Err(tonic::Status::invalid_argument("user id is invalid"));
+        };
+
+        match mfa_recovery::cancel(&self.state, self.state.prisma(), user_id, "admin").await {
+            Ok(_) => Ok(tonic::Response::new(CancelMfaRecoveryResponse {})),
+            Err(MfaRecoveryError::NotPending) => {
+                Err(tonic::Status::not_found("no recovery is pending"))
+            }
+            Err(e) => {
+                error!("failed to cancel mfa recovery: {}", e);
+                Err(tonic::Status::internal("internal server error"))
+            }
+        }
+    }
+
     async fn delete_application(
         &self,
         request: tonic::Request<DeleteApplicationRequest>,
@@ -0,0 +1,281 @@
+/// Tonic-generated gRPC bindings
+mod proto_rbac {
+    tonic::include_proto!("authcore.rbac");
+}
+
+use crypto::snowflake::Snowflake;
+pub use proto_rbac::*;
+use tracing::error;
+
+use crate::{
+    core::rbac::{self, RbacError},
+    models::role,
+    state::AppState,
+};
+
+use self::rbac_server::Rbac;
+
+pub struct RbacServer {
+    state: AppState,
+}
+
+impl RbacServer {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+}
+
+impl From<role::Permission> for Permission {
+    fn from(value: role::Permission) -> Self {
+        Self {
+            name: value.name().to_owned(),
+            description: value.description().to_owned(),
+        }
+    }
+}
+
+impl From<role::Role> for Role {
+    fn from(value: role::Role) -> Self {
+        Self {
+            name: value.name().to_owned(),
+            description: value.description().to_owned(),
+            permissions: value.permissions().to_vec(),
+        }
+    }
+}
+
+fn parse_id(id: String, name: &str) -> Result<Snowflake, tonic::Status> {
+    id.try_into()
+        .map_err(|_| tonic::Status::invalid_argument(format!("{} is invalid", name)))
+}
+
+impl From<RbacError> for tonic::Status {
+    fn from(value: RbacError) -> Self {
+        match value {
+            RbacError::InvalidName => tonic::Status::invalid_argument(
+                "name must be 1 to 64 lowercase letters, digits, '_', '-', '.' or ':'",
+            ),
+            RbacError::AlreadyExists => tonic::Status::already_exists("name is already taken"),
+            RbacError::RoleNotFound => tonic::Status::not_found("role not found"),
+            RbacError::PermissionNotFound(name) => {
+                tonic::Status::not_found(format!("permission {} not found", name))
+            }
+            RbacError::UserNotFound => tonic::Status::not_found("user not found"),
+            RbacError::Model(e) => {
+                error!("rbac model error: {}", e);
+                tonic::Status::internal("internal server error")
+            }
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Rbac for RbacServer {
+    async fn create_permission(
+        &self,
+        request: tonic::Request<CreatePermissionRequest>,
+    ) -> Result<tonic::Response<CreatePermissionResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        let permission = rbac::create_permission(
+            &self.state,
+            self.state.prisma(),
+            application_id,
+            data.name,
+            data.description,
+        )
+        .await?;
+
+        Ok(tonic::Response::new(CreatePermissionResponse {
+            permission: Some(permission.into()),
+        }))
+    }
+
+    async fn list_permissions(
+        &self,
+        request: tonic::Request<ListPermissionsRequest>,
+    ) -> Result<tonic::Response<ListPermissionsResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        let permissions = role::Permission::list(self.state.prisma(), application_id)
+            .await
+            .map_err(RbacError::from)?;
+
+        Ok(tonic::Response::new(ListPermissionsResponse {
+            permissions: permissions.into_iter().map(Permission::from).collect(),
+        }))
+    }
+
+    async fn delete_permission(
+        &self,
+        request: tonic::Request<DeletePermissionRequest>,
+    ) -> Result<tonic::Response<DeletePermissionResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        rbac::delete_permission(self.state.prisma(), application_id, data.name).await?;
+
+        Ok(tonic::Response::new(DeletePermissionResponse {}))
+    }
+
+    async fn create_role(
+        &self,
+        request: tonic::Request<CreateRoleRequest>,
+    ) -> Result<tonic::Response<CreateRoleResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        // The role and its permissions are created together
+        let (transaction_controller, prisma_client) = self
+            .state
+            .prisma()
+            ._transaction()
+            .begin()
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        let role = match rbac::create_role(
+            &self.state,
+            &prisma_client,
+            application_id,
+            data.name,
+            data.description,
+            data.permissions,
+        )
+        .await
+        {
+            Ok(role) => role,
+            Err(e) => {
+                let _ = transaction_controller.rollback(prisma_client).await;
+                return Err(e.into());
+            }
+        };
+
+        transaction_controller
+            .commit(prisma_client)
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        Ok(tonic::Response::new(CreateRoleResponse {
+            role: Some(role.into()),
+        }))
+    }
+
+    async fn list_roles(
+        &self,
+        request: tonic::Request<ListRolesRequest>,
+    ) -> Result<tonic::Response<ListRolesResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        let roles = role::Role::list(self.state.prisma(), application_id)
+            .await
+            .map_err(RbacError::from)?;
+
+        Ok(tonic::Response::new(ListRolesResponse {
+            roles: roles.into_iter().map(Role::from).collect(),
+        }))
+    }
+
+    async fn set_role_permissions(
+        &self,
+        request: tonic::Request<SetRolePermissionsRequest>,
+    ) -> Result<tonic::Response<SetRolePermissionsResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        let (transaction_controller, prisma_client) = self
+            .state
+            .prisma()
+            ._transaction()
+            .begin()
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        let role = match rbac::set_role_permissions(
+            &prisma_client,
+            application_id,
+            data.name,
+            data.permissions,
+        )
+        .await
+        {
+            Ok(role) => role,
+            Err(e) => {
+                let _ = transaction_controller.rollback(prisma_client).await;
+                return Err(e.into());
+            }
+        };
+
+        transaction_controller
+            .commit(prisma_client)
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        Ok(tonic::Response::new(SetRolePermissionsResponse {
+            role: Some(role.into()),
+        }))
+    }
+
+    async fn delete_role(
+        &self,
+        request: tonic::Request<DeleteRoleRequest>,
+    ) -> Result<tonic::Response<DeleteRoleResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        rbac::delete_role(self.state.prisma(), application_id, data.name).await?;
+
+        Ok(tonic::Response::new(DeleteRoleResponse {}))
+    }
+
+    async fn assign_role(
+        &self,
+        request: tonic::Request<AssignRoleRequest>,
+    ) -> Result<tonic::Response<AssignRoleResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        rbac::assign_role(self.state.prisma(), user_id, data.role).await?;
+
+        Ok(tonic::Response::new(AssignRoleResponse {}))
+    }
+
+    async fn unassign_role(
+        &self,
+        request: tonic::Request<UnassignRoleRequest>,
+    ) -> Result<tonic::Response<UnassignRoleResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        rbac::unassign_role(self.state.prisma(), user_id, data.role).await?;
+
+        Ok(tonic::Response::new(UnassignRoleResponse {}))
+    }
+
+    async fn get_user_roles(
+        &self,
+        request: tonic::Request<GetUserRolesRequest>,
+    ) -> Result<tonic::Response<GetUserRolesResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        let roles = role::Role::list_by_user(self.state.prisma(), user_id)
+            .await
+            .map_err(RbacError::from)?;
+
+        let mut permissions = roles
+            .iter()
+            .flat_map(|role| role.permissions().iter().cloned())
+            .collect::<Vec<_>>();
+        permissions.sort();
+        permissions.dedup();
+
+        Ok(tonic::Response::new(GetUserRolesResponse {
+            roles: roles.into_iter().map(Role::from).collect(),
+            permissions,
+        }))
+    }
+}
@@ -28,13 +28,29 @@ impl Session for SessionServer {
     ) -> Result<tonic::Response<ValidateResponse>, tonic::Status> {
         let data = request.into_inner();
 
-        let result = core::token::verify_access_token(&self.state, &data.refresh_token);
-        if let Err(e) = result {
-            error!("Failed to validate access token: {:?}", e);
-            return Err(tonic::Status::unauthenticated("Invalid access token"));
-        }
-
-        Ok(tonic::Response::new(ValidateResponse {}))
+        let claims = match core::token::verify_access_token(&self.state, &data.refresh_token) {
+            Ok(claims) => claims,
+            Err(e) => {
+                error!("Failed to validate access token: {:?}", e);
+                return Err(tonic::Status::unauthenticated("Invalid access token"));
+            }
+        };
+
+        let authorization = claims
+            .other()
+            .map(|other| other.authorization().clone())
+            .unwrap_or_default();
+        let metadata = claims
+            .other()
+            .map(|other| serde_json::Value::Object(other.metadata().clone()))
+            .unwrap_or_else(|| serde_json::Value::Object(Default::default()));
+
+        Ok(tonic::Response::new(ValidateResponse {
+            user_id: claims.subject().cloned().unwrap_or_default(),
+            roles: authorization.roles().to_vec(),
+            permissions: authorization.permissions().to_vec(),
+            metadata: metadata.to_string(),
+        }))
     }
 
     async fn invalidate(
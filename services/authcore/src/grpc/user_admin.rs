@@ -0,0 +1,615 @@
+/// Tonic-generated gRPC bindings
+mod proto_user_admin {
+    tonic::include_proto!("authcore.user_admin");
+}
+
+use std::collections::HashMap;
+
+use crypto::snowflake::Snowflake;
+pub use proto_user_admin::*;
+use tracing::error;
+
+use crate::{
+    core::{
+        invitation::{self, InvitationError},
+        user_admin::{self, UserAdminError},
+        user_import::{self, ImportError, Importer},
+    },
+    models::user,
+    state::AppState,
+};
+
+use self::user_admin_server::UserAdmin;
+
+pub struct UserAdminServer {
+    state: AppState,
+}
+
+impl UserAdminServer {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+}
+
+impl From<user::User> for User {
+    fn from(value: user::User) -> Self {
+        let email_address = value.email_address();
+
+        Self {
+            id: value.id().to_string(),
+            email: email_address
+                .map(|email_address| email_address.email_address().to_owned())
+                .unwrap_or_default(),
+            email_verified: email_address.map_or(false, |email_address| email_address.verified()),
+            password_enabled: value.password_enabled(),
+            mfa_enabled: value.mfa_enabled(),
+            disabled: value.disabled(),
+            last_login_at: value
+                .last_login_at()
+                .map(|v| v.to_rfc3339())
+                .unwrap_or_default(),
+            created_at: value.created_at().to_rfc3339(),
+            disabled_at: value
+                .disabled_at()
+                .map(|v| v.to_rfc3339())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl From<invitation::Invitation> for Invitation {
+    fn from(value: invitation::Invitation) -> Self {
+        Self {
+            created_at: value.created_at().to_rfc3339(),
+            expires_at: value.expires_at().to_rfc3339(),
+            expired: value.expired(),
+            user: Some(value.user().clone().into()),
+        }
+    }
+}
+
+impl TryFrom<ImportUser> for user_import::ImportUser {
+    type Error = ImportError;
+
+    fn try_from(value: ImportUser) -> Result<Self, Self::Error> {
+        fn parse_metadata(
+            metadata: HashMap<String, String>,
+        ) -> Result<user_import::Metadata, ImportError> {
+            metadata
+                .into_iter()
+                .map(|(key, value)| match serde_json::from_str(&value) {
+                    Ok(value) => Ok((key, value)),
+                    Err(_) => Err(ImportError::Parse(format!("value of {} is not JSON", key))),
+                })
+                .collect()
+        }
+
+        Ok(Self {
+            email: value.email,
+            password_hash: Some(value.password_hash),
+            email_verified: value.email_verified,
+            public_metadata: parse_metadata(value.public_metadata)?,
+            private_metadata: parse_metadata(value.private_metadata)?,
+            app_metadata: parse_metadata(value.app_metadata)?,
+        })
+    }
+}
+
+impl From<&user_import::RowError> for ImportRowError {
+    fn from(value: &user_import::RowError) -> Self {
+        Self {
+            row: value.row() as i64,
+            email: value.email().to_owned(),
+            error: value.error().to_string(),
+        }
+    }
+}
+
+fn parse_id(id: String, name: &str) -> Result<Snowflake, tonic::Status> {
+    id.try_into()
+        .map_err(|_| tonic::Status::invalid_argument(format!("{} is invalid", name)))
+}
+
+impl From<UserAdminError> for tonic::Status {
+    fn from(value: UserAdminError) -> Self {
+        match value {
+            UserAdminError::ApplicationDoesNotExist => {
+                tonic::Status::not_found("application does not exist")
+            }
+            UserAdminError::NotFound => tonic::Status::not_found("user not found"),
+            UserAdminError::EmailFormat => tonic::Status::invalid_argument("invalid email format"),
+            UserAdminError::PasswordFormat(errors) => {
+                let errors = errors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                tonic::Status::invalid_argument(format!("invalid password format: {}", errors))
+            }
+            UserAdminError::AlreadyExists => {
+                tonic::Status::already_exists("email address already exists")
+            }
+            UserAdminError::PasswordReused => {
+                tonic::Status::invalid_argument("password has been used recently")
+            }
+            UserAdminError::NoPassword => {
+                tonic::Status::failed_precondition("user has no password")
+            }
+            UserAdminError::Overloaded => {
+                tonic::Status::unavailable("too many passwords are being hashed")
+            }
+            UserAdminError::HashError => {
+                error!("failed to hash password");
+                tonic::Status::internal("internal server error")
+            }
+            UserAdminError::Model(e) => {
+                error!("user admin model error: {}", e);
+                tonic::Status::internal("internal server error")
+            }
+        }
+    }
+}
+
+impl From<InvitationError> for tonic::Status {
+    fn from(value: InvitationError) -> Self {
+        match value {
+            InvitationError::ApplicationDoesNotExist => {
+                tonic::Status::not_found("application does not exist")
+            }
+            InvitationError::NoInvitationUrl => {
+                tonic::Status::failed_precondition("application has no invitation url")
+            }
+            InvitationError::EmailFormat => tonic::Status::invalid_argument("invalid email format"),
+            InvitationError::AlreadyExists => {
+                tonic::Status::already_exists("email address already exists")
+            }
+            InvitationError::NotFound
+            | InvitationError::InvalidToken
+            | InvitationError::Expired => tonic::Status::not_found("invitation not found"),
+            InvitationError::Email(e) => {
+                error!("failed to send invitation email: {}", e);
+                tonic::Status::unavailable("failed to send invitation email")
+            }
+            e => {
+                error!("invitation error: {}", e);
+                tonic::Status::internal("internal server error")
+            }
+        }
+    }
+}
+
+impl From<ImportError> for tonic::Status {
+    fn from(value: ImportError) -> Self {
+        match value {
+            ImportError::ApplicationDoesNotExist => {
+                tonic::Status::not_found("application does not exist")
+            }
+            ImportError::Model(e) => {
+                error!("import model error: {}", e);
+                tonic::Status::internal("internal server error")
+            }
+            e => tonic::Status::invalid_argument(e.to_string()),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl UserAdmin for UserAdminServer {
+    async fn get_user(
+        &self,
+        request: tonic::Request<GetUserRequest>,
+    ) -> Result<tonic::Response<GetUserResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        let user = match data.user {
+            Some(get_user_request::User::UserId(user_id)) => {
+                let user_id = parse_id(user_id, "user id")?;
+                user_admin::get_user(self.state.prisma(), application_id, user_id).await?
+            }
+            Some(get_user_request::User::Email(email)) => {
+                user_admin::find_user_by_email(self.state.prisma(), application_id, email).await?
+            }
+            None => {
+                return Err(tonic::Status::invalid_argument(
+                    "user id or email is required",
+                ))
+            }
+        };
+
+        Ok(tonic::Response::new(GetUserResponse {
+            user: Some(user.into()),
+        }))
+    }
+
+    async fn list_users(
+        &self,
+        request: tonic::Request<ListUsersRequest>,
+    ) -> Result<tonic::Response<ListUsersResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        let cursor = match data.cursor.as_str() {
+            "" => None,
+            _ => Some(parse_id(data.cursor, "cursor")?),
+        };
+
+        let (users, next_cursor) = user_admin::list_users(
+            self.state.prisma(),
+            application_id,
+            data.email_query,
+            cursor,
+            data.limit,
+        )
+        .await?;
+
+        Ok(tonic::Response::new(ListUsersResponse {
+            users: users.into_iter().map(User::from).collect(),
+            next_cursor: next_cursor.map(|id| id.to_string()).unwrap_or_default(),
+        }))
+    }
+
+    async fn create_user(
+        &self,
+        request: tonic::Request<CreateUserRequest>,
+    ) -> Result<tonic::Response<CreateUserResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        let (transaction_controller, prisma_client) = self
+            .state
+            .prisma()
+            ._transaction()
+            .begin()
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        let user = match user_admin::create_user(
+            &self.state,
+            &prisma_client,
+            application_id,
+            data.email,
+            data.password,
+            data.email_verified,
+        )
+        .await
+        {
+            Ok(user) => user,
+            Err(e) => {
+                let _ = transaction_controller.rollback(prisma_client).await;
+                return Err(e.into());
+            }
+        };
+
+        transaction_controller
+            .commit(prisma_client)
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        Ok(tonic::Response::new(CreateUserResponse {
+            user: Some(user.into()),
+        }))
+    }
+
+    async fn update_user(
+        &self,
+        request: tonic::Request<UpdateUserRequest>,
+    ) -> Result<tonic::Response<UpdateUserResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        let user = user_admin::update_user(
+            self.state.prisma(),
+            application_id,
+            user_id,
+            data.email,
+            data.email_verified,
+        )
+        .await?;
+
+        Ok(tonic::Response::new(UpdateUserResponse {
+            user: Some(user.into()),
+        }))
+    }
+
+    async fn set_user_disabled(
+        &self,
+        request: tonic::Request<SetUserDisabledRequest>,
+    ) -> Result<tonic::Response<SetUserDisabledResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        // The user is disabled and signed out together
+        let (transaction_controller, prisma_client) = self
+            .state
+            .prisma()
+            ._transaction()
+            .begin()
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        let user =
+            match user_admin::set_disabled(&prisma_client, application_id, user_id, data.disabled)
+                .await
+            {
+                Ok(user) => user,
+                Err(e) => {
+                    let _ = transaction_controller.rollback(prisma_client).await;
+                    return Err(e.into());
+                }
+            };
+
+        transaction_controller
+            .commit(prisma_client)
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        Ok(tonic::Response::new(SetUserDisabledResponse {
+            user: Some(user.into()),
+        }))
+    }
+
+    async fn set_password(
+        &self,
+        request: tonic::Request<SetPasswordRequest>,
+    ) -> Result<tonic::Response<SetPasswordResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        // The password and the password history are replaced together
+        let (transaction_controller, prisma_client) = self
+            .state
+            .prisma()
+            ._transaction()
+            .begin()
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        let user = match user_admin::set_password(
+            &self.state,
+            &prisma_client,
+            application_id,
+            user_id,
+            data.password,
+            data.require_change,
+        )
+        .await
+        {
+            Ok(user) => user,
+            Err(e) => {
+                let _ = transaction_controller.rollback(prisma_client).await;
+                return Err(e.into());
+            }
+        };
+
+        transaction_controller
+            .commit(prisma_client)
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        Ok(tonic::Response::new(SetPasswordResponse {
+            user: Some(user.into()),
+        }))
+    }
+
+    async fn require_password_change(
+        &self,
+        request: tonic::Request<RequirePasswordChangeRequest>,
+    ) -> Result<tonic::Response<RequirePasswordChangeResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        let user =
+            user_admin::require_password_change(self.state.prisma(), application_id, user_id)
+                .await?;
+
+        Ok(tonic::Response::new(RequirePasswordChangeResponse {
+            user: Some(user.into()),
+        }))
+    }
+
+    async fn delete_user(
+        &self,
+        request: tonic::Request<DeleteUserRequest>,
+    ) -> Result<tonic::Response<DeleteUserResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        user_admin::delete_user(self.state.prisma(), application_id, user_id).await?;
+
+        Ok(tonic::Response::new(DeleteUserResponse {}))
+    }
+
+    async fn reset_mfa(
+        &self,
+        request: tonic::Request<ResetMfaRequest>,
+    ) -> Result<tonic::Response<ResetMfaResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        let (transaction_controller, prisma_client) = self
+            .state
+            .prisma()
+            ._transaction()
+            .begin()
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        if let Err(e) = user_admin::reset_mfa(&prisma_client, application_id, user_id).await {
+            let _ = transaction_controller.rollback(prisma_client).await;
+            return Err(e.into());
+        }
+
+        transaction_controller
+            .commit(prisma_client)
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        Ok(tonic::Response::new(ResetMfaResponse {}))
+    }
+
+    async fn force_logout(
+        &self,
+        request: tonic::Request<ForceLogoutRequest>,
+    ) -> Result<tonic::Response<ForceLogoutResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        let revoked_sessions =
+            user_admin::force_logout(self.state.prisma(), application_id, user_id).await?;
+
+        Ok(tonic::Response::new(ForceLogoutResponse {
+            revoked_sessions,
+        }))
+    }
+
+    async fn invite_user(
+        &self,
+        request: tonic::Request<InviteUserRequest>,
+    ) -> Result<tonic::Response<InviteUserResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        // The user is only created if the invitation email is sent
+        let (transaction_controller, prisma_client) = self
+            .state
+            .prisma()
+            ._transaction()
+            .begin()
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        let invitation =
+            match invitation::invite(&self.state, &prisma_client, application_id, data.email).await
+            {
+                Ok(invitation) => invitation,
+                Err(e) => {
+                    let _ = transaction_controller.rollback(prisma_client).await;
+                    return Err(e.into());
+                }
+            };
+
+        transaction_controller
+            .commit(prisma_client)
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        Ok(tonic::Response::new(InviteUserResponse {
+            invitation: Some(invitation.into()),
+        }))
+    }
+
+    async fn list_invitations(
+        &self,
+        request: tonic::Request<ListInvitationsRequest>,
+    ) -> Result<tonic::Response<ListInvitationsResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+
+        let invitations = invitation::list(self.state.prisma(), application_id).await?;
+
+        Ok(tonic::Response::new(ListInvitationsResponse {
+            invitations: invitations.into_iter().map(Invitation::from).collect(),
+        }))
+    }
+
+    async fn resend_invitation(
+        &self,
+        request: tonic::Request<ResendInvitationRequest>,
+    ) -> Result<tonic::Response<ResendInvitationResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        let (transaction_controller, prisma_client) = self
+            .state
+            .prisma()
+            ._transaction()
+            .begin()
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        let invitation =
+            match invitation::resend(&self.state, &prisma_client, application_id, user_id).await {
+                Ok(invitation) => invitation,
+                Err(e) => {
+                    let _ = transaction_controller.rollback(prisma_client).await;
+                    return Err(e.into());
+                }
+            };
+
+        transaction_controller
+            .commit(prisma_client)
+            .await
+            .map_err(|_| tonic::Status::internal("internal server error"))?;
+
+        Ok(tonic::Response::new(ResendInvitationResponse {
+            invitation: Some(invitation.into()),
+        }))
+    }
+
+    async fn revoke_invitation(
+        &self,
+        request: tonic::Request<RevokeInvitationRequest>,
+    ) -> Result<tonic::Response<RevokeInvitationResponse>, tonic::Status> {
+        let data = request.into_inner();
+        let application_id = parse_id(data.application_id, "application id")?;
+        let user_id = parse_id(data.user_id, "user id")?;
+
+        invitation::revoke(self.state.prisma(), application_id, user_id).await?;
+
+        Ok(tonic::Response::new(RevokeInvitationResponse {}))
+    }
+
+    async fn import_users(
+        &self,
+        request: tonic::Request<tonic::Streaming<ImportUsersRequest>>,
+    ) -> Result<tonic::Response<ImportUsersResponse>, tonic::Status> {
+        let mut stream = request.into_inner();
+
+        let options = match stream.message().await? {
+            Some(ImportUsersRequest {
+                item: Some(import_users_request::Item::Options(options)),
+            }) => options,
+            _ => {
+                return Err(tonic::Status::invalid_argument(
+                    "the first message must contain the import options",
+                ))
+            }
+        };
+        let application_id = parse_id(options.application_id, "application id")?;
+
+        let mut importer =
+            Importer::new(self.state.prisma(), application_id, options.dry_run).await?;
+
+        while let Some(request) = stream.message().await? {
+            let user = match request.item {
+                Some(import_users_request::Item::User(user)) => user.try_into(),
+                _ => {
+                    return Err(tonic::Status::invalid_argument(
+                        "only the first message can contain the import options",
+                    ))
+                }
+            };
+
+            importer
+                .import(self.state.id_generator(), self.state.prisma(), user)
+                .await?;
+        }
+
+        let report = importer.finish();
+
+        Ok(tonic::Response::new(ImportUsersResponse {
+            rows: report.rows() as i64,
+            imported: report.imported() as i64,
+            errors: report.errors().iter().map(ImportRowError::from).collect(),
+        }))
+    }
+}
@@ -7,6 +7,7 @@ use axum::{routing::get, Json, Router};
 
 use crate::{state::AppState, ServiceData, SERVICE_DATA};
 
+pub mod middleware;
 pub mod modules;
 pub mod response;
 
@@ -0,0 +1,140 @@
+//! # HTTP middleware
+//! Middleware shared by the HTTP modules.
+//!
+//! - [`require_recent_authentication`]: the user authenticated recently, optionally with MFA
+//! - [`require_permission`]: the access token grants a permission
+
+use axum::{
+    extract::State,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Duration;
+use hyper::{Request, StatusCode};
+use serde::Serialize;
+
+use crate::{
+    http::{modules::get_access_token_claims, response::HTTPResponse},
+    state::AppState,
+};
+
+/// How recently the user must have authenticated, see [`require_recent_authentication`].
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RecentAuthentication {
+    max_age_minutes: i64,
+    mfa: bool,
+}
+
+impl RecentAuthentication {
+    /// Require an authentication within the given number of minutes.
+    pub fn within_minutes(max_age_minutes: i64) -> Self {
+        Self {
+            max_age_minutes,
+            mfa: false,
+        }
+    }
+
+    /// Also require that the authentication used a second factor.
+    pub fn with_mfa(mut self) -> Self {
+        self.mfa = true;
+        self
+    }
+}
+
+/// Reject requests whose access token was not issued for a recent enough authentication, the
+/// client should re-authenticate (`/session/reauthenticate`) and retry with the elevated token.
+///
+/// ```ignore
+/// .route(
+///     "/devices/remove",
+///     post(remove_device::route).layer(middleware::from_fn_with_state(
+///         (state.clone(), RecentAuthentication::within_minutes(10).with_mfa()),
+///         require_recent_authentication,
+///     )),
+/// )
+/// ```
+pub async fn require_recent_authentication<B>(
+    State((state, requirement)): State<(AppState, RecentAuthentication)>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let (parts, body) = request.into_parts();
+
+    let claims = match get_access_token_claims(&state, &parts) {
+        Some(claims) => claims,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response)).into_response();
+        }
+    };
+
+    let recent = claims.other().map_or(false, |other| {
+        let authentication = other.authentication();
+
+        authentication.is_within(Duration::minutes(requirement.max_age_minutes))
+            && (!requirement.mfa || authentication.is_multi_factor())
+    });
+
+    if !recent {
+        let response = HTTPResponse::error(
+            "ReauthenticationRequired",
+            "The operation requires a recent authentication, please re-authenticate".to_owned(),
+            requirement,
+        );
+        return (StatusCode::UNAUTHORIZED, Json(response)).into_response();
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+/// Reject requests whose access token does not grant the given permission, see
+/// [`crate::core::rbac`].
+///
+/// ```ignore
+/// .route(
+///     "/invoices",
+///     get(invoices::route).layer(middleware::from_fn_with_state(
+///         (state.clone(), "invoices:read"),
+///         require_permission,
+///     )),
+/// )
+/// ```
+pub async fn require_permission<B>(
+    State((state, permission)): State<(AppState, &'static str)>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let (parts, body) = request.into_parts();
+
+    let claims = match get_access_token_claims(&state, &parts) {
+        Some(claims) => claims,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response)).into_response();
+        }
+    };
+
+    let permitted = claims.other().map_or(false, |other| {
+        other.authorization().has_permission(permission)
+    });
+
+    if !permitted {
+        let response = HTTPResponse::error(
+            "Forbidden",
+            "The operation requires a permission the user does not have".to_owned(),
+            MissingPermission { permission },
+        );
+        return (StatusCode::FORBIDDEN, Json(response)).into_response();
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+/// Details of a `Forbidden` error of [`require_permission`].
+#[derive(Serialize)]
+struct MissingPermission {
+    permission: &'static str,
+}
@@ -4,14 +4,21 @@
 //! ## Modules
 //! - [`basic_auth`](basic_auth/index.html): Basic authentication module.
 
+use crypto::{snowflake::Snowflake, tokens::paseto::OwnedClaims};
 use hyper::{http::request::Parts, Body};
 use serde::de::DeserializeOwned;
 
-use crate::state::AppState;
+use crate::{
+    core::token::{self, AccessTokenClaims, RestrictedScope},
+    state::AppState,
+};
 
 pub mod basic;
+pub mod invitation;
+pub mod mfa;
 pub mod session;
 pub mod totp;
+pub mod user;
 
 /// Verification submodule for handling user verification.
 pub mod verification;
@@ -23,6 +30,9 @@ pub fn router(state: AppState) -> axum::Router {
         .nest("/basic", basic::router(state.clone()))
         .nest("/verify", verification::router(state.clone()))
         .nest("/totp", totp::router(state.clone()))
+        .nest("/mfa", mfa::router(state.clone()))
+        .nest("/user", user::router(state.clone()))
+        .nest("/invitation", invitation::router(state.clone()))
         .nest("/session", session::router(state))
 }
 
@@ -50,3 +60,66 @@ where
         _ => None,
     }
 }
+
+/// Get the bearer token in the authorization header.
+fn get_bearer_token(parts: &Parts) -> Option<&str> {
+    let auth = parts.headers.get("Authorization")?.to_str().ok()?;
+
+    // Parse the authorization header
+    match auth.split(' ').collect::<Vec<_>>().as_slice() {
+        ["Bearer", token] => Some(token),
+        _ => None,
+    }
+}
+
+/// Get the claims of the bearer access token in the authorization header.
+///
+/// Returns `None` if the header is missing or malformed, or if the access token is invalid.
+pub(super) fn get_access_token_claims(
+    state: &AppState,
+    parts: &Parts,
+) -> Option<OwnedClaims<AccessTokenClaims>> {
+    let access_token = get_bearer_token(parts)?;
+
+    // Verify the access token
+    token::verify_access_token(state, access_token).ok()
+}
+
+/// Get the ID of the user authenticated by the bearer access token in the authorization header.
+///
+/// Returns `None` if the header is missing or malformed, or if the access token is invalid.
+fn get_authenticated_user_id(state: &AppState, parts: &Parts) -> Option<Snowflake> {
+    let claims = get_access_token_claims(state, parts)?;
+    claims.subject()?.parse().ok()
+}
+
+/// Get the ID of the user enrolling a second factor, authenticated by either an access token or
+/// an enrolment token (see [`RestrictedScope::MfaEnrolment`]) in the authorization header.
+///
+/// Returns `None` if the header is missing or malformed, or if the token is invalid.
+fn get_enrolling_user_id(state: &AppState, parts: &Parts) -> Option<Snowflake> {
+    if let Some(user_id) = get_authenticated_user_id(state, parts) {
+        return Some(user_id);
+    }
+
+    let enrolment_token = get_bearer_token(parts)?;
+    token::verify_restricted_token(state, enrolment_token, RestrictedScope::MfaEnrolment).ok()
+}
+
+/// Get the ID of the user changing their password, authenticated by either an access token or a
+/// password change token (see [`RestrictedScope::PasswordChange`]) in the authorization header.
+///
+/// Returns `None` if the header is missing or malformed, or if the token is invalid.
+fn get_password_changing_user_id(state: &AppState, parts: &Parts) -> Option<Snowflake> {
+    if let Some(user_id) = get_authenticated_user_id(state, parts) {
+        return Some(user_id);
+    }
+
+    let password_change_token = get_bearer_token(parts)?;
+    token::verify_restricted_token(
+        state,
+        password_change_token,
+        RestrictedScope::PasswordChange,
+    )
+    .ok()
+}
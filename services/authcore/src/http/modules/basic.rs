@@ -2,17 +2,64 @@
 //!
 //! This module provides the functionality for traditional password-based authentication.
 //! It includes both the login and registration processes.
+//!
+//! Changing the password requires a recent authentication, except with the password change token
+//! of a user whose password expired, who can not sign in to re-authenticate.
 
-use axum::{routing::post, Router};
+use axum::{
+    extract::State,
+    middleware::{from_fn_with_state, Next},
+    response::Response,
+    routing::post,
+    Router,
+};
+use hyper::Request;
 
-use crate::state::AppState;
+use crate::{
+    core::token::{self, RestrictedScope},
+    http::middleware::{require_recent_authentication, RecentAuthentication},
+    state::AppState,
+};
 
 /// Login submodule for handling user authentication using a username/email and password.
 pub mod login;
 
+/// Module for changing the password of a signed in user.
+pub mod change_password;
+
 /// Router for handling routing within basic_auth.
 pub fn router(state: AppState) -> Router {
+    let recent = from_fn_with_state(
+        (state.clone(), RecentAuthentication::within_minutes(10)),
+        require_recent_authentication_or_password_change,
+    );
+
     Router::new()
         .route("/login", post(login::route))
+        .route(
+            "/change_password",
+            post(change_password::route).layer(recent),
+        )
         .with_state(state)
 }
+
+/// [`require_recent_authentication`], but a password change token is let through as it is only
+/// issued by a login with the expired password.
+async fn require_recent_authentication_or_password_change<B>(
+    State((state, requirement)): State<(AppState, RecentAuthentication)>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let (parts, body) = request.into_parts();
+
+    let password_change = super::get_bearer_token(&parts).map_or(false, |token| {
+        token::verify_restricted_token(&state, token, RestrictedScope::PasswordChange).is_ok()
+    });
+
+    let request = Request::from_parts(parts, body);
+    if password_change {
+        return next.run(request).await;
+    }
+
+    require_recent_authentication(State((state, requirement)), request, next).await
+}
@@ -0,0 +1,139 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    core::basic::password::{self, PasswordChangeError},
+    http::{
+        modules::{get_password_changing_user_id, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    current_password: String,
+    new_password: String,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user, users with an expired password use a password change token
+    let user_id = match get_password_changing_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: ChangePasswordRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    // Begin a transaction
+    let (transaction_controller, prisma_client) = match state.prisma()._transaction().begin().await
+    {
+        Ok(t) => t,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to change password".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    if let Err(e) = password::change_password(
+        &state,
+        &prisma_client,
+        user_id,
+        data.current_password,
+        data.new_password,
+    )
+    .await
+    {
+        let _ = transaction_controller.rollback(prisma_client).await;
+
+        let (status, response) = match e {
+            PasswordChangeError::WrongCredentials | PasswordChangeError::NotFound => (
+                StatusCode::UNAUTHORIZED,
+                HTTPResponse::error("Unauthorized", "Invalid password".to_owned(), ()),
+            ),
+            PasswordChangeError::PasswordFormat(errors) => (
+                StatusCode::BAD_REQUEST,
+                HTTPResponse::error(
+                    "PasswordFormat",
+                    "The password does not meet the requirements".to_owned(),
+                    errors,
+                ),
+            ),
+            PasswordChangeError::Reused => (
+                StatusCode::BAD_REQUEST,
+                HTTPResponse::error(
+                    "PasswordReused",
+                    "The password has been used recently".to_owned(),
+                    (),
+                ),
+            ),
+            PasswordChangeError::Overloaded => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                HTTPResponse::error(
+                    "ServiceUnavailable",
+                    "Too many requests are being processed, try again later".to_owned(),
+                    (),
+                ),
+            ),
+            e => {
+                error!("Failed to change password: {}", e);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    HTTPResponse::error(
+                        "InternalServerError",
+                        "Failed to change password".to_owned(),
+                        (),
+                    ),
+                )
+            }
+        };
+
+        return (status, Json(response));
+    }
+
+    // Commit the transaction
+    if transaction_controller.commit(prisma_client).await.is_err() {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to change password".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    }
+
+    (StatusCode::OK, Json(HTTPResponse::empty()))
+}
@@ -6,12 +6,19 @@ use axum::{
     Json,
 };
 use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
 use hyper::{Body, Request, StatusCode};
 use serde::{Deserialize, Serialize};
 use tracing::error;
 
 use crate::{
-    core::{basic::login, token},
+    core::{
+        basic::login,
+        mfa::{self, FactorType},
+        token::{self, Authentication, RestrictedScope},
+        trusted_device,
+    },
     http::{modules::get_request, response::HTTPResponse},
     state::AppState,
 };
@@ -28,6 +35,61 @@ pub struct LoginResponse {
     pub access: String,
 }
 
+/// Details of a `MfaEnrolmentRequired` error.
+#[derive(Serialize)]
+pub struct EnrolmentResponse {
+    /// Token which only authenticates the user to enrol a second factor
+    pub enrolment_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Details of a `PasswordChangeRequired` error.
+#[derive(Serialize)]
+pub struct PasswordChangeResponse {
+    /// Token which only authenticates the user to change their password
+    pub password_change_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Respond with a password change token instead of a session, the user signs in again after
+/// changing their password.
+pub(crate) fn password_change_required(
+    state: &AppState,
+    user_id: Snowflake,
+) -> (StatusCode, HTTPResponse) {
+    let (password_change_token, expires_at) =
+        match token::new_restricted_token(state, user_id, RestrictedScope::PasswordChange) {
+            Ok(token) => token,
+            Err(e) => {
+                error!("Failed to generate password change token: {}", e);
+
+                let response = HTTPResponse::error(
+                    "InternalServerError",
+                    "Failed to create the password change token.",
+                    (),
+                );
+                return (StatusCode::INTERNAL_SERVER_ERROR, response);
+            }
+        };
+
+    let response = HTTPResponse::error(
+        "PasswordChangeRequired",
+        "The user needs to change their password".to_owned(),
+        PasswordChangeResponse {
+            password_change_token,
+            expires_at,
+        },
+    );
+    (StatusCode::FORBIDDEN, response)
+}
+
+/// Details of a `NeedFurtherVerificationThrough2FA` error.
+#[derive(Serialize)]
+pub struct TwoFactorResponse {
+    pub flow_token: String,
+    pub factors: Vec<FactorType>,
+}
+
 pub async fn route(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     State(state): State<AppState>,
@@ -76,23 +138,28 @@ pub async fn route(
 
     // Call core and try to login with basic auth
     let user = match login::with_basic_auth(
+        &state,
         &prisma_client,
         data.email,
         data.password,
         application_id,
         addr.ip().to_string(),
+        trusted_device::get_trusted_device_cookie(&jar, application_id),
     )
     .await
     {
         Ok(user) => user,
         Err(e) => match e {
             login::BasicLoginError::NeedFurtherVerificationThrough2FA(user) => {
+                // Bind the TOTP flow token to the device and a new session
+                let session_id = crate::core::totp::new_flow_session_id();
+
                 // Generate a TOTP flow token
                 let flow_token = crate::core::totp::new_totp_flow_token(
                     &state,
                     user.id(),
-                    None, // TODO: implement device ID
-                    None, // TODO: implement session ID
+                    crate::core::totp::get_device_id(&parts.headers),
+                    Some(session_id.clone()),
                     Some(addr.ip().to_string()),
                     Some(
                         parts
@@ -114,14 +181,86 @@ pub async fn route(
                     }
                 };
 
+                // List the factors the user can verify with
+                let factors = match mfa::available_factors(&prisma_client, &user).await {
+                    Ok(factors) => factors,
+                    Err(e) => {
+                        error!("Failed to get available 2FA factors: {}", e);
+
+                        let response = HTTPResponse::error(
+                            "InternalServerError",
+                            "Failed to get the available 2FA methods.",
+                            (),
+                        );
+                        return (StatusCode::INTERNAL_SERVER_ERROR, jar, Json(response));
+                    }
+                };
+
                 let response = HTTPResponse::error(
                     "NeedFurtherVerificationThrough2FA",
                     "The user needs to verify their identity through 2FA".to_owned(),
-                    flow_token,
+                    TwoFactorResponse {
+                        flow_token,
+                        factors,
+                    },
                 );
 
+                let jar = jar.add(crate::core::totp::create_flow_session_cookie(session_id));
                 return (StatusCode::UNAUTHORIZED, jar, Json(response));
             }
+            login::BasicLoginError::NeedMfaEnrolment(user) => {
+                // Only allow the user to enrol a second factor, the user signs in again afterwards
+                let (enrolment_token, expires_at) = match token::new_restricted_token(
+                    &state,
+                    user.id(),
+                    RestrictedScope::MfaEnrolment,
+                ) {
+                    Ok(token) => token,
+                    Err(e) => {
+                        error!("Failed to generate enrolment token: {}", e);
+
+                        let response = HTTPResponse::error(
+                            "InternalServerError",
+                            "Failed to create the enrolment token.",
+                            (),
+                        );
+                        return (StatusCode::INTERNAL_SERVER_ERROR, jar, Json(response));
+                    }
+                };
+
+                let response = HTTPResponse::error(
+                    "MfaEnrolmentRequired",
+                    "The application requires a second factor, the user needs to enrol one"
+                        .to_owned(),
+                    EnrolmentResponse {
+                        enrolment_token,
+                        expires_at,
+                    },
+                );
+                return (StatusCode::FORBIDDEN, jar, Json(response));
+            }
+            login::BasicLoginError::NeedPasswordChange(user) => {
+                let (status, response) = password_change_required(&state, user.id());
+                return (status, jar, Json(response));
+            }
+            login::BasicLoginError::Overloaded => {
+                let response = HTTPResponse::error(
+                    "ServiceUnavailable",
+                    "Too many sign-in attempts are being processed, try again later".to_owned(),
+                    (),
+                );
+
+                return (StatusCode::SERVICE_UNAVAILABLE, jar, Json(response));
+            }
+            login::BasicLoginError::Disabled => {
+                let response = HTTPResponse::error(
+                    "AccountDisabled",
+                    "The account has been disabled".to_owned(),
+                    (),
+                );
+
+                return (StatusCode::FORBIDDEN, jar, Json(response));
+            }
             _ => {
                 let response =
                     HTTPResponse::error("Unauthorized", "Invalid email or password".to_owned(), ());
@@ -136,6 +275,7 @@ pub async fn route(
         &state,
         &prisma_client,
         &user,
+        Authentication::password(),
         Some(addr.ip().to_string()),
         user_agent,
     )
@@ -0,0 +1,17 @@
+//! Invitation Module
+//!
+//! This module lets invited users accept their invitation, see [`crate::core::invitation`].
+
+use axum::{routing::post, Router};
+
+use crate::state::AppState;
+
+/// Module for accepting an invitation by choosing a password.
+pub mod accept;
+
+/// Router for handling routing within invitation.
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/accept", post(accept::route))
+        .with_state(state)
+}
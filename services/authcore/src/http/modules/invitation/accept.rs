@@ -0,0 +1,131 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    core::invitation::{self, InvitationError},
+    http::{modules::get_request, response::HTTPResponse},
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct AcceptRequest {
+    token: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+pub struct AcceptResponse {
+    user_id: String,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: AcceptRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    // Begin a transaction
+    let (transaction_controller, prisma_client) = match state.prisma()._transaction().begin().await
+    {
+        Ok(t) => t,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to accept invitation".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    let user = match invitation::accept(&state, &prisma_client, &data.token, data.password).await {
+        Ok(user) => user,
+        Err(e) => {
+            let _ = transaction_controller.rollback(prisma_client).await;
+
+            let (status, response) = match e {
+                InvitationError::InvalidToken | InvitationError::NotFound => (
+                    StatusCode::UNAUTHORIZED,
+                    HTTPResponse::error("InvalidToken", "Invalid invitation token".to_owned(), ()),
+                ),
+                InvitationError::Overloaded => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    HTTPResponse::error(
+                        "ServiceUnavailable",
+                        "Too many requests are being processed, try again later".to_owned(),
+                        (),
+                    ),
+                ),
+                InvitationError::Expired => (
+                    StatusCode::GONE,
+                    HTTPResponse::error(
+                        "InvitationExpired",
+                        "The invitation has expired".to_owned(),
+                        (),
+                    ),
+                ),
+                InvitationError::PasswordFormat(errors) => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error(
+                        "PasswordFormat",
+                        "The password does not meet the requirements".to_owned(),
+                        errors,
+                    ),
+                ),
+                e => {
+                    error!("Failed to accept invitation: {}", e);
+
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        HTTPResponse::error(
+                            "InternalServerError",
+                            "Failed to accept invitation".to_owned(),
+                            (),
+                        ),
+                    )
+                }
+            };
+
+            return (status, Json(response));
+        }
+    };
+
+    // Commit the transaction
+    if transaction_controller.commit(prisma_client).await.is_err() {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to accept invitation".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    }
+
+    (
+        StatusCode::OK,
+        Json(HTTPResponse::ok(AcceptResponse {
+            user_id: user.id().to_string(),
+        })),
+    )
+}
@@ -0,0 +1,93 @@
+//! MFA Module
+//!
+//! This module provides the management of a user's second factor devices.
+
+use axum::{
+    middleware::from_fn_with_state,
+    routing::{get, post},
+    Router,
+};
+
+use crate::{
+    http::middleware::{require_recent_authentication, RecentAuthentication},
+    state::AppState,
+};
+
+/// Module for listing the MFA devices of a user.
+pub mod devices;
+
+/// Module for handling removal of an MFA device.
+pub mod remove_device;
+
+/// Module for enabling SMS 2FA with a verified phone number.
+pub mod enable_sms;
+
+/// Module for sending an SMS code during the login or a re-authentication.
+pub mod send_sms;
+
+/// Module for enabling email 2FA with a verified email address.
+pub mod enable_email;
+
+/// Module for sending an email code during the login or a re-authentication.
+pub mod send_email;
+
+/// Module for listing the trusted devices of a user.
+pub mod trusted_devices;
+
+/// Module for revoking trusted devices.
+pub mod revoke_trusted_device;
+
+/// Module for importing an HOTP hardware token.
+pub mod import_hotp;
+
+/// Module for resynchronising the counter of an HOTP hardware token.
+pub mod resync_hotp;
+
+/// Module for sending a recovery code to a user who lost their second factor.
+pub mod send_recovery;
+
+/// Module for starting the recovery of a lost second factor.
+pub mod start_recovery;
+
+/// Module for cancelling a pending recovery.
+pub mod cancel_recovery;
+
+/// Router for handling routing within mfa.
+pub fn router(state: AppState) -> Router {
+    // Removing a second factor or a trusted device, and cancelling a recovery, requires a recent
+    // authentication with MFA
+    let recent_mfa = from_fn_with_state(
+        (
+            state.clone(),
+            RecentAuthentication::within_minutes(10).with_mfa(),
+        ),
+        require_recent_authentication,
+    );
+
+    Router::new()
+        .route("/devices", get(devices::route))
+        .route(
+            "/devices/remove",
+            post(remove_device::route).layer(recent_mfa.clone()),
+        )
+        .route("/sms/enable", post(enable_sms::route))
+        .route("/sms/send", post(send_sms::route))
+        .route("/email/enable", post(enable_email::route))
+        .route("/email/send", post(send_email::route))
+        .route("/hotp/import", post(import_hotp::route))
+        .route("/hotp/resync", post(resync_hotp::route))
+        .route("/trusted-devices", get(trusted_devices::route))
+        .route(
+            "/trusted-devices/revoke",
+            post(revoke_trusted_device::route).layer(recent_mfa.clone()),
+        )
+        .route("/recovery/send", post(send_recovery::route))
+        .route("/recovery/start", post(start_recovery::route))
+        .route(
+            "/recovery/cancel",
+            post(cancel_recovery::route).layer(recent_mfa),
+        )
+        // Codes of all factors are verified the same way as TOTP codes
+        .route("/verify", post(super::totp::verify::route))
+        .with_state(state)
+}
@@ -0,0 +1,55 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use tracing::error;
+
+use crate::{
+    core::mfa_recovery::{self, MfaRecoveryError},
+    http::{modules::get_authenticated_user_id, response::HTTPResponse},
+    state::AppState,
+};
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, _body) = request.into_parts();
+
+    // Get the authenticated user, the router requires a recent authentication with the second
+    // factor so that a password alone can not cancel a recovery
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    match mfa_recovery::cancel(&state, state.prisma(), user_id, "user").await {
+        Ok(_) => (StatusCode::OK, Json(HTTPResponse::empty())),
+        Err(MfaRecoveryError::NotPending) => {
+            let response = HTTPResponse::error(
+                "NotFound",
+                "No recovery is pending for the account".to_owned(),
+                (),
+            );
+            (StatusCode::NOT_FOUND, Json(response))
+        }
+        Err(e) => {
+            error!("Failed to cancel mfa recovery: {}", e);
+
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to cancel recovery".to_owned(),
+                (),
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
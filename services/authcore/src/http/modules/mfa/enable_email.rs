@@ -0,0 +1,128 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::Serialize;
+use tracing::{error, info};
+
+use crate::{
+    core::{
+        email_otp::{self, EnableMfaError},
+        totp::BackupCodesError,
+    },
+    http::{modules::get_enrolling_user_id, response::HTTPResponse},
+    state::AppState,
+};
+
+#[derive(Serialize)]
+pub struct EnableEmailResponse {
+    /// Only returned when the user has no backup codes left
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup_codes: Option<Vec<String>>,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, _body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_enrolling_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Begin a transaction
+    let (transaction_controller, prisma_client) = match state.prisma()._transaction().begin().await
+    {
+        Ok(t) => t,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to enable email 2FA".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    let backup_codes = match email_otp::enable_mfa(&state, &prisma_client, user_id).await {
+        Ok(backup_codes) => backup_codes,
+        Err(e) => {
+            let _ = transaction_controller.rollback(prisma_client).await;
+
+            let (status, response) = match e {
+                EnableMfaError::NotFound => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error(
+                        "BadRequest",
+                        "The user has no email address".to_owned(),
+                        (),
+                    ),
+                ),
+                EnableMfaError::NotVerified => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error(
+                        "EmailNotVerified",
+                        "The email address has to be verified first".to_owned(),
+                        (),
+                    ),
+                ),
+                EnableMfaError::NotAllowed => (
+                    StatusCode::FORBIDDEN,
+                    HTTPResponse::error(
+                        "EmailOtpNotAllowed",
+                        "The application does not allow email codes as a second factor".to_owned(),
+                        (),
+                    ),
+                ),
+                EnableMfaError::BackupCodes(BackupCodesError::Overloaded) => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    HTTPResponse::error(
+                        "ServiceUnavailable",
+                        "Too many requests are being processed, try again later".to_owned(),
+                        (),
+                    ),
+                ),
+                e => {
+                    error!("Failed to enable email 2FA: {}", e);
+
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        HTTPResponse::error(
+                            "InternalServerError",
+                            "Failed to enable email 2FA".to_owned(),
+                            (),
+                        ),
+                    )
+                }
+            };
+
+            return (status, Json(response));
+        }
+    };
+
+    // Commit the transaction
+    if transaction_controller.commit(prisma_client).await.is_err() {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to enable email 2FA".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    }
+
+    info!("enabled email 2FA for user {}", user_id);
+
+    let response = EnableEmailResponse { backup_codes };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
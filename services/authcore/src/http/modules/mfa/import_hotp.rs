@@ -0,0 +1,223 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use crypto::totp::Algorithm;
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    core::{
+        hotp::{self, HotpImport, ImportError, SecretEncoding},
+        totp::BackupCodesError,
+    },
+    http::{
+        modules::{get_enrolling_user_id, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+/// Maximum length of a device name.
+const MAX_NAME_LENGTH: usize = 64;
+
+#[derive(Deserialize)]
+pub struct ImportRequest {
+    /// Name of the device, e.g. "YubiKey"
+    name: Option<String>,
+    /// Secret of the token as shipped by the vendor
+    secret: String,
+    /// `base32` (default) or `hex`
+    #[serde(default)]
+    secret_encoding: SecretEncoding,
+    /// `SHA1` (default), `SHA256` or `SHA512`
+    algorithm: Option<String>,
+    /// Defaults to 6
+    digits: Option<u32>,
+    /// Counter of the token as shipped by the vendor, defaults to 0
+    counter: Option<u64>,
+    /// Current code of the token
+    code: String,
+}
+
+#[derive(Serialize)]
+pub struct ImportResponse {
+    id: String,
+    name: String,
+
+    /// Only returned when the first device is enrolled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup_codes: Option<Vec<String>>,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Users that have to enrol a second factor authenticate with their enrolment token
+    let user_id = match get_enrolling_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: ImportRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    let name = data.name.map(|name| name.trim().to_owned());
+    if name.as_ref().map_or(false, |name| {
+        name.is_empty() || name.chars().count() > MAX_NAME_LENGTH
+    }) {
+        let response = HTTPResponse::error(
+            "BadRequest",
+            format!(
+                "Device name must be between 1 and {} characters",
+                MAX_NAME_LENGTH
+            ),
+            (),
+        );
+        return (StatusCode::BAD_REQUEST, Json(response));
+    }
+
+    let algorithm = match data.algorithm.as_deref().map(str::parse::<Algorithm>) {
+        Some(Ok(algorithm)) => algorithm,
+        Some(Err(_)) => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid algorithm, expected SHA1, SHA256 or SHA512".to_owned(),
+                (),
+            );
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+        None => Algorithm::default(),
+    };
+
+    // Begin a transaction
+    let (transaction_controller, prisma_client) = match state.prisma()._transaction().begin().await
+    {
+        Ok(t) => t,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to import hotp token".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    let import = HotpImport {
+        name,
+        secret: data.secret,
+        secret_encoding: data.secret_encoding,
+        algorithm,
+        digits: data.digits.unwrap_or(6),
+        counter: data.counter.unwrap_or(0),
+        code: data.code,
+    };
+
+    let (totp, backup_codes) = match hotp::import(&state, &prisma_client, user_id, import).await {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = transaction_controller.rollback(prisma_client).await;
+
+            let (status, response) = match e {
+                ImportError::NotAllowed => (
+                    StatusCode::FORBIDDEN,
+                    HTTPResponse::error(
+                        "MfaNotAllowed",
+                        "The application does not allow enrolling a second factor".to_owned(),
+                        (),
+                    ),
+                ),
+                ImportError::InvalidSecret => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error(
+                        "InvalidSecret",
+                        "The secret is invalid or shorter than 128 bits".to_owned(),
+                        (),
+                    ),
+                ),
+                ImportError::InvalidDigits => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error(
+                        "BadRequest",
+                        "Invalid number of digits, expected 6 to 8".to_owned(),
+                        (),
+                    ),
+                ),
+                ImportError::InvalidCounter => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error("BadRequest", "Invalid counter".to_owned(), ()),
+                ),
+                ImportError::WrongCode => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error(
+                        "InvalidCode",
+                        "The code does not match the secret and counter of the token".to_owned(),
+                        (),
+                    ),
+                ),
+                ImportError::BackupCodes(BackupCodesError::Overloaded) => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    HTTPResponse::error(
+                        "ServiceUnavailable",
+                        "Too many requests are being processed, try again later".to_owned(),
+                        (),
+                    ),
+                ),
+                e => {
+                    error!("Failed to import hotp token: {}", e);
+
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        HTTPResponse::error(
+                            "InternalServerError",
+                            "Failed to import hotp token".to_owned(),
+                            (),
+                        ),
+                    )
+                }
+            };
+
+            return (status, Json(response));
+        }
+    };
+
+    // Commit the transaction
+    if transaction_controller.commit(prisma_client).await.is_err() {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to import hotp token".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    }
+
+    let response = ImportResponse {
+        id: totp.id().to_string(),
+        name: totp.name().to_owned(),
+        backup_codes,
+    };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
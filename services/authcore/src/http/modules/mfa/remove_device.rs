@@ -0,0 +1,126 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use crypto::snowflake::Snowflake;
+use hyper::{Body, Request, StatusCode};
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::{
+    core::mfa::{self, RemoveDeviceError},
+    http::{
+        modules::{get_authenticated_user_id, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct RemoveDeviceRequest {
+    device_id: String,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: RemoveDeviceRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    let device_id: Snowflake = match data.device_id.parse() {
+        Ok(id) => id,
+        Err(_) => {
+            let response = HTTPResponse::error("BadRequest", "Invalid device ID".to_owned(), ());
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    // Begin a transaction
+    let (transaction_controller, prisma_client) = match state.prisma()._transaction().begin().await
+    {
+        Ok(t) => t,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to remove device".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    if let Err(e) = mfa::remove_device(&prisma_client, user_id, device_id).await {
+        let _ = transaction_controller.rollback(prisma_client).await;
+
+        let (status, response) = match e {
+            RemoveDeviceError::NotFound => (
+                StatusCode::NOT_FOUND,
+                HTTPResponse::error("NotFound", "Device not found".to_owned(), ()),
+            ),
+            RemoveDeviceError::Required => (
+                StatusCode::FORBIDDEN,
+                HTTPResponse::error(
+                    "MfaRequired",
+                    "The application requires a second factor, the last device can not be removed"
+                        .to_owned(),
+                    (),
+                ),
+            ),
+            e => {
+                error!("Failed to remove mfa device: {}", e);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    HTTPResponse::error(
+                        "InternalServerError",
+                        "Failed to remove device".to_owned(),
+                        (),
+                    ),
+                )
+            }
+        };
+
+        return (status, Json(response));
+    }
+
+    // Commit the transaction
+    if transaction_controller.commit(prisma_client).await.is_err() {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to remove device".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    }
+
+    info!("removed mfa device {} of user {}", device_id, user_id);
+
+    (StatusCode::OK, Json(HTTPResponse::empty()))
+}
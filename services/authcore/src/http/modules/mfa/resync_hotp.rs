@@ -0,0 +1,164 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use crypto::{snowflake::Snowflake, tokens::jsonwebtoken::Claims};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    core::{
+        hotp::{self, ResyncError},
+        totp,
+    },
+    http::{
+        modules::{get_authenticated_user_id, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct ResyncRequest {
+    /// TOTP flow token returned by the login, signed in users use their access token instead
+    token: Option<String>,
+    /// Device to resynchronise, all HOTP devices of the user are tried if missing
+    device_id: Option<String>,
+    first_code: String,
+    /// The code generated right after the first code
+    second_code: String,
+}
+
+#[derive(Serialize)]
+pub struct ResyncResponse {
+    device_id: String,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Request<Body>,
+) -> impl IntoResponse {
+    let (parts, body) = request.into_parts();
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: ResyncRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, jar, Json(response));
+        }
+    };
+
+    let device_id: Option<Snowflake> = match data.device_id.map(Snowflake::try_from) {
+        Some(Ok(device_id)) => Some(device_id),
+        Some(Err(_)) => {
+            let response = HTTPResponse::error("BadRequest", "Invalid device ID".to_owned(), ());
+            return (StatusCode::BAD_REQUEST, jar, Json(response));
+        }
+        None => None,
+    };
+
+    // A user whose token drifted can not sign in, they resynchronise with the flow token of the login
+    let user_id: Snowflake = match data.token {
+        Some(token) => {
+            let user_agent = parts
+                .headers
+                .get("user-agent")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned);
+
+            // Every resynchronisation tried uses an attempt of the flow token
+            let flow_token = match totp::verify_totp_flow_token(
+                &state,
+                token,
+                totp::get_device_id(&parts.headers),
+                totp::get_flow_session_id(&jar),
+                user_agent,
+            )
+            .await
+            {
+                Ok(t) => t,
+                Err(totp::VerifyFlowTokenError::TooManyAttempts) => {
+                    let jar = totp::remove_flow_session_cookie(jar);
+                    let response = HTTPResponse::error(
+                        "TooManyAttempts",
+                        "Too many attempts, please login again".to_owned(),
+                        (),
+                    );
+                    return (StatusCode::TOO_MANY_REQUESTS, jar, Json(response));
+                }
+                Err(totp::VerifyFlowTokenError::Expired) => {
+                    let response =
+                        HTTPResponse::error("Expired", "TOTP flow token is expired".to_owned(), ());
+                    return (StatusCode::UNAUTHORIZED, jar, Json(response));
+                }
+                Err(_) => {
+                    let response =
+                        HTTPResponse::error("Invalid", "TOTP flow token is invalid".to_owned(), ());
+                    return (StatusCode::UNAUTHORIZED, jar, Json(response));
+                }
+            };
+
+            flow_token.claims().sub().try_into().unwrap()
+        }
+        None => match get_authenticated_user_id(&state, &parts) {
+            Some(user_id) => user_id,
+            None => {
+                let response =
+                    HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+                return (StatusCode::UNAUTHORIZED, jar, Json(response));
+            }
+        },
+    };
+
+    match hotp::resync(
+        state.prisma(),
+        user_id,
+        device_id,
+        &data.first_code,
+        &data.second_code,
+    )
+    .await
+    {
+        Ok(device_id) => {
+            let response = ResyncResponse {
+                device_id: device_id.to_string(),
+            };
+            (StatusCode::OK, jar, Json(HTTPResponse::ok(response)))
+        }
+        Err(ResyncError::NotFound) => {
+            let response = HTTPResponse::error("NotFound", "HOTP device not found".to_owned(), ());
+            (StatusCode::NOT_FOUND, jar, Json(response))
+        }
+        Err(ResyncError::WrongCodes) => {
+            let response = HTTPResponse::error(
+                "InvalidCode",
+                "The codes are not consecutive codes of the token".to_owned(),
+                (),
+            );
+            (StatusCode::UNAUTHORIZED, jar, Json(response))
+        }
+        Err(e) => {
+            error!("Failed to resynchronise hotp token: {}", e);
+
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to resynchronise hotp token".to_owned(),
+                (),
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, jar, Json(response))
+        }
+    }
+}
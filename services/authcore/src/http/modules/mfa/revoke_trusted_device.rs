@@ -0,0 +1,101 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use crypto::snowflake::Snowflake;
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    core::trusted_device::{self, RevokeError},
+    http::{
+        modules::{get_authenticated_user_id, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+#[derive(Deserialize, Default)]
+pub struct RevokeTrustedDeviceRequest {
+    /// The trusted device to revoke, all trusted devices are revoked if omitted
+    device_id: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RevokeTrustedDeviceResponse {
+    revoked: i64,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // The body is optional, without a device ID all trusted devices are revoked
+    let data: RevokeTrustedDeviceRequest = get_request(&parts, body).await.unwrap_or_default();
+
+    let revoked = match data.device_id {
+        Some(device_id) => {
+            let device_id: Snowflake = match device_id.parse() {
+                Ok(id) => id,
+                Err(_) => {
+                    let response =
+                        HTTPResponse::error("BadRequest", "Invalid device ID".to_owned(), ());
+                    return (StatusCode::BAD_REQUEST, Json(response));
+                }
+            };
+
+            match trusted_device::revoke(state.prisma(), user_id, device_id).await {
+                Ok(()) => 1,
+                Err(RevokeError::NotFound) => {
+                    let response =
+                        HTTPResponse::error("NotFound", "Trusted device not found".to_owned(), ());
+                    return (StatusCode::NOT_FOUND, Json(response));
+                }
+                Err(e) => {
+                    error!("Failed to revoke trusted device: {}", e);
+
+                    let response = HTTPResponse::error(
+                        "InternalServerError",
+                        "Failed to revoke trusted device".to_owned(),
+                        (),
+                    );
+                    return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+                }
+            }
+        }
+        None => match trusted_device::revoke_all(state.prisma(), user_id).await {
+            Ok(revoked) => revoked,
+            Err(e) => {
+                error!("Failed to revoke trusted devices: {}", e);
+
+                let response = HTTPResponse::error(
+                    "InternalServerError",
+                    "Failed to revoke trusted devices".to_owned(),
+                    (),
+                );
+                return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+            }
+        },
+    };
+
+    info!("revoked {} trusted devices of user {}", revoked, user_id);
+
+    let response = RevokeTrustedDeviceResponse { revoked };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
@@ -0,0 +1,196 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use crypto::{snowflake::Snowflake, tokens::jsonwebtoken::Claims};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    core::{email_otp, otp::SendCodeError, totp},
+    http::{
+        modules::{get_authenticated_user_id, get_request},
+        response::HTTPResponse,
+    },
+    models::user::{User, UserWith},
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct SendEmailRequest {
+    /// TOTP flow token returned by the login, a signed in user re-authenticating omits it and
+    /// uses their access token instead
+    token: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SendEmailResponse {
+    /// The email address the code was sent to, with most of the local part hidden
+    email_address: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct RetryAfterResponse {
+    retry_after: i64,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: SendEmailRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    let user_id: Snowflake = match data.token {
+        Some(token) => {
+            // Get user agent (used to verify totp flow token)
+            let user_agent = parts
+                .headers
+                .get("user-agent")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned);
+
+            // Sending a code does not use an attempt of the flow token
+            let claims = match totp::check_totp_flow_token(
+                &state,
+                token,
+                totp::get_device_id(&parts.headers),
+                totp::get_flow_session_id(&jar),
+                user_agent,
+            )
+            .await
+            {
+                Ok(claims) => claims,
+                Err(totp::VerifyFlowTokenError::Expired) => {
+                    let response =
+                        HTTPResponse::error("Expired", "TOTP flow token is expired".to_owned(), ());
+                    return (StatusCode::UNAUTHORIZED, Json(response));
+                }
+                Err(_) => {
+                    let response =
+                        HTTPResponse::error("Invalid", "TOTP flow token is invalid".to_owned(), ());
+                    return (StatusCode::UNAUTHORIZED, Json(response));
+                }
+            };
+
+            claims.sub().try_into().unwrap()
+        }
+        None => match get_authenticated_user_id(&state, &parts) {
+            Some(user_id) => user_id,
+            None => {
+                let response =
+                    HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+                return (StatusCode::UNAUTHORIZED, Json(response));
+            }
+        },
+    };
+
+    let user = match User::get(state.prisma(), user_id, vec![UserWith::EmailAddress]).await {
+        Ok(user) => user,
+        Err(_) => {
+            let response =
+                HTTPResponse::error("InternalServerError", "Failed to send code".to_owned(), ());
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    let email_address = match user.email_address().filter(|e| e.mfa_enabled()) {
+        Some(email_address) => email_address,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Email 2FA is not enabled for the user".to_owned(),
+                (),
+            );
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    match email_otp::is_allowed(state.prisma(), user.application_id()).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let response = HTTPResponse::error(
+                "EmailOtpNotAllowed",
+                "The application does not allow email codes as a second factor".to_owned(),
+                (),
+            );
+            return (StatusCode::FORBIDDEN, Json(response));
+        }
+        Err(e) => {
+            error!("Failed to get mfa config: {}", e);
+
+            let response =
+                HTTPResponse::error("InternalServerError", "Failed to send code".to_owned(), ());
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    }
+
+    let expires_at =
+        match email_otp::send_code(&state, state.prisma(), email_address, user.application_id())
+            .await
+        {
+            Ok(expires_at) => expires_at,
+            Err(SendCodeError::TooSoon { retry_after }) => {
+                let response = HTTPResponse::error(
+                    "TooManyRequests",
+                    "A code was sent recently, please wait before requesting a new one".to_owned(),
+                    RetryAfterResponse { retry_after },
+                );
+                return (StatusCode::TOO_MANY_REQUESTS, Json(response));
+            }
+            Err(SendCodeError::TooManyRequests) => {
+                let response = HTTPResponse::error(
+                    "TooManyRequests",
+                    "Too many codes have been sent to the email address, please try again later"
+                        .to_owned(),
+                    (),
+                );
+                return (StatusCode::TOO_MANY_REQUESTS, Json(response));
+            }
+            Err(SendCodeError::Overloaded) => {
+                let response = HTTPResponse::error(
+                    "ServiceUnavailable",
+                    "Too many requests are being processed, try again later".to_owned(),
+                    (),
+                );
+                return (StatusCode::SERVICE_UNAVAILABLE, Json(response));
+            }
+            Err(e) => {
+                error!("Failed to send email sign-in code: {}", e);
+
+                let response = HTTPResponse::error(
+                    "InternalServerError",
+                    "Failed to send code".to_owned(),
+                    (),
+                );
+                return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+            }
+        };
+
+    let response = SendEmailResponse {
+        email_address: email_address.masked(),
+        expires_at,
+    };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
@@ -0,0 +1,154 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use crypto::{snowflake::Snowflake, tokens::jsonwebtoken::Claims};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    core::{
+        mfa_recovery::{self, MfaRecoveryError},
+        otp::SendCodeError,
+        totp,
+    },
+    http::{modules::get_request, response::HTTPResponse},
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct SendRecoveryRequest {
+    /// TOTP flow token returned by the login
+    token: String,
+}
+
+#[derive(Serialize)]
+pub struct SendRecoveryResponse {
+    /// The email address the code was sent to, with most of the local part hidden
+    email_address: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct RetryAfterResponse {
+    retry_after: i64,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: SendRecoveryRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    // Get user agent (used to verify totp flow token)
+    let user_agent = parts
+        .headers
+        .get("user-agent")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+
+    // Sending a code does not use an attempt of the flow token
+    let claims = match totp::check_totp_flow_token(
+        &state,
+        data.token,
+        totp::get_device_id(&parts.headers),
+        totp::get_flow_session_id(&jar),
+        user_agent,
+    )
+    .await
+    {
+        Ok(claims) => claims,
+        Err(totp::VerifyFlowTokenError::Expired) => {
+            let response =
+                HTTPResponse::error("Expired", "TOTP flow token is expired".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+        Err(_) => {
+            let response =
+                HTTPResponse::error("Invalid", "TOTP flow token is invalid".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+    let user_id: Snowflake = claims.sub().try_into().unwrap();
+
+    let (email_address, expires_at) = match mfa_recovery::send_code(&state, state.prisma(), user_id)
+        .await
+    {
+        Ok(sent) => sent,
+        Err(MfaRecoveryError::NoVerifiedEmailAddress) => {
+            let response = HTTPResponse::error(
+                "EmailAddressNotVerified",
+                "A verified email address is required to recover the account".to_owned(),
+                (),
+            );
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+        Err(MfaRecoveryError::AlreadyPending) => {
+            let response = HTTPResponse::error(
+                "RecoveryPending",
+                "A recovery is already pending for the account".to_owned(),
+                (),
+            );
+            return (StatusCode::CONFLICT, Json(response));
+        }
+        Err(MfaRecoveryError::SendCode(SendCodeError::TooSoon { retry_after })) => {
+            let response = HTTPResponse::error(
+                "TooManyRequests",
+                "A code was sent recently, please wait before requesting a new one".to_owned(),
+                RetryAfterResponse { retry_after },
+            );
+            return (StatusCode::TOO_MANY_REQUESTS, Json(response));
+        }
+        Err(MfaRecoveryError::SendCode(SendCodeError::TooManyRequests)) => {
+            let response = HTTPResponse::error(
+                "TooManyRequests",
+                "Too many codes have been sent to the email address, please try again later"
+                    .to_owned(),
+                (),
+            );
+            return (StatusCode::TOO_MANY_REQUESTS, Json(response));
+        }
+        Err(MfaRecoveryError::SendCode(SendCodeError::Overloaded)) => {
+            let response = HTTPResponse::error(
+                "ServiceUnavailable",
+                "Too many requests are being processed, try again later".to_owned(),
+                (),
+            );
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(response));
+        }
+        Err(e) => {
+            error!("Failed to send mfa recovery code: {}", e);
+
+            let response =
+                HTTPResponse::error("InternalServerError", "Failed to send code".to_owned(), ());
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    let response = SendRecoveryResponse {
+        email_address: email_address.masked(),
+        expires_at,
+    };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
@@ -0,0 +1,215 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Utc};
+use crypto::{snowflake::Snowflake, tokens::jsonwebtoken::Claims};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    core::{
+        mfa_recovery::{self, MfaRecoveryError},
+        otp::VerifyCodeError,
+        totp,
+    },
+    http::{modules::get_request, response::HTTPResponse},
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct StartRecoveryRequest {
+    /// TOTP flow token returned by the login
+    token: String,
+    /// Recovery code sent to the email address
+    code: String,
+}
+
+#[derive(Serialize)]
+pub struct StartRecoveryResponse {
+    id: String,
+    /// The second factors are removed at this time unless the recovery is cancelled
+    completes_at: DateTime<Utc>,
+}
+
+pub async fn route(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Request<Body>,
+) -> impl IntoResponse {
+    let (parts, body) = request.into_parts();
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: StartRecoveryRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, jar, Json(response));
+        }
+    };
+
+    // Get user agent (used to verify totp flow token)
+    let user_agent = parts
+        .headers
+        .get("user-agent")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+
+    // Verify the totp flow token, every code tried uses an attempt
+    let flow_token = match totp::verify_totp_flow_token(
+        &state,
+        data.token,
+        totp::get_device_id(&parts.headers),
+        totp::get_flow_session_id(&jar),
+        user_agent.clone(),
+    )
+    .await
+    {
+        Ok(t) => t,
+        Err(totp::VerifyFlowTokenError::TooManyAttempts) => {
+            let jar = totp::remove_flow_session_cookie(jar);
+            let response = HTTPResponse::error(
+                "TooManyAttempts",
+                "Too many attempts, please login again".to_owned(),
+                (),
+            );
+            return (StatusCode::TOO_MANY_REQUESTS, jar, Json(response));
+        }
+        Err(totp::VerifyFlowTokenError::Expired) => {
+            let response =
+                HTTPResponse::error("Expired", "TOTP flow token is expired".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, jar, Json(response));
+        }
+        Err(_) => {
+            let response =
+                HTTPResponse::error("Invalid", "TOTP flow token is invalid".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, jar, Json(response));
+        }
+    };
+    let user_id: Snowflake = flow_token.claims().sub().try_into().unwrap();
+
+    // Begin a transaction
+    let (transaction_controller, prisma_client) = match state.prisma()._transaction().begin().await
+    {
+        Ok(t) => t,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to start recovery".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, jar, Json(response));
+        }
+    };
+
+    let recovery = match mfa_recovery::start(
+        &state,
+        &prisma_client,
+        user_id,
+        &data.code,
+        Some(addr.ip().to_string()),
+        user_agent,
+    )
+    .await
+    {
+        Ok(recovery) => recovery,
+        Err(e) => {
+            // A wrong code still counts as an attempt of the code
+            let _ = match e {
+                MfaRecoveryError::WrongCode => transaction_controller.commit(prisma_client).await,
+                _ => transaction_controller.rollback(prisma_client).await,
+            };
+
+            let (status, response) = match e {
+                MfaRecoveryError::WrongCode
+                | MfaRecoveryError::VerifyCode(VerifyCodeError::NotFound) => (
+                    StatusCode::UNAUTHORIZED,
+                    HTTPResponse::error(
+                        "InvalidCode",
+                        "The recovery code is invalid or has expired".to_owned(),
+                        (),
+                    ),
+                ),
+                MfaRecoveryError::VerifyCode(VerifyCodeError::TooManyAttempts) => (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    HTTPResponse::error(
+                        "TooManyAttempts",
+                        "Too many attempts, please request a new code".to_owned(),
+                        (),
+                    ),
+                ),
+                MfaRecoveryError::AlreadyPending => (
+                    StatusCode::CONFLICT,
+                    HTTPResponse::error(
+                        "RecoveryPending",
+                        "A recovery is already pending for the account".to_owned(),
+                        (),
+                    ),
+                ),
+                MfaRecoveryError::NoVerifiedEmailAddress => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error(
+                        "EmailAddressNotVerified",
+                        "A verified email address is required to recover the account".to_owned(),
+                        (),
+                    ),
+                ),
+                MfaRecoveryError::VerifyCode(VerifyCodeError::Overloaded) => (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    HTTPResponse::error(
+                        "ServiceUnavailable",
+                        "Too many requests are being processed, try again later".to_owned(),
+                        (),
+                    ),
+                ),
+                e => {
+                    error!("Failed to start mfa recovery: {}", e);
+
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        HTTPResponse::error(
+                            "InternalServerError",
+                            "Failed to start recovery".to_owned(),
+                            (),
+                        ),
+                    )
+                }
+            };
+
+            return (status, jar, Json(response));
+        }
+    };
+
+    // The flow token can not be used again
+    if totp::consume_totp_flow_token(&prisma_client, &flow_token)
+        .await
+        .is_err()
+        || transaction_controller.commit(prisma_client).await.is_err()
+    {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to start recovery".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, jar, Json(response));
+    }
+
+    let jar = totp::remove_flow_session_cookie(jar);
+
+    let response = StartRecoveryResponse {
+        id: recovery.id().to_string(),
+        completes_at: recovery.completes_at(),
+    };
+    (StatusCode::OK, jar, Json(HTTPResponse::ok(response)))
+}
@@ -0,0 +1,54 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::Serialize;
+
+use crate::{
+    core::trusted_device::{self, TrustedDevice},
+    http::{modules::get_authenticated_user_id, response::HTTPResponse},
+    state::AppState,
+};
+
+#[derive(Serialize)]
+pub struct TrustedDevicesResponse {
+    devices: Vec<TrustedDevice>,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, _) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    let devices = match trusted_device::list(state.prisma(), user_id).await {
+        Ok(devices) => devices,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to get trusted devices".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(HTTPResponse::ok(TrustedDevicesResponse { devices })),
+    )
+}
@@ -9,9 +9,13 @@ use crate::state::AppState;
 
 pub mod refresh;
 
+/// Module for step-up re-authentication before sensitive operations.
+pub mod reauthenticate;
+
 /// Router for handling routing within session.
 pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/refresh", post(refresh::route))
+        .route("/reauthenticate", post(reauthenticate::route))
         .with_state(state)
 }
@@ -0,0 +1,135 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    core::reauthenticate::{self, ReauthenticateError},
+    http::{
+        modules::{get_access_token_claims, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct ReauthenticateRequest {
+    password: Option<String>,
+    /// A code of any second factor of the user
+    code: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ReauthenticateResponse {
+    /// Short-lived access token for sensitive operations
+    access: String,
+    expires_at: DateTime<Utc>,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user and their session
+    let claims = match get_access_token_claims(&state, &parts) {
+        Some(claims) => claims,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    let (user_id, refresh_token_id) = match (
+        claims.subject().and_then(|sub| sub.parse().ok()),
+        claims.other(),
+    ) {
+        (Some(user_id), Some(other)) => (user_id, other.refresh_token_id()),
+        _ => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: ReauthenticateRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    // Codes are verified outside of a transaction so that used codes are always stored
+    let (access, expires_at) = match reauthenticate::reauthenticate(
+        &state,
+        state.prisma(),
+        user_id,
+        refresh_token_id,
+        data.password,
+        data.code,
+    )
+    .await
+    {
+        Ok(elevated) => elevated,
+        Err(ReauthenticateError::MissingCredentials) => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "A password or a code is required".to_owned(),
+                (),
+            );
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+        Err(ReauthenticateError::WrongCredentials) => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid password or code".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+        Err(ReauthenticateError::TooManyAttempts) => {
+            let response = HTTPResponse::error(
+                "TooManyAttempts",
+                "Too many attempts, please try again later".to_owned(),
+                (),
+            );
+            return (StatusCode::TOO_MANY_REQUESTS, Json(response));
+        }
+        Err(ReauthenticateError::Overloaded) => {
+            let response = HTTPResponse::error(
+                "ServiceUnavailable",
+                "Too many requests are being processed, try again later".to_owned(),
+                (),
+            );
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(response));
+        }
+        Err(e) => {
+            error!("Failed to re-authenticate user: {}", e);
+
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to re-authenticate".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    info!("re-authenticated user {}", user_id);
+
+    let response = ReauthenticateResponse { access, expires_at };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
@@ -10,7 +10,10 @@ use hyper::{Body, Request, StatusCode};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    core::token::{self, get_refresh_cookie},
+    core::{
+        metadata, rbac,
+        token::{self, get_refresh_cookie},
+    },
     http::{modules::get_request, response::HTTPResponse},
     state::AppState,
 };
@@ -49,12 +52,12 @@ pub async fn route(
 
     // Get refresh token from cookie
     let refresh = get_refresh_cookie(&jar, data.application_id);
-    let token = match refresh {
+    let (token, authentication) = match refresh {
         Some(refresh) => {
             // Fetch refresh token from database
             let token = refresh.value();
             match token::verify_refresh_token(&state, state.prisma(), token).await {
-                Ok(token) => token,
+                Ok(verified) => verified,
                 Err(_) => {
                     let response =
                         HTTPResponse::error("Unauthorized", "Invalid refresh token".to_owned(), ());
@@ -69,11 +72,43 @@ pub async fn route(
         }
     };
 
+    // Roles, permissions and projected metadata may have changed since the login
+    let authorization = match rbac::get_authorization(state.prisma(), token.user_id()).await {
+        Ok(authorization) => authorization,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to generate access token".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+    let metadata = match metadata::get_token_claims(state.prisma(), token.user_id()).await {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to generate access token".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
     // Set access token expire to 1 minute
     let expiration = chrono::Utc::now() + chrono::Duration::minutes(1);
 
-    // Generate new access token
-    let access_token = token::new_access_token(&state, token.user_id(), expiration, token.id());
+    // Generate new access token, the authentication of the login is kept
+    let access_token = token::new_access_token(
+        &state,
+        token.user_id(),
+        expiration,
+        token.id(),
+        authentication,
+        authorization,
+        metadata,
+    );
     let access_token = match access_token {
         Ok(access_token) => access_token,
         Err(_) => {
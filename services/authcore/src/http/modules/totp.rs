@@ -3,11 +3,15 @@
 //! This module provides the functionality for TOTP (Time-based One-time Password) authentication.
 
 use axum::{
+    middleware::from_fn_with_state,
     routing::{get, post},
     Router,
 };
 
-use crate::state::AppState;
+use crate::{
+    http::middleware::{require_recent_authentication, RecentAuthentication},
+    state::AppState,
+};
 
 /// Module for handling verification of TOTP codes and authentication.
 pub mod verify;
@@ -18,16 +22,37 @@ pub mod delete;
 /// Module for handling setup of TOTP.
 pub mod setup;
 
+/// Module for handling confirmation of a pending TOTP enrolment.
+pub mod confirm;
+
 /// Module for handling backup codes of TOTP.
 pub mod backup_codes;
 
+/// Module for handling regeneration of TOTP backup codes.
+pub mod regenerate_backup_codes;
+
 /// Router for handling routing within totp.
 pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/verify", post(verify::route))
-        // TODO: Place endpoints behind authentication middleware (using Authorization header)
-        .route("/delete", post(delete::route))
+        // The other routes check the token themselves, deleting the device also requires a recent
+        // authentication with MFA
+        .route(
+            "/delete",
+            post(delete::route).layer(from_fn_with_state(
+                (
+                    state.clone(),
+                    RecentAuthentication::within_minutes(10).with_mfa(),
+                ),
+                require_recent_authentication,
+            )),
+        )
         .route("/setup", post(setup::route))
+        .route("/confirm", post(confirm::route))
         .route("/backup_codes", get(backup_codes::route))
+        .route(
+            "/backup_codes/regenerate",
+            post(regenerate_backup_codes::route),
+        )
         .with_state(state)
 }
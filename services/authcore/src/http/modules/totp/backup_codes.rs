@@ -5,39 +5,69 @@ use axum::{
     Json,
 };
 use hyper::{Body, Request, StatusCode};
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 
 use crate::{
-    http::{modules::get_request, response::HTTPResponse},
+    http::{modules::get_authenticated_user_id, response::HTTPResponse},
+    models::{
+        error::ModelError,
+        user::{totp::TOTPBackupCode, User},
+    },
     state::AppState,
 };
 
-#[derive(Deserialize)]
-pub struct BackupCodesRequest {}
-
 #[derive(Serialize)]
-pub struct BackupCodesResponse {}
+pub struct BackupCodesResponse {
+    remaining: i64,
+}
 
 pub async fn route(
     ConnectInfo(_addr): ConnectInfo<SocketAddr>,
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     request: Request<Body>,
 ) -> (StatusCode, Json<HTTPResponse>) {
-    let (parts, body) = request.into_parts();
+    let (parts, _) = request.into_parts();
 
-    // Accept multiple different ways to give the data, url encoded form data or json body
-    let _data: BackupCodesRequest = match get_request(&parts, body).await {
-        Some(d) => d,
+    // Get the authenticated user
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
         None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Backup codes are only usable while the user has TOTP enabled
+    let user = match User::get(state.prisma(), user_id, vec![]).await {
+        Ok(user) if user.totp_enabled() => user,
+        Ok(_) | Err(ModelError::NotFound) => {
+            let response = HTTPResponse::error("BadRequest", "TOTP is not enabled".to_owned(), ());
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+        Err(_) => {
             let response = HTTPResponse::error(
-                "BadRequest",
-                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                "InternalServerError",
+                "Failed to get backup codes".to_owned(),
                 (),
             );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
 
-            return (StatusCode::BAD_REQUEST, Json(response));
+    let remaining = match TOTPBackupCode::count_remaining(state.prisma(), user.id()).await {
+        Ok(remaining) => remaining,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to get backup codes".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
         }
     };
 
-    (StatusCode::OK, Json(HTTPResponse::empty()))
+    let response = BackupCodesResponse { remaining };
+
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
 }
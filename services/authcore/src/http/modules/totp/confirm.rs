@@ -0,0 +1,155 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    core::totp::{self, BackupCodesError, ConfirmEnrolmentError},
+    http::{
+        modules::{get_enrolling_user_id, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct ConfirmRequest {
+    totp_code: String,
+}
+
+#[derive(Serialize)]
+pub struct ConfirmResponse {
+    /// Only returned when the first device is enrolled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup_codes: Option<Vec<String>>,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_enrolling_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: ConfirmRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    // Begin a transaction
+    let (transaction_controller, prisma_client) = match state.prisma()._transaction().begin().await
+    {
+        Ok(t) => t,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to confirm totp".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    let backup_codes =
+        match totp::confirm_enrolment(&state, &prisma_client, user_id, data.totp_code).await {
+            Ok(backup_codes) => backup_codes,
+            Err(ConfirmEnrolmentError::Expired) => {
+                // Commit so that the expired enrolment is deleted
+                let _ = transaction_controller.commit(prisma_client).await;
+
+                let response = HTTPResponse::error(
+                    "Expired",
+                    "TOTP enrolment is expired, please setup totp again".to_owned(),
+                    (),
+                );
+                return (StatusCode::BAD_REQUEST, Json(response));
+            }
+            Err(e) => {
+                let _ = transaction_controller.rollback(prisma_client).await;
+
+                let (status, response) = match e {
+                    ConfirmEnrolmentError::WrongCode => (
+                        StatusCode::UNAUTHORIZED,
+                        HTTPResponse::error("Unauthorized", "Invalid totp code".to_owned(), ()),
+                    ),
+                    ConfirmEnrolmentError::TooManyAttempts => (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        HTTPResponse::error(
+                            "TooManyAttempts",
+                            "Too many attempts, please setup totp again".to_owned(),
+                            (),
+                        ),
+                    ),
+                    ConfirmEnrolmentError::NotFound => (
+                        StatusCode::BAD_REQUEST,
+                        HTTPResponse::error(
+                            "BadRequest",
+                            "No pending totp enrolment".to_owned(),
+                            (),
+                        ),
+                    ),
+                    ConfirmEnrolmentError::BackupCodes(BackupCodesError::Overloaded) => (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        HTTPResponse::error(
+                            "ServiceUnavailable",
+                            "Too many requests are being processed, try again later".to_owned(),
+                            (),
+                        ),
+                    ),
+                    e => {
+                        error!("Failed to confirm totp enrolment: {}", e);
+
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            HTTPResponse::error(
+                                "InternalServerError",
+                                "Failed to confirm totp".to_owned(),
+                                (),
+                            ),
+                        )
+                    }
+                };
+
+                return (status, Json(response));
+            }
+        };
+
+    // Commit the transaction
+    if transaction_controller.commit(prisma_client).await.is_err() {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to confirm totp".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    }
+
+    info!("confirmed totp device for user {}", user_id);
+
+    let response = ConfirmResponse { backup_codes };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
@@ -0,0 +1,130 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    core::totp::{self, BackupCodesError},
+    http::{
+        modules::{get_authenticated_user_id, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct RegenerateBackupCodesRequest {
+    password: String,
+}
+
+#[derive(Serialize)]
+pub struct RegenerateBackupCodesResponse {
+    backup_codes: Vec<String>,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: RegenerateBackupCodesRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    // Begin a transaction
+    let (transaction_controller, prisma_client) = match state.prisma()._transaction().begin().await
+    {
+        Ok(t) => t,
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to regenerate backup codes".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    let backup_codes =
+        match totp::regenerate_backup_codes(&state, &prisma_client, user_id, data.password).await {
+            Ok(backup_codes) => backup_codes,
+            Err(e) => {
+                let _ = transaction_controller.rollback(prisma_client).await;
+
+                let (status, response) = match e {
+                    BackupCodesError::WrongCredentials => (
+                        StatusCode::UNAUTHORIZED,
+                        HTTPResponse::error("Unauthorized", "Invalid password".to_owned(), ()),
+                    ),
+                    BackupCodesError::Overloaded => (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        HTTPResponse::error(
+                            "ServiceUnavailable",
+                            "Too many requests are being processed, try again later".to_owned(),
+                            (),
+                        ),
+                    ),
+                    BackupCodesError::NotEnabled => (
+                        StatusCode::BAD_REQUEST,
+                        HTTPResponse::error("BadRequest", "TOTP is not enabled".to_owned(), ()),
+                    ),
+                    e => {
+                        error!("Failed to regenerate backup codes: {}", e);
+
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            HTTPResponse::error(
+                                "InternalServerError",
+                                "Failed to regenerate backup codes".to_owned(),
+                                (),
+                            ),
+                        )
+                    }
+                };
+
+                return (status, Json(response));
+            }
+        };
+
+    // Commit the transaction
+    if transaction_controller.commit(prisma_client).await.is_err() {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to regenerate backup codes".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    }
+
+    info!("regenerated backup codes for user {}", user_id);
+
+    let response = RegenerateBackupCodesResponse { backup_codes };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
@@ -1,32 +1,56 @@
-use std::{net::SocketAddr, str::FromStr};
+use std::net::SocketAddr;
 
 use axum::{
     extract::{ConnectInfo, State},
     Json,
 };
-use crypto::{snowflake::Snowflake, totp};
+use chrono::{DateTime, Utc};
+use crypto::totp::KeyUri;
 use hyper::{Body, Request, StatusCode};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tracing::info;
 
 use crate::{
-    core::token,
-    http::response::HTTPResponse,
+    core::totp as core_totp,
+    http::{
+        modules::{get_enrolling_user_id, get_request},
+        response::HTTPResponse,
+    },
     models::{
-        prisma,
-        user::{
-            totp::{TOTPBackupCode, TOTP},
-            User, UserWith,
-        },
+        application::ReplicatedApplication,
+        user::{totp::TOTP, User, UserWith},
     },
     state::AppState,
 };
 
+/// Maximum length of a device name.
+const MAX_NAME_LENGTH: usize = 64;
+
+#[derive(Deserialize, Default)]
+pub struct SetupRequest {
+    /// Name of the device, e.g. "Work phone"
+    name: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct SetupResponse {
+    id: String,
+    name: String,
+
     totp_secret: String,
     interval: u32,
-    backup_codes: Vec<String>,
+    algorithm: String,
+    digits: u32,
+
+    /// The enrolment has to be confirmed with a code before this time
+    expires_at: DateTime<Utc>,
+
+    /// `otpauth://` provisioning URI for authenticator apps
+    uri: String,
+    /// QR code of the provisioning URI as SVG
+    qr_code_svg: String,
+    /// QR code of the provisioning URI as PNG data URI
+    qr_code_png: String,
 }
 
 pub async fn route(
@@ -34,45 +58,12 @@ pub async fn route(
     State(state): State<AppState>,
     request: Request<Body>,
 ) -> (StatusCode, Json<HTTPResponse>) {
-    // Get authorization header
-    // ! Temporary till middleware is implemented
-    let auth = match request.headers().get("Authorization") {
-        Some(auth) => auth,
-        None => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(HTTPResponse::error(
-                    "Unauthorized",
-                    "Missing authorization header".to_owned(),
-                    (),
-                )),
-            );
-        }
-    }
-    .to_str()
-    .unwrap();
-
-    // Parse the authorization header
-    let auth = match auth.split(' ').collect::<Vec<_>>().as_slice() {
-        ["Bearer", token] => token,
-        _ => {
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(HTTPResponse::error(
-                    "Unauthorized",
-                    "Invalid authorization header".to_owned(),
-                    (),
-                )),
-            );
-        }
-    }
-    .to_owned();
+    let (parts, body) = request.into_parts();
 
-    // Verify the access token
-    let access_token = token::verify_access_token(&state, auth);
-    let access_token = match access_token {
-        Ok(access_token) => access_token,
-        Err(_) => {
+    // Users that have to enrol a second factor authenticate with their enrolment token
+    let user_id = match get_enrolling_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
             return (
                 StatusCode::UNAUTHORIZED,
                 Json(HTTPResponse::error(
@@ -86,8 +77,8 @@ pub async fn route(
 
     let user = match User::get(
         state.prisma(),
-        Snowflake::from_str(access_token.subject().unwrap()).unwrap(),
-        vec![UserWith::TOTP],
+        user_id,
+        vec![UserWith::TOTP, UserWith::EmailAddress],
     )
     .await
     {
@@ -104,13 +95,20 @@ pub async fn route(
         }
     };
 
-    // Check if user has totp enabled
-    if user.totp().is_some() {
+    // The body is optional, without a name the device gets a default name
+    let data: SetupRequest = get_request(&parts, body).await.unwrap_or_default();
+    let name = data.name.map(|name| name.trim().to_owned());
+    if name.as_ref().map_or(false, |name| {
+        name.is_empty() || name.chars().count() > MAX_NAME_LENGTH
+    }) {
         return (
             StatusCode::BAD_REQUEST,
             Json(HTTPResponse::error(
                 "BadRequest",
-                "TOTP already enabled".to_owned(),
+                format!(
+                    "Device name must be between 1 and {} characters",
+                    MAX_NAME_LENGTH
+                ),
                 (),
             )),
         );
@@ -118,10 +116,57 @@ pub async fn route(
 
     // Generate a new totp secret
     let totp_secret = crypto::totp::generate_totp_secret();
-    let totp_interval = 30;
+
+    // The application decides the parameters of new authenticators
+    let mut application =
+        match ReplicatedApplication::get(state.prisma(), user.application_id()).await {
+            Ok(application) => application,
+            Err(_) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(HTTPResponse::error(
+                        "InternalServerError",
+                        "Failed to get application".to_owned(),
+                        (),
+                    )),
+                );
+            }
+        };
+
+    let mfa_config = match application.mfa_config(state.prisma()).await {
+        Ok(mfa_config) => mfa_config,
+        Err(_) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(HTTPResponse::error(
+                    "InternalServerError",
+                    "Failed to get application".to_owned(),
+                    (),
+                )),
+            );
+        }
+    };
+
+    if !mfa_config.enrolment_allowed() {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(HTTPResponse::error(
+                "MfaNotAllowed",
+                "The application does not allow enrolling a second factor".to_owned(),
+                (),
+            )),
+        );
+    }
+
+    let totp_interval = mfa_config.totp_interval();
 
     // Try to generate, verify that it succeeds
-    let totp = crypto::totp::generate_totp(totp_secret.as_bytes(), totp_interval);
+    let totp = crypto::totp::generate_totp(
+        totp_secret.as_bytes(),
+        totp_interval,
+        mfa_config.totp_algorithm(),
+        mfa_config.totp_digits(),
+    );
     if totp.is_err() {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -133,6 +178,32 @@ pub async fn route(
         );
     }
 
+    // Build the provisioning URI, the application name is shown as issuer in authenticator apps
+    let account_name = match user.email_address() {
+        Some(email_address) => email_address.email_address().to_owned(),
+        None => user.id().to_string(),
+    };
+
+    let key_uri = KeyUri::new(totp_secret.clone(), account_name)
+        .issuer(application.name())
+        .algorithm(mfa_config.totp_algorithm())
+        .digits(mfa_config.totp_digits())
+        .period(totp_interval);
+
+    let (qr_code_svg, qr_code_png) = match (key_uri.to_qr_svg(), key_uri.to_qr_png_data_uri()) {
+        (Ok(svg), Ok(png)) => (svg, png),
+        _ => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(HTTPResponse::error(
+                    "InternalServerError",
+                    "Failed to generate totp qr code".to_owned(),
+                    (),
+                )),
+            );
+        }
+    };
+
     let transaction = state.prisma()._transaction().begin().await;
     let (transaction_controller, prisma_client) = match transaction {
         Ok(transaction) => transaction,
@@ -148,14 +219,35 @@ pub async fn route(
         }
     };
 
-    // Create the totp
+    // Delete an earlier enrolment that has not been confirmed, a user can only enrol one device at a time
+    for pending in user.totps().iter().filter(|totp| totp.is_pending()) {
+        if TOTP::delete(&prisma_client, pending.id()).await.is_err() {
+            let _ = transaction_controller.rollback(prisma_client).await;
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(HTTPResponse::error(
+                    "InternalServerError",
+                    "Failed to setup totp".to_owned(),
+                    (),
+                )),
+            );
+        }
+    }
+
+    // Create the totp as pending, it is activated once the user confirms it with a code
+    let pending_until =
+        chrono::Utc::now() + chrono::Duration::minutes(core_totp::ENROLMENT_LIFETIME_MINUTES);
     let totp = TOTP::builder(
         state.id_generator().next_snowflake().unwrap(),
         user.id(),
-        totp_secret,
+        totp_secret.clone(),
         totp_interval,
     )
-    .create(state.prisma())
+    .name(name)
+    .algorithm(mfa_config.totp_algorithm())
+    .digits(mfa_config.totp_digits())
+    .pending_until(pending_until)
+    .create(&prisma_client)
     .await;
 
     // Check if totp was created
@@ -174,64 +266,6 @@ pub async fn route(
         }
     };
 
-    // Generate backup codes
-    let backup_codes = (0..10)
-        .map(|_| totp::generate_backup_code())
-        .collect::<Vec<_>>();
-
-    // Create the backup codes
-    let res = TOTPBackupCode::builder(
-        totp.id(),
-        backup_codes
-            .iter()
-            .map(|code| {
-                (
-                    state.id_generator().next_snowflake().unwrap(),
-                    code.to_owned(),
-                )
-            })
-            .collect(),
-    )
-    .create(state.prisma())
-    .await;
-
-    // Rollback if totp backup code insert fails
-    if res.is_err() {
-        let _ = transaction_controller.rollback(prisma_client).await;
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(HTTPResponse::error(
-                "InternalServerError",
-                "Failed to setup totp".to_owned(),
-                (),
-            )),
-        );
-    }
-
-    // Update user to check totp enabled flag
-    let res = state
-        .prisma()
-        .user()
-        .update(
-            prisma::user::id::equals(user.id().to_id_signed()),
-            vec![prisma::user::totp_enabled::set(true)],
-        )
-        .exec()
-        .await;
-
-    // Rollback if user update fails
-    if res.is_err() {
-        let _ = transaction_controller.rollback(prisma_client).await;
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(HTTPResponse::error(
-                "InternalServerError",
-                "Failed to setup totp".to_owned(),
-                (),
-            )),
-        );
-    }
-
     // Commit the transaction
     if (transaction_controller.commit(prisma_client).await).is_err() {
         return (
@@ -245,16 +279,23 @@ pub async fn route(
     }
 
     info!(
-        "enabled totp for user {} with interval {}",
+        "started totp enrolment for user {} with interval {}",
         user.id().to_id_signed(),
         totp_interval
     );
 
     // Return the totp secret and interval
     let response = HTTPResponse::ok(SetupResponse {
-        totp_secret: totp.secret().to_string(),
+        id: totp.id().to_string(),
+        name: totp.name().to_owned(),
+        totp_secret,
         interval: totp.interval(),
-        backup_codes,
+        algorithm: totp.algorithm().to_string(),
+        digits: totp.digits(),
+        expires_at: pending_until,
+        uri: key_uri.to_string(),
+        qr_code_svg,
+        qr_code_png,
     });
 
     (StatusCode::OK, Json(response))
@@ -8,21 +8,40 @@ use axum::{
 use axum_extra::extract::CookieJar;
 use crypto::tokens::jsonwebtoken::Claims;
 use hyper::{Body, Request, StatusCode};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    core::{basic::login, token, totp},
+    core::{
+        basic::login,
+        token::{self, Authentication},
+        totp::{self, VerifyFactorError},
+        trusted_device::{self, TrustDeviceError},
+    },
     http::{
-        modules::{basic::login::LoginResponse, get_request},
+        modules::{
+            basic::login::{password_change_required, LoginResponse},
+            get_request,
+        },
         response::HTTPResponse,
     },
+    models::application::ReplicatedApplication,
     state::AppState,
 };
 
 #[derive(Deserialize)]
 pub struct VerifyRequest {
     token: String,
+    /// A code of any second factor, also accepted as `code`
+    #[serde(alias = "code")]
     totp_code: String,
+    /// Trust the device to skip the second factor on future logins
+    #[serde(default)]
+    remember_device: bool,
+}
+
+#[derive(Serialize)]
+pub struct InvalidCodeResponse {
+    remaining_attempts: i32,
 }
 
 pub async fn route(
@@ -51,7 +70,7 @@ pub async fn route(
     if data.totp_code.len() < 6 || data.totp_code.len() > 9 {
         let response = HTTPResponse::error(
             "BadRequest",
-            "Invalid totp code, expected 6 digits or 9 characters for backup code".to_owned(),
+            "Invalid totp code, expected 6 to 8 digits or 9 characters for backup code".to_owned(),
             (),
         );
 
@@ -81,18 +100,30 @@ pub async fn route(
         }
     };
 
-    // Verify the totp flow token
-    let claims = match totp::verify_totp_flow_token(
+    // Verify the totp flow token, it must be used from the device and session it was issued to
+    let flow_token = match totp::verify_totp_flow_token(
         &state,
         data.token,
-        None,
-        None,
+        totp::get_device_id(&parts.headers),
+        totp::get_flow_session_id(&jar),
         user_agent.clone(),
     )
     .await
     {
-        Ok(c) => c,
+        Ok(t) => t,
         Err(e) => match e {
+            totp::VerifyFlowTokenError::TooManyAttempts => {
+                let jar = totp::remove_flow_session_cookie(jar);
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    jar,
+                    Json(HTTPResponse::error(
+                        "TooManyAttempts",
+                        "Too many attempts, please login again".to_owned(),
+                        (),
+                    )),
+                );
+            }
             totp::VerifyFlowTokenError::Expired => {
                 return (
                     StatusCode::UNAUTHORIZED,
@@ -119,10 +150,14 @@ pub async fn route(
     };
 
     // Fetch user from database
-    let user = match crate::models::user::User::get(
+    let mut user = match crate::models::user::User::get(
         &prisma_client,
-        claims.sub().try_into().unwrap(),
-        vec![crate::models::user::UserWith::TOTP],
+        flow_token.claims().sub().try_into().unwrap(),
+        vec![
+            crate::models::user::UserWith::TOTP,
+            crate::models::user::UserWith::PhoneNumber,
+            crate::models::user::UserWith::EmailAddress,
+        ],
     )
     .await
     {
@@ -140,8 +175,8 @@ pub async fn route(
         }
     };
 
-    // Check that user has totp
-    if user.totp().is_none() {
+    // Check that user has an active second factor
+    if !user.mfa_enabled() {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
             jar,
@@ -153,27 +188,144 @@ pub async fn route(
         );
     }
 
-    let totp = user.totp().take().unwrap();
+    // Match the code against all devices of the user, or the backup codes
+    let factor_type = match totp::verify_code(&state, &prisma_client, &user, data.totp_code).await {
+        Ok(Some(factor_type)) => factor_type,
+        Err(VerifyFactorError::Overloaded) => {
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                jar,
+                Json(HTTPResponse::error(
+                    "ServiceUnavailable",
+                    "Too many requests are being processed, try again later".to_owned(),
+                    (),
+                )),
+            );
+        }
+        Ok(None) | Err(_) => {
+            let remaining_attempts = flow_token.remaining_attempts();
+
+            // Invalidate the flow token after the last attempt
+            let jar = if remaining_attempts <= 0 {
+                let _ = totp::consume_totp_flow_token(state.prisma(), &flow_token).await;
+                totp::remove_flow_session_cookie(jar)
+            } else {
+                jar
+            };
 
-    // Match totp code
-    let totp_result = totp.verify(&prisma_client, data.totp_code).await;
-    if totp_result.is_err() || !totp_result.unwrap() {
+            return (
+                StatusCode::UNAUTHORIZED,
+                jar,
+                Json(HTTPResponse::error(
+                    "Unauthorized",
+                    "Invalid totp code".to_owned(),
+                    InvalidCodeResponse { remaining_attempts },
+                )),
+            );
+        }
+    };
+
+    // The flow token is single-use
+    if totp::consume_totp_flow_token(&prisma_client, &flow_token)
+        .await
+        .is_err()
+    {
         return (
-            StatusCode::UNAUTHORIZED,
+            StatusCode::INTERNAL_SERVER_ERROR,
             jar,
             Json(HTTPResponse::error(
-                "Unauthorized",
-                "Invalid totp code".to_owned(),
+                "InternalServerError",
+                "Could not verify totp".to_owned(),
                 (),
             )),
         );
     }
 
+    // Trust the device if the user asked for it and the application allows it
+    let trusted_device_token = if data.remember_device {
+        match trusted_device::trust_device(
+            &state,
+            &prisma_client,
+            user.id(),
+            user.application_id(),
+            Some(addr.ip().to_string()),
+            user_agent.clone(),
+        )
+        .await
+        {
+            Ok(token) => Some(token),
+            Err(TrustDeviceError::NotEnabled) => None,
+            Err(_) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    jar,
+                    Json(HTTPResponse::error(
+                        "InternalServerError",
+                        "Could not trust the device".to_owned(),
+                        (),
+                    )),
+                );
+            }
+        }
+    } else {
+        None
+    };
+
+    let jar = match trusted_device_token {
+        Some(token) => jar.add(trusted_device::create_trusted_device_cookie(
+            token.token().to_string(),
+            token.expires_at(),
+            user.application_id(),
+        )),
+        None => jar,
+    };
+
+    // Users with an expired password only get a session after changing it, the device stays
+    // trusted so the user is not asked for the second factor again
+    let mut application =
+        match ReplicatedApplication::get(&prisma_client, user.application_id()).await {
+            Ok(application) => application,
+            Err(_) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    jar,
+                    Json(HTTPResponse::error(
+                        "InternalServerError",
+                        "Could not verify totp".to_owned(),
+                        (),
+                    )),
+                );
+            }
+        };
+
+    if login::needs_password_change(&prisma_client, &mut application, &mut user).await {
+        // The flow token has been used, commit its consumption and the trusted device
+        if transaction_controller.commit(prisma_client).await.is_err() {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                jar,
+                Json(HTTPResponse::error(
+                    "InternalServerError",
+                    "Could not verify totp".to_owned(),
+                    (),
+                )),
+            );
+        }
+
+        let (status, response) = password_change_required(&state, user.id());
+        return (
+            status,
+            totp::remove_flow_session_cookie(jar),
+            Json(response),
+        );
+    }
+
     // Generate refresh and access token
     let (refresh_token, access_token) = match login::create_refresh_and_access_token(
         &state,
         &prisma_client,
         &user,
+        Authentication::password_and_second_factor(factor_type),
         Some(addr.ip().to_string()),
         user_agent,
     )
@@ -211,7 +363,7 @@ pub async fn route(
     };
 
     // Write refresh to cookie
-    let jar = jar.add(token::create_refresh_cookie(
+    let jar = totp::remove_flow_session_cookie(jar).add(token::create_refresh_cookie(
         refresh_token.token().to_string(),
         refresh_token.expires_at(),
         user.application_id(),
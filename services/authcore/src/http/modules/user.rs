@@ -0,0 +1,28 @@
+//! User Module
+//!
+//! This module lets an authenticated user manage their own account data.
+
+use axum::{
+    routing::{get, post},
+    Router,
+};
+
+use crate::state::AppState;
+
+/// Module for listing the public and private metadata of the user.
+pub mod metadata;
+
+/// Module for creating or replacing a private metadata entry.
+pub mod set_metadata;
+
+/// Module for deleting a private metadata entry.
+pub mod delete_metadata;
+
+/// Router for handling routing within user.
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/metadata", get(metadata::route))
+        .route("/metadata/set", post(set_metadata::route))
+        .route("/metadata/delete", post(delete_metadata::route))
+        .with_state(state)
+}
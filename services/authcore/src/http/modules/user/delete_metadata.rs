@@ -0,0 +1,76 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    core::metadata::{self, MetadataError},
+    http::{
+        modules::{get_authenticated_user_id, get_request},
+        response::HTTPResponse,
+    },
+    models::user::metadata::MetadataScope,
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct DeleteMetadataRequest {
+    key: String,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: DeleteMetadataRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    // Users can only delete their private entries
+    match metadata::delete(state.prisma(), user_id, data.key, &[MetadataScope::Private]).await {
+        Ok(()) => (StatusCode::OK, Json(HTTPResponse::empty())),
+        Err(MetadataError::NotFound) => {
+            let response =
+                HTTPResponse::error("NotFound", "Metadata entry not found".to_owned(), ());
+            (StatusCode::NOT_FOUND, Json(response))
+        }
+        Err(e) => {
+            error!("Failed to delete user metadata: {}", e);
+
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to delete metadata".to_owned(),
+                (),
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
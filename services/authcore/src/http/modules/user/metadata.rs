@@ -0,0 +1,99 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hyper::{Body, Request, StatusCode};
+use serde::Serialize;
+use tracing::error;
+
+use crate::{
+    core::metadata::{self, MetadataError, USER_READABLE_SCOPES},
+    http::{modules::get_authenticated_user_id, response::HTTPResponse},
+    models::user::{metadata::MetadataScope, UserMetadata},
+    state::AppState,
+};
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataEntryScope {
+    Public,
+    Private,
+}
+
+/// A metadata entry as seen by the user.
+#[derive(Serialize)]
+pub struct MetadataEntry {
+    key: String,
+    value: serde_json::Value,
+    scope: MetadataEntryScope,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<UserMetadata> for MetadataEntry {
+    fn from(value: UserMetadata) -> Self {
+        // Only public and private entries are returned to the user
+        let scope = match value.scope() {
+            MetadataScope::Private => MetadataEntryScope::Private,
+            _ => MetadataEntryScope::Public,
+        };
+
+        Self {
+            key: value.key().to_owned(),
+            value: value.value().clone(),
+            scope,
+            updated_at: value.updated_at(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct MetadataResponse {
+    metadata: Vec<MetadataEntry>,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, _) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    let entries = match metadata::list(state.prisma(), user_id, USER_READABLE_SCOPES.to_vec()).await
+    {
+        Ok(entries) => entries,
+        Err(MetadataError::UserNotFound) => {
+            let response = HTTPResponse::error("NotFound", "User not found".to_owned(), ());
+            return (StatusCode::NOT_FOUND, Json(response));
+        }
+        Err(e) => {
+            error!("Failed to list user metadata: {}", e);
+
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to get metadata".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    (
+        StatusCode::OK,
+        Json(HTTPResponse::ok(MetadataResponse {
+            metadata: entries.into_iter().map(MetadataEntry::from).collect(),
+        })),
+    )
+}
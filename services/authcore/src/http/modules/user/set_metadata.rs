@@ -0,0 +1,137 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    core::metadata::{self, MetadataError},
+    http::{
+        modules::{get_authenticated_user_id, get_request},
+        response::HTTPResponse,
+    },
+    state::AppState,
+};
+
+use super::metadata::MetadataEntry;
+
+#[derive(Deserialize)]
+pub struct SetMetadataRequest {
+    key: String,
+    value: serde_json::Value,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_authenticated_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // JSON values can only be given in a json body
+    let data: SetMetadataRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    let entry =
+        match metadata::set_by_user(&state, state.prisma(), user_id, data.key, data.value).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                let (status, response) = match e {
+                MetadataError::InvalidKey => (
+                    StatusCode::BAD_REQUEST,
+                    HTTPResponse::error(
+                        "InvalidKey",
+                        format!(
+                            "Key must be 1 to {} letters, digits, '_', '-' or '.'",
+                            metadata::MAX_KEY_LENGTH
+                        ),
+                        (),
+                    ),
+                ),
+                MetadataError::ValueTooLarge => (
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    HTTPResponse::error(
+                        "ValueTooLarge",
+                        format!("Value must be at most {} bytes", metadata::MAX_VALUE_SIZE),
+                        (),
+                    ),
+                ),
+                MetadataError::TooManyEntries => (
+                    StatusCode::CONFLICT,
+                    HTTPResponse::error(
+                        "TooManyEntries",
+                        format!(
+                            "A user can have at most {} metadata entries",
+                            metadata::MAX_ENTRIES
+                        ),
+                        (),
+                    ),
+                ),
+                MetadataError::TokenTooLarge => (
+                    StatusCode::CONFLICT,
+                    HTTPResponse::error(
+                        "TokenTooLarge",
+                        "The entry is projected into the access token and the value is too large"
+                            .to_owned(),
+                        (),
+                    ),
+                ),
+                MetadataError::ReadOnly => (
+                    StatusCode::FORBIDDEN,
+                    HTTPResponse::error(
+                        "ReadOnly",
+                        "The metadata entry can not be changed by the user".to_owned(),
+                        (),
+                    ),
+                ),
+                MetadataError::UserNotFound | MetadataError::NotFound => (
+                    StatusCode::NOT_FOUND,
+                    HTTPResponse::error("NotFound", "User not found".to_owned(), ()),
+                ),
+                MetadataError::Model(e) => {
+                    error!("Failed to set user metadata: {}", e);
+
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        HTTPResponse::error(
+                            "InternalServerError",
+                            "Failed to set metadata".to_owned(),
+                            (),
+                        ),
+                    )
+                }
+            };
+
+                return (status, Json(response));
+            }
+        };
+
+    (
+        StatusCode::OK,
+        Json(HTTPResponse::ok(MetadataEntry::from(entry))),
+    )
+}
@@ -1,12 +1,20 @@
-use axum::{routing::get, Router};
+use axum::{
+    routing::{get, post},
+    Router,
+};
 
 use crate::state::AppState;
 
 pub mod email;
 
+/// Verification of phone numbers with codes sent by SMS.
+pub mod sms;
+
 /// Router for handling routing within verification.
 pub fn router(state: AppState) -> Router {
     Router::new()
         .route("/email/:token", get(email::route))
+        .route("/sms/send", post(sms::send::route))
+        .route("/sms/confirm", post(sms::confirm::route))
         .with_state(state)
 }
@@ -0,0 +1,5 @@
+/// Module for sending a verification code to a user's phone number.
+pub mod send;
+
+/// Module for confirming a phone number with the code sent by SMS.
+pub mod confirm;
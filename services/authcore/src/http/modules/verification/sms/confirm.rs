@@ -0,0 +1,160 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    core::{otp::VerifyCodeError, sms},
+    http::{
+        modules::{get_enrolling_user_id, get_request},
+        response::HTTPResponse,
+    },
+    models::{error::ModelError, prisma::OneTimeCodePurpose, user::PhoneNumber},
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct ConfirmRequest {
+    code: String,
+}
+
+#[derive(Serialize)]
+pub struct ConfirmResponse {
+    /// The verified phone number, with most digits hidden
+    phone_number: String,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_enrolling_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    // Accept multiple different ways to give the data, url encoded form data or json body
+    let data: ConfirmRequest = match get_request(&parts, body).await {
+        Some(d) => d,
+        None => {
+            let response = HTTPResponse::error(
+                "BadRequest",
+                "Invalid content type, expected application/x-www-form-urlencoded or application/json".to_owned(),
+                (),
+            );
+
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    let mut phone_number = match PhoneNumber::get_by_user(state.prisma(), user_id).await {
+        Ok(phone_number) => phone_number,
+        Err(ModelError::NotFound) => {
+            let response =
+                HTTPResponse::error("BadRequest", "The user has no phone number".to_owned(), ());
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+        Err(_) => {
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to verify phone number".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    if phone_number.verified() {
+        let response = HTTPResponse::error(
+            "AlreadyVerified",
+            "The phone number is already verified".to_owned(),
+            (),
+        );
+        return (StatusCode::BAD_REQUEST, Json(response));
+    }
+
+    // Attempts are counted outside of a transaction so that failed attempts are always stored
+    match sms::verify_code(
+        &state,
+        state.prisma(),
+        &phone_number,
+        OneTimeCodePurpose::Verification,
+        &data.code,
+    )
+    .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            let response = HTTPResponse::error("Unauthorized", "Invalid code".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+        Err(VerifyCodeError::NotFound) => {
+            let response = HTTPResponse::error(
+                "Expired",
+                "No code has been sent or the code has expired, please request a new code"
+                    .to_owned(),
+                (),
+            );
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+        Err(VerifyCodeError::TooManyAttempts) => {
+            let response = HTTPResponse::error(
+                "TooManyAttempts",
+                "Too many attempts, please request a new code".to_owned(),
+                (),
+            );
+            return (StatusCode::TOO_MANY_REQUESTS, Json(response));
+        }
+        Err(VerifyCodeError::Overloaded) => {
+            let response = HTTPResponse::error(
+                "ServiceUnavailable",
+                "Too many requests are being processed, try again later".to_owned(),
+                (),
+            );
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(response));
+        }
+        Err(e) => {
+            error!("Failed to verify sms code: {}", e);
+
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to verify phone number".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    }
+
+    if phone_number.set_verified(state.prisma()).await.is_err() {
+        let response = HTTPResponse::error(
+            "InternalServerError",
+            "Failed to verify phone number".to_owned(),
+            (),
+        );
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+    }
+
+    info!(
+        "verified phone number {} of user {}",
+        phone_number.id(),
+        user_id
+    );
+
+    let response = ConfirmResponse {
+        phone_number: phone_number.masked(),
+    };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
@@ -0,0 +1,183 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use crypto::input::phone::normalize_phone_number;
+use hyper::{Body, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    core::{otp::SendCodeError, sms},
+    http::{
+        modules::{get_enrolling_user_id, get_request},
+        response::HTTPResponse,
+    },
+    models::{
+        prisma::OneTimeCodePurpose,
+        user::{PhoneNumber, User, UserWith},
+    },
+    state::AppState,
+};
+
+#[derive(Deserialize, Default)]
+pub struct SendRequest {
+    /// New phone number of the user, the code is sent to the current phone number if omitted
+    phone_number: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SendResponse {
+    /// The phone number the code was sent to, with most digits hidden
+    phone_number: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct RetryAfterResponse {
+    retry_after: i64,
+}
+
+pub async fn route(
+    ConnectInfo(_addr): ConnectInfo<SocketAddr>,
+    State(state): State<AppState>,
+    request: Request<Body>,
+) -> (StatusCode, Json<HTTPResponse>) {
+    let (parts, body) = request.into_parts();
+
+    // Get the authenticated user
+    let user_id = match get_enrolling_user_id(&state, &parts) {
+        Some(user_id) => user_id,
+        None => {
+            let response =
+                HTTPResponse::error("Unauthorized", "Invalid access token".to_owned(), ());
+            return (StatusCode::UNAUTHORIZED, Json(response));
+        }
+    };
+
+    let user = match User::get(state.prisma(), user_id, vec![UserWith::PhoneNumber]).await {
+        Ok(user) => user,
+        Err(_) => {
+            let response =
+                HTTPResponse::error("InternalServerError", "Failed to get user".to_owned(), ());
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    // The body is optional, without a phone number the code is sent to the current number
+    let data: SendRequest = get_request(&parts, body).await.unwrap_or_default();
+    let phone_number = match data.phone_number {
+        Some(phone_number) => {
+            let phone_number = match normalize_phone_number(&phone_number) {
+                Some(phone_number) => phone_number,
+                None => {
+                    let response = HTTPResponse::error(
+                        "InvalidPhoneNumber",
+                        "Invalid phone number, expected the international format, e.g. +46701234567"
+                            .to_owned(),
+                        (),
+                    );
+                    return (StatusCode::BAD_REQUEST, Json(response));
+                }
+            };
+
+            match PhoneNumber::set(
+                state.prisma(),
+                state.id_generator().next_snowflake().unwrap(),
+                user.id(),
+                user.application_id(),
+                phone_number,
+            )
+            .await
+            {
+                Ok(phone_number) => phone_number,
+                Err(e) => {
+                    error!("Failed to set phone number: {}", e);
+
+                    let response = HTTPResponse::error(
+                        "InternalServerError",
+                        "Failed to set phone number".to_owned(),
+                        (),
+                    );
+                    return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+                }
+            }
+        }
+        None => match user.phone_number() {
+            Some(phone_number) => phone_number.clone(),
+            None => {
+                let response = HTTPResponse::error(
+                    "BadRequest",
+                    "The user has no phone number".to_owned(),
+                    (),
+                );
+                return (StatusCode::BAD_REQUEST, Json(response));
+            }
+        },
+    };
+
+    if phone_number.verified() {
+        let response = HTTPResponse::error(
+            "AlreadyVerified",
+            "The phone number is already verified".to_owned(),
+            (),
+        );
+        return (StatusCode::BAD_REQUEST, Json(response));
+    }
+
+    // Codes are sent outside of a transaction so that the throttling always applies
+    let expires_at = match sms::send_code(
+        &state,
+        state.prisma(),
+        &phone_number,
+        OneTimeCodePurpose::Verification,
+    )
+    .await
+    {
+        Ok(expires_at) => expires_at,
+        Err(SendCodeError::TooSoon { retry_after }) => {
+            let response = HTTPResponse::error(
+                "TooManyRequests",
+                "A code was sent recently, please wait before requesting a new one".to_owned(),
+                RetryAfterResponse { retry_after },
+            );
+            return (StatusCode::TOO_MANY_REQUESTS, Json(response));
+        }
+        Err(SendCodeError::TooManyRequests) => {
+            let response = HTTPResponse::error(
+                "TooManyRequests",
+                "Too many codes have been sent to the phone number, please try again later"
+                    .to_owned(),
+                (),
+            );
+            return (StatusCode::TOO_MANY_REQUESTS, Json(response));
+        }
+        Err(SendCodeError::Overloaded) => {
+            let response = HTTPResponse::error(
+                "ServiceUnavailable",
+                "Too many requests are being processed, try again later".to_owned(),
+                (),
+            );
+            return (StatusCode::SERVICE_UNAVAILABLE, Json(response));
+        }
+        Err(e) => {
+            error!("Failed to send sms verification code: {}", e);
+
+            let response = HTTPResponse::error(
+                "InternalServerError",
+                "Failed to send verification code".to_owned(),
+                (),
+            );
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    let response = SendResponse {
+        phone_number: phone_number.masked(),
+        expires_at,
+    };
+    (StatusCode::OK, Json(HTTPResponse::ok(response)))
+}
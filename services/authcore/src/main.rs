@@ -45,12 +45,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     console_subscriber::init();
     info!("starting service");
 
+    // Run a maintenance command instead of the service if one is given, commands that do not use
+    // the database run without connecting to it
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.split_first();
+    if let Some((command, args)) = command {
+        if let Some(result) = run_offline_command(command, args) {
+            return result;
+        }
+    }
+
     // Create a shared app state
     let prisma = models::PrismaClient::_builder()
         .with_url(DATABASE_URL.to_string())
         .build()
         .await?;
 
+    if let Some((command, args)) = command {
+        return run_command(&prisma, command, args).await;
+    }
+
     let id_generator = crypto::snowflake::SnowflakeGenerator::new(0, 0);
     let app_state = State::new(
         prisma,
@@ -63,6 +77,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let app_state = Arc::new(app_state);
 
+    // Clean up abandoned TOTP enrolments in the background
+    tokio::spawn(core::totp::delete_expired_enrolments_task(
+        app_state.clone(),
+    ));
+
+    // Remove the second factors of users whose recovery waiting period is over
+    tokio::spawn(core::mfa_recovery::complete_due_recoveries_task(
+        app_state.clone(),
+    ));
+
     // If we are running in debug mode, bind to localhost
     let ip = if cfg!(debug_assertions) {
         tracing::info!("running in debug mode");
@@ -92,6 +116,121 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Runs a maintenance command that uses the database.
+///
+/// * `reencrypt-totp-secrets`: Re-encrypt TOTP secrets with the primary data-encryption key,
+///   run after adding a new key to `DATA_ENCRYPTION_KEYS`.
+/// * `import-users <application id> <file> [--dry-run]`: Import the users of another identity
+///   system from a JSON lines (`.jsonl`) or CSV (`.csv`) file, see [`core::user_import`].
+async fn run_command(
+    prisma: &models::PrismaClient,
+    command: &str,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        "reencrypt-totp-secrets" => {
+            let count = models::user::totp::TOTP::reencrypt_secrets(prisma, 100).await?;
+            info!("re-encrypted {} totp secrets", count);
+        }
+        "import-users" => {
+            let (application_id, path, dry_run) = match args {
+                [application_id, path] => (application_id, path, false),
+                [application_id, path, flag] if flag == "--dry-run" => (application_id, path, true),
+                _ => return Err("usage: import-users <application id> <file> [--dry-run]".into()),
+            };
+            let application_id: crypto::snowflake::Snowflake = application_id
+                .clone()
+                .try_into()
+                .map_err(|_| "invalid application id")?;
+
+            let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+            let users: Box<dyn Iterator<Item = _>> = if path.ends_with(".csv") {
+                Box::new(core::user_import::read_csv(reader))
+            } else {
+                Box::new(core::user_import::read_jsonl(reader))
+            };
+
+            // A different process ID than the service, which may be running at the same time
+            let id_generator = crypto::snowflake::SnowflakeGenerator::new(0, 1);
+
+            let mut importer =
+                core::user_import::Importer::new(prisma, application_id, dry_run).await?;
+            for user in users {
+                importer.import(&id_generator, prisma, user).await?;
+            }
+
+            let report = importer.finish();
+            for error in report.errors() {
+                println!("row {} ({}): {}", error.row(), error.email(), error.error());
+            }
+            println!(
+                "{} of {} users {}",
+                report.imported(),
+                report.rows(),
+                if dry_run { "valid" } else { "imported" }
+            );
+        }
+        _ => return Err(format!("unknown command: {}", command).into()),
+    }
+
+    Ok(())
+}
+
+/// Runs a maintenance command that does not use the database, returns `None` for other commands.
+///
+/// * `benchmark-password-hashing [target milliseconds]`: Recommend argon2 costs for which hashing a
+///   password on this host takes at most the target, 500 milliseconds by default.
+/// * `build-breached-password-index <input> <output>`: Build the index of the breached password
+///   dataset from a list of SHA-1 hashes, see [`crypto::input::breached`].
+fn run_offline_command(
+    command: &str,
+    args: &[String],
+) -> Option<Result<(), Box<dyn std::error::Error>>> {
+    let result = match command {
+        "benchmark-password-hashing" => benchmark_password_hashing(args),
+        "build-breached-password-index" => build_breached_password_index(args),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+fn benchmark_password_hashing(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let target = match args.first() {
+        Some(target) => target.parse().map_err(|_| "invalid target")?,
+        None => 500,
+    };
+    let p_cost = state::CONFIG.password_hashing().params().p_cost();
+
+    let (params, duration) =
+        crypto::password::benchmark(std::time::Duration::from_millis(target), p_cost)?;
+
+    println!(
+        "hashing takes {} ms with the recommended costs",
+        duration.as_millis()
+    );
+    println!("PASSWORD_HASH_MEMORY_COST={}", params.m_cost());
+    println!("PASSWORD_HASH_ITERATIONS={}", params.t_cost());
+    println!("PASSWORD_HASH_PARALLELISM={}", params.p_cost());
+
+    Ok(())
+}
+
+fn build_breached_password_index(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (input, output) = match args {
+        [input, output] => (input, output),
+        _ => return Err("usage: build-breached-password-index <input> <output>".into()),
+    };
+
+    let hashes = crypto::input::breached::read_hashes(input)?;
+    let writer = std::io::BufWriter::new(std::fs::File::create(output)?);
+    let count = crypto::input::breached::write_index(hashes, writer)?;
+
+    println!("wrote {} hashes to {}", count, output);
+
+    Ok(())
+}
+
 async fn start_grpc(
     app_state: AppState,
     grpc_addr: SocketAddr,
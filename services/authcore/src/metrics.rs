@@ -1,12 +1,30 @@
+use tracing::info;
+
 use crate::state::AppState;
 
+/// How often the metrics are logged.
+const INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 /// MetricsError enumerates the possible errors that can occur
 /// when running the metrics thread.
 #[derive(Debug, thiserror::Error)]
 pub enum MetricsError {}
 
-pub async fn run(_state: AppState) -> Result<(), MetricsError> {
-    tokio::time::sleep(tokio::time::Duration::from_secs(60 * 60 * 24 * 7)).await;
+pub async fn run(state: AppState) -> Result<(), MetricsError> {
+    let mut interval = tokio::time::interval(INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
 
-    Ok(())
+        let hashing = state.hashing_pool().metrics().take();
+        info!(
+            completed = hashing.completed,
+            rejected = hashing.rejected,
+            pending = state.hashing_pool().pending(),
+            average_queue_time_ms = hashing.average_queue_time.as_millis() as u64,
+            max_queue_time_ms = hashing.max_queue_time.as_millis() as u64,
+            "password hashing"
+        );
+    }
 }
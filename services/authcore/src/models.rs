@@ -8,4 +8,5 @@ pub use prisma::PrismaClient;
 
 pub mod application;
 pub mod error;
+pub mod role;
 pub mod user;
@@ -1,19 +1,23 @@
-use chrono::{DateTime, Utc};
-use crypto::{input::password::PasswordRequirements, snowflake::Snowflake};
+use chrono::{DateTime, Duration, Utc};
+use crypto::{input::password::PasswordRequirements, snowflake::Snowflake, totp::Algorithm};
 use prisma_client_rust::QueryError;
 
 use super::{error::ModelError, PrismaClient};
 
-pub use super::prisma::EmailVerificationType;
+pub use super::prisma::{EmailVerificationType, MfaPolicy};
 
 #[derive(Debug, Clone)]
 pub struct ReplicatedApplication {
     application_id: Snowflake,
 
+    domain_name: String,
+    name: String,
+
     basic_auth_enabled: bool,
 
     basic_auth_config: Option<BasicAuthConfig>,
     verification_config: Option<VerificationConfig>,
+    mfa_config: Option<MFAConfig>,
 
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
@@ -24,20 +28,27 @@ impl ReplicatedApplication {
         client: &PrismaClient,
         application_id: Snowflake,
         domain_name: String,
+        name: String,
         basic_auth_config_builder: BasicAuthConfigBuilder,
         verification_config_builder: VerificationConfigBuilder,
+        mfa_config_builder: MFAConfigBuilder,
     ) -> Result<Self, QueryError> {
-        let (app, basic_auth_cfg, verification_cfg): (
+        let (app, basic_auth_cfg, verification_cfg, mfa_cfg): (
             super::prisma::replicated_application::Data,
             BasicAuthConfig,
             VerificationConfig,
+            MFAConfig,
         ) = client
             ._transaction()
             .run::<QueryError, _, _, _>(|client| async move {
                 // Insert replicated application
                 let d1_app = client
                     .replicated_application()
-                    .create(application_id.to_id_signed(), domain_name, vec![])
+                    .create(
+                        application_id.to_id_signed(),
+                        domain_name,
+                        vec![super::prisma::replicated_application::name::set(name)],
+                    )
                     .exec()
                     .await?;
 
@@ -51,15 +62,21 @@ impl ReplicatedApplication {
                     .build(&client, application_id)
                     .await?;
 
-                Ok((d1_app, basic_auth_config, verification_config))
+                // Insert mfa config
+                let mfa_config = mfa_config_builder.build(&client, application_id).await?;
+
+                Ok((d1_app, basic_auth_config, verification_config, mfa_config))
             })
             .await?;
 
         let app = Self {
             application_id,
+            domain_name: app.domain_name,
+            name: app.name,
             basic_auth_enabled: app.basic_auth_enabled,
             basic_auth_config: Some(basic_auth_cfg),
             verification_config: Some(verification_cfg),
+            mfa_config: Some(mfa_cfg),
             created_at: app.created_at.into(),
             updated_at: app.updated_at.into(),
         };
@@ -110,6 +127,7 @@ impl ReplicatedApplication {
             ])
             .with(super::prisma::replicated_application::basic_auth_config::fetch())
             .with(super::prisma::replicated_application::verification_config::fetch())
+            .with(super::prisma::replicated_application::mfa_config::fetch())
             .exec()
             .await?;
 
@@ -123,6 +141,19 @@ impl ReplicatedApplication {
         self.application_id
     }
 
+    pub fn domain_name(&self) -> &str {
+        self.domain_name.as_ref()
+    }
+
+    /// Display name of the application, falls back to the domain name if no name is set.
+    pub fn name(&self) -> &str {
+        if self.name.is_empty() {
+            self.domain_name.as_ref()
+        } else {
+            self.name.as_ref()
+        }
+    }
+
     pub fn basic_auth_enabled(&self) -> bool {
         self.basic_auth_enabled
     }
@@ -181,6 +212,116 @@ impl ReplicatedApplication {
         }
     }
 
+    pub async fn mfa_config(&mut self, client: &PrismaClient) -> Result<MFAConfig, QueryError> {
+        // If config is present, unwrap it and return
+        if let Some(cfg) = &self.mfa_config {
+            return Ok(cfg.clone());
+        }
+
+        // Otherwise fetch config from database
+        let cfg = client
+            .mfa_config()
+            .find_first(vec![super::prisma::mfa_config::application_id::equals(
+                self.application_id.to_id_signed(),
+            )])
+            .exec()
+            .await?;
+
+        // Applications created before the config existed get the default config
+        let cfg = match cfg {
+            Some(cfg) => MFAConfig::from(cfg),
+            None => {
+                MFAConfig::builder()
+                    .build(client, self.application_id)
+                    .await?
+            }
+        };
+
+        // Update config
+        self.mfa_config = Some(cfg.clone());
+
+        Ok(cfg)
+    }
+
+    /// Update the MFA policy of the application. The grace period of a required policy starts
+    /// when it becomes required, changing only the grace period keeps the start.
+    pub async fn set_mfa_policy(
+        &mut self,
+        client: &PrismaClient,
+        mfa_policy: MfaPolicy,
+        mfa_grace_period_days: u32,
+    ) -> Result<MFAConfig, QueryError> {
+        let current = self.mfa_config(client).await?;
+
+        let mfa_required_at = match mfa_policy {
+            MfaPolicy::Required => Some(current.mfa_required_at().unwrap_or_else(Utc::now)),
+            _ => None,
+        };
+
+        let data = client
+            .mfa_config()
+            .update(
+                super::prisma::mfa_config::application_id::equals(
+                    self.application_id.to_id_signed(),
+                ),
+                vec![
+                    super::prisma::mfa_config::mfa_policy::set(mfa_policy),
+                    super::prisma::mfa_config::mfa_grace_period_days::set(
+                        mfa_grace_period_days as i32,
+                    ),
+                    super::prisma::mfa_config::mfa_required_at::set(
+                        mfa_required_at.map(|required_at| required_at.into()),
+                    ),
+                ],
+            )
+            .exec()
+            .await?;
+
+        let cfg = MFAConfig::from(data);
+
+        // Update config
+        self.mfa_config = Some(cfg.clone());
+
+        Ok(cfg)
+    }
+
+    /// Update the basic auth config of the application, only the values set on the builder are
+    /// changed.
+    pub async fn set_basic_auth_config(
+        &mut self,
+        client: &PrismaClient,
+        basic_auth_config_builder: BasicAuthConfigBuilder,
+    ) -> Result<BasicAuthConfig, QueryError> {
+        let cfg = basic_auth_config_builder
+            .update(client, self.application_id)
+            .await?;
+
+        // Update config
+        self.basic_auth_config = Some(cfg.clone());
+
+        Ok(cfg)
+    }
+
+    /// Update the MFA config of the application, only the values set on the builder are changed.
+    /// The grace period of a required policy starts when it becomes required.
+    pub async fn set_mfa_config(
+        &mut self,
+        client: &PrismaClient,
+        mfa_config_builder: MFAConfigBuilder,
+    ) -> Result<MFAConfig, QueryError> {
+        // Creates the default config for applications created before the config existed
+        let current = self.mfa_config(client).await?;
+
+        let cfg = mfa_config_builder
+            .update(client, self.application_id, current.mfa_required_at())
+            .await?;
+
+        // Update config
+        self.mfa_config = Some(cfg.clone());
+
+        Ok(cfg)
+    }
+
     pub fn created_at(&self) -> DateTime<Utc> {
         self.created_at
     }
@@ -205,11 +346,19 @@ impl From<super::prisma::replicated_application::Data> for ReplicatedApplication
             None => None,
         };
 
+        let mfa_config = match value.mfa_config {
+            Some(mfa_config) => mfa_config.map(|mfa_config| MFAConfig::from(*mfa_config)),
+            None => None,
+        };
+
         Self {
             application_id: value.application_id.try_into().unwrap(),
+            domain_name: value.domain_name,
+            name: value.name,
             basic_auth_enabled: value.basic_auth_enabled,
             basic_auth_config,
             verification_config,
+            mfa_config,
             created_at: value.created_at.into(),
             updated_at: value.updated_at.into(),
         }
@@ -232,6 +381,10 @@ pub struct BasicAuthConfig {
     min_numbers: u8,
     min_symbols: u8,
 
+    check_breached_passwords: bool,
+    password_history_size: u8,
+    password_max_age_days: u32,
+
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
@@ -289,6 +442,29 @@ impl BasicAuthConfig {
         self.zxcvbn_minimum_score
     }
 
+    /// Whether passwords are checked against the breached password dataset.
+    pub fn check_breached_passwords(&self) -> bool {
+        self.check_breached_passwords
+    }
+
+    /// The number of recent passwords, including the current one, a user can not change back to.
+    pub fn password_history_size(&self) -> u8 {
+        self.password_history_size
+    }
+
+    pub fn password_max_age_days(&self) -> u32 {
+        self.password_max_age_days
+    }
+
+    /// How long a password can be used before it has to be changed, `None` if passwords do not
+    /// expire.
+    pub fn password_max_age(&self) -> Option<Duration> {
+        match self.password_max_age_days {
+            0 => None,
+            days => Some(Duration::days(days.into())),
+        }
+    }
+
     pub fn as_password_requirements_config(&self) -> PasswordRequirements {
         PasswordRequirements {
             min_length: self.min_password_length,
@@ -301,6 +477,8 @@ impl BasicAuthConfig {
             min_symbols: self.min_symbols,
 
             min_zxcvbn_score: self.zxcvbn_minimum_score,
+
+            check_breached: self.check_breached_passwords,
         }
     }
 }
@@ -323,6 +501,10 @@ impl From<super::prisma::basic_auth_config::Data> for BasicAuthConfig {
             min_numbers: value.min_numbers.try_into().unwrap(),
             min_symbols: value.min_symbols.try_into().unwrap(),
 
+            check_breached_passwords: value.check_breached_passwords,
+            password_history_size: value.password_history_size.try_into().unwrap(),
+            password_max_age_days: value.password_max_age_days.try_into().unwrap(),
+
             created_at: value.created_at.into(),
             updated_at: value.updated_at.into(),
         }
@@ -348,6 +530,15 @@ pub struct BasicAuthConfigBuilder {
     password_strength_check: Option<BasicAuthConfigBuilderZxcvbn>,
 
     enable_strict_password: Option<BasicAuthConfigBuilderStrict>,
+
+    /// Defaults to true
+    check_breached_passwords: Option<bool>,
+
+    /// Defaults to 0, passwords can be reused
+    password_history_size: Option<u8>,
+
+    /// Defaults to 0, passwords do not expire
+    password_max_age_days: Option<u32>,
 }
 
 impl BasicAuthConfigBuilder {
@@ -359,6 +550,12 @@ impl BasicAuthConfigBuilder {
             password_strength_check: None,
 
             enable_strict_password: None,
+
+            check_breached_passwords: None,
+
+            password_history_size: None,
+
+            password_max_age_days: None,
         }
     }
 
@@ -395,79 +592,125 @@ impl BasicAuthConfigBuilder {
         self
     }
 
+    pub fn check_breached_passwords(&mut self, check_breached_passwords: bool) -> &mut Self {
+        self.check_breached_passwords = Some(check_breached_passwords);
+        self
+    }
+
+    pub fn password_history_size(&mut self, password_history_size: u8) -> &mut Self {
+        self.password_history_size = Some(password_history_size);
+        self
+    }
+
+    pub fn password_max_age_days(&mut self, password_max_age_days: u32) -> &mut Self {
+        self.password_max_age_days = Some(password_max_age_days);
+        self
+    }
+
     pub async fn build(
         self,
         client: &PrismaClient,
         application_id: Snowflake,
     ) -> Result<BasicAuthConfig, QueryError> {
-        let mut create_params = Vec::new();
+        let data = client
+            .basic_auth_config()
+            .create(
+                super::prisma::replicated_application::application_id::equals(
+                    application_id.to_id_signed(),
+                ),
+                self.params(),
+            )
+            .exec()
+            .await?;
+
+        Ok(BasicAuthConfig::from(data))
+    }
+
+    /// Update the config of an existing application, only the values set on the builder are
+    /// changed.
+    pub async fn update(
+        self,
+        client: &PrismaClient,
+        application_id: Snowflake,
+    ) -> Result<BasicAuthConfig, QueryError> {
+        let data = client
+            .basic_auth_config()
+            .update(
+                super::prisma::basic_auth_config::application_id::equals(
+                    application_id.to_id_signed(),
+                ),
+                self.params(),
+            )
+            .exec()
+            .await?;
+
+        Ok(BasicAuthConfig::from(data))
+    }
+
+    fn params(&self) -> Vec<super::prisma::basic_auth_config::SetParam> {
+        let mut params = Vec::new();
 
         if let Some(min_password_length) = self.min_password_length {
-            create_params.push(super::prisma::basic_auth_config::min_password_length::set(
+            params.push(super::prisma::basic_auth_config::min_password_length::set(
                 min_password_length.into(),
             ));
         }
 
         if let Some(max_password_length) = self.max_password_length {
-            create_params.push(super::prisma::basic_auth_config::max_password_length::set(
+            params.push(super::prisma::basic_auth_config::max_password_length::set(
                 max_password_length.into(),
             ));
         }
 
-        if let Some(enable_strict_password) = self.enable_strict_password {
-            create_params.push(super::prisma::basic_auth_config::enable_strict_password::set(true));
-            create_params.push(super::prisma::basic_auth_config::min_uppercase::set(
+        if let Some(enable_strict_password) = &self.enable_strict_password {
+            params.push(super::prisma::basic_auth_config::enable_strict_password::set(true));
+            params.push(super::prisma::basic_auth_config::min_uppercase::set(
                 enable_strict_password.min_uppercase.into(),
             ));
-            create_params.push(super::prisma::basic_auth_config::min_lowercase::set(
+            params.push(super::prisma::basic_auth_config::min_lowercase::set(
                 enable_strict_password.min_lowercase.into(),
             ));
-            create_params.push(super::prisma::basic_auth_config::min_numbers::set(
+            params.push(super::prisma::basic_auth_config::min_numbers::set(
                 enable_strict_password.min_numbers.into(),
             ));
-            create_params.push(super::prisma::basic_auth_config::min_symbols::set(
+            params.push(super::prisma::basic_auth_config::min_symbols::set(
                 enable_strict_password.min_symbols.into(),
             ));
         }
 
-        if let Some(password_strength_check) = self.password_strength_check {
-            create_params
+        if let Some(password_strength_check) = &self.password_strength_check {
+            params
                 .push(super::prisma::basic_auth_config::enable_password_strength_check::set(true));
-            create_params.push(super::prisma::basic_auth_config::zxcvbn_min_score::set(
+            params.push(super::prisma::basic_auth_config::zxcvbn_min_score::set(
                 password_strength_check.zxcvbn_minimum_score.into(),
             ));
         }
 
-        let data = client
-            .basic_auth_config()
-            .create(
-                super::prisma::replicated_application::application_id::equals(
-                    application_id.to_id_signed(),
+        if let Some(check_breached_passwords) = self.check_breached_passwords {
+            params.push(
+                super::prisma::basic_auth_config::check_breached_passwords::set(
+                    check_breached_passwords,
                 ),
-                create_params,
-            )
-            .exec()
-            .await?;
-
-        Ok(BasicAuthConfig {
-            application_id: data.application_id.try_into().unwrap(),
-
-            enable_password_strength_check: data.enable_password_strength_check,
-            zxcvbn_minimum_score: data.zxcvbn_min_score.try_into().unwrap(),
-
-            min_password_length: data.min_password_length.try_into().unwrap(),
-            max_password_length: data.max_password_length.try_into().unwrap(),
+            );
+        }
 
-            enable_strict_password: data.enable_strict_password,
+        if let Some(password_history_size) = self.password_history_size {
+            params.push(
+                super::prisma::basic_auth_config::password_history_size::set(
+                    password_history_size.into(),
+                ),
+            );
+        }
 
-            min_uppercase: data.min_uppercase.try_into().unwrap(),
-            min_lowercase: data.min_lowercase.try_into().unwrap(),
-            min_numbers: data.min_numbers.try_into().unwrap(),
-            min_symbols: data.min_symbols.try_into().unwrap(),
+        if let Some(password_max_age_days) = self.password_max_age_days {
+            params.push(
+                super::prisma::basic_auth_config::password_max_age_days::set(
+                    password_max_age_days.try_into().unwrap_or(i32::MAX),
+                ),
+            );
+        }
 
-            created_at: data.created_at.into(),
-            updated_at: data.updated_at.into(),
-        })
+        params
     }
 }
 
@@ -485,6 +728,9 @@ pub struct VerificationConfig {
     expires_after: u32,
     email_verification_type: EmailVerificationType,
 
+    invitation_url: Option<String>,
+    invitation_expires_after: u32,
+
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
@@ -517,6 +763,16 @@ impl VerificationConfig {
     pub fn email_verification_type(&self) -> &EmailVerificationType {
         &self.email_verification_type
     }
+
+    /// Page of the application where invited users set their password.
+    pub fn invitation_url(&self) -> Option<&String> {
+        self.invitation_url.as_ref()
+    }
+
+    /// Lifetime of an invitation in seconds.
+    pub fn invitation_expires_after(&self) -> u32 {
+        self.invitation_expires_after
+    }
 }
 
 impl From<super::prisma::verification_config::Data> for VerificationConfig {
@@ -528,6 +784,9 @@ impl From<super::prisma::verification_config::Data> for VerificationConfig {
             expires_after: value.expires_after.try_into().unwrap(),
             email_verification_type: value.email_verification_type,
 
+            invitation_url: value.invitation_url,
+            invitation_expires_after: value.invitation_expires_after.try_into().unwrap(),
+
             created_at: value.created_at.into(),
             updated_at: value.updated_at.into(),
         }
@@ -538,6 +797,8 @@ pub struct VerificationConfigBuilder {
     email_redirect_url: Option<String>,
     expires_after: Option<u32>,
     email_verification_type: Option<EmailVerificationType>,
+    invitation_url: Option<String>,
+    invitation_expires_after: Option<u32>,
 }
 
 impl VerificationConfigBuilder {
@@ -546,6 +807,8 @@ impl VerificationConfigBuilder {
             email_redirect_url: None,
             expires_after: None,
             email_verification_type: None,
+            invitation_url: None,
+            invitation_expires_after: None,
         }
     }
 
@@ -567,6 +830,16 @@ impl VerificationConfigBuilder {
         self
     }
 
+    pub fn invitation_url(&mut self, invitation_url: String) -> &mut Self {
+        self.invitation_url = Some(invitation_url);
+        self
+    }
+
+    pub fn invitation_expires_after(&mut self, invitation_expires_after: u32) -> &mut Self {
+        self.invitation_expires_after = Some(invitation_expires_after);
+        self
+    }
+
     pub async fn build(
         self,
         client: &PrismaClient,
@@ -594,6 +867,20 @@ impl VerificationConfigBuilder {
             );
         }
 
+        if let Some(invitation_url) = self.invitation_url {
+            create_params.push(super::prisma::verification_config::invitation_url::set(
+                Some(invitation_url),
+            ));
+        }
+
+        if let Some(invitation_expires_after) = self.invitation_expires_after {
+            create_params.push(
+                super::prisma::verification_config::invitation_expires_after::set(
+                    invitation_expires_after as i32,
+                ),
+            );
+        }
+
         let data = client
             .verification_config()
             .create(
@@ -612,6 +899,9 @@ impl VerificationConfigBuilder {
             expires_after: data.expires_after.try_into().unwrap(),
             email_verification_type: data.email_verification_type,
 
+            invitation_url: data.invitation_url,
+            invitation_expires_after: data.invitation_expires_after.try_into().unwrap(),
+
             created_at: data.created_at.into(),
             updated_at: data.updated_at.into(),
         })
@@ -623,3 +913,347 @@ impl Default for VerificationConfigBuilder {
         Self::new()
     }
 }
+
+/// Number of digits of TOTP codes if an application does not configure it.
+pub const DEFAULT_TOTP_DIGITS: u32 = 6;
+
+/// Interval of TOTP codes in seconds if an application does not configure it.
+pub const DEFAULT_TOTP_INTERVAL: u32 = 30;
+
+/// Number of days a device stays trusted if an application does not configure it.
+pub const DEFAULT_TRUSTED_DEVICE_DAYS: u32 = 30;
+
+/// Number of days before a recovery of a second factor completes if an application does not
+/// configure it.
+pub const DEFAULT_MFA_RECOVERY_WAITING_DAYS: u32 = 7;
+
+#[derive(Debug, Clone)]
+pub struct MFAConfig {
+    application_id: Snowflake,
+
+    totp_algorithm: Algorithm,
+    totp_digits: u32,
+    totp_interval: u32,
+
+    email_otp_enabled: bool,
+
+    trusted_devices_enabled: bool,
+    trusted_device_days: u32,
+
+    mfa_policy: MfaPolicy,
+    mfa_grace_period_days: u32,
+    mfa_required_at: Option<DateTime<Utc>>,
+
+    mfa_recovery_waiting_days: u32,
+
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl MFAConfig {
+    pub fn builder() -> MFAConfigBuilder {
+        MFAConfigBuilder::new()
+    }
+
+    pub fn application_id(&self) -> Snowflake {
+        self.application_id
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    /// Algorithm of newly enrolled TOTP authenticators.
+    pub fn totp_algorithm(&self) -> Algorithm {
+        self.totp_algorithm
+    }
+
+    /// Number of digits of newly enrolled TOTP authenticators.
+    pub fn totp_digits(&self) -> u32 {
+        self.totp_digits
+    }
+
+    /// Interval (in seconds) of newly enrolled TOTP authenticators.
+    pub fn totp_interval(&self) -> u32 {
+        self.totp_interval
+    }
+
+    /// Whether users can receive one-time codes by email as a second factor.
+    pub fn email_otp_enabled(&self) -> bool {
+        self.email_otp_enabled
+    }
+
+    /// Whether users can trust a device to skip the second factor.
+    pub fn trusted_devices_enabled(&self) -> bool {
+        self.trusted_devices_enabled
+    }
+
+    /// Number of days a trusted device skips the second factor.
+    pub fn trusted_device_days(&self) -> u32 {
+        self.trusted_device_days
+    }
+
+    /// Whether users can, or have to, enrol a second factor.
+    pub fn mfa_policy(&self) -> MfaPolicy {
+        self.mfa_policy
+    }
+
+    /// Number of days users without a second factor can still sign in normally after the policy
+    /// became required.
+    pub fn mfa_grace_period_days(&self) -> u32 {
+        self.mfa_grace_period_days
+    }
+
+    /// When the policy became required.
+    pub fn mfa_required_at(&self) -> Option<DateTime<Utc>> {
+        self.mfa_required_at
+    }
+
+    /// Number of days between a user starting the recovery of a lost second factor and its
+    /// removal.
+    pub fn mfa_recovery_waiting_days(&self) -> u32 {
+        self.mfa_recovery_waiting_days
+    }
+
+    /// Whether users can enrol a second factor.
+    pub fn enrolment_allowed(&self) -> bool {
+        self.mfa_policy != MfaPolicy::Off
+    }
+
+    /// When a user created at the given time has to have enrolled a second factor, `None` if the
+    /// policy is not required.
+    ///
+    /// The grace period starts when the policy became required, or when the user was created if
+    /// that was later.
+    pub fn enrolment_deadline(&self, user_created_at: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.mfa_policy != MfaPolicy::Required {
+            return None;
+        }
+
+        let start = match self.mfa_required_at {
+            Some(required_at) => required_at.max(user_created_at),
+            None => user_created_at,
+        };
+
+        Some(start + Duration::days(self.mfa_grace_period_days.into()))
+    }
+}
+
+impl From<super::prisma::mfa_config::Data> for MFAConfig {
+    fn from(value: super::prisma::mfa_config::Data) -> Self {
+        Self {
+            application_id: value.application_id.try_into().unwrap(),
+
+            totp_algorithm: value.totp_algorithm.into(),
+            totp_digits: value.totp_digits.try_into().unwrap(),
+            totp_interval: value.totp_interval.try_into().unwrap(),
+
+            email_otp_enabled: value.email_otp_enabled,
+
+            trusted_devices_enabled: value.trusted_devices_enabled,
+            trusted_device_days: value.trusted_device_days.try_into().unwrap(),
+
+            mfa_policy: value.mfa_policy,
+            mfa_grace_period_days: value.mfa_grace_period_days.try_into().unwrap(),
+            mfa_required_at: value.mfa_required_at.map(|required_at| required_at.into()),
+
+            mfa_recovery_waiting_days: value.mfa_recovery_waiting_days.try_into().unwrap(),
+
+            created_at: value.created_at.into(),
+            updated_at: value.updated_at.into(),
+        }
+    }
+}
+
+pub struct MFAConfigBuilder {
+    totp_algorithm: Option<Algorithm>,
+    totp_digits: Option<u32>,
+    totp_interval: Option<u32>,
+    email_otp_enabled: Option<bool>,
+    trusted_devices_enabled: Option<bool>,
+    trusted_device_days: Option<u32>,
+    mfa_policy: Option<MfaPolicy>,
+    mfa_grace_period_days: Option<u32>,
+    mfa_recovery_waiting_days: Option<u32>,
+}
+
+impl MFAConfigBuilder {
+    pub fn new() -> Self {
+        Self {
+            totp_algorithm: None,
+            totp_digits: None,
+            totp_interval: None,
+            email_otp_enabled: None,
+            trusted_devices_enabled: None,
+            trusted_device_days: None,
+            mfa_policy: None,
+            mfa_grace_period_days: None,
+            mfa_recovery_waiting_days: None,
+        }
+    }
+
+    pub fn totp_algorithm(&mut self, totp_algorithm: Algorithm) -> &mut Self {
+        self.totp_algorithm = Some(totp_algorithm);
+        self
+    }
+
+    pub fn totp_digits(&mut self, totp_digits: u32) -> &mut Self {
+        self.totp_digits = Some(totp_digits);
+        self
+    }
+
+    pub fn totp_interval(&mut self, totp_interval: u32) -> &mut Self {
+        self.totp_interval = Some(totp_interval);
+        self
+    }
+
+    pub fn email_otp_enabled(&mut self, email_otp_enabled: bool) -> &mut Self {
+        self.email_otp_enabled = Some(email_otp_enabled);
+        self
+    }
+
+    pub fn trusted_devices_enabled(&mut self, trusted_devices_enabled: bool) -> &mut Self {
+        self.trusted_devices_enabled = Some(trusted_devices_enabled);
+        self
+    }
+
+    pub fn trusted_device_days(&mut self, trusted_device_days: u32) -> &mut Self {
+        self.trusted_device_days = Some(trusted_device_days);
+        self
+    }
+
+    pub fn mfa_policy(&mut self, mfa_policy: MfaPolicy) -> &mut Self {
+        self.mfa_policy = Some(mfa_policy);
+        self
+    }
+
+    pub fn mfa_grace_period_days(&mut self, mfa_grace_period_days: u32) -> &mut Self {
+        self.mfa_grace_period_days = Some(mfa_grace_period_days);
+        self
+    }
+
+    pub fn mfa_recovery_waiting_days(&mut self, mfa_recovery_waiting_days: u32) -> &mut Self {
+        self.mfa_recovery_waiting_days = Some(mfa_recovery_waiting_days);
+        self
+    }
+
+    pub async fn build(
+        self,
+        client: &PrismaClient,
+        application_id: Snowflake,
+    ) -> Result<MFAConfig, QueryError> {
+        let data = client
+            .mfa_config()
+            .create(
+                super::prisma::replicated_application::application_id::equals(
+                    application_id.to_id_signed(),
+                ),
+                self.params(None),
+            )
+            .exec()
+            .await?;
+
+        Ok(MFAConfig::from(data))
+    }
+
+    /// Update the config of an existing application, only the values set on the builder are
+    /// changed. `mfa_required_at` is the time the current policy became required, which is kept
+    /// if the policy stays required.
+    pub async fn update(
+        self,
+        client: &PrismaClient,
+        application_id: Snowflake,
+        mfa_required_at: Option<DateTime<Utc>>,
+    ) -> Result<MFAConfig, QueryError> {
+        let data = client
+            .mfa_config()
+            .update(
+                super::prisma::mfa_config::application_id::equals(application_id.to_id_signed()),
+                self.params(mfa_required_at),
+            )
+            .exec()
+            .await?;
+
+        Ok(MFAConfig::from(data))
+    }
+
+    fn params(
+        &self,
+        mfa_required_at: Option<DateTime<Utc>>,
+    ) -> Vec<super::prisma::mfa_config::SetParam> {
+        let mut params = Vec::new();
+
+        if let Some(totp_algorithm) = self.totp_algorithm {
+            params.push(super::prisma::mfa_config::totp_algorithm::set(
+                totp_algorithm.into(),
+            ));
+        }
+
+        if let Some(totp_digits) = self.totp_digits {
+            params.push(super::prisma::mfa_config::totp_digits::set(
+                totp_digits as i32,
+            ));
+        }
+
+        if let Some(totp_interval) = self.totp_interval {
+            params.push(super::prisma::mfa_config::totp_interval::set(
+                totp_interval as i32,
+            ));
+        }
+
+        if let Some(email_otp_enabled) = self.email_otp_enabled {
+            params.push(super::prisma::mfa_config::email_otp_enabled::set(
+                email_otp_enabled,
+            ));
+        }
+
+        if let Some(trusted_devices_enabled) = self.trusted_devices_enabled {
+            params.push(super::prisma::mfa_config::trusted_devices_enabled::set(
+                trusted_devices_enabled,
+            ));
+        }
+
+        if let Some(trusted_device_days) = self.trusted_device_days {
+            params.push(super::prisma::mfa_config::trusted_device_days::set(
+                trusted_device_days as i32,
+            ));
+        }
+
+        if let Some(mfa_policy) = self.mfa_policy {
+            params.push(super::prisma::mfa_config::mfa_policy::set(mfa_policy));
+
+            // The grace period starts when the policy becomes required
+            let mfa_required_at = match mfa_policy {
+                MfaPolicy::Required => Some(mfa_required_at.unwrap_or_else(Utc::now)),
+                _ => None,
+            };
+            params.push(super::prisma::mfa_config::mfa_required_at::set(
+                mfa_required_at.map(|required_at| required_at.into()),
+            ));
+        }
+
+        if let Some(mfa_grace_period_days) = self.mfa_grace_period_days {
+            params.push(super::prisma::mfa_config::mfa_grace_period_days::set(
+                mfa_grace_period_days as i32,
+            ));
+        }
+
+        if let Some(mfa_recovery_waiting_days) = self.mfa_recovery_waiting_days {
+            params.push(super::prisma::mfa_config::mfa_recovery_waiting_days::set(
+                mfa_recovery_waiting_days as i32,
+            ));
+        }
+
+        params
+    }
+}
+
+impl Default for MFAConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -8,4 +8,10 @@ pub enum ModelError {
 
     #[error("missing field in builder")]
     MissingField(String),
+
+    #[error("failed to hash value")]
+    HashError(#[from] crypto::password::CryptoError),
+
+    #[error("failed to encrypt or decrypt value")]
+    EncryptionError(#[from] crypto::aead::Error),
 }
@@ -0,0 +1,425 @@
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+use prisma_client_rust::Direction;
+
+use super::{
+    error::ModelError,
+    prisma::{self, PrismaClient},
+};
+
+/// A permission defined by an application, e.g. `invoices:read`.
+#[derive(Debug, Clone)]
+pub struct Permission {
+    id: Snowflake,
+    application_id: Snowflake,
+
+    name: String,
+    description: String,
+
+    created_at: DateTime<Utc>,
+}
+
+impl Permission {
+    pub async fn create(
+        client: &PrismaClient,
+        id: Snowflake,
+        application_id: Snowflake,
+        name: String,
+        description: String,
+    ) -> Result<Permission, ModelError> {
+        let data = client
+            .permission()
+            .create(
+                id.to_id_signed(),
+                prisma::replicated_application::application_id::equals(
+                    application_id.to_id_signed(),
+                ),
+                name,
+                vec![prisma::permission::description::set(description)],
+            )
+            .exec()
+            .await?;
+
+        Ok(data.into())
+    }
+
+    /// List the permissions of an application, ordered by name.
+    pub async fn list(
+        client: &PrismaClient,
+        application_id: Snowflake,
+    ) -> Result<Vec<Permission>, ModelError> {
+        let data = client
+            .permission()
+            .find_many(vec![prisma::permission::application_id::equals(
+                application_id.to_id_signed(),
+            )])
+            .order_by(prisma::permission::name::order(Direction::Asc))
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(Permission::from).collect())
+    }
+
+    /// Get the permissions of an application with the given names, unknown names are skipped.
+    pub async fn get_many(
+        client: &PrismaClient,
+        application_id: Snowflake,
+        names: Vec<String>,
+    ) -> Result<Vec<Permission>, ModelError> {
+        let data = client
+            .permission()
+            .find_many(vec![
+                prisma::permission::application_id::equals(application_id.to_id_signed()),
+                prisma::permission::name::in_vec(names),
+            ])
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(Permission::from).collect())
+    }
+
+    /// Delete a permission, it is removed from every role. Returns false if it does not exist.
+    pub async fn delete(
+        client: &PrismaClient,
+        application_id: Snowflake,
+        name: String,
+    ) -> Result<bool, ModelError> {
+        let count = client
+            .permission()
+            .delete_many(vec![
+                prisma::permission::application_id::equals(application_id.to_id_signed()),
+                prisma::permission::name::equals(name),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    pub fn id(&self) -> Snowflake {
+        self.id
+    }
+
+    pub fn application_id(&self) -> Snowflake {
+        self.application_id
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    pub fn description(&self) -> &str {
+        self.description.as_ref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+impl From<prisma::permission::Data> for Permission {
+    fn from(value: prisma::permission::Data) -> Self {
+        Self {
+            id: value.id.try_into().unwrap(),
+            application_id: value.application_id.try_into().unwrap(),
+
+            name: value.name,
+            description: value.description,
+
+            created_at: value.created_at.into(),
+        }
+    }
+}
+
+/// A named set of permissions of an application, assigned to users.
+#[derive(Debug, Clone)]
+pub struct Role {
+    id: Snowflake,
+    application_id: Snowflake,
+
+    name: String,
+    description: String,
+
+    /// Names of the permissions granted by the role, ordered by name.
+    permissions: Vec<String>,
+
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+/// Fetch the permissions of a role together with the role.
+fn permissions_fetch() -> prisma::role::permissions::Fetch {
+    prisma::role::permissions::fetch(vec![]).with(prisma::role_permission::permission::fetch())
+}
+
+impl Role {
+    /// Create a role granting the given permissions, the permissions must belong to the same
+    /// application.
+    pub async fn create(
+        client: &PrismaClient,
+        id: Snowflake,
+        application_id: Snowflake,
+        name: String,
+        description: String,
+        permissions: &[Permission],
+    ) -> Result<Role, ModelError> {
+        client
+            .role()
+            .create(
+                id.to_id_signed(),
+                prisma::replicated_application::application_id::equals(
+                    application_id.to_id_signed(),
+                ),
+                name,
+                vec![prisma::role::description::set(description)],
+            )
+            .exec()
+            .await?;
+
+        let mut role = Role::get(client, application_id, id).await?;
+        role.set_permissions(client, permissions).await?;
+
+        Ok(role)
+    }
+
+    pub async fn get(
+        client: &PrismaClient,
+        application_id: Snowflake,
+        id: Snowflake,
+    ) -> Result<Role, ModelError> {
+        let data = client
+            .role()
+            .find_first(vec![
+                prisma::role::id::equals(id.to_id_signed()),
+                prisma::role::application_id::equals(application_id.to_id_signed()),
+            ])
+            .with(permissions_fetch())
+            .exec()
+            .await?;
+
+        match data {
+            Some(data) => Ok(data.into()),
+            None => Err(ModelError::NotFound),
+        }
+    }
+
+    pub async fn get_by_name(
+        client: &PrismaClient,
+        application_id: Snowflake,
+        name: String,
+    ) -> Result<Role, ModelError> {
+        let data = client
+            .role()
+            .find_first(vec![
+                prisma::role::application_id::equals(application_id.to_id_signed()),
+                prisma::role::name::equals(name),
+            ])
+            .with(permissions_fetch())
+            .exec()
+            .await?;
+
+        match data {
+            Some(data) => Ok(data.into()),
+            None => Err(ModelError::NotFound),
+        }
+    }
+
+    /// List the roles of an application, ordered by name.
+    pub async fn list(
+        client: &PrismaClient,
+        application_id: Snowflake,
+    ) -> Result<Vec<Role>, ModelError> {
+        let data = client
+            .role()
+            .find_many(vec![prisma::role::application_id::equals(
+                application_id.to_id_signed(),
+            )])
+            .with(permissions_fetch())
+            .order_by(prisma::role::name::order(Direction::Asc))
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(Role::from).collect())
+    }
+
+    /// List the roles assigned to a user, ordered by name.
+    pub async fn list_by_user(
+        client: &PrismaClient,
+        user_id: Snowflake,
+    ) -> Result<Vec<Role>, ModelError> {
+        let data = client
+            .role()
+            .find_many(vec![prisma::role::users::some(vec![
+                prisma::user_role::user_id::equals(user_id.to_id_signed()),
+            ])])
+            .with(permissions_fetch())
+            .order_by(prisma::role::name::order(Direction::Asc))
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(Role::from).collect())
+    }
+
+    /// Replace the permissions granted by the role.
+    pub async fn set_permissions(
+        &mut self,
+        client: &PrismaClient,
+        permissions: &[Permission],
+    ) -> Result<(), ModelError> {
+        client
+            .role_permission()
+            .delete_many(vec![prisma::role_permission::role_id::equals(
+                self.id.to_id_signed(),
+            )])
+            .exec()
+            .await?;
+
+        client
+            .role_permission()
+            .create_many(
+                permissions
+                    .iter()
+                    .map(|permission| {
+                        (
+                            self.id.to_id_signed(),
+                            permission.id().to_id_signed(),
+                            vec![],
+                        )
+                    })
+                    .collect(),
+            )
+            .exec()
+            .await?;
+
+        let mut names = permissions
+            .iter()
+            .map(|permission| permission.name().to_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        self.permissions = names;
+
+        Ok(())
+    }
+
+    /// Delete the role, it is removed from every user. Returns false if it does not exist.
+    pub async fn delete(
+        client: &PrismaClient,
+        application_id: Snowflake,
+        name: String,
+    ) -> Result<bool, ModelError> {
+        let count = client
+            .role()
+            .delete_many(vec![
+                prisma::role::application_id::equals(application_id.to_id_signed()),
+                prisma::role::name::equals(name),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Assign the role to a user, returns false if the user already has the role.
+    pub async fn assign(
+        &self,
+        client: &PrismaClient,
+        user_id: Snowflake,
+    ) -> Result<bool, ModelError> {
+        let assigned = client
+            .user_role()
+            .count(vec![
+                prisma::user_role::user_id::equals(user_id.to_id_signed()),
+                prisma::user_role::role_id::equals(self.id.to_id_signed()),
+            ])
+            .exec()
+            .await?;
+        if assigned > 0 {
+            return Ok(false);
+        }
+
+        client
+            .user_role()
+            .create(
+                prisma::user::id::equals(user_id.to_id_signed()),
+                prisma::role::id::equals(self.id.to_id_signed()),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Remove the role from a user, returns false if the user does not have the role.
+    pub async fn unassign(
+        &self,
+        client: &PrismaClient,
+        user_id: Snowflake,
+    ) -> Result<bool, ModelError> {
+        let count = client
+            .user_role()
+            .delete_many(vec![
+                prisma::user_role::user_id::equals(user_id.to_id_signed()),
+                prisma::user_role::role_id::equals(self.id.to_id_signed()),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    pub fn id(&self) -> Snowflake {
+        self.id
+    }
+
+    pub fn application_id(&self) -> Snowflake {
+        self.application_id
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    pub fn description(&self) -> &str {
+        self.description.as_ref()
+    }
+
+    /// Names of the permissions granted by the role, ordered by name.
+    pub fn permissions(&self) -> &[String] {
+        self.permissions.as_ref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl From<prisma::role::Data> for Role {
+    fn from(value: prisma::role::Data) -> Self {
+        let mut permissions = value
+            .permissions
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|role_permission| role_permission.permission)
+            .map(|permission| permission.name)
+            .collect::<Vec<_>>();
+        permissions.sort();
+
+        Self {
+            id: value.id.try_into().unwrap(),
+            application_id: value.application_id.try_into().unwrap(),
+
+            name: value.name,
+            description: value.description,
+
+            permissions,
+
+            created_at: value.created_at.into(),
+            updated_at: value.updated_at.into(),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use chrono::{DateTime, Utc};
 use crypto::snowflake::{Snowflake, SnowflakeGenerator};
-use prisma_client_rust::QueryError;
+use prisma_client_rust::{Direction, QueryError};
 
 use self::{
     basic_auth::{BasicAuth, BasicAuthBuilder},
@@ -15,11 +15,21 @@ use super::{
 
 pub use email_address::EmailAddress;
 pub use external_user::ExternalUser;
+pub use metadata::UserMetadata;
+pub use mfa_recovery::MFARecovery;
+pub use one_time_code::OneTimeCode;
+pub use password_history::PasswordHistory;
+pub use phone_number::PhoneNumber;
 pub use token::UserToken;
 
 pub mod basic_auth;
 pub mod email_address;
 pub mod external_user;
+pub mod metadata;
+pub mod mfa_recovery;
+pub mod one_time_code;
+pub mod password_history;
+pub mod phone_number;
 pub mod token;
 pub mod totp;
 
@@ -28,16 +38,21 @@ pub struct User {
     id: Snowflake,
 
     email_address: Option<EmailAddress>,
+    phone_number: Option<PhoneNumber>,
 
     password_enabled: bool,
     basic_auth: Option<BasicAuth>,
 
     totp_enabled: bool,
-    totp: Option<TOTP>,
+    totps: Vec<TOTP>,
 
     last_login_at: Option<DateTime<Utc>>,
     last_login_ip: Option<String>,
 
+    /// Disabled users can not sign in, see [`User::set_disabled`].
+    disabled: bool,
+    disabled_at: Option<DateTime<Utc>>,
+
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 
@@ -46,6 +61,7 @@ pub struct User {
 
 pub enum UserWith {
     EmailAddress,
+    PhoneNumber,
     BasicAuth,
     TOTP,
 }
@@ -64,12 +80,9 @@ impl User {
         for w in with {
             user = match w {
                 UserWith::EmailAddress => user.with(prisma::user::email_address::fetch()),
+                UserWith::PhoneNumber => user.with(prisma::user::phone_number::fetch()),
                 UserWith::BasicAuth => user.with(prisma::user::basic_auth::fetch()),
-                UserWith::TOTP => user.with(prisma::user::totp::fetch().with(
-                    prisma::totp::totp_backup_code::fetch(vec![
-                        prisma::totp_backup_code::expired::equals(false),
-                    ]),
-                )),
+                UserWith::TOTP => user.with(prisma::user::totp::fetch(vec![])),
             };
         }
 
@@ -82,6 +95,77 @@ impl User {
         }
     }
 
+    /// Get the users with the given IDs, unknown IDs are skipped.
+    pub async fn get_many(
+        client: &PrismaClient,
+        ids: Vec<Snowflake>,
+        with: Vec<UserWith>,
+    ) -> Result<Vec<User>, ModelError> {
+        let mut users = client.user().find_many(vec![prisma::user::id::in_vec(
+            ids.into_iter().map(|id| id.to_id_signed()).collect(),
+        )]);
+
+        // Add with params (joins)
+        for w in with {
+            users = match w {
+                UserWith::EmailAddress => users.with(prisma::user::email_address::fetch()),
+                UserWith::PhoneNumber => users.with(prisma::user::phone_number::fetch()),
+                UserWith::BasicAuth => users.with(prisma::user::basic_auth::fetch()),
+                UserWith::TOTP => users.with(prisma::user::totp::fetch(vec![])),
+            };
+        }
+
+        let users = users.exec().await?;
+
+        Ok(users.into_iter().map(User::from).collect())
+    }
+
+    /// List the users of an application ordered by ID, starting after the `after` cursor.
+    ///
+    /// If `email_query` is set only users whose email address contains it are listed, ignoring
+    /// case.
+    pub async fn list(
+        client: &PrismaClient,
+        application_id: Snowflake,
+        email_query: Option<String>,
+        after: Option<Snowflake>,
+        limit: i64,
+        with: Vec<UserWith>,
+    ) -> Result<Vec<User>, ModelError> {
+        let mut filter = vec![prisma::user::replicated_application_id::equals(
+            application_id.to_id_signed(),
+        )];
+        if let Some(after) = after {
+            filter.push(prisma::user::id::gt(after.to_id_signed()));
+        }
+        if let Some(email_query) = email_query {
+            filter.push(prisma::user::email_address::is(vec![
+                prisma::email_address::email_address::contains(email_query),
+                prisma::email_address::email_address::mode(prisma::QueryMode::Insensitive),
+            ]));
+        }
+
+        let mut users = client
+            .user()
+            .find_many(filter)
+            .order_by(prisma::user::id::order(Direction::Asc))
+            .take(limit);
+
+        // Add with params (joins)
+        for w in with {
+            users = match w {
+                UserWith::EmailAddress => users.with(prisma::user::email_address::fetch()),
+                UserWith::PhoneNumber => users.with(prisma::user::phone_number::fetch()),
+                UserWith::BasicAuth => users.with(prisma::user::basic_auth::fetch()),
+                UserWith::TOTP => users.with(prisma::user::totp::fetch(vec![])),
+            };
+        }
+
+        let users = users.exec().await?;
+
+        Ok(users.into_iter().map(User::from).collect())
+    }
+
     pub async fn find_by_email<C>(
         client: &PrismaClient,
         email: C,
@@ -97,8 +181,11 @@ impl User {
         for with in user_with {
             user_fetch = match with {
                 UserWith::EmailAddress => user_fetch,
+                UserWith::PhoneNumber => {
+                    user_fetch.with(super::prisma::user::phone_number::fetch())
+                }
                 UserWith::BasicAuth => user_fetch.with(super::prisma::user::basic_auth::fetch()),
-                UserWith::TOTP => user_fetch.with(super::prisma::user::totp::fetch()),
+                UserWith::TOTP => user_fetch.with(super::prisma::user::totp::fetch(vec![])),
             };
         }
 
@@ -144,6 +231,7 @@ impl User {
             client,
 
             email_builder,
+            email_verified: false,
             basic_auth_builder: None,
 
             id: user_id,
@@ -153,6 +241,72 @@ impl User {
         }
     }
 
+    /// Disable or enable the user.
+    pub async fn set_disabled(
+        &mut self,
+        client: &PrismaClient,
+        disabled: bool,
+    ) -> Result<(), ModelError> {
+        let disabled_at = disabled.then(Utc::now);
+
+        client
+            .user()
+            .update(
+                prisma::user::id::equals(self.id.to_id_signed()),
+                vec![
+                    prisma::user::disabled::set(disabled),
+                    prisma::user::disabled_at::set(disabled_at.map(|v| v.into())),
+                ],
+            )
+            .exec()
+            .await?;
+
+        self.disabled = disabled;
+        self.disabled_at = disabled_at;
+        Ok(())
+    }
+
+    /// Set the password of a user, e.g. when they accept an invitation or change their password.
+    pub async fn set_password(
+        &mut self,
+        client: &PrismaClient,
+        password_hash: String,
+    ) -> Result<(), ModelError> {
+        // Replace the hash of a user that already has a password
+        self.basic_auth(Some(client)).await;
+        if let Some(basic_auth) = self.basic_auth.as_mut() {
+            return basic_auth.change_password_hash(client, password_hash).await;
+        }
+
+        let basic_auth = BasicAuthBuilder::new(self.id, password_hash)
+            .build(client)
+            .await?;
+
+        client
+            .user()
+            .update(
+                prisma::user::id::equals(self.id.to_id_signed()),
+                vec![prisma::user::password_enabled::set(true)],
+            )
+            .exec()
+            .await?;
+
+        self.basic_auth = Some(basic_auth);
+        self.password_enabled = true;
+        Ok(())
+    }
+
+    /// Delete the user together with all of their data.
+    pub async fn delete(client: &PrismaClient, id: Snowflake) -> Result<(), ModelError> {
+        client
+            .user()
+            .delete(prisma::user::id::equals(id.to_id_signed()))
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
     pub fn id(&self) -> Snowflake {
         self.id
     }
@@ -161,6 +315,10 @@ impl User {
         self.email_address.as_ref()
     }
 
+    pub fn phone_number(&self) -> Option<&PhoneNumber> {
+        self.phone_number.as_ref()
+    }
+
     pub fn password_enabled(&self) -> bool {
         self.password_enabled
     }
@@ -198,6 +356,14 @@ impl User {
         self.last_login_ip.as_ref()
     }
 
+    pub fn disabled(&self) -> bool {
+        self.disabled
+    }
+
+    pub fn disabled_at(&self) -> Option<DateTime<Utc>> {
+        self.disabled_at
+    }
+
     pub fn created_at(&self) -> DateTime<Utc> {
         self.created_at
     }
@@ -214,8 +380,28 @@ impl User {
         self.totp_enabled
     }
 
-    pub fn totp(&self) -> Option<&TOTP> {
-        self.totp.as_ref()
+    /// Whether the user has to sign in with a second factor, the user must be fetched with their
+    /// phone number and email address for SMS and email 2FA to be taken into account.
+    pub fn mfa_enabled(&self) -> bool {
+        self.totp_enabled
+            || self
+                .phone_number
+                .as_ref()
+                .map_or(false, |phone_number| phone_number.mfa_enabled())
+            || self
+                .email_address
+                .as_ref()
+                .map_or(false, |email_address| email_address.mfa_enabled())
+    }
+
+    /// All TOTP devices of the user, including a pending enrolment.
+    pub fn totps(&self) -> &[TOTP] {
+        self.totps.as_ref()
+    }
+
+    /// The TOTP devices of the user that can be used to sign in.
+    pub fn active_totps(&self) -> impl Iterator<Item = &TOTP> {
+        self.totps.iter().filter(|totp| !totp.is_pending())
     }
 }
 
@@ -227,18 +413,25 @@ impl From<Data> for User {
             None => None,
         };
 
-        let basic_auth = match value.basic_auth {
-            Some(Some(basic_auth)) => Some((*basic_auth).into()),
+        let phone_number = match value.phone_number {
+            Some(Some(phone_number)) => Some((*phone_number).into()),
             Some(None) => None,
             None => None,
         };
 
-        let totp = match value.totp {
-            Some(Some(totp)) => Some((*totp).into()),
+        let basic_auth = match value.basic_auth {
+            Some(Some(basic_auth)) => Some((*basic_auth).into()),
             Some(None) => None,
             None => None,
         };
 
+        let totps = value
+            .totp
+            .unwrap_or_default()
+            .into_iter()
+            .map(|totp| totp.into())
+            .collect();
+
         User {
             // Parse fields that must have a value
             id: value.id.try_into().unwrap(),
@@ -247,13 +440,16 @@ impl From<Data> for User {
             created_at: value.created_at.into(),
             updated_at: value.updated_at.into(),
             last_login_at: value.last_login_at.map(|v| v.into()),
+            disabled: value.disabled,
+            disabled_at: value.disabled_at.map(|v| v.into()),
             application_id: value.replicated_application_id.try_into().unwrap(),
             totp_enabled: value.totp_enabled,
 
             // Parse fields that may not have a value
             email_address,
+            phone_number,
             basic_auth,
-            totp,
+            totps,
         }
     }
 }
@@ -272,18 +468,13 @@ pub struct Session {
     updated_at: DateTime<Utc>,
 }
 
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub struct UserMetadata {
+/// A metadata entry inserted together with the user, see [`UserBuilder::user_metadata`].
+struct NewUserMetadata {
     id: Snowflake,
 
-    user_id: Snowflake,
-
+    scope: metadata::MetadataScope,
     key: String,
-    value: String,
-
-    created_at: DateTime<Utc>,
-    updated_at: DateTime<Utc>,
+    value: serde_json::Value,
 }
 
 pub struct UserBuilder<'a> {
@@ -291,30 +482,41 @@ pub struct UserBuilder<'a> {
     client: &'a PrismaClient,
 
     email_builder: EmailAddressBuilder,
+    email_verified: bool,
     basic_auth_builder: Option<BasicAuthBuilder>,
 
     id: Snowflake,
     application_id: Snowflake,
 
-    user_metadata: Vec<UserMetadata>,
+    user_metadata: Vec<NewUserMetadata>,
 }
 
 impl<'a> UserBuilder<'a> {
-    pub fn user_metadata(&mut self, key: String, value: String) -> &mut Self {
-        self.user_metadata.push(UserMetadata {
+    pub fn user_metadata(
+        &mut self,
+        key: String,
+        scope: metadata::MetadataScope,
+        value: serde_json::Value,
+    ) -> &mut Self {
+        self.user_metadata.push(NewUserMetadata {
             id: self
                 .id_generator
                 .next_snowflake()
                 .expect("failed to generate snowflake"),
-            user_id: self.id,
+            scope,
             key,
             value,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
         });
         self
     }
 
+    /// Mark the email address as verified, e.g. for users created by the backend of the
+    /// application.
+    pub fn email_verified(&mut self, verified: bool) -> &mut Self {
+        self.email_verified = verified;
+        self
+    }
+
     pub fn basic_auth(&mut self, password_hash: String) -> &mut Self {
         let builder = BasicAuthBuilder::new(self.id, password_hash);
 
@@ -335,7 +537,7 @@ impl<'a> UserBuilder<'a> {
         let (email_address, basic_auth, _, user): (
             EmailAddress,
             Option<BasicAuth>,
-            Vec<UserMetadata>,
+            Vec<NewUserMetadata>,
             prisma::user::Data,
         ) = self
             .client
@@ -356,6 +558,7 @@ impl<'a> UserBuilder<'a> {
                 // Insert and build email address
                 let email_address = self
                     .email_builder
+                    .verified(self.email_verified)
                     .build(&client, user_id, self.application_id)
                     .await?;
 
@@ -374,7 +577,7 @@ impl<'a> UserBuilder<'a> {
                             prisma::user::id::equals(user_id.to_id_signed()),
                             metadata.key.clone(),
                             metadata.value.clone(),
-                            vec![],
+                            vec![prisma::user_metadata::scope::set(metadata.scope)],
                         )
                         .exec()
                         .await?;
@@ -388,14 +591,17 @@ impl<'a> UserBuilder<'a> {
             id: user_id,
             application_id,
             email_address: Some(email_address),
+            phone_number: None,
             basic_auth,
             password_enabled: user.password_enabled,
             last_login_at: None,
             last_login_ip: None,
+            disabled: false,
+            disabled_at: None,
             created_at: user.created_at.into(),
             updated_at: user.updated_at.into(),
             totp_enabled: false,
-            totp: None,
+            totps: Vec::new(),
         };
 
         Ok(user)
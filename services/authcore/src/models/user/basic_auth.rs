@@ -1,8 +1,9 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use crypto::snowflake::Snowflake;
 use prisma_client_rust::QueryError;
 
 use crate::models::{
+    error::ModelError,
     prisma::{self, basic_auth},
     PrismaClient,
 };
@@ -13,6 +14,11 @@ pub struct BasicAuth {
 
     password_hash: String,
 
+    /// When the user last chose a password, not changed when the hash is replaced.
+    password_changed_at: DateTime<Utc>,
+    /// The user has to change their password before signing in.
+    must_change_password: bool,
+
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
@@ -37,6 +43,86 @@ impl BasicAuth {
     pub fn password_hash(&self) -> &str {
         self.password_hash.as_ref()
     }
+
+    pub fn password_changed_at(&self) -> DateTime<Utc> {
+        self.password_changed_at
+    }
+
+    pub fn must_change_password(&self) -> bool {
+        self.must_change_password
+    }
+
+    /// Whether the user has to change their password before signing in, because it is marked to be
+    /// changed or is older than `max_age`.
+    pub fn needs_password_change(&self, max_age: Option<Duration>) -> bool {
+        self.must_change_password
+            || max_age.map_or(false, |max_age| {
+                self.password_changed_at + max_age <= Utc::now()
+            })
+    }
+
+    /// Replace the password with a new one chosen by or for the user, which also clears
+    /// [`must_change_password`](Self::must_change_password).
+    pub async fn change_password_hash(
+        &mut self,
+        client: &PrismaClient,
+        password_hash: String,
+    ) -> Result<(), ModelError> {
+        let data = client
+            .basic_auth()
+            .update(
+                basic_auth::user_id::equals(self.user_id.to_id_signed()),
+                vec![
+                    basic_auth::password_hash::set(password_hash),
+                    basic_auth::password_changed_at::set(Utc::now().into()),
+                    basic_auth::must_change_password::set(false),
+                ],
+            )
+            .exec()
+            .await?;
+
+        *self = data.into();
+        Ok(())
+    }
+
+    /// Mark whether the user has to change their password before signing in.
+    pub async fn set_must_change_password(
+        &mut self,
+        client: &PrismaClient,
+        must_change_password: bool,
+    ) -> Result<(), ModelError> {
+        let data = client
+            .basic_auth()
+            .update(
+                basic_auth::user_id::equals(self.user_id.to_id_signed()),
+                vec![basic_auth::must_change_password::set(must_change_password)],
+            )
+            .exec()
+            .await?;
+
+        *self = data.into();
+        Ok(())
+    }
+
+    /// Replace the password hash, e.g. with a hash of the same password with stronger parameters.
+    pub async fn update_password_hash(
+        &mut self,
+        client: &PrismaClient,
+        password_hash: String,
+    ) -> Result<(), ModelError> {
+        let data = client
+            .basic_auth()
+            .update(
+                basic_auth::user_id::equals(self.user_id.to_id_signed()),
+                vec![basic_auth::password_hash::set(password_hash)],
+            )
+            .exec()
+            .await?;
+
+        self.password_hash = data.password_hash;
+        self.updated_at = data.updated_at.into();
+        Ok(())
+    }
 }
 
 impl From<basic_auth::Data> for BasicAuth {
@@ -44,6 +130,8 @@ impl From<basic_auth::Data> for BasicAuth {
         BasicAuth {
             user_id: value.user_id.try_into().unwrap(),
             password_hash: value.password_hash,
+            password_changed_at: value.password_changed_at.into(),
+            must_change_password: value.must_change_password,
             created_at: value.created_at.into(),
             updated_at: value.updated_at.into(),
         }
@@ -75,11 +163,6 @@ impl BasicAuthBuilder {
             .exec()
             .await?;
 
-        Ok(BasicAuth {
-            user_id: data.user_id.try_into().unwrap(),
-            password_hash: data.password_hash,
-            created_at: data.created_at.into(),
-            updated_at: data.updated_at.into(),
-        })
+        Ok(data.into())
     }
 }
@@ -20,6 +20,9 @@ pub struct EmailAddress {
     verified_at: Option<DateTime<Utc>>,
     verified_ip: Option<String>,
 
+    /// Codes sent by email are accepted as a second factor, see [`EmailAddress::set_mfa_enabled`].
+    mfa_enabled: bool,
+
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
 }
@@ -112,6 +115,60 @@ impl EmailAddress {
         Ok(())
     }
 
+    /// Change the address and whether it is verified, e.g. by the backend of the application.
+    pub async fn update(
+        &mut self,
+        client: &PrismaClient,
+        email_address: String,
+        verified: bool,
+    ) -> Result<(), ModelError> {
+        // Keep the verification time if the address stays verified
+        let verified_at = match (
+            verified,
+            self.verified && email_address == self.email_address,
+        ) {
+            (true, true) => self.verified_at,
+            (true, false) => Some(Utc::now()),
+            (false, _) => None,
+        };
+
+        client
+            .email_address()
+            .update(
+                prisma::email_address::id::equals(self.id.to_id_signed()),
+                vec![
+                    prisma::email_address::email_address::set(email_address.clone()),
+                    prisma::email_address::verified::set(verified),
+                    prisma::email_address::verified_at::set(verified_at.map(|v| v.into())),
+                ],
+            )
+            .exec()
+            .await?;
+
+        self.email_address = email_address;
+        self.verified = verified;
+        self.verified_at = verified_at;
+        Ok(())
+    }
+
+    pub async fn set_mfa_enabled(
+        &mut self,
+        client: &PrismaClient,
+        mfa_enabled: bool,
+    ) -> Result<(), ModelError> {
+        client
+            .email_address()
+            .update(
+                prisma::email_address::id::equals(self.id.to_id_signed()),
+                vec![prisma::email_address::mfa_enabled::set(mfa_enabled)],
+            )
+            .exec()
+            .await?;
+
+        self.mfa_enabled = mfa_enabled;
+        Ok(())
+    }
+
     pub fn id(&self) -> Snowflake {
         self.id
     }
@@ -132,6 +189,12 @@ impl EmailAddress {
         self.verified_ip.as_ref()
     }
 
+    /// Whether codes sent by email can be used as a second factor, the application must also allow
+    /// email codes.
+    pub fn mfa_enabled(&self) -> bool {
+        self.verified && self.mfa_enabled
+    }
+
     pub fn created_at(&self) -> DateTime<Utc> {
         self.created_at
     }
@@ -143,6 +206,19 @@ impl EmailAddress {
     pub fn email_address(&self) -> &str {
         self.email_address.as_ref()
     }
+
+    /// The email address with all but the first character of the local part hidden, e.g.
+    /// `a****@example.com`.
+    pub fn masked(&self) -> String {
+        match self.email_address.split_once('@') {
+            Some((local, domain)) => {
+                let mut chars = local.chars();
+                let first = chars.next().map(String::from).unwrap_or_default();
+                format!("{}{}@{}", first, "*".repeat(chars.count()), domain)
+            }
+            None => self.email_address.clone(),
+        }
+    }
 }
 
 impl From<Data> for EmailAddress {
@@ -154,6 +230,7 @@ impl From<Data> for EmailAddress {
             verified: value.verified,
             verified_at: value.verified_at.map(|v| v.into()),
             verified_ip: value.verified_ip,
+            mfa_enabled: value.mfa_enabled,
             created_at: value.created_at.into(),
             updated_at: value.updated_at.into(),
         }
@@ -183,7 +260,12 @@ impl EmailAddressBuilder {
                     application_id.to_id_signed(),
                 ),
                 email_address.email_address().to_owned(),
-                vec![],
+                vec![
+                    prisma::email_address::verified::set(email_address.verified),
+                    prisma::email_address::verified_at::set(
+                        email_address.verified_at.map(|v| v.into()),
+                    ),
+                ],
             )
             .exec()
             .await?;
@@ -198,4 +280,10 @@ impl EmailAddressBuilder {
         self.email_address.email_address = email_address.into();
         self
     }
+
+    pub fn verified(mut self, verified: bool) -> Self {
+        self.email_address.verified = verified;
+        self.email_address.verified_at = verified.then(Utc::now);
+        self
+    }
 }
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+use prisma_client_rust::Direction;
+
+use crate::models::{
+    error::ModelError,
+    prisma::{self, user_metadata::Data},
+    PrismaClient,
+};
+
+pub use crate::models::prisma::MetadataScope;
+
+/// A key/value entry of a user with a JSON value, see [`MetadataScope`] for who can access it.
+#[derive(Debug, Clone)]
+pub struct UserMetadata {
+    id: Snowflake,
+
+    user_id: Snowflake,
+
+    scope: MetadataScope,
+    key: String,
+    value: serde_json::Value,
+
+    /// Whether the entry is projected into the access tokens of the user.
+    in_token: bool,
+
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl UserMetadata {
+    /// List the entries of a user in the given scopes, ordered by key.
+    pub async fn list(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        scopes: Vec<MetadataScope>,
+    ) -> Result<Vec<UserMetadata>, ModelError> {
+        let data = client
+            .user_metadata()
+            .find_many(vec![
+                prisma::user_metadata::user_id::equals(user_id.to_id_signed()),
+                prisma::user_metadata::scope::in_vec(scopes),
+            ])
+            .order_by(prisma::user_metadata::key::order(Direction::Asc))
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(UserMetadata::from).collect())
+    }
+
+    /// List the entries of a user that are projected into their access tokens.
+    pub async fn list_in_token(
+        client: &PrismaClient,
+        user_id: Snowflake,
+    ) -> Result<Vec<UserMetadata>, ModelError> {
+        let data = client
+            .user_metadata()
+            .find_many(vec![
+                prisma::user_metadata::user_id::equals(user_id.to_id_signed()),
+                prisma::user_metadata::in_token::equals(true),
+            ])
+            .order_by(prisma::user_metadata::key::order(Direction::Asc))
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(UserMetadata::from).collect())
+    }
+
+    pub async fn get(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        key: String,
+    ) -> Result<UserMetadata, ModelError> {
+        let data = client
+            .user_metadata()
+            .find_first(vec![
+                prisma::user_metadata::user_id::equals(user_id.to_id_signed()),
+                prisma::user_metadata::key::equals(key),
+            ])
+            .exec()
+            .await?;
+
+        match data {
+            Some(data) => Ok(data.into()),
+            None => Err(ModelError::NotFound),
+        }
+    }
+
+    /// Number of entries of a user in all scopes.
+    pub async fn count(client: &PrismaClient, user_id: Snowflake) -> Result<i64, ModelError> {
+        let count = client
+            .user_metadata()
+            .count(vec![prisma::user_metadata::user_id::equals(
+                user_id.to_id_signed(),
+            )])
+            .exec()
+            .await?;
+
+        Ok(count)
+    }
+
+    pub async fn create(
+        client: &PrismaClient,
+        id: Snowflake,
+        user_id: Snowflake,
+        scope: MetadataScope,
+        key: String,
+        value: serde_json::Value,
+        in_token: bool,
+    ) -> Result<UserMetadata, ModelError> {
+        let data = client
+            .user_metadata()
+            .create(
+                id.to_id_signed(),
+                prisma::user::id::equals(user_id.to_id_signed()),
+                key,
+                value,
+                vec![
+                    prisma::user_metadata::scope::set(scope),
+                    prisma::user_metadata::in_token::set(in_token),
+                ],
+            )
+            .exec()
+            .await?;
+
+        Ok(data.into())
+    }
+
+    /// Replace the scope, value and token projection of the entry.
+    pub async fn update(
+        &mut self,
+        client: &PrismaClient,
+        scope: MetadataScope,
+        value: serde_json::Value,
+        in_token: bool,
+    ) -> Result<(), ModelError> {
+        let data = client
+            .user_metadata()
+            .update(
+                prisma::user_metadata::id::equals(self.id.to_id_signed()),
+                vec![
+                    prisma::user_metadata::scope::set(scope),
+                    prisma::user_metadata::value::set(value),
+                    prisma::user_metadata::in_token::set(in_token),
+                ],
+            )
+            .exec()
+            .await?;
+
+        *self = data.into();
+        Ok(())
+    }
+
+    /// Delete an entry of a user, returns false if it does not exist.
+    pub async fn delete(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        key: String,
+    ) -> Result<bool, ModelError> {
+        let count = client
+            .user_metadata()
+            .delete_many(vec![
+                prisma::user_metadata::user_id::equals(user_id.to_id_signed()),
+                prisma::user_metadata::key::equals(key),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    pub fn id(&self) -> Snowflake {
+        self.id
+    }
+
+    pub fn user_id(&self) -> Snowflake {
+        self.user_id
+    }
+
+    pub fn scope(&self) -> MetadataScope {
+        self.scope
+    }
+
+    pub fn key(&self) -> &str {
+        self.key.as_ref()
+    }
+
+    pub fn value(&self) -> &serde_json::Value {
+        &self.value
+    }
+
+    pub fn in_token(&self) -> bool {
+        self.in_token
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl From<Data> for UserMetadata {
+    fn from(value: Data) -> Self {
+        Self {
+            id: value.id.try_into().unwrap(),
+            user_id: value.user_id.try_into().unwrap(),
+            scope: value.scope,
+            key: value.key,
+            value: value.value,
+            in_token: value.in_token,
+            created_at: value.created_at.into(),
+            updated_at: value.updated_at.into(),
+        }
+    }
+}
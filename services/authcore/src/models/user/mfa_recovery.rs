@@ -0,0 +1,213 @@
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+
+use crate::models::{
+    error::ModelError,
+    prisma::{self, mfa_recovery::Data, MfaRecoveryStatus},
+    PrismaClient,
+};
+
+/// A request of a user that lost their second factor to remove it once the waiting period is over.
+#[derive(Debug, Clone)]
+pub struct MFARecovery {
+    id: Snowflake,
+
+    user_id: Snowflake,
+
+    status: MfaRecoveryStatus,
+
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+
+    completes_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+    cancelled_at: Option<DateTime<Utc>>,
+    cancelled_by: Option<String>,
+
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl MFARecovery {
+    /// Start a recovery that completes at the given time.
+    pub async fn create(
+        client: &PrismaClient,
+        id: Snowflake,
+        user_id: Snowflake,
+        completes_at: DateTime<Utc>,
+        ip_address: Option<String>,
+        user_agent: Option<String>,
+    ) -> Result<MFARecovery, ModelError> {
+        let data = client
+            .mfa_recovery()
+            .create(
+                id.to_id_signed(),
+                prisma::user::id::equals(user_id.to_id_signed()),
+                completes_at.into(),
+                vec![
+                    prisma::mfa_recovery::ip_address::set(ip_address),
+                    prisma::mfa_recovery::user_agent::set(user_agent),
+                ],
+            )
+            .exec()
+            .await?;
+
+        Ok(data.into())
+    }
+
+    /// Get the pending recovery of a user, a user has at most one.
+    pub async fn get_pending(
+        client: &PrismaClient,
+        user_id: Snowflake,
+    ) -> Result<Option<MFARecovery>, ModelError> {
+        let data = client
+            .mfa_recovery()
+            .find_first(vec![
+                prisma::mfa_recovery::user_id::equals(user_id.to_id_signed()),
+                prisma::mfa_recovery::status::equals(MfaRecoveryStatus::Pending),
+            ])
+            .exec()
+            .await?;
+
+        Ok(data.map(MFARecovery::from))
+    }
+
+    /// List the pending recoveries whose waiting period is over.
+    pub async fn list_due(client: &PrismaClient) -> Result<Vec<MFARecovery>, ModelError> {
+        let data = client
+            .mfa_recovery()
+            .find_many(vec![
+                prisma::mfa_recovery::status::equals(MfaRecoveryStatus::Pending),
+                prisma::mfa_recovery::completes_at::lte(Utc::now().into()),
+            ])
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(MFARecovery::from).collect())
+    }
+
+    /// Mark the recovery as completed, returns false if it is no longer pending.
+    pub async fn complete(&mut self, client: &PrismaClient) -> Result<bool, ModelError> {
+        let now = Utc::now();
+
+        let count = client
+            .mfa_recovery()
+            .update_many(
+                vec![
+                    prisma::mfa_recovery::id::equals(self.id.to_id_signed()),
+                    prisma::mfa_recovery::status::equals(MfaRecoveryStatus::Pending),
+                ],
+                vec![
+                    prisma::mfa_recovery::status::set(MfaRecoveryStatus::Completed),
+                    prisma::mfa_recovery::completed_at::set(Some(now.into())),
+                ],
+            )
+            .exec()
+            .await?;
+
+        if count > 0 {
+            self.status = MfaRecoveryStatus::Completed;
+            self.completed_at = Some(now);
+        }
+
+        Ok(count > 0)
+    }
+
+    /// Mark the recovery as cancelled, returns false if it is no longer pending.
+    ///
+    /// `cancelled_by` records who cancelled the recovery, e.g. `user` or `admin`.
+    pub async fn cancel(
+        &mut self,
+        client: &PrismaClient,
+        cancelled_by: &str,
+    ) -> Result<bool, ModelError> {
+        let now = Utc::now();
+
+        let count = client
+            .mfa_recovery()
+            .update_many(
+                vec![
+                    prisma::mfa_recovery::id::equals(self.id.to_id_signed()),
+                    prisma::mfa_recovery::status::equals(MfaRecoveryStatus::Pending),
+                ],
+                vec![
+                    prisma::mfa_recovery::status::set(MfaRecoveryStatus::Cancelled),
+                    prisma::mfa_recovery::cancelled_at::set(Some(now.into())),
+                    prisma::mfa_recovery::cancelled_by::set(Some(cancelled_by.to_owned())),
+                ],
+            )
+            .exec()
+            .await?;
+
+        if count > 0 {
+            self.status = MfaRecoveryStatus::Cancelled;
+            self.cancelled_at = Some(now);
+            self.cancelled_by = Some(cancelled_by.to_owned());
+        }
+
+        Ok(count > 0)
+    }
+
+    pub fn id(&self) -> Snowflake {
+        self.id
+    }
+
+    pub fn user_id(&self) -> Snowflake {
+        self.user_id
+    }
+
+    pub fn status(&self) -> MfaRecoveryStatus {
+        self.status
+    }
+
+    pub fn ip_address(&self) -> Option<&String> {
+        self.ip_address.as_ref()
+    }
+
+    pub fn user_agent(&self) -> Option<&String> {
+        self.user_agent.as_ref()
+    }
+
+    /// When the second factors of the user are removed.
+    pub fn completes_at(&self) -> DateTime<Utc> {
+        self.completes_at
+    }
+
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.completed_at
+    }
+
+    pub fn cancelled_at(&self) -> Option<DateTime<Utc>> {
+        self.cancelled_at
+    }
+
+    pub fn cancelled_by(&self) -> Option<&String> {
+        self.cancelled_by.as_ref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl From<Data> for MFARecovery {
+    fn from(value: Data) -> Self {
+        Self {
+            id: value.id.try_into().unwrap(),
+            user_id: value.user_id.try_into().unwrap(),
+            status: value.status,
+            ip_address: value.ip_address,
+            user_agent: value.user_agent,
+            completes_at: value.completes_at.into(),
+            completed_at: value.completed_at.map(|v| v.into()),
+            cancelled_at: value.cancelled_at.map(|v| v.into()),
+            cancelled_by: value.cancelled_by,
+            created_at: value.created_at.into(),
+            updated_at: value.updated_at.into(),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+use prisma_client_rust::Direction;
+
+use crate::models::{
+    error::ModelError,
+    prisma::{self, one_time_code::Data, OneTimeCodeChannel, OneTimeCodePurpose},
+    PrismaClient,
+};
+
+/// A one-time code sent to a user by SMS or email, only the argon2 hash of the code is stored (see
+/// [`HashingPool::hash_one_time_code`](crate::core::hashing::HashingPool::hash_one_time_code)).
+#[derive(Debug, Clone)]
+pub struct OneTimeCode {
+    id: Snowflake,
+
+    user_id: Snowflake,
+
+    channel: OneTimeCodeChannel,
+    purpose: OneTimeCodePurpose,
+    code_hash: String,
+
+    attempts: i32,
+    used_at: Option<DateTime<Utc>>,
+
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+impl OneTimeCode {
+    /// Store the hash of a code that has been sent to a user.
+    pub async fn create(
+        client: &PrismaClient,
+        id: Snowflake,
+        user_id: Snowflake,
+        channel: OneTimeCodeChannel,
+        purpose: OneTimeCodePurpose,
+        code_hash: String,
+        expires_at: DateTime<Utc>,
+    ) -> Result<OneTimeCode, ModelError> {
+        let data = client
+            .one_time_code()
+            .create(
+                id.to_id_signed(),
+                prisma::user::id::equals(user_id.to_id_signed()),
+                channel,
+                purpose,
+                code_hash,
+                expires_at.into(),
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        Ok(data.into())
+    }
+
+    /// Get the most recently sent code for a channel and purpose, only the latest code sent can be
+    /// used.
+    ///
+    /// Returns `None` if the latest code has been used, expired or if no code has been sent.
+    pub async fn latest(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        channel: OneTimeCodeChannel,
+        purpose: OneTimeCodePurpose,
+    ) -> Result<Option<OneTimeCode>, ModelError> {
+        let data = client
+            .one_time_code()
+            .find_first(vec![
+                prisma::one_time_code::user_id::equals(user_id.to_id_signed()),
+                prisma::one_time_code::channel::equals(channel),
+                prisma::one_time_code::purpose::equals(purpose),
+            ])
+            .order_by(prisma::one_time_code::created_at::order(Direction::Desc))
+            .exec()
+            .await?;
+
+        Ok(data
+            .map(OneTimeCode::from)
+            .filter(|code| code.used_at.is_none() && !code.is_expired()))
+    }
+
+    /// Get the most recently sent code of any purpose through a channel, used to throttle sending.
+    pub async fn last_sent(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        channel: OneTimeCodeChannel,
+    ) -> Result<Option<OneTimeCode>, ModelError> {
+        let data = client
+            .one_time_code()
+            .find_first(vec![
+                prisma::one_time_code::user_id::equals(user_id.to_id_signed()),
+                prisma::one_time_code::channel::equals(channel),
+            ])
+            .order_by(prisma::one_time_code::created_at::order(Direction::Desc))
+            .exec()
+            .await?;
+
+        Ok(data.map(OneTimeCode::from))
+    }
+
+    /// Count the codes sent to a user through a channel since the given time.
+    pub async fn count_sent_since(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        channel: OneTimeCodeChannel,
+        since: DateTime<Utc>,
+    ) -> Result<i64, ModelError> {
+        let count = client
+            .one_time_code()
+            .count(vec![
+                prisma::one_time_code::user_id::equals(user_id.to_id_signed()),
+                prisma::one_time_code::channel::equals(channel),
+                prisma::one_time_code::created_at::gte(since.into()),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Delete the codes sent to a user before the given time, they are no longer needed for
+    /// throttling.
+    pub async fn delete_sent_before(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        before: DateTime<Utc>,
+    ) -> Result<i64, ModelError> {
+        let count = client
+            .one_time_code()
+            .delete_many(vec![
+                prisma::one_time_code::user_id::equals(user_id.to_id_signed()),
+                prisma::one_time_code::created_at::lt(before.into()),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Mark all unused codes sent to a user through a channel as used, e.g. after the phone number
+    /// has changed.
+    pub async fn invalidate_all(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        channel: OneTimeCodeChannel,
+    ) -> Result<(), ModelError> {
+        client
+            .one_time_code()
+            .update_many(
+                vec![
+                    prisma::one_time_code::user_id::equals(user_id.to_id_signed()),
+                    prisma::one_time_code::channel::equals(channel),
+                    prisma::one_time_code::used_at::equals(None),
+                ],
+                vec![prisma::one_time_code::used_at::set(Some(Utc::now().into()))],
+            )
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Count an attempt of verifying the code, returns false without counting if `max_attempts`
+    /// has already been reached.
+    pub async fn try_attempt(
+        &self,
+        client: &PrismaClient,
+        max_attempts: i32,
+    ) -> Result<bool, ModelError> {
+        let count = client
+            .one_time_code()
+            .update_many(
+                vec![
+                    prisma::one_time_code::id::equals(self.id.to_id_signed()),
+                    prisma::one_time_code::attempts::lt(max_attempts),
+                ],
+                vec![prisma::one_time_code::attempts::increment(1)],
+            )
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Mark a code that has been verified as used, returns false if the code has already been
+    /// used.
+    ///
+    /// Attempts must be counted with [`OneTimeCode::try_attempt`] before verifying.
+    pub async fn mark_used(&self, client: &PrismaClient) -> Result<bool, ModelError> {
+        // Only one request can use the code
+        let count = client
+            .one_time_code()
+            .update_many(
+                vec![
+                    prisma::one_time_code::id::equals(self.id.to_id_signed()),
+                    prisma::one_time_code::used_at::equals(None),
+                ],
+                vec![prisma::one_time_code::used_at::set(Some(Utc::now().into()))],
+            )
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    pub fn id(&self) -> Snowflake {
+        self.id
+    }
+
+    pub fn user_id(&self) -> Snowflake {
+        self.user_id
+    }
+
+    pub fn channel(&self) -> OneTimeCodeChannel {
+        self.channel
+    }
+
+    pub fn purpose(&self) -> OneTimeCodePurpose {
+        self.purpose
+    }
+
+    pub fn code_hash(&self) -> &str {
+        self.code_hash.as_ref()
+    }
+
+    /// Number of codes tried against this code.
+    pub fn attempts(&self) -> i32 {
+        self.attempts
+    }
+
+    pub fn used_at(&self) -> Option<DateTime<Utc>> {
+        self.used_at
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+impl From<Data> for OneTimeCode {
+    fn from(value: Data) -> Self {
+        Self {
+            id: value.id.try_into().unwrap(),
+            user_id: value.user_id.try_into().unwrap(),
+            channel: value.channel,
+            purpose: value.purpose,
+            code_hash: value.code_hash,
+            attempts: value.attempts,
+            used_at: value.used_at.map(|v| v.into()),
+            created_at: value.created_at.into(),
+            expires_at: value.expires_at.into(),
+        }
+    }
+}
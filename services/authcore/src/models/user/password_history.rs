@@ -0,0 +1,122 @@
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+use prisma_client_rust::Direction;
+
+use crate::models::{
+    error::ModelError,
+    prisma::{self, password_history::Data},
+    PrismaClient,
+};
+
+/// The hash of a password a user has replaced, kept to prevent changing back to it.
+#[derive(Debug, Clone)]
+pub struct PasswordHistory {
+    id: Snowflake,
+
+    user_id: Snowflake,
+
+    password_hash: String,
+
+    /// When the password was replaced.
+    created_at: DateTime<Utc>,
+}
+
+impl PasswordHistory {
+    /// Record the hash of a replaced password and remove all but the newest `keep` entries of the
+    /// user.
+    pub async fn push(
+        client: &PrismaClient,
+        id: Snowflake,
+        user_id: Snowflake,
+        password_hash: String,
+        keep: i64,
+    ) -> Result<(), ModelError> {
+        client
+            .password_history()
+            .create(
+                id.to_id_signed(),
+                prisma::user::id::equals(user_id.to_id_signed()),
+                password_hash,
+                vec![],
+            )
+            .exec()
+            .await?;
+
+        Self::prune(client, user_id, keep).await
+    }
+
+    /// Get the newest `count` entries of a user, newest first.
+    pub async fn recent(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        count: i64,
+    ) -> Result<Vec<PasswordHistory>, ModelError> {
+        let data = client
+            .password_history()
+            .find_many(vec![prisma::password_history::user_id::equals(
+                user_id.to_id_signed(),
+            )])
+            .order_by(prisma::password_history::id::order(Direction::Desc))
+            .take(count)
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(PasswordHistory::from).collect())
+    }
+
+    /// Remove all but the newest `keep` entries of a user.
+    pub async fn prune(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        keep: i64,
+    ) -> Result<(), ModelError> {
+        let expired = client
+            .password_history()
+            .find_many(vec![prisma::password_history::user_id::equals(
+                user_id.to_id_signed(),
+            )])
+            .order_by(prisma::password_history::id::order(Direction::Desc))
+            .skip(keep)
+            .exec()
+            .await?;
+
+        if !expired.is_empty() {
+            client
+                .password_history()
+                .delete_many(vec![prisma::password_history::id::in_vec(
+                    expired.into_iter().map(|entry| entry.id).collect(),
+                )])
+                .exec()
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub fn id(&self) -> Snowflake {
+        self.id
+    }
+
+    pub fn user_id(&self) -> Snowflake {
+        self.user_id
+    }
+
+    pub fn password_hash(&self) -> &str {
+        self.password_hash.as_ref()
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+impl From<Data> for PasswordHistory {
+    fn from(value: Data) -> Self {
+        Self {
+            id: value.id.try_into().unwrap(),
+            user_id: value.user_id.try_into().unwrap(),
+            password_hash: value.password_hash,
+            created_at: value.created_at.into(),
+        }
+    }
+}
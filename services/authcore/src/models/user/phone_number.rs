@@ -0,0 +1,215 @@
+use chrono::{DateTime, Utc};
+use crypto::snowflake::Snowflake;
+
+use crate::models::{
+    error::ModelError,
+    prisma::{self, phone_number::Data, OneTimeCodeChannel},
+    PrismaClient,
+};
+
+use super::OneTimeCode;
+
+#[derive(Debug, Clone)]
+pub struct PhoneNumber {
+    id: Snowflake,
+
+    user_id: Snowflake,
+    application_id: Snowflake,
+
+    /// E.164 formatted phone number.
+    phone_number: String,
+
+    verified: bool,
+    verified_at: Option<DateTime<Utc>>,
+
+    /// Codes sent by SMS are accepted as a second factor, see [`PhoneNumber::set_mfa_enabled`].
+    mfa_enabled: bool,
+
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl PhoneNumber {
+    /// Get the phone number of a user.
+    pub async fn get_by_user(
+        client: &PrismaClient,
+        user_id: Snowflake,
+    ) -> Result<PhoneNumber, ModelError> {
+        let phone_number = client
+            .phone_number()
+            .find_first(vec![prisma::phone_number::user_id::equals(
+                user_id.to_id_signed(),
+            )])
+            .exec()
+            .await?;
+
+        match phone_number {
+            Some(phone_number) => Ok(phone_number.into()),
+            None => Err(ModelError::NotFound),
+        }
+    }
+
+    /// Set the phone number of a user, replacing an earlier number.
+    ///
+    /// A changed number has to be verified again and SMS 2FA is disabled until it is, codes sent
+    /// to the earlier number are invalidated. The sent codes are kept so that changing the number
+    /// does not reset the SMS throttling.
+    pub async fn set(
+        client: &PrismaClient,
+        id: Snowflake,
+        user_id: Snowflake,
+        application_id: Snowflake,
+        phone_number: String,
+    ) -> Result<PhoneNumber, ModelError> {
+        let existing = match Self::get_by_user(client, user_id).await {
+            Ok(existing) => Some(existing),
+            Err(ModelError::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+
+        let data = match existing {
+            Some(existing) if existing.phone_number == phone_number => return Ok(existing),
+            Some(existing) => {
+                OneTimeCode::invalidate_all(client, user_id, OneTimeCodeChannel::Sms).await?;
+
+                client
+                    .phone_number()
+                    .update(
+                        prisma::phone_number::id::equals(existing.id.to_id_signed()),
+                        vec![
+                            prisma::phone_number::phone_number::set(phone_number),
+                            prisma::phone_number::verified::set(false),
+                            prisma::phone_number::verified_at::set(None),
+                            prisma::phone_number::mfa_enabled::set(false),
+                        ],
+                    )
+                    .exec()
+                    .await?
+            }
+            None => {
+                client
+                    .phone_number()
+                    .create(
+                        id.to_id_signed(),
+                        prisma::user::id::equals(user_id.to_id_signed()),
+                        prisma::replicated_application::application_id::equals(
+                            application_id.to_id_signed(),
+                        ),
+                        phone_number,
+                        vec![],
+                    )
+                    .exec()
+                    .await?
+            }
+        };
+
+        Ok(data.into())
+    }
+
+    pub async fn set_verified(&mut self, client: &PrismaClient) -> Result<(), ModelError> {
+        let verified_at = Utc::now();
+
+        client
+            .phone_number()
+            .update(
+                prisma::phone_number::id::equals(self.id.to_id_signed()),
+                vec![
+                    prisma::phone_number::verified::set(true),
+                    prisma::phone_number::verified_at::set(Some(verified_at.into())),
+                ],
+            )
+            .exec()
+            .await?;
+
+        self.verified = true;
+        self.verified_at = Some(verified_at);
+        Ok(())
+    }
+
+    pub async fn set_mfa_enabled(
+        &mut self,
+        client: &PrismaClient,
+        mfa_enabled: bool,
+    ) -> Result<(), ModelError> {
+        client
+            .phone_number()
+            .update(
+                prisma::phone_number::id::equals(self.id.to_id_signed()),
+                vec![prisma::phone_number::mfa_enabled::set(mfa_enabled)],
+            )
+            .exec()
+            .await?;
+
+        self.mfa_enabled = mfa_enabled;
+        Ok(())
+    }
+
+    pub fn id(&self) -> Snowflake {
+        self.id
+    }
+
+    pub fn user_id(&self) -> Snowflake {
+        self.user_id
+    }
+
+    pub fn application_id(&self) -> Snowflake {
+        self.application_id
+    }
+
+    pub fn phone_number(&self) -> &str {
+        self.phone_number.as_ref()
+    }
+
+    /// The phone number with all but the country code prefix and the last two digits hidden,
+    /// e.g. `+46*******67`.
+    pub fn masked(&self) -> String {
+        let len = self.phone_number.len();
+        if len <= 5 {
+            return self.phone_number.clone();
+        }
+
+        format!(
+            "{}{}{}",
+            &self.phone_number[..3],
+            "*".repeat(len - 5),
+            &self.phone_number[len - 2..]
+        )
+    }
+
+    pub fn verified(&self) -> bool {
+        self.verified
+    }
+
+    pub fn verified_at(&self) -> Option<DateTime<Utc>> {
+        self.verified_at
+    }
+
+    /// Whether codes sent by SMS can be used as a second factor.
+    pub fn mfa_enabled(&self) -> bool {
+        self.verified && self.mfa_enabled
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+}
+
+impl From<Data> for PhoneNumber {
+    fn from(value: Data) -> Self {
+        Self {
+            id: value.id.try_into().unwrap(),
+            user_id: value.user_id.try_into().unwrap(),
+            application_id: value.replicated_application_id.try_into().unwrap(),
+            phone_number: value.phone_number,
+            verified: value.verified,
+            verified_at: value.verified_at.map(|v| v.into()),
+            mfa_enabled: value.mfa_enabled,
+            created_at: value.created_at.into(),
+            updated_at: value.updated_at.into(),
+        }
+    }
+}
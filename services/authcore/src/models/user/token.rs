@@ -2,6 +2,7 @@ use std::vec;
 
 use chrono::{DateTime, Utc};
 use crypto::snowflake::Snowflake;
+use prisma_client_rust::Direction;
 
 use crate::models::{
     error::ModelError,
@@ -20,6 +21,8 @@ pub struct UserToken {
     ip_address: Option<String>,
     user_agent: Option<String>,
 
+    attempts: i32,
+
     created_at: DateTime<Utc>,
     updated_at: DateTime<Utc>,
     expires_at: DateTime<Utc>,
@@ -69,6 +72,128 @@ impl UserToken {
         Ok(data.unwrap().into())
     }
 
+    /// List the unexpired tokens of a type of a user, newest first.
+    pub async fn list(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        token_type: UserTokenType,
+    ) -> Result<Vec<Self>, ModelError> {
+        let data = client
+            .user_token()
+            .find_many(vec![
+                super::prisma::user_token::user_id::equals(user_id.to_id_signed()),
+                super::prisma::user_token::token_type::equals(token_type),
+                super::prisma::user_token::expires_at::gt(Utc::now().into()),
+            ])
+            .order_by(super::prisma::user_token::created_at::order(
+                Direction::Desc,
+            ))
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(Self::from).collect())
+    }
+
+    /// Count the tokens of a type of a user, including expired tokens.
+    pub async fn count(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        token_type: UserTokenType,
+    ) -> Result<i64, ModelError> {
+        let count = client
+            .user_token()
+            .count(vec![
+                super::prisma::user_token::user_id::equals(user_id.to_id_signed()),
+                super::prisma::user_token::token_type::equals(token_type),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count)
+    }
+
+    /// List the tokens of a type of the users of an application, including expired tokens, newest
+    /// first.
+    pub async fn list_for_application(
+        client: &PrismaClient,
+        application_id: Snowflake,
+        token_type: UserTokenType,
+    ) -> Result<Vec<Self>, ModelError> {
+        let data = client
+            .user_token()
+            .find_many(vec![
+                super::prisma::user_token::token_type::equals(token_type),
+                super::prisma::user_token::user::is(vec![
+                    super::prisma::user::replicated_application_id::equals(
+                        application_id.to_id_signed(),
+                    ),
+                ]),
+            ])
+            .order_by(super::prisma::user_token::created_at::order(
+                Direction::Desc,
+            ))
+            .exec()
+            .await?;
+
+        Ok(data.into_iter().map(Self::from).collect())
+    }
+
+    /// Count an attempt of using the token, returns false without counting if `max_attempts` has
+    /// already been reached.
+    ///
+    /// The check and the increment happen in a single query, so concurrent requests can not use
+    /// more attempts than allowed.
+    pub async fn try_attempt(
+        &self,
+        client: &PrismaClient,
+        max_attempts: i32,
+    ) -> Result<bool, ModelError> {
+        let count = client
+            .user_token()
+            .update_many(
+                vec![
+                    super::prisma::user_token::id::equals(self.id.to_id_signed()),
+                    super::prisma::user_token::attempts::lt(max_attempts),
+                ],
+                vec![super::prisma::user_token::attempts::increment(1)],
+            )
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Delete a user token, e.g. after it has been used.
+    pub async fn delete(client: &PrismaClient, token_id: Snowflake) -> Result<(), ModelError> {
+        client
+            .user_token()
+            .delete_many(vec![super::prisma::user_token::id::equals(
+                token_id.to_id_signed(),
+            )])
+            .exec()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Delete all tokens of a type of a user, returns the number of deleted tokens.
+    pub async fn delete_all(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        token_type: UserTokenType,
+    ) -> Result<i64, ModelError> {
+        let count = client
+            .user_token()
+            .delete_many(vec![
+                super::prisma::user_token::user_id::equals(user_id.to_id_signed()),
+                super::prisma::user_token::token_type::equals(token_type),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count)
+    }
+
     /// User token ID.
     pub fn id(&self) -> Snowflake {
         self.id
@@ -111,6 +236,11 @@ impl UserToken {
     pub fn user_agent(&self) -> Option<&String> {
         self.user_agent.as_ref()
     }
+
+    /// Number of times the token has been used.
+    pub fn attempts(&self) -> i32 {
+        self.attempts
+    }
 }
 
 impl From<Data> for UserToken {
@@ -125,6 +255,8 @@ impl From<Data> for UserToken {
             ip_address: data.ip_address,
             user_agent: data.user_agent,
 
+            attempts: data.attempts,
+
             created_at: data.created_at.into(),
             updated_at: data.updated_at.into(),
             expires_at: data.expires_at.into(),
@@ -1,12 +1,25 @@
 use chrono::{DateTime, Utc};
-use crypto::snowflake::Snowflake;
-
-use crate::models::{
-    error::ModelError,
-    prisma::{self},
-    PrismaClient,
+use crypto::{snowflake::Snowflake, totp::Algorithm};
+use prisma_client_rust::Direction;
+
+use crate::{
+    models::{
+        error::ModelError,
+        prisma::{self, HashAlgorithm},
+        PrismaClient,
+    },
+    state::CONFIG,
 };
 
+pub use crate::models::prisma::OtpKind;
+
+/// Number of counters after the expected counter of an HOTP device that are accepted, the counter of
+/// a hardware token also advances when a code is generated but never used.
+pub const HOTP_LOOK_AHEAD: u64 = 10;
+
+/// Number of counters searched when resynchronising an HOTP device with two consecutive codes.
+pub const HOTP_RESYNC_WINDOW: u64 = 100;
+
 #[derive(Debug, Clone)]
 #[allow(clippy::upper_case_acronyms)]
 pub struct TOTP {
@@ -14,11 +27,26 @@ pub struct TOTP {
 
     user_id: Snowflake,
 
+    /// Name of the device given by the user.
+    name: String,
+
+    /// Secret encrypted with the data-encryption key, see [`TOTP::decrypt_secret`].
     secret: String,
     interval: u32,
+    algorithm: Algorithm,
+    digits: u32,
+
+    /// TOTP devices use the time as moving factor, HOTP devices (hardware tokens) a counter.
+    kind: OtpKind,
+    /// Next counter expected from an HOTP device.
+    counter: u64,
 
-    totp_backup_codes: Vec<TOTPBackupCode>,
+    /// Set while the enrolment is pending, see [`TOTP::is_pending`].
+    pending_until: Option<DateTime<Utc>>,
+    /// Number of codes tried to confirm the pending enrolment.
+    confirm_attempts: i32,
 
+    last_used_at: Option<DateTime<Utc>>,
     created_at: DateTime<Utc>,
 }
 
@@ -32,18 +60,29 @@ impl TOTP {
         TOTPBuilder {
             id,
             user_id,
+            name: None,
             secret,
             interval,
+            algorithm: Algorithm::default(),
+            digits: 6,
+            kind: OtpKind::Totp,
+            counter: 0,
+            pending_until: None,
         }
     }
 
-    pub async fn get(client: &PrismaClient, user_id: Snowflake) -> Result<Self, ModelError> {
+    /// Get a TOTP device of a user by its ID, pending enrolments included.
+    pub async fn get(
+        client: &PrismaClient,
+        user_id: Snowflake,
+        id: Snowflake,
+    ) -> Result<Self, ModelError> {
         let result = client
             .totp()
-            .find_first(vec![prisma::totp::user_id::equals(user_id.to_id_signed())])
-            .with(prisma::totp::totp_backup_code::fetch(vec![
-                prisma::totp_backup_code::expired::equals(false),
-            ]))
+            .find_first(vec![
+                prisma::totp::id::equals(id.to_id_signed()),
+                prisma::totp::user_id::equals(user_id.to_id_signed()),
+            ])
             .exec()
             .await?;
 
@@ -53,49 +92,258 @@ impl TOTP {
         }
     }
 
+    /// List the active (confirmed) TOTP devices of a user, oldest first.
+    pub async fn list(client: &PrismaClient, user_id: Snowflake) -> Result<Vec<Self>, ModelError> {
+        let result = client
+            .totp()
+            .find_many(vec![
+                prisma::totp::user_id::equals(user_id.to_id_signed()),
+                prisma::totp::pending_until::equals(None),
+            ])
+            .order_by(prisma::totp::created_at::order(Direction::Asc))
+            .exec()
+            .await?;
+
+        Ok(result.into_iter().map(|totp| totp.into()).collect())
+    }
+
+    /// Verify a code generated by this device, the last used time is updated if the code is valid.
+    ///
+    /// The counter of an HOTP device is moved past the counter of the code so that it can not be
+    /// used again, see [`HOTP_LOOK_AHEAD`].
+    ///
+    /// Backup codes are verified with [`crate::core::totp::verify_code`].
     pub async fn verify(&self, client: &PrismaClient, code: String) -> Result<bool, ModelError> {
-        // Check against backup codes if the code contains a dash
-        if code.contains('-') {
-            let backup_code = self
-                .totp_backup_codes
-                .iter()
-                .find(|backup_code| backup_code.code() == code);
-
-            if let Some(backup_code) = backup_code {
-                if backup_code.expired() {
-                    return Ok(false);
-                }
+        let secret = self.decrypt_secret()?;
+
+        if self.kind == OtpKind::Hotp {
+            let res = crypto::totp::verify_hotp(
+                &code,
+                secret.as_bytes(),
+                self.counter,
+                self.algorithm,
+                self.digits,
+                HOTP_LOOK_AHEAD,
+            );
+
+            return match res {
+                Ok(Some(counter)) => self.advance_counter(client, counter + 1).await,
+                _ => Ok(false),
+            };
+        }
 
-                // Expire the backup code
-                self.expire_a_backup_code(client, backup_code.id()).await?;
+        let res = crypto::totp::verify_totp(
+            &code,
+            secret.as_bytes(),
+            self.interval,
+            self.algorithm,
+            self.digits,
+            Some(1),
+        );
+
+        if !res.unwrap_or(false) {
+            return Ok(false);
+        }
 
-                return Ok(true);
-            } else {
-                return Ok(false);
-            }
+        client
+            .totp()
+            .update(
+                prisma::totp::id::equals(self.id.to_id_signed()),
+                vec![prisma::totp::last_used_at::set(Some(Utc::now().into()))],
+            )
+            .exec()
+            .await?;
+
+        Ok(true)
+    }
+
+    /// Resynchronise the counter of an HOTP device that drifted outside of the look-ahead window,
+    /// using two consecutive codes generated by the device. See [`HOTP_RESYNC_WINDOW`].
+    ///
+    /// Returns false if the codes do not match or the device is not an HOTP device.
+    pub async fn resync(
+        &self,
+        client: &PrismaClient,
+        first_code: &str,
+        second_code: &str,
+    ) -> Result<bool, ModelError> {
+        if self.kind != OtpKind::Hotp {
+            return Ok(false);
         }
 
-        let res = crypto::totp::verify_totp(&code, self.secret.as_bytes(), self.interval, Some(1));
-        Ok(res.unwrap_or(false))
+        let secret = self.decrypt_secret()?;
+        let res = crypto::totp::resync_hotp(
+            first_code,
+            second_code,
+            secret.as_bytes(),
+            self.counter,
+            self.algorithm,
+            self.digits,
+            HOTP_RESYNC_WINDOW,
+        );
+
+        match res {
+            Ok(Some(counter)) => self.advance_counter(client, counter + 1).await,
+            _ => Ok(false),
+        }
     }
 
-    pub async fn expire_a_backup_code(
+    /// Move the counter of an HOTP device forward, returns false if the counter was changed in the
+    /// meantime so that a code can not be used twice by concurrent requests.
+    async fn advance_counter(
         &self,
         client: &PrismaClient,
-        code_id: Snowflake,
-    ) -> Result<(), ModelError> {
+        counter: u64,
+    ) -> Result<bool, ModelError> {
+        let count = client
+            .totp()
+            .update_many(
+                vec![
+                    prisma::totp::id::equals(self.id.to_id_signed()),
+                    prisma::totp::counter::equals(self.counter as i64),
+                ],
+                vec![
+                    prisma::totp::counter::set(counter as i64),
+                    prisma::totp::last_used_at::set(Some(Utc::now().into())),
+                ],
+            )
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Decrypt the secret with the data-encryption key.
+    ///
+    /// Secrets that were stored before encryption at rest was introduced are returned as is until
+    /// they are re-encrypted with [`TOTP::reencrypt_secrets`].
+    pub fn decrypt_secret(&self) -> Result<String, ModelError> {
+        if !crypto::aead::is_encrypted(&self.secret) {
+            return Ok(self.secret.clone());
+        }
+
+        let secret = CONFIG
+            .data_encryption_keys()
+            .decrypt(&self.secret, &secret_associated_data(self.user_id))?;
+
+        String::from_utf8(secret).map_err(|_| crypto::aead::Error::InvalidCiphertext.into())
+    }
+
+    /// Re-encrypt every secret that is stored in plaintext or not encrypted with the primary
+    /// data-encryption key, used after rotating keys. Returns the number of re-encrypted secrets.
+    pub async fn reencrypt_secrets(
+        client: &PrismaClient,
+        batch_size: i64,
+    ) -> Result<u64, ModelError> {
+        let keys = CONFIG.data_encryption_keys();
+
+        let mut count = 0;
+        let mut last_id = i64::MIN;
+        loop {
+            let batch = client
+                .totp()
+                .find_many(vec![prisma::totp::id::gt(last_id)])
+                .order_by(prisma::totp::id::order(Direction::Asc))
+                .take(batch_size)
+                .exec()
+                .await?;
+
+            let last = match batch.last() {
+                Some(last) => last.id,
+                None => break,
+            };
+
+            for data in batch {
+                let totp: TOTP = data.into();
+                if crypto::aead::is_encrypted(&totp.secret)
+                    && !keys.needs_reencryption(&totp.secret)
+                {
+                    continue;
+                }
+
+                let secret = keys.encrypt(
+                    totp.decrypt_secret()?.as_bytes(),
+                    &secret_associated_data(totp.user_id),
+                )?;
+
+                client
+                    .totp()
+                    .update(
+                        prisma::totp::id::equals(totp.id.to_id_signed()),
+                        vec![prisma::totp::secret::set(secret)],
+                    )
+                    .exec()
+                    .await?;
+
+                count += 1;
+            }
+
+            last_id = last;
+        }
+
+        Ok(count)
+    }
+
+    /// Activate a pending enrolment after the user has proven that their authenticator works.
+    pub async fn confirm(&mut self, client: &PrismaClient) -> Result<(), ModelError> {
         client
-            .totp_backup_code()
+            .totp()
             .update(
-                prisma::totp_backup_code::id::equals(code_id.to_id_signed()),
-                vec![prisma::totp_backup_code::expired::set(true)],
+                prisma::totp::id::equals(self.id.to_id_signed()),
+                vec![prisma::totp::pending_until::set(None)],
+            )
+            .exec()
+            .await?;
+
+        self.pending_until = None;
+        Ok(())
+    }
+
+    /// Count an attempt of confirming the pending enrolment, returns false without counting if
+    /// `max_attempts` has already been reached.
+    pub async fn try_confirm_attempt(
+        &self,
+        client: &PrismaClient,
+        max_attempts: i32,
+    ) -> Result<bool, ModelError> {
+        let count = client
+            .totp()
+            .update_many(
+                vec![
+                    prisma::totp::id::equals(self.id.to_id_signed()),
+                    prisma::totp::pending_until::not(None),
+                    prisma::totp::confirm_attempts::lt(max_attempts),
+                ],
+                vec![prisma::totp::confirm_attempts::increment(1)],
             )
             .exec()
             .await?;
 
+        Ok(count > 0)
+    }
+
+    pub async fn delete(client: &PrismaClient, id: Snowflake) -> Result<(), ModelError> {
+        client
+            .totp()
+            .delete_many(vec![prisma::totp::id::equals(id.to_id_signed())])
+            .exec()
+            .await?;
+
         Ok(())
     }
 
+    /// Delete pending enrolments that have not been confirmed in time, returns the number of
+    /// deleted enrolments.
+    pub async fn delete_expired_enrolments(client: &PrismaClient) -> Result<i64, ModelError> {
+        let count = client
+            .totp()
+            .delete_many(vec![prisma::totp::pending_until::lt(Utc::now().into())])
+            .exec()
+            .await?;
+
+        Ok(count)
+    }
+
     pub fn id(&self) -> Snowflake {
         self.id
     }
@@ -104,37 +352,84 @@ impl TOTP {
         self.user_id
     }
 
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    pub fn last_used_at(&self) -> Option<DateTime<Utc>> {
+        self.last_used_at
+    }
+
     pub fn created_at(&self) -> DateTime<Utc> {
         self.created_at
     }
 
-    pub fn secret(&self) -> &str {
+    /// True if the enrolment has not been confirmed with a code yet, a pending TOTP can not be
+    /// used to sign in.
+    pub fn is_pending(&self) -> bool {
+        self.pending_until.is_some()
+    }
+
+    /// True if the enrolment is pending and has not been confirmed in time.
+    pub fn is_expired_enrolment(&self) -> bool {
+        matches!(self.pending_until, Some(pending_until) if pending_until < Utc::now())
+    }
+
+    pub fn pending_until(&self) -> Option<DateTime<Utc>> {
+        self.pending_until
+    }
+
+    pub fn confirm_attempts(&self) -> i32 {
+        self.confirm_attempts
+    }
+
+    /// The encrypted secret, use [`TOTP::decrypt_secret`] to get the plaintext secret.
+    pub fn encrypted_secret(&self) -> &str {
         self.secret.as_ref()
     }
 
     pub fn interval(&self) -> u32 {
         self.interval
     }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn digits(&self) -> u32 {
+        self.digits
+    }
+
+    pub fn kind(&self) -> OtpKind {
+        self.kind
+    }
+
+    /// Next counter expected from an HOTP device, always zero for TOTP devices.
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
 }
 
 impl From<prisma::totp::Data> for TOTP {
     fn from(value: prisma::totp::Data) -> Self {
-        let totp_backup_codes = value
-            .totp_backup_code
-            .unwrap_or(Vec::new())
-            .into_iter()
-            .map(|code| code.into())
-            .collect();
-
         Self {
             id: value.id.try_into().unwrap(),
             user_id: value.user_id.try_into().unwrap(),
 
+            name: value.name,
+
             secret: value.secret,
             interval: value.interval as u32,
+            algorithm: value.algorithm.into(),
+            digits: value.digits as u32,
+
+            kind: value.kind,
+            counter: value.counter as u64,
 
-            totp_backup_codes,
+            pending_until: value.pending_until.map(|v| v.into()),
+            confirm_attempts: value.confirm_attempts,
 
+            last_used_at: value.last_used_at.map(|v| v.into()),
             created_at: value.created_at.into(),
         }
     }
@@ -145,19 +440,72 @@ pub struct TOTPBuilder {
 
     user_id: Snowflake,
 
+    name: Option<String>,
+
     secret: String,
     interval: u32,
+    algorithm: Algorithm,
+    digits: u32,
+    kind: OtpKind,
+    counter: u64,
+    pending_until: Option<DateTime<Utc>>,
 }
 
 impl TOTPBuilder {
+    pub fn name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Create the TOTP as a pending enrolment which has to be confirmed before the given time.
+    pub fn pending_until(mut self, pending_until: DateTime<Utc>) -> Self {
+        self.pending_until = Some(pending_until);
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    pub fn digits(mut self, digits: u32) -> Self {
+        self.digits = digits;
+        self
+    }
+
+    /// Create an HOTP device whose next expected counter is `counter`, the interval is ignored.
+    pub fn hotp(mut self, counter: u64) -> Self {
+        self.kind = OtpKind::Hotp;
+        self.counter = counter;
+        self
+    }
+
+    /// Encrypt the secret with the data-encryption key and insert the TOTP.
     pub async fn create(self, client: &PrismaClient) -> Result<TOTP, ModelError> {
+        let secret = CONFIG.data_encryption_keys().encrypt(
+            self.secret.as_bytes(),
+            &secret_associated_data(self.user_id),
+        )?;
+
+        let mut params = vec![
+            prisma::totp::interval::set(self.interval as i32),
+            prisma::totp::algorithm::set(self.algorithm.into()),
+            prisma::totp::digits::set(self.digits as i32),
+            prisma::totp::kind::set(self.kind),
+            prisma::totp::counter::set(self.counter as i64),
+            prisma::totp::pending_until::set(self.pending_until.map(|v| v.into())),
+        ];
+        if let Some(name) = self.name {
+            params.push(prisma::totp::name::set(name));
+        }
+
         let res = client
             .totp()
             .create(
                 self.id.to_id_signed(),
                 prisma::user::id::equals(self.user_id.to_id_signed()),
-                self.secret,
-                vec![prisma::totp::interval::set(self.interval as i32)],
+                secret,
+                params,
             )
             .exec()
             .await?;
@@ -166,37 +514,148 @@ impl TOTPBuilder {
     }
 }
 
+impl From<HashAlgorithm> for Algorithm {
+    fn from(value: HashAlgorithm) -> Self {
+        match value {
+            HashAlgorithm::Sha1 => Algorithm::Sha1,
+            HashAlgorithm::Sha256 => Algorithm::Sha256,
+            HashAlgorithm::Sha512 => Algorithm::Sha512,
+        }
+    }
+}
+
+impl From<Algorithm> for HashAlgorithm {
+    fn from(value: Algorithm) -> Self {
+        match value {
+            Algorithm::Sha1 => HashAlgorithm::Sha1,
+            Algorithm::Sha256 => HashAlgorithm::Sha256,
+            Algorithm::Sha512 => HashAlgorithm::Sha512,
+        }
+    }
+}
+
+/// Binds an encrypted secret to its user so that it can not be moved to another user's row.
+fn secret_associated_data(user_id: Snowflake) -> Vec<u8> {
+    format!("totp:{}", user_id).into_bytes()
+}
+
 #[derive(Debug, Clone)]
 pub struct TOTPBackupCode {
     id: Snowflake,
 
-    code: String,
+    code_hash: String,
     expired: bool,
+    used_at: Option<DateTime<Utc>>,
 
-    totp_id: Snowflake,
+    user_id: Snowflake,
 
     created_at: DateTime<Utc>,
 }
 
 impl TOTPBackupCode {
-    pub fn builder(totp_id: Snowflake, codes: Vec<(Snowflake, String)>) -> TOTPBackupCodeBuilder {
-        TOTPBackupCodeBuilder { totp_id, codes }
+    /// Store backup codes, given as pairs of ID and hash (see
+    /// [`crypto::totp::hash_backup_code`]). The plaintext codes are never stored.
+    pub fn builder(
+        user_id: Snowflake,
+        code_hashes: Vec<(Snowflake, String)>,
+    ) -> TOTPBackupCodeBuilder {
+        TOTPBackupCodeBuilder {
+            user_id,
+            code_hashes,
+        }
+    }
+
+    /// Get the backup codes of a user that have not been used or expired.
+    pub async fn list_remaining(
+        client: &PrismaClient,
+        user_id: Snowflake,
+    ) -> Result<Vec<TOTPBackupCode>, ModelError> {
+        let backup_codes = client
+            .totp_backup_code()
+            .find_many(vec![
+                prisma::totp_backup_code::user_id::equals(user_id.to_id_signed()),
+                prisma::totp_backup_code::expired::equals(false),
+            ])
+            .exec()
+            .await?;
+
+        Ok(backup_codes.into_iter().map(TOTPBackupCode::from).collect())
+    }
+
+    /// Expire a backup code that has been verified so that it can only be used once, returns false
+    /// if the code has already been used.
+    pub async fn redeem(&self, client: &PrismaClient) -> Result<bool, ModelError> {
+        // Only one request can redeem the code
+        let count = client
+            .totp_backup_code()
+            .update_many(
+                vec![
+                    prisma::totp_backup_code::id::equals(self.id.to_id_signed()),
+                    prisma::totp_backup_code::expired::equals(false),
+                ],
+                vec![
+                    prisma::totp_backup_code::expired::set(true),
+                    prisma::totp_backup_code::used_at::set(Some(Utc::now().into())),
+                ],
+            )
+            .exec()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Expire all remaining backup codes of a user, used when a new set of backup codes is generated.
+    pub async fn expire_all(client: &PrismaClient, user_id: Snowflake) -> Result<i64, ModelError> {
+        let count = client
+            .totp_backup_code()
+            .update_many(
+                vec![
+                    prisma::totp_backup_code::user_id::equals(user_id.to_id_signed()),
+                    prisma::totp_backup_code::expired::equals(false),
+                ],
+                vec![prisma::totp_backup_code::expired::set(true)],
+            )
+            .exec()
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Number of backup codes of a user that have not been used or expired.
+    pub async fn count_remaining(
+        client: &PrismaClient,
+        user_id: Snowflake,
+    ) -> Result<i64, ModelError> {
+        let count = client
+            .totp_backup_code()
+            .count(vec![
+                prisma::totp_backup_code::user_id::equals(user_id.to_id_signed()),
+                prisma::totp_backup_code::expired::equals(false),
+            ])
+            .exec()
+            .await?;
+
+        Ok(count)
     }
 
     pub fn id(&self) -> Snowflake {
         self.id
     }
 
-    pub fn code(&self) -> &str {
-        self.code.as_ref()
+    pub fn code_hash(&self) -> &str {
+        self.code_hash.as_ref()
     }
 
     pub fn expired(&self) -> bool {
         self.expired
     }
 
-    pub fn totp_id(&self) -> Snowflake {
-        self.totp_id
+    pub fn used_at(&self) -> Option<DateTime<Utc>> {
+        self.used_at
+    }
+
+    pub fn user_id(&self) -> Snowflake {
+        self.user_id
     }
 
     pub fn created_at(&self) -> DateTime<Utc> {
@@ -209,10 +668,11 @@ impl From<prisma::totp_backup_code::Data> for TOTPBackupCode {
         Self {
             id: value.id.try_into().unwrap(),
 
-            code: value.code,
+            code_hash: value.code_hash,
             expired: value.expired,
+            used_at: value.used_at.map(|v| v.into()),
 
-            totp_id: value.totpid.try_into().unwrap(),
+            user_id: value.user_id.try_into().unwrap(),
 
             created_at: value.created_at.into(),
         }
@@ -221,16 +681,24 @@ impl From<prisma::totp_backup_code::Data> for TOTPBackupCode {
 
 #[derive(Debug, Clone)]
 pub struct TOTPBackupCodeBuilder {
-    totp_id: Snowflake,
-    codes: Vec<(Snowflake, String)>,
+    user_id: Snowflake,
+    code_hashes: Vec<(Snowflake, String)>,
 }
 
 impl TOTPBackupCodeBuilder {
+    /// Insert the hashed backup codes.
     pub async fn create(self, client: &PrismaClient) -> Result<i64, ModelError> {
         let codes: Vec<(i64, String, i64, Vec<prisma::totp_backup_code::SetParam>)> = self
-            .codes
+            .code_hashes
             .into_iter()
-            .map(|(id, code)| (id.to_id_signed(), code, self.totp_id.to_id_signed(), vec![]))
+            .map(|(id, code_hash)| {
+                (
+                    id.to_id_signed(),
+                    code_hash,
+                    self.user_id.to_id_signed(),
+                    vec![],
+                )
+            })
             .collect();
 
         let count = client.totp_backup_code().create_many(codes).exec().await?;
@@ -13,9 +13,17 @@ use crypto::{
 use rand::{rngs::StdRng, SeedableRng};
 use tokio::sync::Mutex;
 
-use crate::{grpc, models::PrismaClient, ServiceData};
+use crate::{
+    core::{
+        hashing::HashingPool,
+        sms::{GrpcSmsTransport, SmsTransport, StubSmsTransport},
+    },
+    grpc,
+    models::PrismaClient,
+    ServiceData,
+};
 
-pub use config::{Config, CONFIG};
+pub use config::{Config, SmsTransportKind, CONFIG};
 
 mod config;
 
@@ -29,6 +37,9 @@ pub struct State {
     jwt_pub_key: Vec<u8>,
 
     email_grpc_client: Mutex<grpc::client::EmailClient>,
+    sms_transport: Box<dyn SmsTransport>,
+
+    hashing_pool: HashingPool,
 }
 
 impl State {
@@ -76,6 +87,15 @@ impl State {
                 .expect("failed to connect to gRPC server"),
         );
 
+        let sms_transport: Box<dyn SmsTransport> = match CONFIG.sms_transport() {
+            SmsTransportKind::Grpc => Box::new(GrpcSmsTransport::new(
+                grpc::client::connect_to_sms_grpc_server()
+                    .await
+                    .expect("failed to connect to gRPC server"),
+            )),
+            SmsTransportKind::Stub => Box::<StubSmsTransport>::default(),
+        };
+
         Self {
             prisma_client,
             id_generator,
@@ -84,9 +104,23 @@ impl State {
             jwt_priv_key: priv_jwt_key,
             jwt_pub_key: pub_key_pem,
             email_grpc_client,
+            sms_transport,
+            hashing_pool: HashingPool::new(
+                CONFIG.password_hashing_workers(),
+                CONFIG.password_hashing_queue_size(),
+            ),
         }
     }
 
+    /// Replace the SMS transport, e.g. with a [`StubSmsTransport`] in tests.
+    pub fn with_sms_transport<T>(mut self, sms_transport: T) -> Self
+    where
+        T: SmsTransport + 'static,
+    {
+        self.sms_transport = Box::new(sms_transport);
+        self
+    }
+
     pub fn verify_email_url() -> String {
         format!("{}/verify/email", CONFIG.authcore_url())
     }
@@ -122,6 +156,14 @@ impl State {
     pub fn email_grpc_client(&self) -> &Mutex<grpc::client::EmailClient> {
         &self.email_grpc_client
     }
+
+    pub fn sms_transport(&self) -> &dyn SmsTransport {
+        self.sms_transport.as_ref()
+    }
+
+    pub fn hashing_pool(&self) -> &HashingPool {
+        &self.hashing_pool
+    }
 }
 
 /// `AppState` is an alias for an `Arc<State>` to provide shared ownership
@@ -1,22 +1,119 @@
-use crypto::input::password::PasswordRequirements;
+use crypto::{
+    aead::KeyRing,
+    input::{breached::BreachedPasswords, password::PasswordRequirements},
+    password::HashConfig,
+};
 
 pub static CONFIG: once_cell::sync::Lazy<Config> = once_cell::sync::Lazy::new(Config::new);
 
+/// Data-encryption key used in debug builds when `DATA_ENCRYPTION_KEYS` is not set.
+#[cfg(debug_assertions)]
+const DEBUG_DATA_ENCRYPTION_KEYS: &str = "debug:MDEyMzQ1Njc4OTAxMjM0NTY3ODkwMTIzNDU2Nzg5MDE=";
+
+/// How SMS messages are delivered, see [`crate::core::sms`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SmsTransportKind {
+    /// Send through the messaging service.
+    #[default]
+    Grpc,
+    /// Only record the messages in-process.
+    Stub,
+}
+
 #[derive(Debug, Default)]
 pub struct Config {
     default_password_requirements: PasswordRequirements,
     authcore_url: String,
+    data_encryption_keys: KeyRing,
+    password_hashing: HashConfig,
+    password_hashing_workers: usize,
+    password_hashing_queue_size: usize,
+    breached_passwords: Option<BreachedPasswords>,
+    sms_transport: SmsTransportKind,
 }
 
 impl Config {
     pub fn new() -> Self {
+        // One worker per core by default, hashing is CPU bound
+        let password_hashing_workers = match std::env::var("PASSWORD_HASHING_WORKERS") {
+            Ok(value) => value
+                .parse()
+                .ok()
+                .filter(|&workers| workers > 0)
+                .unwrap_or_else(|| panic!("PASSWORD_HASHING_WORKERS is invalid: {}", value)),
+            Err(_) => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+
         Self {
             authcore_url: std::env::var("AUTHCORE_URL")
                 .unwrap_or_else(|_| "http://localhost:8080".to_string()),
+            data_encryption_keys: Self::load_data_encryption_keys(),
+            password_hashing: Self::load_password_hashing(),
+            password_hashing_workers,
+            password_hashing_queue_size: match std::env::var("PASSWORD_HASHING_QUEUE_SIZE") {
+                Ok(value) => value.parse().unwrap_or_else(|_| {
+                    panic!("PASSWORD_HASHING_QUEUE_SIZE is invalid: {}", value)
+                }),
+                Err(_) => password_hashing_workers * 8,
+            },
+            breached_passwords: std::env::var("BREACHED_PASSWORDS_PATH").ok().map(|path| {
+                BreachedPasswords::open(&path)
+                    .unwrap_or_else(|e| panic!("BREACHED_PASSWORDS_PATH is invalid: {}", e))
+            }),
+            sms_transport: match std::env::var("SMS_TRANSPORT").as_deref() {
+                Ok("stub") => SmsTransportKind::Stub,
+                Ok("grpc") | Err(_) => SmsTransportKind::Grpc,
+                Ok(other) => panic!("SMS_TRANSPORT is invalid: {}", other),
+            },
             ..Default::default()
         }
     }
 
+    /// Load the data-encryption keys from `DATA_ENCRYPTION_KEYS`, a comma separated list of
+    /// `<key id>:<base64 key>` pairs where the first key is used to encrypt new values.
+    fn load_data_encryption_keys() -> KeyRing {
+        let keys = std::env::var("DATA_ENCRYPTION_KEYS");
+
+        #[cfg(debug_assertions)]
+        let keys = keys.or_else(|_| {
+            tracing::warn!("DATA_ENCRYPTION_KEYS is not set, using the debug data-encryption key");
+            Ok::<_, std::env::VarError>(DEBUG_DATA_ENCRYPTION_KEYS.to_string())
+        });
+
+        let keys = keys.expect("DATA_ENCRYPTION_KEYS must be set");
+        KeyRing::parse(&keys).expect("DATA_ENCRYPTION_KEYS is invalid")
+    }
+
+    /// Load the argon2 costs of password hashes from `PASSWORD_HASH_MEMORY_COST` (in KiB),
+    /// `PASSWORD_HASH_ITERATIONS` and `PASSWORD_HASH_PARALLELISM`, and the peppers from
+    /// `PASSWORD_PEPPERS`, a comma separated list of `<pepper id>:<base64 pepper>` pairs where the
+    /// first pepper is used for new hashes.
+    fn load_password_hashing() -> HashConfig {
+        fn cost(name: &str, default: u32) -> u32 {
+            match std::env::var(name) {
+                Ok(value) => value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("{} is invalid: {}", name, value)),
+                Err(_) => default,
+            }
+        }
+
+        let mut config = HashConfig::with_costs(
+            cost("PASSWORD_HASH_MEMORY_COST", argon2::Params::DEFAULT_M_COST),
+            cost("PASSWORD_HASH_ITERATIONS", argon2::Params::DEFAULT_T_COST),
+            cost("PASSWORD_HASH_PARALLELISM", argon2::Params::DEFAULT_P_COST),
+        )
+        .expect("password hashing costs are invalid");
+
+        if let Ok(peppers) = std::env::var("PASSWORD_PEPPERS") {
+            config
+                .parse_peppers(&peppers)
+                .expect("PASSWORD_PEPPERS is invalid");
+        }
+
+        config
+    }
+
     pub fn set_default_password_requirements(&mut self, requirements: PasswordRequirements) {
         self.default_password_requirements = requirements;
     }
@@ -28,4 +125,33 @@ impl Config {
     pub fn authcore_url(&self) -> &str {
         self.authcore_url.as_ref()
     }
+
+    pub fn data_encryption_keys(&self) -> &KeyRing {
+        &self.data_encryption_keys
+    }
+
+    /// The argon2 costs and peppers of password hashes.
+    pub fn password_hashing(&self) -> &HashConfig {
+        &self.password_hashing
+    }
+
+    /// The number of passwords hashed at the same time, see [`crate::core::hashing`].
+    pub fn password_hashing_workers(&self) -> usize {
+        self.password_hashing_workers
+    }
+
+    /// The number of password hashing requests that wait for a worker before requests are
+    /// rejected.
+    pub fn password_hashing_queue_size(&self) -> usize {
+        self.password_hashing_queue_size
+    }
+
+    /// The breached password dataset new passwords are checked against, if one is configured.
+    pub fn breached_passwords(&self) -> Option<&BreachedPasswords> {
+        self.breached_passwords.as_ref()
+    }
+
+    pub fn sms_transport(&self) -> SmsTransportKind {
+        self.sms_transport
+    }
 }
@@ -4,6 +4,7 @@ import { Server, ServerCredentials } from "@grpc/grpc-js";
 
 import { Service, ServiceService } from "./services/service";
 import { Email, EmailServiceService } from "./services/email";
+import { Sms, SmsServiceService } from "./services/sms";
 
 // Configure gRPC server
 const server = new Server({
@@ -21,6 +22,7 @@ function main() {
     console.log(`Starting gRPC server on port ${port}...`);
     server.addService(ServiceService, new Service());
     server.addService(EmailServiceService, new Email());
+    server.addService(SmsServiceService, new Sms());
 
     // gRPC uses async methods to start the server
     server.bindAsync(
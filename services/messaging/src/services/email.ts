@@ -8,10 +8,19 @@ import {
     EmailServiceServer,
     EmailServiceService,
     SendVerificationEmailRequest,
+    SendCodeEmailRequest,
+    SendMfaRecoveryEmailRequest,
+    SendInvitationEmailRequest,
     SendEmailResponse,
+    MfaRecoveryStatus,
 } from "../models/email";
 
 import renderVerificationEmail from "../templates/verify";
+import renderSignInCodeEmail from "../templates/signin-code";
+import renderMfaRecoveryEmail, {
+    RecoveryStatus,
+} from "../templates/mfa-recovery";
+import renderInvitationEmail from "../templates/invitation";
 import sendEmail from "../email/send";
 
 /**
@@ -84,6 +93,224 @@ class Email implements EmailServiceServer {
             return callback(new Error("Internal server error"));
         });
     }
+
+    /**
+     * Sends a one-time sign-in code to the specified email address.
+     *
+     * @param call The gRPC call object
+     * @param callback The callback function
+     */
+    public sendCodeEmail(
+        call: ServerUnaryCall<SendCodeEmailRequest, SendEmailResponse>,
+        callback: sendUnaryData<SendEmailResponse>
+    ): void {
+        (async () => {
+            console.log("Received sendCodeEmail request");
+
+            // Get request data
+            const request = call.request;
+            const emailData = request.emailData;
+            const emailApplication = request.emailApplication;
+
+            // Validate request data
+            if (!emailData) {
+                return callback(new Error("Email data is undefined"));
+            }
+
+            if (!emailApplication) {
+                return callback(new Error("Email application is undefined"));
+            }
+
+            if (!request.code) {
+                return callback(new Error("Code is undefined"));
+            }
+
+            // Render email template to HTML
+            const emailHtml = renderSignInCodeEmail({
+                code: request.code,
+                application: emailApplication.name,
+            });
+
+            // Send email
+            const subject = `Your ${emailApplication.name} sign-in code`;
+            const emailOptions = {
+                from: emailData.from,
+                to: emailData.to,
+                subject: subject,
+                cc: emailData.cc,
+                bcc: emailData.bcc,
+                replyTo: emailData.replyTo,
+                html: emailHtml,
+            };
+
+            let result = await sendEmail(emailOptions);
+            if (!result) {
+                return callback(new Error("Email failed to send"));
+            }
+
+            console.log("Sending sign-in code email to: %s", emailData.to);
+
+            // Return response
+            return callback(null, {
+                emailId: "", // TODO: Implement Email IDs and logging
+                message: "Email sent successfully",
+            });
+        })().catch((err) => {
+            console.error("Error in sendCodeEmail:", err);
+            return callback(new Error("Internal server error"));
+        });
+    }
+
+    /**
+     * Notifies the specified email address that the recovery of a lost second
+     * factor was started, completed or cancelled.
+     *
+     * @param call The gRPC call object
+     * @param callback The callback function
+     */
+    public sendMfaRecoveryEmail(
+        call: ServerUnaryCall<SendMfaRecoveryEmailRequest, SendEmailResponse>,
+        callback: sendUnaryData<SendEmailResponse>
+    ): void {
+        (async () => {
+            console.log("Received sendMfaRecoveryEmail request");
+
+            // Get request data
+            const request = call.request;
+            const emailData = request.emailData;
+            const emailApplication = request.emailApplication;
+
+            // Validate request data
+            if (!emailData) {
+                return callback(new Error("Email data is undefined"));
+            }
+
+            if (!emailApplication) {
+                return callback(new Error("Email application is undefined"));
+            }
+
+            let status: RecoveryStatus;
+            switch (request.status) {
+                case MfaRecoveryStatus.MFA_RECOVERY_STATUS_STARTED:
+                    status = "started";
+                    break;
+                case MfaRecoveryStatus.MFA_RECOVERY_STATUS_COMPLETED:
+                    status = "completed";
+                    break;
+                case MfaRecoveryStatus.MFA_RECOVERY_STATUS_CANCELLED:
+                    status = "cancelled";
+                    break;
+                default:
+                    return callback(new Error("Recovery status is invalid"));
+            }
+
+            // Render email template to HTML
+            const emailHtml = renderMfaRecoveryEmail({
+                status: status,
+                application: emailApplication.name,
+                completesAt: request.completesAt
+                    ? new Date(Number(request.completesAt) * 1000)
+                    : undefined,
+            });
+
+            // Send email
+            const subject = `${emailApplication.name} two-factor recovery ${status}`;
+            const emailOptions = {
+                from: emailData.from,
+                to: emailData.to,
+                subject: subject,
+                cc: emailData.cc,
+                bcc: emailData.bcc,
+                replyTo: emailData.replyTo,
+                html: emailHtml,
+            };
+
+            let result = await sendEmail(emailOptions);
+            if (!result) {
+                return callback(new Error("Email failed to send"));
+            }
+
+            console.log("Sending mfa recovery email to: %s", emailData.to);
+
+            // Return response
+            return callback(null, {
+                emailId: "", // TODO: Implement Email IDs and logging
+                message: "Email sent successfully",
+            });
+        })().catch((err) => {
+            console.error("Error in sendMfaRecoveryEmail:", err);
+            return callback(new Error("Internal server error"));
+        });
+    }
+
+    /**
+     * Sends an invitation to set a password to a user created by an
+     * application.
+     *
+     * @param call The gRPC call object
+     * @param callback The callback function
+     */
+    public sendInvitationEmail(
+        call: ServerUnaryCall<SendInvitationEmailRequest, SendEmailResponse>,
+        callback: sendUnaryData<SendEmailResponse>
+    ): void {
+        (async () => {
+            console.log("Received sendInvitationEmail request");
+
+            // Get request data
+            const request = call.request;
+            const emailData = request.emailData;
+            const emailApplication = request.emailApplication;
+
+            // Validate request data
+            if (!emailData) {
+                return callback(new Error("Email data is undefined"));
+            }
+
+            if (!emailApplication) {
+                return callback(new Error("Email application is undefined"));
+            }
+
+            if (!request.invitationUrl) {
+                return callback(new Error("Invitation URL is undefined"));
+            }
+
+            // Render email template to HTML
+            const emailHtml = renderInvitationEmail({
+                url: request.invitationUrl,
+                application: emailApplication.name,
+                expiresAt: new Date(Number(request.expiresAt) * 1000),
+            });
+
+            // Send email
+            const subject = `You have been invited to ${emailApplication.name}`;
+            const emailOptions = {
+                from: emailData.from,
+                to: emailData.to,
+                subject: subject,
+                cc: emailData.cc,
+                bcc: emailData.bcc,
+                replyTo: emailData.replyTo,
+                html: emailHtml,
+            };
+
+            let result = await sendEmail(emailOptions);
+            if (!result) {
+                return callback(new Error("Email failed to send"));
+            }
+
+            console.log("Sending invitation email to: %s", emailData.to);
+
+            // Return response
+            return callback(null, {
+                emailId: "", // TODO: Implement Email IDs and logging
+                message: "Email sent successfully",
+            });
+        })().catch((err) => {
+            console.error("Error in sendInvitationEmail:", err);
+            return callback(new Error("Internal server error"));
+        });
+    }
 }
 
 export { Email, EmailServiceService };
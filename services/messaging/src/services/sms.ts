@@ -0,0 +1,72 @@
+import {
+    ServerUnaryCall,
+    UntypedHandleCall,
+    sendUnaryData,
+} from "@grpc/grpc-js";
+
+import {
+    SmsServiceServer,
+    SmsServiceService,
+    SendCodeSmsRequest,
+    SendSmsResponse,
+    SmsCodePurpose,
+} from "../models/sms";
+
+import sendSms from "../sms/send";
+
+/**
+ * The SMS service (gRPC) handles all SMS related tasks.
+ */
+class Sms implements SmsServiceServer {
+    [method: string]: UntypedHandleCall;
+
+    /**
+     * Sends a one-time code to the specified phone number.
+     *
+     * @param call The gRPC call object
+     * @param callback The callback function
+     */
+    public sendCodeSms(
+        call: ServerUnaryCall<SendCodeSmsRequest, SendSmsResponse>,
+        callback: sendUnaryData<SendSmsResponse>
+    ): void {
+        (async () => {
+            console.log("Received sendCodeSms request");
+
+            // Get request data
+            const request = call.request;
+            const smsApplication = request.smsApplication;
+
+            // Validate request data
+            if (!request.to || !request.code) {
+                return callback(new Error("Recipient or code is undefined"));
+            }
+
+            if (!smsApplication) {
+                return callback(new Error("SMS application is undefined"));
+            }
+
+            // Build the message, the code is placed first so that it is visible in notifications
+            const body =
+                request.purpose === SmsCodePurpose.SMS_CODE_PURPOSE_SIGN_IN
+                    ? `${request.code} is your ${smsApplication.name} sign-in code.`
+                    : `${request.code} is your ${smsApplication.name} verification code.`;
+
+            const smsId = await sendSms({ to: request.to, body: body });
+            if (smsId === null) {
+                return callback(new Error("SMS failed to send"));
+            }
+
+            // Return response
+            return callback(null, {
+                smsId: smsId,
+                message: "SMS sent successfully",
+            });
+        })().catch((err) => {
+            console.error("Error in sendCodeSms:", err);
+            return callback(new Error("Internal server error"));
+        });
+    }
+}
+
+export { Sms, SmsServiceService };
@@ -0,0 +1,59 @@
+/**
+ * The SMS data object.
+ */
+export interface Data {
+    to: string;
+    body: string;
+}
+
+/**
+ * Sends an SMS through the Twilio REST API.
+ *
+ * Without `TWILIO_ACCOUNT_SID` the message is only logged, which is useful
+ * during development.
+ *
+ * @param data The SMS data
+ */
+export default async function sendSms(data: Data): Promise<string | null> {
+    const accountSid = process.env.TWILIO_ACCOUNT_SID;
+    const authToken = process.env.TWILIO_AUTH_TOKEN;
+    const from = process.env.TWILIO_FROM_NUMBER;
+
+    if (!accountSid || !authToken || !from) {
+        console.warn(
+            "Twilio is not configured, SMS to %s: %s",
+            data.to,
+            data.body
+        );
+        return "";
+    }
+
+    const url = `https://api.twilio.com/2010-04-01/Accounts/${accountSid}/Messages.json`;
+    const auth = Buffer.from(`${accountSid}:${authToken}`).toString("base64");
+
+    try {
+        const response = await fetch(url, {
+            method: "POST",
+            headers: {
+                Authorization: `Basic ${auth}`,
+                "Content-Type": "application/x-www-form-urlencoded",
+            },
+            body: new URLSearchParams({
+                To: data.to,
+                From: from,
+                Body: data.body,
+            }),
+        });
+
+        if (!response.ok) {
+            console.error("Failed to send SMS: %s", await response.text());
+            return null;
+        }
+
+        const message = (await response.json()) as { sid?: string };
+        return message.sid ?? "";
+    } catch (err) {
+        console.error("Failed to send SMS:", err);
+        return null;
+    }
+}
@@ -0,0 +1,123 @@
+import * as React from "react";
+import { render } from "@react-email/render";
+
+import {
+    Body,
+    Container,
+    Head,
+    Heading,
+    Html,
+    Link,
+    Preview,
+    Section,
+    Text,
+} from "@react-email/components";
+
+interface EmailProps {
+    url: string;
+    application: string;
+    expiresAt: Date;
+}
+
+/// Email template component for invitations, uses react-email to render a HTML email
+export const InvitationEmail = ({ url, application, expiresAt }: EmailProps) => (
+    <Html>
+        <Head />
+        <Preview>You have been invited to {application}</Preview>
+        <Body style={main}>
+            <Container style={container}>
+                <Heading style={h1}>You have been invited</Heading>
+
+                <Text style={heroText}>
+                    An account has been created for you on {application}.
+                    Click the button below to choose your password and
+                    activate your account.
+                </Text>
+                <Section style={linkBox}>
+                    <Link href={url} style={acceptURL}>
+                        Accept invitation
+                    </Link>
+                </Section>
+
+                <Text style={text}>
+                    The invitation expires on {expiresAt.toUTCString()}. If you
+                    didn't expect this invitation, you can safely ignore it.
+                </Text>
+            </Container>
+        </Body>
+    </Html>
+);
+
+// Styles
+const main = {
+    backgroundColor: "#ffffff",
+    margin: "0 auto",
+    fontFamily:
+        "-apple-system, BlinkMacSystemFont, 'Segoe UI', 'Roboto', 'Oxygen', 'Ubuntu', 'Cantarell', 'Fira Sans', 'Droid Sans', 'Helvetica Neue', sans-serif",
+};
+
+const container = {
+    maxWidth: "600px",
+    margin: "0 auto",
+};
+
+const h1 = {
+    color: "#1d1c1d",
+    fontSize: "36px",
+    fontWeight: "700",
+    margin: "30px 0",
+    padding: "0",
+    lineHeight: "42px",
+};
+
+const heroText = {
+    fontSize: "20px",
+    lineHeight: "28px",
+    marginBottom: "30px",
+};
+
+const linkBox = {
+    background: "rgb(245, 244, 245)",
+    borderRadius: "4px",
+    marginRight: "50px",
+    marginBottom: "30px",
+    padding: "43px 23px",
+};
+
+const acceptURL = {
+    color: "#fff",
+    background: "#4a154b",
+    padding: "10px 20px",
+    borderRadius: "4px",
+    fontSize: "16px",
+    fontWeight: "700",
+    textDecoration: "none",
+};
+
+const text = {
+    color: "#000",
+    fontSize: "14px",
+    lineHeight: "24px",
+};
+
+/**
+ * Renders the invitation email template
+ *
+ * @param {string} props.url - The URL where the invited user sets their password
+ * @param {string} props.application - The name of the application the user is invited to
+ * @param {Date} props.expiresAt - When the invitation expires
+ * @returns string
+ */
+export default function renderInvitationEmail({
+    url,
+    application,
+    expiresAt,
+}: EmailProps): string {
+    return render(
+        <InvitationEmail
+            url={url}
+            application={application}
+            expiresAt={expiresAt}
+        />
+    );
+}
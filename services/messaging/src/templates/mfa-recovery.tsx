@@ -0,0 +1,136 @@
+import * as React from "react";
+import { render } from "@react-email/render";
+
+import {
+    Body,
+    Container,
+    Head,
+    Heading,
+    Html,
+    Preview,
+    Text,
+} from "@react-email/components";
+
+export type RecoveryStatus = "started" | "completed" | "cancelled";
+
+interface EmailProps {
+    status: RecoveryStatus;
+    application: string;
+    completesAt?: Date;
+}
+
+const headings: Record<RecoveryStatus, string> = {
+    started: "Two-factor recovery started",
+    completed: "Two-factor authentication removed",
+    cancelled: "Two-factor recovery cancelled",
+};
+
+/// Email template component for the recovery of a lost second factor, uses react-email to render a HTML email
+export const MfaRecoveryEmail = ({
+    status,
+    application,
+    completesAt,
+}: EmailProps) => (
+    <Html>
+        <Head />
+        <Preview>{headings[status]}</Preview>
+        <Body style={main}>
+            <Container style={container}>
+                <Heading style={h1}>{headings[status]}</Heading>
+
+                {status === "started" && (
+                    <>
+                        <Text style={heroText}>
+                            Someone proved access to this email address and
+                            started removing two-factor authentication from
+                            your {application} account. It will be removed on{" "}
+                            {completesAt?.toUTCString()}.
+                        </Text>
+                        <Text style={text}>
+                            If this wasn't you, sign in with your second factor
+                            and cancel the recovery, and change your password
+                            as soon as possible.
+                        </Text>
+                    </>
+                )}
+
+                {status === "completed" && (
+                    <>
+                        <Text style={heroText}>
+                            Two-factor authentication has been removed from
+                            your {application} account, you can sign in with
+                            your password and set up a new second factor.
+                        </Text>
+                        <Text style={text}>
+                            If you didn't request this, contact support
+                            immediately.
+                        </Text>
+                    </>
+                )}
+
+                {status === "cancelled" && (
+                    <Text style={heroText}>
+                        The recovery of your {application} account was
+                        cancelled, your second factor has not been removed.
+                    </Text>
+                )}
+            </Container>
+        </Body>
+    </Html>
+);
+
+// Styles
+const main = {
+    backgroundColor: "#ffffff",
+    margin: "0 auto",
+    fontFamily:
+        "-apple-system, BlinkMacSystemFont, 'Segoe UI', 'Roboto', 'Oxygen', 'Ubuntu', 'Cantarell', 'Fira Sans', 'Droid Sans', 'Helvetica Neue', sans-serif",
+};
+
+const container = {
+    maxWidth: "600px",
+    margin: "0 auto",
+};
+
+const h1 = {
+    color: "#1d1c1d",
+    fontSize: "36px",
+    fontWeight: "700",
+    margin: "30px 0",
+    padding: "0",
+    lineHeight: "42px",
+};
+
+const heroText = {
+    fontSize: "20px",
+    lineHeight: "28px",
+    marginBottom: "30px",
+};
+
+const text = {
+    color: "#000",
+    fontSize: "14px",
+    lineHeight: "24px",
+};
+
+/**
+ * Renders the second factor recovery email template
+ *
+ * @param {RecoveryStatus} props.status - Whether the recovery was started, completed or cancelled
+ * @param {string} props.application - The name of the application the account belongs to
+ * @param {Date} props.completesAt - When the second factor is removed, for a started recovery
+ * @returns string
+ */
+export default function renderMfaRecoveryEmail({
+    status,
+    application,
+    completesAt,
+}: EmailProps): string {
+    return render(
+        <MfaRecoveryEmail
+            status={status}
+            application={application}
+            completesAt={completesAt}
+        />
+    );
+}
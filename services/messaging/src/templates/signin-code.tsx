@@ -0,0 +1,107 @@
+import * as React from "react";
+import { render } from "@react-email/render";
+
+import {
+    Body,
+    Container,
+    Head,
+    Heading,
+    Html,
+    Preview,
+    Section,
+    Text,
+} from "@react-email/components";
+
+interface EmailProps {
+    code: string;
+    application: string;
+}
+
+/// Email template component for sign-in codes, uses react-email to render a HTML email
+export const SignInCodeEmail = ({ code, application }: EmailProps) => (
+    <Html>
+        <Head />
+        <Preview>Your {application} sign-in code</Preview>
+        <Body style={main}>
+            <Container style={container}>
+                <Heading style={h1}>Your sign-in code</Heading>
+
+                <Text style={heroText}>
+                    Your sign-in code for {application} is below - enter it in
+                    your open browser window to finish signing in. The code
+                    expires in 10 minutes.
+                </Text>
+                <Section style={codeBox}>
+                    <Text style={confirmationCodeText}>{code}</Text>
+                </Section>
+
+                <Text style={text}>
+                    If you didn't try to sign in, someone else may know your
+                    password - change it as soon as possible.
+                </Text>
+            </Container>
+        </Body>
+    </Html>
+);
+
+// Styles
+const main = {
+    backgroundColor: "#ffffff",
+    margin: "0 auto",
+    fontFamily:
+        "-apple-system, BlinkMacSystemFont, 'Segoe UI', 'Roboto', 'Oxygen', 'Ubuntu', 'Cantarell', 'Fira Sans', 'Droid Sans', 'Helvetica Neue', sans-serif",
+};
+
+const container = {
+    maxWidth: "600px",
+    margin: "0 auto",
+};
+
+const h1 = {
+    color: "#1d1c1d",
+    fontSize: "36px",
+    fontWeight: "700",
+    margin: "30px 0",
+    padding: "0",
+    lineHeight: "42px",
+};
+
+const heroText = {
+    fontSize: "20px",
+    lineHeight: "28px",
+    marginBottom: "30px",
+};
+
+const codeBox = {
+    background: "rgb(245, 244, 245)",
+    borderRadius: "4px",
+    marginRight: "50px",
+    marginBottom: "30px",
+    padding: "43px 23px",
+};
+
+const confirmationCodeText = {
+    fontSize: "30px",
+    textAlign: "center" as const,
+    verticalAlign: "middle",
+};
+
+const text = {
+    color: "#000",
+    fontSize: "14px",
+    lineHeight: "24px",
+};
+
+/**
+ * Renders the sign-in code email template
+ *
+ * @param {string} props.code - The one-time code
+ * @param {string} props.application - The name of the application the code is for
+ * @returns string
+ */
+export default function renderSignInCodeEmail({
+    code,
+    application,
+}: EmailProps): string {
+    return render(<SignInCodeEmail code={code} application={application} />);
+}
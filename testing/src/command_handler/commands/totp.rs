@@ -66,7 +66,13 @@ impl Command for TOTPCommand {
     }
 
     fn execute(&self, _ctx: Context) -> CommandResult {
-        let code = crypto::totp::generate_totp(self.secret.as_bytes(), 30).unwrap();
+        let code = crypto::totp::generate_totp(
+            self.secret.as_bytes(),
+            30,
+            crypto::totp::Algorithm::Sha1,
+            6,
+        )
+        .unwrap();
         println!("TOTP code: {}", code);
 
         CommandResult::Success